@@ -4,10 +4,13 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro2::Ident;
-use quote::{format_ident, quote};
+use proc_macro2::{Delimiter, Ident, Literal, TokenTree};
+use quote::{format_ident, quote, ToTokens};
 use syn::parse::{Parse, ParseStream};
-use syn::{parse_macro_input, punctuated::Punctuated, DeriveInput, LitInt, Path, Token, Variant};
+use syn::{
+    parse_macro_input, parse_quote, punctuated::Punctuated, Attribute, DeriveInput, Generics, Lit,
+    LitInt, LitStr, Meta, Path, Token, Type, Variant,
+};
 
 /// Information extracted from the name of a struct.
 struct NameInfo {
@@ -19,7 +22,7 @@ struct NameInfo {
 /// `str::rsplit_once` function is introduced in Rust v1.52. This provides
 /// the same functionality until v1.52 is available widely enough that we
 /// can require it.
-fn rsplit_once<'a>(s: &'a str, delimiter: char) -> Option<(&'a str, &'a str)> {
+fn rsplit_once(s: &str, delimiter: char) -> Option<(&str, &str)> {
     let split_pos = s.rfind(delimiter)?;
 
     let a = &s[..split_pos];
@@ -39,7 +42,7 @@ impl NameInfo {
                 if base.is_empty() {
                     panic!("failed to parse struct name");
                 }
-                let base = Ident::new(&base, ident.span());
+                let base = Ident::new(base, ident.span());
                 let version: u16 = version.parse().expect("failed to parse struct version");
                 (base, version)
             }
@@ -54,6 +57,84 @@ impl NameInfo {
     }
 }
 
+/// Describe a data structure's field names and types.
+///
+/// The fields are rendered into a canonical string. Tokens are separated
+/// by exactly one space, so the result doesn't depend on how the source
+/// code was formatted.
+fn schema_string(data: &syn::Data) -> String {
+    let mut schema = String::new();
+    match data {
+        syn::Data::Struct(data) => push_fields(&mut schema, &data.fields),
+        syn::Data::Enum(data) => {
+            for variant in &data.variants {
+                schema.push_str(&variant.ident.to_string());
+                schema.push('{');
+                push_fields(&mut schema, &variant.fields);
+                schema.push('}');
+            }
+        }
+        syn::Data::Union(data) => {
+            for field in &data.fields.named {
+                push_field(
+                    &mut schema,
+                    field.ident.as_ref().unwrap().to_string(),
+                    field,
+                );
+            }
+        }
+    }
+
+    schema
+}
+
+/// Compute a stable hash of a schema string, with 64-bit FNV-1a.
+fn schema_hash(schema: &str) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    schema.bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn push_fields(schema: &mut String, fields: &syn::Fields) {
+    for (index, field) in fields.iter().enumerate() {
+        // Tuple fields are named by their position.
+        let name = match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => index.to_string(),
+        };
+        push_field(schema, name, field);
+    }
+}
+
+fn push_field(schema: &mut String, name: String, field: &syn::Field) {
+    schema.push_str(&name);
+    schema.push(':');
+    push_tokens(schema, field.ty.to_token_stream());
+    schema.push(';');
+}
+
+fn push_tokens(schema: &mut String, tokens: proc_macro2::TokenStream) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                schema.push_str(open);
+                push_tokens(schema, group.stream());
+                schema.push_str(close);
+            }
+            other => schema.push_str(&other.to_string()),
+        }
+        schema.push(' ');
+    }
+}
+
 fn versioned_name(base: &Ident, version: u16) -> Ident {
     let name = format!("{}V{}", base, version);
     Ident::new(&name, base.span())
@@ -61,7 +142,56 @@ fn versioned_name(base: &Ident, version: u16) -> Ident {
 
 /// Derive the `Versioned` trait on a struct.
 ///
-#[proc_macro_derive(Versioned)]
+/// A minor version can be set with `#[versioned(minor = N)]`.
+///
+/// If fields were added to a struct without a version bump, mark the
+/// struct with `#[versioned(additive)]` and each new field with
+/// `#[versioned(added)]`. The derive then checks that each added field can
+/// be left out: it must have `#[serde(default)]` (on the field or the
+/// struct), or be an `Option`.
+///
+/// A struct that must always serialize to `N` bytes with
+/// `FixedSizeCodec` can be marked with `#[versioned(fixed_size = N)]`.
+/// This implements `FixedSize`, and fails to compile unless every field
+/// has a fixed size and the sizes add up to `N`. See
+/// `aversion::util::fixed` for the supported field types.
+///
+/// The latest version can be marked with `#[versioned(latest)]`. This
+/// implements the `IsLatest` marker trait, and fails to compile unless the
+/// type alias points at this struct.
+///
+/// Version 0 means legacy data that predates versioning, e.g. `FooV0`.
+/// If it exists, mark the latest version with `#[versioned(legacy)]`, so
+/// that `UpgradeLatest` and `RawVersions` include it.
+///
+/// If each version is upgraded by reference, with `From<&FooVN>` for
+/// `FooV(N+1)`, mark the latest version with `#[versioned(by_ref)]`; see
+/// `UpgradeLatest`.
+///
+/// Only the type name is used to find the version, so this works the same
+/// way on structs with named fields, tuple structs, and newtypes. Other
+/// attributes, such as `#[serde(transparent)]`, are left alone. Note that
+/// a tuple struct can't be constructed through its type alias: write
+/// `TimestampV2(0)`, not `Timestamp(0)`.
+///
+/// Generic structs are supported; `Base` is the type alias with the same
+/// type parameters, e.g. `Envelope<T>` for `EnvelopeV1<T>`. Lifetime
+/// parameters aren't passed on, so a borrowing struct should have an
+/// alias like `type Blob = BlobV1<'static>`.
+///
+/// Enums can be messages too, including enums with serde's internal
+/// (`#[serde(tag = "type")]`) or adjacent (`#[serde(tag = "t", content =
+/// "c")]`) tagging. The derive doesn't look at serde attributes, but Rust
+/// requires them to come after the `#[derive]` that introduces them.
+/// The schema hash doesn't include serde attributes either, so renaming a
+/// tag doesn't change it. Tagged enums need a self-describing codec such
+/// as CBOR; they can't be decoded by `FixedSizeCodec`.
+///
+/// A derive can't add serde attributes to fields, so there's no
+/// `#[versioned(bytes)]`. To encode a `Vec<u8>` field as a byte string,
+/// use `#[serde(with = "aversion::util::bytes")]`.
+///
+#[proc_macro_derive(Versioned, attributes(versioned))]
 pub fn derive_versioned(input: TokenStream) -> TokenStream {
     // parse the input into a DeriveInput syntax tree
     let input = parse_macro_input!(input as DeriveInput);
@@ -72,8 +202,45 @@ pub fn derive_versioned(input: TokenStream) -> TokenStream {
         struct_version,
     } = NameInfo::from_name(&input.ident);
 
+    let schema = schema_string(&input.data);
+    let schema_hash = schema_hash(&schema);
+    let VersionedAttrs {
+        minor,
+        additive,
+        latest,
+        legacy: _,
+        by_ref: _,
+        fixed_size,
+    } = VersionedAttrs::from_attrs(&input.attrs);
+    check_added_fields(&input, additive);
+    let minor = minor.map(|minor| quote! { const MINOR_VER: u16 = #minor; });
+
     // The original generic parameters from the input struct
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let base_generics = base_generics(&input.generics);
+
+    // Mark the latest version, and check that the type alias points at it.
+    let latest = if latest {
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics _aversion::IsLatest
+            for #struct_name #ty_generics #where_clause {}
+
+            #[allow(dead_code)]
+            fn check_latest_alias #impl_generics (
+                msg: #struct_base #base_generics,
+            ) -> #struct_name #ty_generics #where_clause {
+                msg
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let fixed_size = match fixed_size {
+        Some(size) => fixed_size_check(&input, &size),
+        None => quote! {},
+    };
 
     let expanded = quote! {
         #[doc(hidden)]
@@ -92,14 +259,70 @@ pub fn derive_versioned(input: TokenStream) -> TokenStream {
             impl #impl_generics _aversion::Versioned
             for #struct_name #ty_generics #where_clause {
                 const VER: u16 = #struct_version;
-                type Base = #struct_base;
+                type Base = #struct_base #base_generics;
+                const SCHEMA_HASH: u64 = #schema_hash;
+                const SCHEMA: &'static str = #schema;
+                #minor
             }
+
+            #latest
+            #fixed_size
         };
     };
     // proc_macro2::TokenStream -> proc_macro::TokenStream
     expanded.into()
 }
 
+/// Implement `FixedSize` for a struct, and check that its fields add up
+/// to the declared size.
+fn fixed_size_check(input: &DeriveInput, size: &LitInt) -> proc_macro2::TokenStream {
+    if !input.generics.params.is_empty() {
+        panic!("#[versioned(fixed_size = N)] isn't supported on generic structs");
+    }
+    let fields = match &input.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => panic!("#[versioned(fixed_size = N)] is only supported on structs"),
+    };
+    let struct_name = &input.ident;
+    let field_types = fields.iter().map(|field| &field.ty);
+    let message = format!(
+        "the fields of {} don't add up to fixed_size = {}",
+        struct_name, size
+    );
+    quote! {
+        #[automatically_derived]
+        impl _aversion::util::fixed::FixedSize for #struct_name {
+            const FIXED_SIZE: usize = #size;
+        }
+
+        const _: () = assert!(
+            0 #(+ <#field_types as _aversion::util::fixed::FixedSize>::FIXED_SIZE)* == #size,
+            #message
+        );
+    }
+}
+
+/// The generic arguments for `Base`, e.g. `<T>` in `type Base = Foo<T>`.
+///
+/// Lifetime parameters are left out: a borrowing struct like `FooV1<'a>`
+/// is expected to have an alias like `type Foo = FooV1<'static>`.
+fn base_generics(generics: &Generics) -> proc_macro2::TokenStream {
+    let args = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            syn::GenericParam::Type(ty) => Some(ty.ident.clone()),
+            syn::GenericParam::Const(c) => Some(c.ident.clone()),
+            syn::GenericParam::Lifetime(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if args.is_empty() {
+        quote! {}
+    } else {
+        quote! { <#(#args),*> }
+    }
+}
+
 /// Derive the `UpgradeLatest` trait on a struct.
 ///
 /// It is assumed that all versions 1..N exist, i.e. if `UpgradeLatest`
@@ -109,6 +332,35 @@ pub fn derive_versioned(input: TokenStream) -> TokenStream {
 /// It is further assumed that a type alias `Foo` exists and is equivalent
 /// to the latest version. In other words: `type Foo = FooV3`
 ///
+/// The upgrade path is determined by the version numbers alone: version N
+/// is upgraded using `FromVersion<FooVN>` for `FooV(N+1)`, and so on up to
+/// the latest version. The path only ever moves to higher versions, so it
+/// can't contain a cycle. Other `FromVersion` impls, such as a downgrade
+/// from `FooV2` to `FooV1`, are allowed but are never used by the upgrade.
+///
+/// Upgrading version N takes `latest - N` steps. If that's more than the
+/// data source's `max_upgrade_steps()`, the message isn't decoded, and the
+/// data source's `upgrade_too_deep` error is returned.
+///
+/// `SUPPORTED_VERSIONS` lists every version from 1 to the latest.
+///
+/// If the struct is marked `#[versioned(legacy)]`, version 0 is included
+/// too: a version 0 header is decoded as `FooV0`, the legacy format that
+/// predates versioning, and upgraded from there.
+///
+/// On a generic struct, every version must have the same generic
+/// parameters. The impl is bounded on what it uses: each version must
+/// implement `DeserializeOwned`, and the latest must implement
+/// `FromVersion` for each version.
+///
+/// `upgrade_latest` owns the message it decodes, so each step consumes
+/// the previous version and nothing is cloned. To upgrade a borrowed
+/// value instead, use `FromVersionRef`. If the struct is marked
+/// `#[versioned(by_ref)]`, each step is written as `From<&FooVN>` for
+/// `FooV(N+1)`: the derive implements `FromVersion` for each step by
+/// passing it a reference, and `From<&FooVN>` for the latest version by
+/// chaining the steps, so `FromVersionRef` works from any version.
+///
 #[proc_macro_derive(UpgradeLatest)]
 pub fn derive_upgrade_latest(input: TokenStream) -> TokenStream {
     // parse the input into a DeriveInput syntax tree
@@ -121,26 +373,49 @@ pub fn derive_upgrade_latest(input: TokenStream) -> TokenStream {
     } = NameInfo::from_name(&input.ident);
 
     // The original generic parameters from the input struct
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
 
     // Create a list of (version, StructVx), one for each version between 1 and this.
-    let all_versions = (1..=struct_version)
-        .into_iter()
-        .map(|ii| (ii, versioned_name(&struct_base, ii)))
+    // Every version is assumed to have the same generic parameters.
+    let first_version = VersionedAttrs::first_version(&input.attrs);
+    let all_versions = (first_version..=struct_version)
+        .map(|ii| {
+            let name = versioned_name(&struct_base, ii);
+            (ii, quote! { #name #ty_generics })
+        })
         .collect::<Vec<_>>();
+    let target = quote! { #struct_name #ty_generics };
+    let supported_versions = all_versions.iter().map(|(v, _)| v);
+
+    let bounded = upgrade_bounds(&input.generics, &all_versions, &target);
+    let (impl_generics, _, where_clause) = bounded.split_for_impl();
 
     // Generate the match arm tokens for each version.
     let read_message_arms = all_versions
         .iter()
-        .map(|(v, n)| quote_read_message_arm(*v, n, &struct_name));
+        .map(|(v, n)| quote_read_message_arm(*v, n, &target));
+
+    let read_message_vec_arms = all_versions
+        .iter()
+        .map(|(v, n)| quote_read_message_vec_arm(*v, n, &target));
 
     // Generate the FromVersion impls that skip intermediate versions,
     // and jump directly to the latest.
-    let all_hops = (1..struct_version - 1)
-        .into_iter()
-        .map(|ii| quote_from_version_hop(&struct_base, ii, struct_version))
+    let all_hops = (first_version..struct_version.saturating_sub(1))
+        .map(|ii| quote_from_version_hop(&struct_base, ii, struct_version, &input.generics))
         .collect::<Vec<_>>();
 
+    // With `#[versioned(by_ref)]`, each step is a `From<&FooVN>` impl.
+    let ref_impls = if VersionedAttrs::from_attrs(&input.attrs).by_ref {
+        let steps = (first_version..struct_version)
+            .map(|ii| quote_from_version_ref_step(&struct_base, ii, &input.generics));
+        let hops = (first_version..struct_version.saturating_sub(1))
+            .map(|ii| quote_from_ref_hop(&struct_base, ii, struct_version, &input.generics));
+        steps.chain(hops).collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
+
     let expanded = quote! {
         #[doc(hidden)]
         #[allow(
@@ -157,6 +432,7 @@ pub fn derive_upgrade_latest(input: TokenStream) -> TokenStream {
             #[automatically_derived]
             impl #impl_generics _aversion::group::UpgradeLatest
             for #struct_name #ty_generics #where_clause {
+                const SUPPORTED_VERSIONS: &'static [u16] = &[#(#supported_versions),*];
 
                 fn upgrade_latest<Src>(src: &mut Src, header: Src::Header) -> ::std::result::Result<Self, Src::Error>
                 where
@@ -165,15 +441,41 @@ pub fn derive_upgrade_latest(input: TokenStream) -> TokenStream {
                     use _aversion::group::GroupHeader;
 
                     let ver = header.msg_ver();
-                    match ver {
-                        #(#read_message_arms)*
+                    _aversion::__private::read_span::<Self, _, _, _>(header.wide_msg_id(), ver, || {
+                        if ver < #struct_version && #struct_version - ver > src.max_upgrade_steps() {
+                            return Err(src.upgrade_too_deep::<#struct_base #ty_generics>(ver));
+                        }
+                        match ver {
+                            #(#read_message_arms)*
 
-                        _ => Err(src.unknown_version::<#struct_base>(ver)),
-                    }
+                            _ => Err(src.unknown_version::<#struct_base #ty_generics>(ver)),
+                        }
+                    })
+                }
+
+                fn upgrade_latest_vec<Src>(src: &mut Src, header: Src::Header) -> ::std::result::Result<::std::vec::Vec<Self>, Src::Error>
+                where
+                    Src: _aversion::group::DataSource,
+                {
+                    use _aversion::group::GroupHeader;
+
+                    let ver = header.msg_ver();
+                    _aversion::__private::read_span::<Self, _, _, _>(header.wide_msg_id(), ver, || {
+                        if ver < #struct_version && #struct_version - ver > src.max_upgrade_steps() {
+                            return Err(src.upgrade_too_deep::<#struct_base #ty_generics>(ver));
+                        }
+                        match ver {
+                            #(#read_message_vec_arms)*
+
+                            _ => Err(src.unknown_version::<#struct_base #ty_generics>(ver)),
+                        }
+                    })
                 }
             }
 
             #(#all_hops)*
+
+            #(#ref_impls)*
         };
     };
     // proc_macro2::TokenStream -> proc_macro::TokenStream
@@ -182,8 +484,8 @@ pub fn derive_upgrade_latest(input: TokenStream) -> TokenStream {
 
 fn quote_read_message_arm(
     version: u16,
-    versioned_name: &Ident,
-    target_name: &Ident,
+    versioned_name: &proc_macro2::TokenStream,
+    target_name: &proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
     quote! {
         #version => {
@@ -194,12 +496,63 @@ fn quote_read_message_arm(
     }
 }
 
+/// Add the bounds that generated upgrade code needs on a generic struct.
+///
+/// For a generic struct like `FooV2<T>`, each version can only be
+/// deserialized and upgraded for some `T`. Rather than guess at bounds on
+/// `T`, require exactly what the generated code uses:
+/// `FooVN<T>: DeserializeOwned` and `FooV2<T>: FromVersion<FooVN<T>>`.
+///
+/// Non-generic structs are returned unchanged.
+fn upgrade_bounds(
+    generics: &Generics,
+    versions: &[(u16, proc_macro2::TokenStream)],
+    target: &proc_macro2::TokenStream,
+) -> Generics {
+    let mut generics = generics.clone();
+    if generics.params.is_empty() {
+        return generics;
+    }
+    let where_clause = generics.make_where_clause();
+    for (_, version) in versions {
+        where_clause
+            .predicates
+            .push(parse_quote!(#version: _aversion::__private::DeserializeOwned));
+        where_clause
+            .predicates
+            .push(parse_quote!(#target: _aversion::FromVersion<#version>));
+    }
+    generics
+}
+
+fn quote_read_message_vec_arm(
+    version: u16,
+    versioned_name: &proc_macro2::TokenStream,
+    target_name: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    quote! {
+        #version => {
+            let msgs = src.read_message::<::std::vec::Vec<#versioned_name>>(&header)?;
+            let upgraded = msgs
+                .into_iter()
+                .map(<#target_name as _aversion::FromVersion::<#versioned_name>>::from_version)
+                .collect();
+            Ok(upgraded)
+        }
+    }
+}
+
 /// Chain FromVersion implementations to skip directly to the latest version.
 ///
 /// If there is a FooV1..FooV4, and there is a FromVersion for each N to N+1,
 /// generate the code for `FromVersion<FooV1> for FooV4`.
 ///
-fn quote_from_version_hop(base: &Ident, lo: u16, hi: u16) -> proc_macro2::TokenStream {
+fn quote_from_version_hop(
+    base: &Ident,
+    lo: u16,
+    hi: u16,
+    generics: &syn::Generics,
+) -> proc_macro2::TokenStream {
     assert!(hi > lo);
     if hi - lo < 2 {
         // The user should already have provided FromVersion<___N> for ___M
@@ -213,7 +566,6 @@ fn quote_from_version_hop(base: &Ident, lo: u16, hi: u16) -> proc_macro2::TokenS
 
     // Create a chain of upgrades.
     let upgrade_chain = (lo..hi)
-        .into_iter()
         .map(|ii| {
             let jj = ii + 1;
             let tmp_ii = tmp_ident(ii);
@@ -230,9 +582,129 @@ fn quote_from_version_hop(base: &Ident, lo: u16, hi: u16) -> proc_macro2::TokenS
     let lo_tmp = tmp_ident(lo);
     let hi_tmp = tmp_ident(hi);
 
+    let mut generics = generics.clone();
+    if !generics.params.is_empty() {
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let steps = (lo..hi)
+            .map(|ii| {
+                let from = versioned_name(base, ii);
+                let to = versioned_name(base, ii + 1);
+                quote! { #to #ty_generics: FromVersion<#from #ty_generics> }
+            })
+            .collect::<Vec<_>>();
+        let where_clause = generics.make_where_clause();
+        for step in steps {
+            where_clause.predicates.push(parse_quote!(#step));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics FromVersion<#lo_ident #ty_generics>
+        for #hi_ident #ty_generics #where_clause {
+            fn from_version(#lo_tmp: #lo_ident #ty_generics) -> Self {
+                #(#upgrade_chain)*
+                #hi_tmp
+            }
+        }
+    }
+}
+
+/// Generate `FromVersion<FooVN> for FooV(N+1)` from the user's
+/// `From<&FooVN> for FooV(N+1)`.
+fn quote_from_version_ref_step(
+    base: &Ident,
+    lo: u16,
+    generics: &syn::Generics,
+) -> proc_macro2::TokenStream {
+    let lo_ident = versioned_name(base, lo);
+    let hi_ident = versioned_name(base, lo + 1);
+
+    let mut generics = generics.clone();
+    if !generics.params.is_empty() {
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let step = quote! {
+            #hi_ident #ty_generics: for<'__a> ::std::convert::From<&'__a #lo_ident #ty_generics>
+        };
+        generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#step));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics _aversion::FromVersion<#lo_ident #ty_generics>
+        for #hi_ident #ty_generics #where_clause {
+            fn from_version(prev: #lo_ident #ty_generics) -> Self {
+                <Self as ::std::convert::From<&#lo_ident #ty_generics>>::from(&prev)
+            }
+        }
+    }
+}
+
+/// If there is a FooV1..FooV4, and there is a `From<&FooVN>` for each
+/// `FooV(N+1)`, generate `From<&FooV1> for FooV4`.
+///
+/// Only the first step borrows the caller's value; the intermediate
+/// versions are temporaries.
+fn quote_from_ref_hop(
+    base: &Ident,
+    lo: u16,
+    hi: u16,
+    generics: &syn::Generics,
+) -> proc_macro2::TokenStream {
+    assert!(hi - lo >= 2);
+
+    fn tmp_ident(x: u16) -> Ident {
+        format_ident!("v{}", x)
+    }
+
+    let lo_ident = versioned_name(base, lo);
+    let hi_ident = versioned_name(base, hi);
+    let lo_tmp = tmp_ident(lo);
+
+    // The first step borrows the argument; later steps borrow the
+    // temporary from the step before.
+    let upgrade_chain = (lo..hi)
+        .map(|ii| {
+            let jj = ii + 1;
+            let tmp_ii = tmp_ident(ii);
+            let tmp_jj = tmp_ident(jj);
+            let ident_jj = versioned_name(base, jj);
+            let arg = if ii == lo {
+                quote! { #tmp_ii }
+            } else {
+                quote! { &#tmp_ii }
+            };
+            quote! {
+                let #tmp_jj = #ident_jj::from(#arg);
+            }
+        })
+        .collect::<Vec<_>>();
+    let hi_tmp = tmp_ident(hi);
+
+    let mut generics = generics.clone();
+    if !generics.params.is_empty() {
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let steps = (lo..hi)
+            .map(|ii| {
+                let from = versioned_name(base, ii);
+                let to = versioned_name(base, ii + 1);
+                quote! { #to #ty_generics: for<'__a> ::std::convert::From<&'__a #from #ty_generics> }
+            })
+            .collect::<Vec<_>>();
+        let where_clause = generics.make_where_clause();
+        for step in steps {
+            where_clause.predicates.push(parse_quote!(#step));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
     quote! {
-        impl FromVersion<#lo_ident> for #hi_ident {
-            fn from_version(#lo_tmp: #lo_ident) -> Self {
+        impl #impl_generics ::std::convert::From<&#lo_ident #ty_generics>
+        for #hi_ident #ty_generics #where_clause {
+            fn from(#lo_tmp: &#lo_ident #ty_generics) -> Self {
                 #(#upgrade_chain)*
                 #hi_tmp
             }
@@ -240,12 +712,258 @@ fn quote_from_version_hop(base: &Ident, lo: u16, hi: u16) -> proc_macro2::TokenS
     }
 }
 
+/// Derive the `RawVersions` trait on a struct.
+///
+/// This should be used on the latest version of a struct, e.g. `FooV3`,
+/// which usually also derives `UpgradeLatest`. It generates an enum
+/// named `FooRawVersion`, with one variant for each version:
+///
+/// ```text
+/// enum FooRawVersion {
+///     V1(FooV1),
+///     V2(FooV2),
+///     V3(FooV3),
+/// }
+/// ```
+///
+/// The enum has a `version()` method that returns the version number,
+/// and an `upgrade()` method that converts any variant to the latest
+/// version using `FromVersion`.
+///
+#[proc_macro_derive(RawVersions)]
+pub fn derive_raw_versions(input: TokenStream) -> TokenStream {
+    // parse the input into a DeriveInput syntax tree
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let NameInfo {
+        struct_name,
+        struct_base,
+        struct_version,
+    } = NameInfo::from_name(&input.ident);
+
+    let vis = &input.vis;
+    let generics = &input.generics;
+    let (_, ty_generics, enum_where_clause) = generics.split_for_impl();
+    let enum_name = format_ident!("{}RawVersion", struct_base);
+    let enum_doc = format!("Any version of `{}`, without upgrading.", struct_base);
+
+    let first_version = VersionedAttrs::first_version(&input.attrs);
+    let variants = (first_version..=struct_version)
+        .map(|ii| {
+            (
+                ii,
+                format_ident!("V{}", ii),
+                versioned_name(&struct_base, ii),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let all_versions = variants
+        .iter()
+        .map(|(v, _, n)| (*v, quote! { #n #ty_generics }))
+        .collect::<Vec<_>>();
+    let target = quote! { #struct_name #ty_generics };
+    let bounded = upgrade_bounds(generics, &all_versions, &target);
+    let (impl_generics, _, where_clause) = bounded.split_for_impl();
+
+    let enum_variants = variants.iter().map(|(v, var, n)| {
+        let doc = format!("Version {}", v);
+        quote! {
+            #[doc = #doc]
+            #var(#n #ty_generics),
+        }
+    });
+    let version_arms = variants.iter().map(|(v, var, _)| {
+        quote! { #enum_name::#var(_) => #v, }
+    });
+    let upgrade_arms = variants.iter().map(|(_, var, n)| {
+        quote! {
+            #enum_name::#var(msg) => <#struct_name #ty_generics as _aversion::FromVersion::<#n #ty_generics>>::from_version(msg),
+        }
+    });
+    let read_arms = variants.iter().map(|(v, var, n)| {
+        quote! {
+            #v => Ok(#enum_name::#var(src.read_message::<#n #ty_generics>(&header)?)),
+        }
+    });
+
+    let expanded = quote! {
+        #[doc = #enum_doc]
+        #[derive(Debug)]
+        #vis enum #enum_name #generics #enum_where_clause {
+            #(#enum_variants)*
+        }
+
+        #[doc(hidden)]
+        #[allow(
+            non_upper_case_globals,
+            unused_attributes,
+            unused_qualifications,
+            non_camel_case_types,
+            non_snake_case
+        )]
+        const _: () = {
+            #[allow(rust_2018_idioms, clippy::useless_attribute)]
+            extern crate aversion as _aversion;
+
+            #[automatically_derived]
+            impl #impl_generics #enum_name #ty_generics #where_clause {
+                /// The version number of this message.
+                pub fn version(&self) -> u16 {
+                    match self {
+                        #(#version_arms)*
+                    }
+                }
+
+                /// Upgrade this message to the latest version.
+                pub fn upgrade(self) -> #struct_name #ty_generics {
+                    match self {
+                        #(#upgrade_arms)*
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl #impl_generics _aversion::group::RawVersions
+            for #struct_name #ty_generics #where_clause {
+                type RawVersion = #enum_name #ty_generics;
+
+                fn read_raw_version<Src>(src: &mut Src, header: Src::Header) -> ::std::result::Result<Self::RawVersion, Src::Error>
+                where
+                    Src: _aversion::group::DataSource,
+                {
+                    use _aversion::group::GroupHeader;
+
+                    let ver = header.msg_ver();
+                    match ver {
+                        #(#read_arms)*
+
+                        _ => Err(src.unknown_version::<#struct_base #ty_generics>(ver)),
+                    }
+                }
+            }
+        };
+    };
+    // proc_macro2::TokenStream -> proc_macro::TokenStream
+    expanded.into()
+}
+
+/// Derive the `Diffable` trait on a struct.
+///
+/// The struct must have named fields, and each field must implement
+/// `PartialEq`, `Serialize` and `DeserializeOwned`. Fields are compared
+/// with `==`, and the diff names each changed field by its Rust name, not
+/// its serde name. See `aversion::util::diff`.
+///
+#[proc_macro_derive(Diffable)]
+pub fn derive_diffable(input: TokenStream) -> TokenStream {
+    // parse the input into a DeriveInput syntax tree
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let struct_name = &input.ident;
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => panic!("Diffable can only be derived on a struct with named fields"),
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let idents = fields
+        .iter()
+        .map(|field| field.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
+    let names = idents
+        .iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>();
+    let types = fields.iter().map(|field| &field.ty);
+    // Changed values are decoded into temporaries first, so a bad diff
+    // leaves the struct unchanged.
+    let temps = idents
+        .iter()
+        .map(|ident| format_ident!("new_{}", ident))
+        .collect::<Vec<_>>();
+
+    let expanded = quote! {
+        #[doc(hidden)]
+        #[allow(
+            non_upper_case_globals,
+            unused_attributes,
+            unused_qualifications,
+            non_camel_case_types,
+            non_snake_case
+        )]
+        const _: () = {
+            #[allow(rust_2018_idioms, clippy::useless_attribute)]
+            extern crate aversion as _aversion;
+
+            #[automatically_derived]
+            impl #impl_generics _aversion::util::diff::Diffable
+            for #struct_name #ty_generics #where_clause {
+                fn diff(&self, target: &Self) -> ::std::result::Result<
+                    _aversion::util::diff::FieldDiff,
+                    _aversion::util::cbor::CborDataError,
+                > {
+                    let mut diff = _aversion::util::diff::FieldDiff::new();
+                    #(
+                        if self.#idents != target.#idents {
+                            diff.insert(#names, &target.#idents)?;
+                        }
+                    )*
+                    Ok(diff)
+                }
+
+                fn apply_diff(
+                    &mut self,
+                    diff: _aversion::util::diff::FieldDiff,
+                ) -> ::std::result::Result<(), _aversion::util::cbor::CborDataError> {
+                    #(
+                        let mut #temps: ::std::option::Option<#types> = None;
+                    )*
+                    for (name, value) in diff {
+                        match name.as_str() {
+                            #(
+                                #names => #temps = Some(_aversion::util::diff::decode_field(value)?),
+                            )*
+                            _ => {
+                                return Err(_aversion::util::cbor::CborDataError::UnknownDiffField {
+                                    field: name,
+                                })
+                            }
+                        }
+                    }
+                    #(
+                        if let Some(value) = #temps {
+                            self.#idents = value;
+                        }
+                    )*
+                    Ok(())
+                }
+            }
+        };
+    };
+    // proc_macro2::TokenStream -> proc_macro::TokenStream
+    expanded.into()
+}
+
 /// Derive the `GroupDeserialize` trait on a struct.
 ///
 /// This macro expects an enum as input, where each variant contains exactly
 /// one field: a type that implements `Versioned + MessageId`.
 ///
-#[proc_macro_derive(GroupDeserialize)]
+/// Message ids that are no longer in use can be marked with the
+/// `#[reserved(id, ...)]` and `#[deprecated_msg(id, "note")]` attributes
+/// on the enum.
+///
+/// The version of the group itself can be set with `#[group(version = N)]`,
+/// which sets `GroupDeserialize::GROUP_VERSION`.
+///
+/// This also implements `GroupAny`, which gives access to the message
+/// inside each variant as `dyn Any`.
+///
+#[proc_macro_derive(GroupDeserialize, attributes(reserved, deprecated_msg, group))]
 pub fn derive_group_deserialize(input: TokenStream) -> TokenStream {
     // parse the input into a DeriveInput syntax tree
     let input = parse_macro_input!(input as DeriveInput);
@@ -254,21 +972,33 @@ pub fn derive_group_deserialize(input: TokenStream) -> TokenStream {
     // The original generic parameters from the input struct
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let source_struct = input.data;
-    let variants = if let syn::Data::Enum(syn::DataEnum { variants, .. }) = source_struct {
-        variants
-    } else {
-        panic!("couldn't find enum variants");
-    };
+    let group_variants = group_variants(&input.data);
+    let retired_ids = RetiredIds::from_attrs(&input.attrs);
+    let group_version = group_version(&input.attrs).map(|version| {
+        quote! {
+            const GROUP_VERSION: u16 = #version;
+        }
+    });
 
-    let match_arms = variants
+    let match_arms = group_variants
         .iter()
-        .map(|v| {
-            // Extract basic data about this variant
-            let gv = GroupVariant::from_enum_variant(v);
-            // Write the GroupDeserialize match arm for this variant
-            gv.to_match_arm(enum_name)
-        })
+        .map(|gv| gv.to_match_arm(enum_name))
+        .collect::<Vec<_>>();
+    let retired_arms = retired_ids.to_match_arms();
+
+    let entries = group_variants
+        .iter()
+        .map(GroupVariant::to_entry)
+        .chain(retired_ids.to_entries())
+        .collect::<Vec<_>>();
+
+    let as_any_arms = group_variants
+        .iter()
+        .map(|gv| gv.to_any_arm(enum_name, false))
+        .collect::<Vec<_>>();
+    let into_any_arms = group_variants
+        .iter()
+        .map(|gv| gv.to_any_arm(enum_name, true))
         .collect::<Vec<_>>();
 
     let expanded = quote! {
@@ -287,20 +1017,127 @@ pub fn derive_group_deserialize(input: TokenStream) -> TokenStream {
             #[automatically_derived]
             impl #impl_generics _aversion::GroupDeserialize
             for #enum_name #ty_generics #where_clause {
+                #group_version
+
                 fn read_message<Src>(src: &mut Src) -> ::std::result::Result<Self, Src::Error>
                 where
                     Src: _aversion::group::DataSource,
+                {
+                    Self::read_message_with(src, |_| ::std::option::Option::None)
+                }
+
+                fn read_message_with<Src, F>(src: &mut Src, mut selector: F) -> ::std::result::Result<Self, Src::Error>
+                where
+                    Src: _aversion::group::DataSource,
+                    F: ::std::ops::FnMut(&Src::Header) -> ::std::option::Option<u32>,
                 {
                     use _aversion::{MessageId, group::{GroupHeader, UpgradeLatest}};
 
                     let header = src.read_header()?;
-                    match header.msg_id() {
+                    let msg_id = match selector(&header) {
+                        ::std::option::Option::Some(msg_id) => msg_id,
+                        ::std::option::Option::None => header.wide_msg_id(),
+                    };
+                    match msg_id {
+                        #(#retired_arms)*
                         #(#match_arms)*
                         _ => {
-                            Err(src.unknown_message(header.msg_id()))
+                            Err(src.unknown_wide_message(msg_id))
                         }
                     }
                 }
+
+                fn messages() -> &'static [_aversion::group::GroupEntry] {
+                    #[allow(unused_imports)]
+                    use _aversion::group::{
+                        EntryStatus, GroupEntry, NoPriority as _, NotReplayable as _, PriorityProbe,
+                        ReplayableProbe,
+                    };
+
+                    const MESSAGES: &[GroupEntry] = &[
+                        #(#entries),*
+                    ];
+                    // Fail to compile if two entries share a message id.
+                    const _: () = _aversion::group::check_unique_ids(MESSAGES);
+                    MESSAGES
+                }
+            }
+
+            #[automatically_derived]
+            impl #impl_generics _aversion::group::GroupAny
+            for #enum_name #ty_generics #where_clause {
+                fn as_any(&self) -> &dyn ::std::any::Any
+                where
+                    Self: 'static,
+                {
+                    match self {
+                        #(#as_any_arms)*
+                    }
+                }
+
+                fn into_any(self) -> ::std::boxed::Box<dyn ::std::any::Any>
+                where
+                    Self: 'static,
+                {
+                    match self {
+                        #(#into_any_arms)*
+                    }
+                }
+            }
+        };
+    };
+
+    // proc_macro2::TokenStream -> proc_macro::TokenStream
+    expanded.into()
+}
+
+/// Derive the `GroupSerialize` trait on an enum.
+///
+/// This macro expects an enum as input, where each variant contains exactly
+/// one field: a type that implements `Versioned + Serialize`, whose base type
+/// implements `MessageId`.
+///
+/// The `#[group(...)]` attribute is accepted but ignored; the group
+/// version is part of `GroupDeserialize`.
+///
+#[proc_macro_derive(GroupSerialize, attributes(reserved, deprecated_msg, group))]
+pub fn derive_group_serialize(input: TokenStream) -> TokenStream {
+    // parse the input into a DeriveInput syntax tree
+    let input = parse_macro_input!(input as DeriveInput);
+    let enum_name = &input.ident;
+
+    // The original generic parameters from the input struct
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let match_arms = group_variants(&input.data)
+        .iter()
+        .map(|gv| gv.to_write_arm(enum_name))
+        .collect::<Vec<_>>();
+
+    let expanded = quote! {
+        #[doc(hidden)]
+        #[allow(
+            non_upper_case_globals,
+            unused_attributes,
+            unused_qualifications,
+            non_camel_case_types,
+            non_snake_case
+        )]
+        const _: () = {
+            #[allow(rust_2018_idioms, clippy::useless_attribute)]
+            extern crate aversion as _aversion;
+
+            #[automatically_derived]
+            impl #impl_generics _aversion::GroupSerialize
+            for #enum_name #ty_generics #where_clause {
+                fn write_message<Snk>(&self, sink: &mut Snk) -> ::std::result::Result<(), Snk::Error>
+                where
+                    Snk: _aversion::group::DataSink,
+                {
+                    match self {
+                        #(#match_arms)*
+                    }
+                }
             }
         };
     };
@@ -309,6 +1146,20 @@ pub fn derive_group_deserialize(input: TokenStream) -> TokenStream {
     expanded.into()
 }
 
+/// Extract the variants of a message group enum.
+fn group_variants(data: &syn::Data) -> Vec<GroupVariant> {
+    let variants = if let syn::Data::Enum(syn::DataEnum { variants, .. }) = data {
+        variants
+    } else {
+        panic!("couldn't find enum variants");
+    };
+
+    variants
+        .iter()
+        .map(GroupVariant::from_enum_variant)
+        .collect()
+}
+
 #[derive(Debug)]
 struct GroupVariant {
     name: Ident,
@@ -345,12 +1196,282 @@ impl GroupVariant {
         let struct_name = &self.target;
 
         quote! {
-            #struct_name::MSG_ID => {
-                let msg = #struct_name::upgrade_latest(src, header)?;
+            <#struct_name as _aversion::MessageId>::WIDE_MSG_ID => {
+                let msg = <#struct_name as _aversion::group::UpgradeLatest>::upgrade_latest(src, header)?;
                 Ok(#enum_name::#enum_variant(msg))
             }
         }
     }
+
+    fn to_entry(&self) -> proc_macro2::TokenStream {
+        let name = self.name.to_string();
+        let struct_name = &self.target;
+
+        quote! {
+            GroupEntry {
+                msg_id: <#struct_name as _aversion::MessageId>::WIDE_MSG_ID,
+                name: ::std::option::Option::Some(#name),
+                status: EntryStatus::Active,
+                replayable: ReplayableProbe::<#struct_name>::IS_REPLAYABLE,
+                versions: <#struct_name as _aversion::group::UpgradeLatest>::SUPPORTED_VERSIONS,
+                priority: PriorityProbe::<#struct_name>::PRIORITY,
+            }
+        }
+    }
+
+    /// A match arm that converts the message to `dyn Any`, either by
+    /// reference or in a `Box`.
+    fn to_any_arm(&self, enum_name: &Ident, boxed: bool) -> proc_macro2::TokenStream {
+        let enum_variant = &self.name;
+
+        if boxed {
+            quote! {
+                #enum_name::#enum_variant(msg) => ::std::boxed::Box::new(msg),
+            }
+        } else {
+            quote! {
+                #enum_name::#enum_variant(msg) => msg,
+            }
+        }
+    }
+
+    fn to_write_arm(&self, enum_name: &Ident) -> proc_macro2::TokenStream {
+        let enum_variant = &self.name;
+
+        quote! {
+            #enum_name::#enum_variant(msg) => sink.write_message(msg),
+        }
+    }
+}
+
+/// Message ids that a group no longer uses.
+///
+/// These are specified by `#[reserved(...)]` and `#[deprecated_msg(...)]`
+/// attributes on a group enum.
+#[derive(Debug, Default)]
+struct RetiredIds {
+    reserved: Vec<LitInt>,
+    deprecated: Vec<(LitInt, LitStr)>,
+}
+
+/// Options from `#[versioned(...)]` attributes on a struct.
+#[derive(Default)]
+struct VersionedAttrs {
+    minor: Option<LitInt>,
+    additive: bool,
+    latest: bool,
+    legacy: bool,
+    by_ref: bool,
+    fixed_size: Option<LitInt>,
+}
+
+impl VersionedAttrs {
+    fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut options = VersionedAttrs::default();
+        for arg in versioned_args(attrs) {
+            match arg {
+                Meta::NameValue(arg) if arg.path.is_ident("minor") => match arg.lit {
+                    Lit::Int(minor) => options.minor = Some(minor),
+                    _ => panic!("expected #[versioned(minor = N)]"),
+                },
+                Meta::NameValue(arg) if arg.path.is_ident("fixed_size") => match arg.lit {
+                    Lit::Int(size) => options.fixed_size = Some(size),
+                    _ => panic!("expected #[versioned(fixed_size = N)]"),
+                },
+                Meta::Path(path) if path.is_ident("additive") => options.additive = true,
+                Meta::Path(path) if path.is_ident("latest") => options.latest = true,
+                Meta::Path(path) if path.is_ident("legacy") => options.legacy = true,
+                Meta::Path(path) if path.is_ident("by_ref") => options.by_ref = true,
+                _ => panic!("unknown versioned option"),
+            }
+        }
+        options
+    }
+
+    /// The oldest version that can be upgraded: 0 for a struct marked
+    /// `#[versioned(legacy)]`, otherwise 1.
+    fn first_version(attrs: &[Attribute]) -> u16 {
+        if Self::from_attrs(attrs).legacy {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Parse the arguments of all `#[versioned(...)]` attributes.
+fn versioned_args(attrs: &[Attribute]) -> Vec<Meta> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("versioned"))
+        .flat_map(|attr| {
+            attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .expect("expected #[versioned(option, ...)]")
+        })
+        .collect()
+}
+
+/// Returns the `N` from `#[group(version = N)]`, if present.
+fn group_version(attrs: &[Attribute]) -> Option<LitInt> {
+    let mut version = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("group")) {
+        let args = attr
+            .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .expect("expected #[group(version = N)]");
+        for arg in args {
+            match arg {
+                Meta::NameValue(arg) if arg.path.is_ident("version") => match arg.lit {
+                    Lit::Int(n) => version = Some(n),
+                    _ => panic!("expected #[group(version = N)]"),
+                },
+                _ => panic!("unknown group option"),
+            }
+        }
+    }
+    version
+}
+
+/// Returns `true` if the attributes contain `#[serde(default)]` or
+/// `#[serde(default = "...")]`.
+fn has_serde_default(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .any(|meta| meta.path().is_ident("default"))
+}
+
+/// Returns `true` if the type is `Option<...>`.
+///
+/// serde fills in a missing `Option` field with `None`.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => {
+            matches!(path.path.segments.last(), Some(segment) if segment.ident == "Option")
+        }
+        _ => false,
+    }
+}
+
+/// Check the fields marked `#[versioned(added)]`.
+///
+/// Fields that were added without a version bump must be missing from
+/// older messages, so they need a default value.
+fn check_added_fields(input: &DeriveInput, additive: bool) {
+    let fields = match &input.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => return,
+    };
+    let container_default = has_serde_default(&input.attrs);
+    for (index, field) in fields.iter().enumerate() {
+        let mut added = false;
+        for arg in versioned_args(&field.attrs) {
+            match arg {
+                Meta::Path(path) if path.is_ident("added") => added = true,
+                _ => panic!("unknown versioned field option"),
+            }
+        }
+        if !added {
+            continue;
+        }
+        let name = match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => index.to_string(),
+        };
+        if !additive {
+            panic!(
+                "field `{}` is marked #[versioned(added)], but the struct isn't #[versioned(additive)]",
+                name
+            );
+        }
+        if !(container_default || has_serde_default(&field.attrs) || is_option(&field.ty)) {
+            panic!(
+                "field `{}` was added without a version bump, so it needs #[serde(default)] or an Option type",
+                name
+            );
+        }
+    }
+}
+
+/// Parse the arguments to `#[deprecated_msg(id, "note")]`.
+struct DeprecatedArgs {
+    msg_id: LitInt,
+    note: LitStr,
+}
+
+impl Parse for DeprecatedArgs {
+    fn parse(input: ParseStream) -> syn::parse::Result<Self> {
+        let msg_id: LitInt = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let note: LitStr = input.parse()?;
+        Ok(DeprecatedArgs { msg_id, note })
+    }
+}
+
+impl RetiredIds {
+    fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut retired = RetiredIds::default();
+        for attr in attrs {
+            if attr.path.is_ident("reserved") {
+                let ids = attr
+                    .parse_args_with(Punctuated::<LitInt, Token![,]>::parse_terminated)
+                    .expect("expected #[reserved(id, ...)]");
+                retired.reserved.extend(ids);
+            } else if attr.path.is_ident("deprecated_msg") {
+                let args: DeprecatedArgs = attr
+                    .parse_args()
+                    .expect("expected #[deprecated_msg(id, \"note\")]");
+                retired.deprecated.push((args.msg_id, args.note));
+            }
+        }
+        retired
+    }
+
+    fn to_match_arms(&self) -> Vec<proc_macro2::TokenStream> {
+        let reserved = self.reserved.iter().map(|msg_id| {
+            quote! {
+                #msg_id => Err(src.deprecated_wide_message(#msg_id, "reserved")),
+            }
+        });
+        let deprecated = self.deprecated.iter().map(|(msg_id, note)| {
+            quote! {
+                #msg_id => Err(src.deprecated_wide_message(#msg_id, #note)),
+            }
+        });
+        reserved.chain(deprecated).collect()
+    }
+
+    fn to_entries(&self) -> Vec<proc_macro2::TokenStream> {
+        let reserved = self.reserved.iter().map(|msg_id| {
+            quote! {
+                GroupEntry {
+                    msg_id: #msg_id,
+                    name: ::std::option::Option::None,
+                    status: EntryStatus::Reserved,
+                    replayable: false,
+                    versions: &[],
+                    priority: 0,
+                }
+            }
+        });
+        let deprecated = self.deprecated.iter().map(|(msg_id, note)| {
+            quote! {
+                GroupEntry {
+                    msg_id: #msg_id,
+                    name: ::std::option::Option::None,
+                    status: EntryStatus::Deprecated(#note),
+                    replayable: false,
+                    versions: &[],
+                    priority: 0,
+                }
+            }
+        });
+        reserved.chain(deprecated).collect()
+    }
 }
 
 // The documentation for this macro is in aversion/src/lib.rs,
@@ -442,6 +1563,18 @@ impl MessageIdValue {
     fn to_impl(&self) -> proc_macro2::TokenStream {
         let name = &self.name;
         let msg_id = &self.msg_id;
+        if msg_id.suffix() == "u32" {
+            // A 32-bit id; MSG_ID is the low 16 bits.
+            let wide: u32 = msg_id.base10_parse().expect("expected a u32 message id");
+            let low = Literal::u16_unsuffixed((wide & 0xffff) as u16);
+            return quote! {
+                #[automatically_derived]
+                impl _aversion::MessageId for #name {
+                    const MSG_ID: u16 = #low;
+                    const WIDE_MSG_ID: u32 = #msg_id;
+                }
+            };
+        }
         quote! {
             #[automatically_derived]
             impl _aversion::MessageId for #name {
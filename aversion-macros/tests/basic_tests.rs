@@ -52,3 +52,82 @@ fn basic() {
         }
     )
 }
+
+#[test]
+#[allow(dead_code)]
+fn schema_hash() {
+    mod original {
+        use aversion::Versioned;
+
+        pub type Thing = ThingV1;
+
+        #[derive(Versioned)]
+        pub struct ThingV1 {
+            pub id: u32,
+            pub tags: Vec<String>,
+        }
+    }
+
+    mod reformatted {
+        use aversion::Versioned;
+
+        pub type Thing = ThingV1;
+
+        #[rustfmt::skip]
+        #[derive(Versioned)]
+        pub struct ThingV1 { pub id :u32, pub tags : Vec < String >, }
+    }
+
+    mod changed_type {
+        use aversion::Versioned;
+
+        pub type Thing = ThingV1;
+
+        #[derive(Versioned)]
+        pub struct ThingV1 {
+            pub id: u64,
+            pub tags: Vec<String>,
+        }
+    }
+
+    mod changed_name {
+        use aversion::Versioned;
+
+        pub type Thing = ThingV1;
+
+        #[derive(Versioned)]
+        pub struct ThingV1 {
+            pub ident: u32,
+            pub tags: Vec<String>,
+        }
+    }
+
+    // The hash must not change between releases, or peers running
+    // different builds will disagree.
+    let hash = original::ThingV1::SCHEMA_HASH;
+    assert_eq!(hash, 0xea34_4a73_acd4_3dd2);
+    assert_eq!(hash, reformatted::ThingV1::SCHEMA_HASH);
+    assert_ne!(hash, changed_type::ThingV1::SCHEMA_HASH);
+    assert_ne!(hash, changed_name::ThingV1::SCHEMA_HASH);
+}
+
+#[test]
+#[allow(dead_code)]
+fn tuple_struct() {
+    type Timestamp = TimestampV2;
+    type Pair = PairV1;
+
+    #[derive(Versioned)]
+    struct TimestampV1(u32);
+
+    #[derive(Versioned)]
+    struct TimestampV2(u64);
+
+    #[derive(Versioned)]
+    struct PairV1(u32, u32);
+
+    assert_eq!(TimestampV1::VER, 1);
+    assert_eq!(TimestampV2::VER, 2);
+    assert_eq!(PairV1::VER, 1);
+    assert_ne!(TimestampV1::SCHEMA_HASH, TimestampV2::SCHEMA_HASH);
+}
@@ -0,0 +1,112 @@
+//! Compare `write_message` with `write_message_batched`.
+//!
+//! `write_message` encodes each message body into a new `Vec`, while
+//! `write_message_batched` reuses the buffer in a `BatchEncoder`. The
+//! number of allocations per message is printed before the timings:
+//! about 4 for `write_message` (the body `Vec` grows as it's encoded and
+//! again when the header is added), and 0 for `write_message_batched`
+//! once its buffer has grown to fit a frame.
+
+use aversion::group::DataSink;
+use aversion::util::cbor::{BatchEncoder, CborData};
+use aversion::{assign_message_ids, Versioned};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A global allocator that counts allocations.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const MESSAGES: usize = 100_000;
+
+#[derive(Debug, Serialize, Versioned)]
+struct RowV1 {
+    id: u64,
+    count: u32,
+    score: f64,
+    flags: [u8; 4],
+}
+
+type Row = RowV1;
+
+assign_message_ids! {
+    Row: 1,
+}
+
+fn row(id: usize) -> RowV1 {
+    RowV1 {
+        id: id as u64,
+        count: 7,
+        score: 0.5,
+        flags: [1, 2, 3, 4],
+    }
+}
+
+/// The output buffer is allocated up front, so that only the message
+/// bodies are counted.
+fn sink() -> CborData<Vec<u8>> {
+    CborData::new(Vec::with_capacity(MESSAGES * 64))
+}
+
+fn write_each() {
+    let mut sink = sink();
+    for id in 0..MESSAGES {
+        sink.write_message(&row(id)).unwrap();
+    }
+    black_box(sink.into_inner());
+}
+
+fn write_batched() {
+    let mut sink = sink();
+    let mut encoder = BatchEncoder::new();
+    for id in 0..MESSAGES {
+        sink.write_message_batched(&mut encoder, &row(id)).unwrap();
+    }
+    black_box(sink.into_inner());
+}
+
+fn allocations_per_message(f: impl Fn()) -> f64 {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let after = ALLOCATIONS.load(Ordering::Relaxed);
+    (after - before) as f64 / MESSAGES as f64
+}
+
+fn bench_batch(c: &mut Criterion) {
+    println!(
+        "allocations per message: write_message {:.3}, write_message_batched {:.3}",
+        allocations_per_message(write_each),
+        allocations_per_message(write_batched),
+    );
+
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    group.bench_function("write_message", |b| b.iter(write_each));
+    group.bench_function("write_message_batched", |b| b.iter(write_batched));
+    group.finish();
+}
+
+criterion_group!(benches, bench_batch);
+criterion_main!(benches);
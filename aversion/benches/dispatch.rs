@@ -0,0 +1,192 @@
+//! Compare the derived `GroupDeserialize` dispatch with other ways of
+//! routing messages by id, for a group with 64 messages.
+//!
+//! - `derived`: the `match` generated by `#[derive(GroupDeserialize)]`.
+//! - `linear`: a chain of `if msg_id == ...` comparisons.
+//! - `dyn_group`: a [`DynGroup`], which looks up a boxed decode function
+//!   in a `HashMap` and returns a `Box<dyn Any>`.
+//!
+//! Message bodies are unit structs, so that the cost of dispatch isn't
+//! hidden by the cost of decoding.
+
+use aversion::group::{DataSink, DataSource, DynGroup, GroupHeader};
+use aversion::util::cbor::{CborData, SliceSource};
+use aversion::{assign_message_ids, GroupDeserialize, MessageId, UpgradeLatest, Versioned};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde::{Deserialize, Serialize};
+
+macro_rules! big_group {
+    ($($name:ident = $v1:ident: $id:literal,)*) => {
+        $(
+            #[derive(Debug, Serialize, Deserialize, Versioned, UpgradeLatest)]
+            struct $v1;
+            type $name = $v1;
+        )*
+
+        assign_message_ids! {
+            $($name: $id,)*
+        }
+
+        #[derive(Debug, GroupDeserialize)]
+        enum BigGroup {
+            $($name($name),)*
+        }
+
+        /// Write one of each message.
+        fn write_each(sink: &mut impl DataSink) {
+            $(
+                sink.write_message(&$v1).ok().unwrap();
+            )*
+        }
+
+        fn read_linear<Src>(src: &mut Src) -> Result<BigGroup, Src::Error>
+        where
+            Src: DataSource,
+        {
+            let header = src.read_header()?;
+            let msg_id = header.msg_id();
+            $(
+                if msg_id == $name::MSG_ID {
+                    return <$name as aversion::group::UpgradeLatest>::upgrade_latest(src, header).map(BigGroup::$name);
+                }
+            )*
+            Err(src.unknown_message(msg_id))
+        }
+
+        fn dyn_group<Src>() -> DynGroup<Src>
+        where
+            Src: DataSource,
+        {
+            let mut group = DynGroup::new();
+            $(
+                group.register::<$name>();
+            )*
+            group
+        }
+    };
+}
+
+big_group! {
+    Msg00 = Msg00V1: 1,
+    Msg01 = Msg01V1: 2,
+    Msg02 = Msg02V1: 3,
+    Msg03 = Msg03V1: 4,
+    Msg04 = Msg04V1: 5,
+    Msg05 = Msg05V1: 6,
+    Msg06 = Msg06V1: 7,
+    Msg07 = Msg07V1: 8,
+    Msg08 = Msg08V1: 9,
+    Msg09 = Msg09V1: 10,
+    Msg10 = Msg10V1: 11,
+    Msg11 = Msg11V1: 12,
+    Msg12 = Msg12V1: 13,
+    Msg13 = Msg13V1: 14,
+    Msg14 = Msg14V1: 15,
+    Msg15 = Msg15V1: 16,
+    Msg16 = Msg16V1: 17,
+    Msg17 = Msg17V1: 18,
+    Msg18 = Msg18V1: 19,
+    Msg19 = Msg19V1: 20,
+    Msg20 = Msg20V1: 21,
+    Msg21 = Msg21V1: 22,
+    Msg22 = Msg22V1: 23,
+    Msg23 = Msg23V1: 24,
+    Msg24 = Msg24V1: 25,
+    Msg25 = Msg25V1: 26,
+    Msg26 = Msg26V1: 27,
+    Msg27 = Msg27V1: 28,
+    Msg28 = Msg28V1: 29,
+    Msg29 = Msg29V1: 30,
+    Msg30 = Msg30V1: 31,
+    Msg31 = Msg31V1: 32,
+    Msg32 = Msg32V1: 33,
+    Msg33 = Msg33V1: 34,
+    Msg34 = Msg34V1: 35,
+    Msg35 = Msg35V1: 36,
+    Msg36 = Msg36V1: 37,
+    Msg37 = Msg37V1: 38,
+    Msg38 = Msg38V1: 39,
+    Msg39 = Msg39V1: 40,
+    Msg40 = Msg40V1: 41,
+    Msg41 = Msg41V1: 42,
+    Msg42 = Msg42V1: 43,
+    Msg43 = Msg43V1: 44,
+    Msg44 = Msg44V1: 45,
+    Msg45 = Msg45V1: 46,
+    Msg46 = Msg46V1: 47,
+    Msg47 = Msg47V1: 48,
+    Msg48 = Msg48V1: 49,
+    Msg49 = Msg49V1: 50,
+    Msg50 = Msg50V1: 51,
+    Msg51 = Msg51V1: 52,
+    Msg52 = Msg52V1: 53,
+    Msg53 = Msg53V1: 54,
+    Msg54 = Msg54V1: 55,
+    Msg55 = Msg55V1: 56,
+    Msg56 = Msg56V1: 57,
+    Msg57 = Msg57V1: 58,
+    Msg58 = Msg58V1: 59,
+    Msg59 = Msg59V1: 60,
+    Msg60 = Msg60V1: 61,
+    Msg61 = Msg61V1: 62,
+    Msg62 = Msg62V1: 63,
+    Msg63 = Msg63V1: 64,
+}
+
+/// One of each message, repeated.
+fn encoded() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    for _ in 0..16 {
+        write_each(&mut sink);
+    }
+    sink.into_inner()
+}
+
+fn count_messages(buf: &[u8]) -> u64 {
+    let mut src = SliceSource::new(buf);
+    let mut count = 0;
+    while !src.remaining().is_empty() {
+        BigGroup::read_message(&mut src).unwrap();
+        count += 1;
+    }
+    count
+}
+
+fn bench_dispatch(c: &mut Criterion) {
+    let buf = encoded();
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(count_messages(&buf)));
+
+    group.bench_function("derived", |b| {
+        b.iter(|| {
+            let mut src = SliceSource::new(&buf);
+            while !src.remaining().is_empty() {
+                black_box(BigGroup::read_message(&mut src).unwrap());
+            }
+        })
+    });
+
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            let mut src = SliceSource::new(&buf);
+            while !src.remaining().is_empty() {
+                black_box(read_linear(&mut src).unwrap());
+            }
+        })
+    });
+
+    let dyn_group = dyn_group();
+    group.bench_function("dyn_group", |b| {
+        b.iter(|| {
+            let mut src = SliceSource::new(&buf);
+            while !src.remaining().is_empty() {
+                black_box(dyn_group.read(&mut src).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_dispatch);
+criterion_main!(benches);
@@ -0,0 +1,170 @@
+//! Compare `read_header` with `read_header_into`, for a header type that
+//! owns heap data.
+//!
+//! `TracedHeader` is an [`ExtendedHeader`] followed by a variable-length
+//! trace id, which is stored in a `Vec`. `read_header` allocates a new
+//! `Vec` for every message, while `read_header_into` reuses the previous
+//! header's allocation. The number of allocations per message is printed
+//! before the timings.
+
+use aversion::group::{DataSink, DataSource, GroupHeader};
+use aversion::util::cbor::CborData;
+use aversion::util::{ExtendedHeader, FramedHeader};
+use aversion::{assign_message_ids, MessageId, UpgradeLatest, Versioned};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A global allocator that counts allocations.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const TRACE_ID: &[u8] = b"0123456789abcdef0123456789abcdef";
+const MESSAGES: usize = 1024;
+
+#[derive(Debug)]
+struct TracedHeader {
+    ext: ExtendedHeader,
+    trace: Vec<u8>,
+}
+
+impl TracedHeader {
+    fn empty() -> Self {
+        TracedHeader {
+            ext: ExtendedHeader::new(0, 0, 0),
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl GroupHeader for TracedHeader {
+    fn msg_id(&self) -> u16 {
+        self.ext.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.ext.msg_ver
+    }
+}
+
+impl FramedHeader for TracedHeader {
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        TracedHeader {
+            ext: ExtendedHeader::for_msg(msg, msg_len).with_timestamp(1_600_000_000),
+            trace: TRACE_ID.to_vec(),
+        }
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.ext.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let mut header = TracedHeader::empty();
+        header.deserialize_into(r)?;
+        Ok(header)
+    }
+
+    fn deserialize_into(&mut self, r: &mut impl Read) -> Result<(), io::Error> {
+        self.ext = ExtendedHeader::deserialize_from(r)?;
+        let mut len = [0u8; 1];
+        r.read_exact(&mut len)?;
+        self.trace.resize(usize::from(len[0]), 0);
+        r.read_exact(&mut self.trace)
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        self.ext.serialize_into(w)?;
+        let len = u8::try_from(self.trace.len()).expect("trace id too long");
+        w.write_all(&[len])?;
+        w.write_all(&self.trace)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Versioned, UpgradeLatest)]
+struct PingV1;
+
+type Ping = PingV1;
+
+assign_message_ids! {
+    Ping: 1,
+}
+
+fn encoded() -> Vec<u8> {
+    let mut sink = CborData::<_, TracedHeader>::with_header(Vec::new());
+    for _ in 0..MESSAGES {
+        sink.write_message(&PingV1).unwrap();
+    }
+    sink.into_inner()
+}
+
+fn read_each(buf: &[u8]) {
+    let mut src = CborData::<_, TracedHeader>::with_header(buf);
+    for _ in 0..MESSAGES {
+        let header = src.read_header().unwrap();
+        src.skip_message(&header).unwrap();
+        black_box(&header);
+    }
+}
+
+fn read_into(buf: &[u8]) {
+    let mut src = CborData::<_, TracedHeader>::with_header(buf);
+    let mut header = TracedHeader::empty();
+    for _ in 0..MESSAGES {
+        src.read_header_into(&mut header).unwrap();
+        src.skip_message(&header).unwrap();
+        black_box(&header);
+    }
+}
+
+fn allocations_per_message(f: impl Fn(&[u8]), buf: &[u8]) -> f64 {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f(buf);
+    let after = ALLOCATIONS.load(Ordering::Relaxed);
+    (after - before) as f64 / MESSAGES as f64
+}
+
+fn bench_header(c: &mut Criterion) {
+    let buf = encoded();
+    println!(
+        "allocations per message: read_header {:.3}, read_header_into {:.3}",
+        allocations_per_message(read_each, &buf),
+        allocations_per_message(read_into, &buf),
+    );
+
+    let mut group = c.benchmark_group("header");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    group.bench_function("read_header", |b| b.iter(|| read_each(&buf)));
+    group.bench_function("read_header_into", |b| b.iter(|| read_into(&buf)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_header);
+criterion_main!(benches);
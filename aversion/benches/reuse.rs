@@ -0,0 +1,111 @@
+//! Compare `read_message` with `read_message_reuse`.
+//!
+//! `read_message` decodes each message into a new value, while
+//! `read_message_reuse` decodes into one value that is kept across the
+//! loop, reusing its `String` and `Vec` allocations. The number of
+//! allocations per message is printed before the timings.
+//!
+//! The dev-dependency on serde_derive enables its `deserialize_in_place`
+//! feature, so that the derived impl decodes each field in place.
+
+use aversion::group::{DataSink, DataSource};
+use aversion::util::cbor::CborData;
+use aversion::{assign_message_ids, Versioned};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A global allocator that counts allocations.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const MESSAGES: usize = 100_000;
+
+#[derive(Debug, Default, Serialize, Deserialize, Versioned)]
+struct SampleV1 {
+    id: u64,
+    name: String,
+    values: Vec<u32>,
+}
+
+type Sample = SampleV1;
+
+assign_message_ids! {
+    Sample: 1,
+}
+
+fn encode() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    for id in 0..MESSAGES {
+        let sample = SampleV1 {
+            id: id as u64,
+            name: format!("sample-{}", id % 100),
+            values: vec![7; 16],
+        };
+        sink.write_message(&sample).unwrap();
+    }
+    sink.into_inner()
+}
+
+fn read_each(buf: &[u8]) {
+    let mut src = CborData::new(buf);
+    while let Some(header) = src.try_read_header().unwrap() {
+        let sample: SampleV1 = src.read_message(&header).unwrap();
+        black_box(&sample);
+    }
+}
+
+fn read_reused(buf: &[u8]) {
+    let mut src = CborData::new(buf);
+    let mut sample = SampleV1::default();
+    while let Some(header) = src.try_read_header().unwrap() {
+        src.read_message_reuse(&header, &mut sample).unwrap();
+        black_box(&sample);
+    }
+}
+
+fn allocations_per_message(f: impl Fn()) -> f64 {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let after = ALLOCATIONS.load(Ordering::Relaxed);
+    (after - before) as f64 / MESSAGES as f64
+}
+
+fn bench_reuse(c: &mut Criterion) {
+    let buf = encode();
+    println!(
+        "allocations per message: read_message {:.3}, read_message_reuse {:.3}",
+        allocations_per_message(|| read_each(&buf)),
+        allocations_per_message(|| read_reused(&buf)),
+    );
+
+    let mut group = c.benchmark_group("reuse");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    group.bench_function("read_message", |b| b.iter(|| read_each(&buf)));
+    group.bench_function("read_message_reuse", |b| b.iter(|| read_reused(&buf)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_reuse);
+criterion_main!(benches);
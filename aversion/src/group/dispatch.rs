@@ -0,0 +1,200 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::Sender;
+
+/// A group enum whose messages can be accessed as `dyn Any`.
+///
+/// This is implemented by `#[derive(GroupDeserialize)]`, and is used by
+/// [`Dispatcher`] to route each message by its type.
+pub trait GroupAny {
+    /// Borrow the message inside this enum variant.
+    fn as_any(&self) -> &dyn Any
+    where
+        Self: 'static;
+
+    /// Move the message out of this enum variant.
+    fn into_any(self) -> Box<dyn Any>
+    where
+        Self: 'static;
+}
+
+/// An error returned by [`Dispatcher::feed`].
+pub enum DispatchError<G> {
+    /// No route is registered for this message's type, and there is no
+    /// fallback. The message is returned.
+    Unrouted(G),
+    /// The receiving end of the route's channel was dropped. The message
+    /// was lost.
+    Disconnected {
+        /// The name of the message type.
+        msg_type: &'static str,
+    },
+}
+
+// Implemented by hand so that `G` doesn't need to implement `Debug`.
+impl<G> fmt::Debug for DispatchError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DispatchError::Unrouted(_) => f.write_str("Unrouted(..)"),
+            DispatchError::Disconnected { msg_type } => f
+                .debug_struct("Disconnected")
+                .field("msg_type", msg_type)
+                .finish(),
+        }
+    }
+}
+
+impl<G> fmt::Display for DispatchError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DispatchError::Unrouted(_) => f.write_str("no route for message"),
+            DispatchError::Disconnected { msg_type } => {
+                write!(f, "receiver for {} was dropped", msg_type)
+            }
+        }
+    }
+}
+
+impl<G> std::error::Error for DispatchError<G> {}
+
+/// A route: a function that receives a message of one type.
+struct Route {
+    msg_type: &'static str,
+    /// Returns `false` if the receiver is gone.
+    send: Box<dyn FnMut(Box<dyn Any>) -> bool>,
+}
+
+/// Routes the messages of a group to a separate channel for each type.
+///
+/// Register a [`Sender`] for each message type with
+/// [`route`][Self::route] (or any function, with
+/// [`route_fn`][Self::route_fn]), and then pass decoded group values to
+/// [`feed`][Self::feed]. Messages of a type without a route are passed to
+/// the [`fallback`][Self::fallback] function, or returned as
+/// [`DispatchError::Unrouted`] if there isn't one.
+///
+/// ```
+/// # use aversion::group::{DataSink, Dispatcher};
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// # use std::sync::mpsc::channel;
+/// # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// # struct FooV1 { foo: u32 }
+/// # type Foo = FooV1;
+/// # assign_message_ids! { Foo: 1 }
+/// #[derive(GroupDeserialize)]
+/// enum MyGroup {
+///     Foo(Foo),
+/// }
+///
+/// let (tx, rx) = channel::<Foo>();
+/// let mut dispatcher = Dispatcher::<MyGroup>::new().route(tx);
+///
+/// # let mut sink = CborData::new(Vec::new());
+/// # sink.write_message(&FooV1 { foo: 1 }).unwrap();
+/// # let buf = sink.into_inner();
+/// let mut src = CborData::new(&buf[..]);
+/// dispatcher.feed(MyGroup::read_message(&mut src).unwrap()).unwrap();
+/// assert_eq!(rx.recv().unwrap(), FooV1 { foo: 1 });
+/// ```
+pub struct Dispatcher<G> {
+    routes: HashMap<TypeId, Route>,
+    fallback: Option<Box<dyn FnMut(G)>>,
+}
+
+impl<G> Default for Dispatcher<G>
+where
+    G: GroupAny + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> Dispatcher<G>
+where
+    G: GroupAny + 'static,
+{
+    /// Create a new `Dispatcher` with no routes.
+    pub fn new() -> Self {
+        Dispatcher {
+            routes: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    /// Send messages of type `T` to a channel.
+    ///
+    /// `T` is the type inside the group enum variant. A later route for
+    /// the same type replaces this one.
+    pub fn route<T>(self, sender: Sender<T>) -> Self
+    where
+        T: 'static,
+    {
+        self.route_with(move |msg: T| sender.send(msg).is_ok())
+    }
+
+    /// Pass messages of type `T` to a function.
+    ///
+    /// This can be used for other kinds of channels, e.g. async ones.
+    pub fn route_fn<T, F>(self, mut f: F) -> Self
+    where
+        T: 'static,
+        F: FnMut(T) + 'static,
+    {
+        self.route_with(move |msg: T| {
+            f(msg);
+            true
+        })
+    }
+
+    fn route_with<T, F>(mut self, mut send: F) -> Self
+    where
+        T: 'static,
+        F: FnMut(T) -> bool + 'static,
+    {
+        let route = Route {
+            msg_type: type_name::<T>(),
+            send: Box::new(move |msg: Box<dyn Any>| {
+                let msg = msg.downcast::<T>().expect("route has the wrong type");
+                send(*msg)
+            }),
+        };
+        self.routes.insert(TypeId::of::<T>(), route);
+        self
+    }
+
+    /// Pass messages that have no route to a function.
+    pub fn fallback<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(G) + 'static,
+    {
+        self.fallback = Some(Box::new(f));
+        self
+    }
+
+    /// Send a message to the route for its type.
+    pub fn feed(&mut self, msg: G) -> Result<(), DispatchError<G>> {
+        let type_id = <dyn Any>::type_id(msg.as_any());
+        match self.routes.get_mut(&type_id) {
+            Some(route) => {
+                if (route.send)(msg.into_any()) {
+                    Ok(())
+                } else {
+                    Err(DispatchError::Disconnected {
+                        msg_type: route.msg_type,
+                    })
+                }
+            }
+            None => match &mut self.fallback {
+                Some(fallback) => {
+                    fallback(msg);
+                    Ok(())
+                }
+                None => Err(DispatchError::Unrouted(msg)),
+            },
+        }
+    }
+}
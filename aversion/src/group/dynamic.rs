@@ -0,0 +1,404 @@
+use crate::group::{DataSource, GroupHeader, UpgradeLatest};
+use crate::MessageId;
+use serde::de::DeserializeOwned;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// A function that decodes one message type from a [`DataSource`].
+///
+/// The header has already been read; the function should read the
+/// message body and return it as a type-erased value.
+pub type DecodeFn<Src> = Box<
+    dyn Fn(
+        &mut Src,
+        <Src as DataSource>::Header,
+    ) -> Result<Box<dyn Any>, <Src as DataSource>::Error>,
+>;
+
+/// A message group that is assembled at runtime.
+///
+/// [`GroupDeserialize`] requires the full set of messages to be known at
+/// compile time. `DynGroup` is useful when that isn't possible, e.g. in a
+/// plugin system where handlers are registered by message id at runtime.
+///
+/// Each message id is registered along with a decode function.
+/// [`read`][Self::read] reads a header, calls the matching decode
+/// function, and returns the message as a `Box<dyn Any>`, which the
+/// caller can downcast to the concrete type.
+///
+/// Message ids are the full 32-bit ids from [`GroupHeader::wide_msg_id`].
+///
+/// ```
+/// # use aversion::group::DynGroup;
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+/// # use serde::Deserialize;
+/// # use std::io::Cursor;
+/// #[derive(Deserialize, Versioned, UpgradeLatest)]
+/// struct FooV1 {
+///     foo: u32,
+/// }
+/// type Foo = FooV1;
+/// # assign_message_ids! { Foo: 1 }
+///
+/// let mut group = DynGroup::<CborData<Cursor<Vec<u8>>>>::new();
+/// group.register::<Foo>();
+/// ```
+///
+/// [`GroupDeserialize`]: crate::GroupDeserialize
+pub struct DynGroup<Src>
+where
+    Src: DataSource,
+{
+    decoders: HashMap<u32, DecodeFn<Src>>,
+}
+
+impl<Src> DynGroup<Src>
+where
+    Src: DataSource,
+{
+    /// Create a new, empty `DynGroup`.
+    pub fn new() -> Self {
+        DynGroup {
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Register a message type.
+    ///
+    /// Messages with id `T::WIDE_MSG_ID` will be deserialized and upgraded
+    /// to the latest version using [`UpgradeLatest`].
+    ///
+    /// If a decoder was already registered for this message id, it
+    /// is replaced.
+    pub fn register<T>(&mut self)
+    where
+        T: MessageId + UpgradeLatest + 'static,
+    {
+        self.register_decoder(
+            T::WIDE_MSG_ID,
+            Box::new(|src, header| {
+                let msg = T::upgrade_latest(src, header)?;
+                Ok(Box::new(msg))
+            }),
+        );
+    }
+
+    /// Register a decode function for a message id.
+    ///
+    /// If a decoder was already registered for this message id, it
+    /// is replaced.
+    pub fn register_decoder(&mut self, msg_id: u32, decode_fn: DecodeFn<Src>) {
+        self.decoders.insert(msg_id, decode_fn);
+    }
+
+    /// Returns `true` if a decoder is registered for this message id.
+    pub fn contains(&self, msg_id: u32) -> bool {
+        self.decoders.contains_key(&msg_id)
+    }
+
+    /// Read the next message from the `DataSource`.
+    ///
+    /// This will read the message header, and if the message id is
+    /// registered, call its decode function.
+    /// Returns the message id and the decoded message.
+    ///
+    /// If the message id is not registered, the error from
+    /// [`DataSource::unknown_wide_message`] is returned.
+    pub fn read(&self, src: &mut Src) -> Result<(u32, Box<dyn Any>), Src::Error> {
+        let header = src.read_header()?;
+        let msg_id = header.wide_msg_id();
+        match self.decoders.get(&msg_id) {
+            Some(decode_fn) => {
+                let msg = decode_fn(src, header)?;
+                Ok((msg_id, msg))
+            }
+            None => Err(src.unknown_wide_message(msg_id)),
+        }
+    }
+}
+
+impl<Src> Default for DynGroup<Src>
+where
+    Src: DataSource,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An error returned by [`DynUpgradeRegistry::decode`].
+#[derive(Debug, Error)]
+pub enum DynUpgradeError<E> {
+    /// The `DataSource` returned an error.
+    #[error("failed to read a message")]
+    Source(#[source] E),
+    /// No decoder is registered for this message version.
+    #[error("unknown version {ver} of message {msg_id}")]
+    UnknownVersion {
+        /// The message id.
+        msg_id: u32,
+        /// The message version that was received.
+        ver: u16,
+    },
+    /// An upgrade function is missing between two registered versions.
+    #[error("no upgrade registered from version {ver} of message {msg_id}")]
+    MissingUpgrade {
+        /// The message id.
+        msg_id: u32,
+        /// The version that has no upgrade function.
+        ver: u16,
+    },
+    /// Upgrading would take more than `max_upgrade_steps` steps.
+    #[error("version {ver} of message {msg_id} needs too many upgrade steps")]
+    UpgradeTooDeep {
+        /// The message id.
+        msg_id: u32,
+        /// The message version that was received.
+        ver: u16,
+    },
+    /// An upgrade function's output isn't the type registered for the
+    /// next version.
+    #[error("upgrade from version {ver} of message {msg_id} returned the wrong type")]
+    TypeMismatch {
+        /// The message id.
+        msg_id: u32,
+        /// The version whose upgrade function returned the wrong type.
+        ver: u16,
+    },
+}
+
+type DynDecodeFn<Src> = Box<
+    dyn Fn(
+        &mut Src,
+        &<Src as DataSource>::Header,
+    ) -> Result<Box<dyn Any>, <Src as DataSource>::Error>,
+>;
+
+type DynUpgradeFn = Box<dyn Fn(Box<dyn Any>) -> Box<dyn Any>>;
+
+/// The first version to decode, and the upgrades to apply to it.
+type UpgradeChain<'a, Src> = (&'a DynVersion<Src>, Vec<&'a DynUpgradeFn>);
+
+/// One registered version of a message.
+struct DynVersion<Src>
+where
+    Src: DataSource,
+{
+    /// The type that this version is deserialized as.
+    type_id: TypeId,
+    decode_fn: DynDecodeFn<Src>,
+    /// The upgrade to the next version, and the type that it returns.
+    upgrade: Option<(TypeId, DynUpgradeFn)>,
+}
+
+/// The registered versions of one message id.
+struct DynVersions<Src>
+where
+    Src: DataSource,
+{
+    latest: Option<u16>,
+    versions: HashMap<u16, DynVersion<Src>>,
+}
+
+/// A message group whose upgrades are registered at runtime.
+///
+/// [`DynGroup`] relies on [`UpgradeLatest`], so every version of a
+/// message must be known at compile time. `DynUpgradeRegistry` is for
+/// cases where that isn't possible, e.g. message types loaded by a
+/// plugin. Each older version of a message is registered along with a
+/// function that upgrades it to the next version, and the latest version
+/// is registered with [`register_latest`][Self::register_latest].
+///
+/// [`decode`][Self::decode] reads a header, deserializes the version that
+/// was received, and then calls upgrade functions until it reaches the
+/// latest version, which it returns as a `Box<dyn Any>`.
+///
+/// As with [`DynGroup`], message ids are 32 bits wide.
+///
+/// ```
+/// # use aversion::group::DynUpgradeRegistry;
+/// # use aversion::util::cbor::CborData;
+/// # use serde::Deserialize;
+/// # use std::io::Cursor;
+/// #[derive(Deserialize)]
+/// struct FooV1 {
+///     foo: u32,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct FooV2 {
+///     foo: u64,
+/// }
+///
+/// let mut registry = DynUpgradeRegistry::<CborData<Cursor<Vec<u8>>>>::new();
+/// registry.register(1, 1, |v1: FooV1| FooV2 { foo: v1.foo.into() });
+/// registry.register_latest::<FooV2>(1, 2);
+/// assert_eq!(registry.latest_version(1), Some(2));
+/// ```
+pub struct DynUpgradeRegistry<Src>
+where
+    Src: DataSource,
+{
+    messages: HashMap<u32, DynVersions<Src>>,
+}
+
+impl<Src> DynUpgradeRegistry<Src>
+where
+    Src: DataSource + 'static,
+{
+    /// Create a new, empty `DynUpgradeRegistry`.
+    pub fn new() -> Self {
+        DynUpgradeRegistry {
+            messages: HashMap::new(),
+        }
+    }
+
+    fn insert<T>(&mut self, msg_id: u32, ver: u16, upgrade: Option<(TypeId, DynUpgradeFn)>)
+    where
+        T: DeserializeOwned + 'static,
+    {
+        let version = DynVersion {
+            type_id: TypeId::of::<T>(),
+            decode_fn: Box::new(|src: &mut Src, header: &Src::Header| {
+                let msg: T = src.read_message(header)?;
+                Ok(Box::new(msg) as Box<dyn Any>)
+            }),
+            upgrade,
+        };
+        self.messages
+            .entry(msg_id)
+            .or_insert_with(|| DynVersions {
+                latest: None,
+                versions: HashMap::new(),
+            })
+            .versions
+            .insert(ver, version);
+    }
+
+    /// Register an older version of a message.
+    ///
+    /// Version `ver` of message `msg_id` will be deserialized as `T`, and
+    /// `upgrade_fn` will convert it to version `ver + 1`. `U` must be the
+    /// type registered for that version.
+    ///
+    /// If this version was already registered, it is replaced.
+    pub fn register<T, U, F>(&mut self, msg_id: u32, ver: u16, upgrade_fn: F)
+    where
+        T: DeserializeOwned + 'static,
+        U: 'static,
+        F: Fn(T) -> U + 'static,
+    {
+        let upgrade_fn: DynUpgradeFn = Box::new(move |msg| {
+            // `decode` checks the types before calling this.
+            let msg = msg.downcast::<T>().expect("upgrade input type");
+            Box::new(upgrade_fn(*msg))
+        });
+        self.insert::<T>(msg_id, ver, Some((TypeId::of::<U>(), upgrade_fn)));
+    }
+
+    /// Register the latest version of a message.
+    ///
+    /// Version `ver` of message `msg_id` will be deserialized as `T`, and
+    /// older versions will be upgraded to `T`.
+    ///
+    /// If this version was already registered, it is replaced. Any newer
+    /// versions are ignored.
+    pub fn register_latest<T>(&mut self, msg_id: u32, ver: u16)
+    where
+        T: DeserializeOwned + 'static,
+    {
+        self.insert::<T>(msg_id, ver, None);
+        if let Some(versions) = self.messages.get_mut(&msg_id) {
+            versions.latest = Some(ver);
+        }
+    }
+
+    /// Returns `true` if any version of this message id is registered.
+    pub fn contains(&self, msg_id: u32) -> bool {
+        self.messages.contains_key(&msg_id)
+    }
+
+    /// Returns the latest registered version of a message id.
+    pub fn latest_version(&self, msg_id: u32) -> Option<u16> {
+        self.messages.get(&msg_id)?.latest
+    }
+
+    /// Read the next message from the `DataSource`, and upgrade it to
+    /// the latest version.
+    ///
+    /// If the message id is not registered, the error from
+    /// [`DataSource::unknown_wide_message`] is returned. The chain of upgrade
+    /// functions is checked before the message body is read; if it's
+    /// incomplete, or the types don't line up, the body is skipped with
+    /// [`DataSource::skip_message`], so that the next message can be read.
+    pub fn decode(&self, src: &mut Src) -> Result<Box<dyn Any>, DynUpgradeError<Src::Error>> {
+        let header = src.read_header().map_err(DynUpgradeError::Source)?;
+        let msg_id = header.wide_msg_id();
+        let versions = match self.messages.get(&msg_id) {
+            Some(versions) => versions,
+            None => return Err(DynUpgradeError::Source(src.unknown_wide_message(msg_id))),
+        };
+        let (first, upgrades) = match Self::upgrade_chain(
+            versions,
+            msg_id,
+            header.msg_ver(),
+            src.max_upgrade_steps(),
+        ) {
+            Ok(chain) => chain,
+            Err(e) => {
+                src.skip_message(&header).map_err(DynUpgradeError::Source)?;
+                return Err(e);
+            }
+        };
+
+        let mut msg = (first.decode_fn)(src, &header).map_err(DynUpgradeError::Source)?;
+        for upgrade_fn in upgrades {
+            msg = upgrade_fn(msg);
+        }
+        Ok(msg)
+    }
+
+    /// Find the decoder for version `ver`, and the upgrade functions that
+    /// lead from it to the latest version.
+    fn upgrade_chain(
+        versions: &DynVersions<Src>,
+        msg_id: u32,
+        ver: u16,
+        max_steps: u16,
+    ) -> Result<UpgradeChain<'_, Src>, DynUpgradeError<Src::Error>> {
+        let unknown_version = || DynUpgradeError::UnknownVersion { msg_id, ver };
+        let latest = match versions.latest {
+            Some(latest) if ver <= latest => latest,
+            _ => return Err(unknown_version()),
+        };
+        let first = versions.versions.get(&ver).ok_or_else(unknown_version)?;
+        if latest - ver > max_steps {
+            return Err(DynUpgradeError::UpgradeTooDeep { msg_id, ver });
+        }
+
+        let mut upgrades = Vec::new();
+        let mut current = first;
+        for v in ver..latest {
+            let missing = || DynUpgradeError::MissingUpgrade { msg_id, ver: v };
+            let (output, upgrade_fn) = current.upgrade.as_ref().ok_or_else(missing)?;
+            let next = versions.versions.get(&(v + 1)).ok_or_else(missing)?;
+            if *output != next.type_id {
+                return Err(DynUpgradeError::TypeMismatch { msg_id, ver: v });
+            }
+            upgrades.push(upgrade_fn);
+            current = next;
+        }
+        Ok((first, upgrades))
+    }
+}
+
+impl<Src> Default for DynUpgradeRegistry<Src>
+where
+    Src: DataSource + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
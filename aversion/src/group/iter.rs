@@ -0,0 +1,398 @@
+use crate::group::{
+    DataSource, EntryStatus, GroupDeserialize, GroupHeader, IsTruncated, UpgradeLatest,
+};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+/// What an iterator does when the data ends in the middle of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Return the error, like any other error. This is the default.
+    Fatal,
+    /// End the iterator without an error, and mark it as incomplete.
+    ///
+    /// This is for reading data that is still being written, such as a
+    /// log file that is being tailed: the last message may only be
+    /// partly written, and can be read again later.
+    Retry,
+}
+
+/// An iterator over the messages of one type from a [`DataSource`].
+///
+/// This is returned by [`GroupDeserialize::iter_filter`].
+pub struct FilterIter<'a, G, T, Src>
+where
+    Src: DataSource,
+{
+    src: &'a mut Src,
+    skip_unknown: bool,
+    is_truncated: Option<fn(&Src::Error) -> bool>,
+    incomplete: bool,
+    done: bool,
+    _types: PhantomData<fn() -> (G, T)>,
+}
+
+impl<'a, G, T, Src> FilterIter<'a, G, T, Src>
+where
+    G: GroupDeserialize,
+    T: MessageId + UpgradeLatest,
+    Src: DataSource,
+{
+    pub(crate) fn new(src: &'a mut Src) -> Self {
+        FilterIter {
+            src,
+            skip_unknown: false,
+            is_truncated: None,
+            incomplete: false,
+            done: false,
+            _types: PhantomData,
+        }
+    }
+
+    /// Skip messages that aren't part of the group, instead of returning
+    /// an error.
+    pub fn skip_unknown(mut self, skip_unknown: bool) -> Self {
+        self.skip_unknown = skip_unknown;
+        self
+    }
+
+    /// Returns `true` if the iterator ended because the data was
+    /// truncated.
+    ///
+    /// This can only happen with [`TruncationPolicy::Retry`].
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    /// Get a mutable reference to the underlying `DataSource`.
+    ///
+    /// Between messages, this can be used to record the position of the
+    /// data, so that reading can resume there after a truncated message.
+    pub fn get_mut(&mut self) -> &mut Src {
+        self.src
+    }
+
+    fn next_message(&mut self) -> Result<Option<T>, Src::Error> {
+        loop {
+            let header = match self.src.try_read_header()? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            if header.wide_msg_id() == T::WIDE_MSG_ID {
+                return T::upgrade_latest(self.src, header).map(Some);
+            }
+            let msg_id = header.msg_id();
+            let wide_msg_id = header.wide_msg_id();
+            let entry = G::messages()
+                .iter()
+                .find(|entry| entry.msg_id == wide_msg_id);
+            match entry.map(|entry| entry.status) {
+                Some(EntryStatus::Active) => {}
+                _ if self.skip_unknown => {}
+                Some(EntryStatus::Reserved) => {
+                    return Err(self.src.deprecated_message(msg_id, "reserved"));
+                }
+                Some(EntryStatus::Deprecated(note)) => {
+                    return Err(self.src.deprecated_message(msg_id, note));
+                }
+                None => return Err(self.src.unknown_wide_message(wide_msg_id)),
+            }
+            self.src.skip_message(&header)?;
+        }
+    }
+}
+
+impl<'a, G, T, Src> FilterIter<'a, G, T, Src>
+where
+    G: GroupDeserialize,
+    T: MessageId + UpgradeLatest,
+    Src: DataSource,
+    Src::Error: IsTruncated,
+{
+    /// Choose what happens when the data ends in the middle of a message.
+    ///
+    /// With [`TruncationPolicy::Retry`], the iterator ends without an
+    /// error, and [`is_incomplete`][Self::is_incomplete] returns `true`.
+    /// The partial message has been consumed from the data source, so to
+    /// read it again, the caller must return to the position after the
+    /// last complete message:
+    ///
+    /// ```
+    /// # use aversion::group::{DataSink, TruncationPolicy};
+    /// # use aversion::util::cbor::CborData;
+    /// # use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+    /// # use serde::{Deserialize, Serialize};
+    /// # use std::io::Cursor;
+    /// # #[derive(Versioned, UpgradeLatest, Serialize, Deserialize)]
+    /// # struct EventV1 {}
+    /// # type Event = EventV1;
+    /// # assign_message_ids! { Event: 1 }
+    /// # #[derive(GroupDeserialize)]
+    /// # enum Log {
+    /// #     Event(Event),
+    /// # }
+    /// # let mut sink = CborData::new(Vec::new());
+    /// # sink.write_message(&EventV1 {}).unwrap();
+    /// # let mut buf = sink.into_inner();
+    /// # buf.extend_from_slice(&[0, 1]);
+    /// let mut src = CborData::new(Cursor::new(buf));
+    /// let mut iter = Log::iter_filter::<Event, _>(&mut src).on_truncation(TruncationPolicy::Retry);
+    /// let mut pos = 0;
+    /// while let Some(event) = iter.next() {
+    ///     let _event = event.unwrap();
+    ///     pos = iter.get_mut().get_ref().position();
+    /// }
+    /// if iter.is_incomplete() {
+    ///     // Try again later, from the end of the last complete message.
+    ///     src.get_mut().set_position(pos);
+    /// }
+    /// ```
+    pub fn on_truncation(mut self, policy: TruncationPolicy) -> Self {
+        self.is_truncated = match policy {
+            TruncationPolicy::Fatal => None,
+            TruncationPolicy::Retry => Some(|err: &Src::Error| err.is_truncated()),
+        };
+        self
+    }
+}
+
+impl<'a, G, T, Src> Iterator for FilterIter<'a, G, T, Src>
+where
+    G: GroupDeserialize,
+    T: MessageId + UpgradeLatest,
+    Src: DataSource,
+{
+    type Item = Result<T, Src::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_message().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        if let (Some(Err(err)), Some(is_truncated)) = (&result, self.is_truncated) {
+            if is_truncated(err) {
+                self.incomplete = true;
+                return None;
+            }
+        }
+        result
+    }
+}
+
+/// What [`RecoveryIter`] does after a message fails to decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Discard the message and continue with the next one.
+    ///
+    /// The header's length field is used to find the next message. If the
+    /// body wasn't read, it's skipped with [`DataSource::skip_message`];
+    /// if it was, the `DataSource` is expected to have consumed all of
+    /// it, as [`CborData`][crate::util::cbor::CborData] does.
+    Skip,
+    /// End the iterator without an error.
+    Stop,
+    /// Return the error, and end the iterator.
+    Propagate,
+}
+
+/// An iterator over every message from a [`DataSource`], which can
+/// recover from errors.
+///
+/// This is returned by [`GroupDeserialize::iter_messages_with_recovery`].
+pub struct RecoveryIter<'a, G, Src, F> {
+    src: &'a mut Src,
+    on_error: F,
+    skipped: usize,
+    done: bool,
+    _types: PhantomData<fn() -> G>,
+}
+
+impl<'a, G, Src, F> RecoveryIter<'a, G, Src, F>
+where
+    G: GroupDeserialize,
+    Src: DataSource,
+    Src::Header: Clone,
+    F: FnMut(&Src::Error, &Src::Header) -> Recovery,
+{
+    pub(crate) fn new(src: &'a mut Src, on_error: F) -> Self {
+        RecoveryIter {
+            src,
+            on_error,
+            skipped: 0,
+            done: false,
+            _types: PhantomData,
+        }
+    }
+
+    /// The number of messages that were skipped.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Get a mutable reference to the underlying `DataSource`.
+    pub fn get_mut(&mut self) -> &mut Src {
+        self.src
+    }
+
+    fn next_message(&mut self) -> Result<Option<G>, Src::Error> {
+        loop {
+            let header = match self.src.try_read_header()? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            let mut src = BodyTracker {
+                inner: &mut *self.src,
+                header: Some(header.clone()),
+                body_read: false,
+            };
+            let err = match G::read_message(&mut src) {
+                Ok(msg) => return Ok(Some(msg)),
+                Err(err) => err,
+            };
+            let body_read = src.body_read;
+            match (self.on_error)(&err, &header) {
+                Recovery::Skip => {
+                    if !body_read {
+                        self.src.skip_message(&header)?;
+                    }
+                    self.skipped += 1;
+                }
+                Recovery::Stop => return Ok(None),
+                Recovery::Propagate => return Err(err),
+            }
+        }
+    }
+}
+
+impl<'a, G, Src, F> Iterator for RecoveryIter<'a, G, Src, F>
+where
+    G: GroupDeserialize,
+    Src: DataSource,
+    Src::Header: Clone,
+    F: FnMut(&Src::Error, &Src::Header) -> Recovery,
+{
+    type Item = Result<G, Src::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_message().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// A `DataSource` that hands out one header that has already been read,
+/// and records whether the message body was read.
+struct BodyTracker<'a, Src>
+where
+    Src: DataSource,
+{
+    inner: &'a mut Src,
+    header: Option<Src::Header>,
+    body_read: bool,
+}
+
+impl<Src> DataSource for BodyTracker<'_, Src>
+where
+    Src: DataSource,
+{
+    type Error = Src::Error;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Src::Error> {
+        match self.header.take() {
+            Some(header) => Ok(header),
+            None => self.inner.read_header(),
+        }
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Src::Error> {
+        match self.header.take() {
+            Some(next) => {
+                *header = next;
+                Ok(())
+            }
+            None => self.inner.read_header_into(header),
+        }
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Src::Error> {
+        match &self.header {
+            Some(header) => Ok(header.msg_id()),
+            None => self.inner.peek_msg_id(),
+        }
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Src::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.body_read = true;
+        self.inner.read_message(header)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Src::Error> {
+        self.body_read = true;
+        self.inner.skip_message(header)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Src::Error> {
+        // The default implementation fails without reading anything.
+        let body = self.inner.read_raw(header);
+        self.body_read |= body.is_ok();
+        body
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Src::Error {
+        self.inner.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Src::Error {
+        self.inner.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Src::Error {
+        self.inner.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Src::Error {
+        self.inner.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Src::Error {
+        self.inner.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Src::Error
+    where
+        T: MessageId,
+    {
+        self.inner.unexpected_message::<T>(msg_id)
+    }
+}
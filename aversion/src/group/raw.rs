@@ -0,0 +1,223 @@
+use crate::group::{DataSource, GroupAny, GroupDeserialize, GroupEntry, GroupHeader};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use std::any::Any;
+
+/// A message that wasn't decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawMessage {
+    /// The message id.
+    ///
+    /// This is the full id from [`GroupHeader::wide_msg_id`].
+    pub id: u32,
+    /// The message version.
+    pub ver: u16,
+    /// The encoded message body.
+    pub bytes: Vec<u8>,
+}
+
+/// A message group that also accepts messages from outside the group.
+///
+/// Reading a `WithUnknown<G>` reads messages of group `G` as usual, but a
+/// message id that isn't part of `G` produces [`WithUnknown::Unknown`]
+/// instead of calling [`DataSource::unknown_message`]. The message body is
+/// captured with [`DataSource::read_raw`], so the application can log or
+/// forward it.
+///
+/// The group's message ids come from [`GroupDeserialize::messages`], so
+/// `G` should use `#[derive(GroupDeserialize)]`. Reserved and deprecated
+/// ids are still handled by `G`, and produce an error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WithUnknown<G> {
+    /// A message from the group.
+    Known(G),
+    /// A message with an id that isn't part of the group.
+    Unknown(RawMessage),
+}
+
+impl<G> GroupDeserialize for WithUnknown<G>
+where
+    G: GroupDeserialize,
+{
+    fn read_message<Src>(src: &mut Src) -> Result<Self, Src::Error>
+    where
+        Src: DataSource,
+    {
+        Self::read_message_with(src, |_| None)
+    }
+
+    fn read_message_with<Src, F>(src: &mut Src, mut selector: F) -> Result<Self, Src::Error>
+    where
+        Src: DataSource,
+        F: FnMut(&Src::Header) -> Option<u32>,
+    {
+        let header = src.read_header()?;
+        let selected = selector(&header);
+        let wide_msg_id = selected.unwrap_or_else(|| header.wide_msg_id());
+        if G::messages()
+            .iter()
+            .any(|entry| entry.msg_id == wide_msg_id)
+        {
+            let mut src = Prefetched::new(src, header);
+            let msg = G::read_message_with(&mut src, |_| selected)?;
+            Ok(WithUnknown::Known(msg))
+        } else {
+            let bytes = src.read_raw(&header)?;
+            Ok(WithUnknown::Unknown(RawMessage {
+                id: wide_msg_id,
+                ver: header.msg_ver(),
+                bytes,
+            }))
+        }
+    }
+
+    fn messages() -> &'static [GroupEntry] {
+        G::messages()
+    }
+}
+
+/// An unknown message is a [`RawMessage`].
+impl<G> GroupAny for WithUnknown<G>
+where
+    G: GroupAny,
+{
+    fn as_any(&self) -> &dyn Any
+    where
+        Self: 'static,
+    {
+        match self {
+            WithUnknown::Known(msg) => msg.as_any(),
+            WithUnknown::Unknown(raw) => raw,
+        }
+    }
+
+    fn into_any(self) -> Box<dyn Any>
+    where
+        Self: 'static,
+    {
+        match self {
+            WithUnknown::Known(msg) => msg.into_any(),
+            WithUnknown::Unknown(raw) => Box::new(raw),
+        }
+    }
+}
+
+/// A `DataSource` that returns a header that has already been read.
+pub(crate) struct Prefetched<'a, Src>
+where
+    Src: DataSource,
+{
+    src: &'a mut Src,
+    header: Option<Src::Header>,
+}
+
+impl<'a, Src> Prefetched<'a, Src>
+where
+    Src: DataSource,
+{
+    pub(crate) fn new(src: &'a mut Src, header: Src::Header) -> Self {
+        Prefetched {
+            src,
+            header: Some(header),
+        }
+    }
+}
+
+impl<'a, Src> DataSource for Prefetched<'a, Src>
+where
+    Src: DataSource,
+{
+    type Error = Src::Error;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Src::Error> {
+        match self.header.take() {
+            Some(header) => Ok(header),
+            None => self.src.read_header(),
+        }
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Src::Error> {
+        match self.header.take() {
+            Some(next) => {
+                *header = next;
+                Ok(())
+            }
+            None => self.src.read_header_into(header),
+        }
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<Src::Header>, Src::Error> {
+        match self.header.take() {
+            Some(header) => Ok(Some(header)),
+            None => self.src.try_read_header(),
+        }
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Src::Error> {
+        match &self.header {
+            Some(header) => Ok(header.msg_id()),
+            None => self.src.peek_msg_id(),
+        }
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Src::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.src.read_message(header)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Src::Error> {
+        self.src.skip_message(header)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Src::Error> {
+        self.src.read_raw(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Src::Error {
+        self.src.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Src::Error {
+        self.src.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Src::Error {
+        self.src.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Src::Error {
+        self.src.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Src::Error {
+        self.src.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.src.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.src.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.src.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Src::Error
+    where
+        T: MessageId,
+    {
+        self.src.unexpected_message::<T>(msg_id)
+    }
+}
@@ -0,0 +1,250 @@
+use crate::group::{DataSource, GroupDeserialize, GroupHeader};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use std::cell::Cell;
+use std::fmt;
+
+/// An error returned by [`GroupDeserialize::read_message_staged`], which
+/// records how far decoding got before it failed.
+///
+/// Each variant holds the error returned by the [`DataSource`], and the
+/// message header if it was read.
+///
+/// [`GroupDeserialize::read_message_staged`]: crate::group::GroupDeserialize::read_message_staged
+#[derive(Debug)]
+pub enum DecodeError<H, E> {
+    /// The message header couldn't be read.
+    Header(E),
+    /// The header was read, but the message id is unknown, reserved, or
+    /// deprecated.
+    UnknownMessage {
+        /// The message header.
+        header: H,
+        /// The error from the `DataSource`.
+        error: E,
+    },
+    /// The message id is known, but its version can't be upgraded.
+    UnsupportedVersion {
+        /// The message header.
+        header: H,
+        /// The error from the `DataSource`.
+        error: E,
+    },
+    /// The message type was found, but the body couldn't be decoded.
+    Body {
+        /// The message header.
+        header: H,
+        /// The error from the `DataSource`.
+        error: E,
+    },
+}
+
+impl<H, E> DecodeError<H, E> {
+    /// The message header, if it was read.
+    pub fn header(&self) -> Option<&H> {
+        match self {
+            DecodeError::Header(_) => None,
+            DecodeError::UnknownMessage { header, .. }
+            | DecodeError::UnsupportedVersion { header, .. }
+            | DecodeError::Body { header, .. } => Some(header),
+        }
+    }
+
+    /// The error from the `DataSource`.
+    pub fn error(&self) -> &E {
+        match self {
+            DecodeError::Header(error)
+            | DecodeError::UnknownMessage { error, .. }
+            | DecodeError::UnsupportedVersion { error, .. }
+            | DecodeError::Body { error, .. } => error,
+        }
+    }
+
+    /// Discard the stage and header, returning the error from the
+    /// `DataSource`.
+    pub fn into_error(self) -> E {
+        match self {
+            DecodeError::Header(error)
+            | DecodeError::UnknownMessage { error, .. }
+            | DecodeError::UnsupportedVersion { error, .. }
+            | DecodeError::Body { error, .. } => error,
+        }
+    }
+}
+
+impl<H, E> fmt::Display for DecodeError<H, E>
+where
+    H: GroupHeader,
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Header(error) => write!(f, "failed to read header: {}", error),
+            DecodeError::UnknownMessage { header, error } => {
+                write!(f, "unknown message id {}: {}", header.wide_msg_id(), error)
+            }
+            DecodeError::UnsupportedVersion { header, error } => write!(
+                f,
+                "unsupported version {} of message id {}: {}",
+                header.msg_ver(),
+                header.wide_msg_id(),
+                error
+            ),
+            DecodeError::Body { header, error } => write!(
+                f,
+                "failed to decode message id {} version {}: {}",
+                header.wide_msg_id(),
+                header.msg_ver(),
+                error
+            ),
+        }
+    }
+}
+
+impl<H, E> std::error::Error for DecodeError<H, E>
+where
+    H: GroupHeader + fmt::Debug,
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error())
+    }
+}
+
+/// How far a message got before decoding failed.
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    UnknownMessage,
+    UnsupportedVersion,
+    Body,
+}
+
+/// Read the next message, and attach the stage and header to any error.
+pub(crate) fn read_staged<G, Src>(src: &mut Src) -> Result<G, DecodeError<Src::Header, Src::Error>>
+where
+    G: GroupDeserialize,
+    Src: DataSource,
+    Src::Header: Clone,
+{
+    let header = src.read_header().map_err(DecodeError::Header)?;
+    let mut tracker = StageTracker {
+        inner: src,
+        header: Some(header.clone()),
+        stage: Cell::new(Stage::Body),
+    };
+    G::read_message(&mut tracker).map_err(|error| match tracker.stage.get() {
+        Stage::UnknownMessage => DecodeError::UnknownMessage { header, error },
+        Stage::UnsupportedVersion => DecodeError::UnsupportedVersion { header, error },
+        Stage::Body => DecodeError::Body { header, error },
+    })
+}
+
+/// A `DataSource` that hands out one header that has already been read,
+/// and records which error hook was called.
+struct StageTracker<'a, Src>
+where
+    Src: DataSource,
+{
+    inner: &'a mut Src,
+    header: Option<Src::Header>,
+    stage: Cell<Stage>,
+}
+
+impl<Src> DataSource for StageTracker<'_, Src>
+where
+    Src: DataSource,
+{
+    type Error = Src::Error;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Src::Error> {
+        match self.header.take() {
+            Some(header) => Ok(header),
+            None => self.inner.read_header(),
+        }
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Src::Error> {
+        match self.header.take() {
+            Some(next) => {
+                *header = next;
+                Ok(())
+            }
+            None => self.inner.read_header_into(header),
+        }
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Src::Error> {
+        match &self.header {
+            Some(header) => Ok(header.msg_id()),
+            None => self.inner.peek_msg_id(),
+        }
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Src::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.inner.read_message(header)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Src::Error> {
+        self.inner.skip_message(header)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Src::Error> {
+        self.inner.read_raw(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Src::Error {
+        self.inner.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Src::Error {
+        self.stage.set(Stage::UnknownMessage);
+        self.inner.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Src::Error {
+        self.stage.set(Stage::UnknownMessage);
+        self.inner.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Src::Error {
+        self.stage.set(Stage::UnknownMessage);
+        self.inner.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Src::Error {
+        self.stage.set(Stage::UnknownMessage);
+        self.inner.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.stage.set(Stage::UnsupportedVersion);
+        self.inner.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.stage.set(Stage::UnsupportedVersion);
+        self.inner.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Src::Error
+    where
+        T: MessageId,
+    {
+        self.stage.set(Stage::UnknownMessage);
+        self.inner.unexpected_message::<T>(msg_id)
+    }
+}
@@ -11,4 +11,80 @@ pub trait MessageId: Versioned {
     /// serialized. The same id will be used for all versions of
     /// this message type.
     const MSG_ID: u16;
+
+    /// The full message id, for protocols that need more than 16 bits.
+    ///
+    /// By default, this is the same as [`MSG_ID`][Self::MSG_ID]. To use a
+    /// 32-bit id, give it a `u32` suffix in [`assign_message_ids!`], e.g.
+    /// `Foo: 0x1_0000u32`; then `MSG_ID` is the low 16 bits of the id.
+    ///
+    /// Group dispatch compares this with
+    /// [`GroupHeader::wide_msg_id`][crate::group::GroupHeader::wide_msg_id].
+    /// Most headers only have room for 16 bits, so messages with a 32-bit
+    /// id must be written with a header that stores all of it, such as
+    /// [`WideHeader`][crate::util::WideHeader]; writing one with another
+    /// header is a compile error (see
+    /// [`FramedHeader::WIDE_IDS`][crate::util::FramedHeader::WIDE_IDS]).
+    ///
+    /// Dispatch selectors, [`RawMessage`], [`DynGroup`],
+    /// [`DynUpgradeRegistry`] and the schema manifest all use the full id.
+    /// A few APIs only see the low 16 bits: [`GroupHeader::msg_id`],
+    /// [`DataSource::peek_msg_id`], and the error hooks other than
+    /// [`DataSource::unknown_wide_message`] and
+    /// [`DataSource::deprecated_wide_message`]. [`CborDataError`] keeps the
+    /// full id.
+    ///
+    /// [`assign_message_ids!`]: crate::assign_message_ids
+    /// [`RawMessage`]: crate::group::RawMessage
+    /// [`DynGroup`]: crate::group::DynGroup
+    /// [`DynUpgradeRegistry`]: crate::group::DynUpgradeRegistry
+    /// [`GroupHeader::msg_id`]: crate::group::GroupHeader::msg_id
+    /// [`DataSource::peek_msg_id`]: crate::group::DataSource::peek_msg_id
+    /// [`DataSource::unknown_wide_message`]: crate::group::DataSource::unknown_wide_message
+    /// [`DataSource::deprecated_wide_message`]: crate::group::DataSource::deprecated_wide_message
+    /// [`CborDataError`]: crate::util::cbor::CborDataError
+    const WIDE_MSG_ID: u32 = Self::MSG_ID as u32;
+}
+
+/// An object-safe way to query a message's version and id.
+///
+/// [`Versioned::VER`] and [`MessageId::MSG_ID`] are associated constants,
+/// so they can't be read through a trait object. This trait is
+/// implemented for every message type (any version whose base type
+/// implements `MessageId`), so that type-erased messages can still
+/// report them, e.g. for logging.
+///
+/// ```
+/// # use aversion::{assign_message_ids, Versioned, VersionedDyn};
+/// #[derive(Versioned)]
+/// struct FooV1 {}
+/// #[derive(Versioned)]
+/// struct FooV2 {}
+/// type Foo = FooV2;
+/// # assign_message_ids! { Foo: 7 }
+///
+/// let msg: Box<dyn VersionedDyn> = Box::new(FooV1 {});
+/// assert_eq!(msg.version(), 1);
+/// assert_eq!(msg.msg_id(), 7);
+/// ```
+pub trait VersionedDyn {
+    /// The message version; see [`Versioned::VER`].
+    fn version(&self) -> u16;
+
+    /// The message id; see [`MessageId::MSG_ID`].
+    fn msg_id(&self) -> u16;
+}
+
+impl<T> VersionedDyn for T
+where
+    T: Versioned,
+    T::Base: MessageId,
+{
+    fn version(&self) -> u16 {
+        T::VER
+    }
+
+    fn msg_id(&self) -> u16 {
+        T::Base::MSG_ID
+    }
 }
@@ -104,6 +104,61 @@
 //! or `FooV2`) and `read_message` deserializes the correct version of the struct,
 //! upgrades it to the latest version, and returns it as a `MyProtocol`
 //! enum, for the caller to handle.
+//!
+//! ### Generic messages
+//!
+//! Versioned structs may have type parameters, e.g. an `EnvelopeV2<T>` that
+//! wraps some payload. The derive macros pass the generic parameters
+//! through, with these rules:
+//! - Every version must have the same generic parameters, and the type
+//!   alias must have them too: `type Envelope<T> = EnvelopeV2<T>`.
+//! - `FromVersion` is implemented generically, e.g.
+//!   `impl<T> FromVersion<EnvelopeV1<T>> for EnvelopeV2<T>`.
+//! - The derived `UpgradeLatest` impl only applies where each version can
+//!   be deserialized and upgraded, so there's no need to add `serde`
+//!   bounds to the struct itself.
+//!
+//! `MSG_ID` is an associated constant, so it can't be chosen at runtime,
+//! but it can be computed from `T`. Implement [`MessageId`] by hand to
+//! give each payload type its own id, or use [`assign_message_ids!`] on
+//! concrete types, e.g. `Envelope<Ping>: 100`.
+//! ```
+//! # use aversion::{FromVersion, MessageId, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Versioned, Serialize, Deserialize)]
+//! struct EnvelopeV1<T> {
+//!     payload: T,
+//! }
+//!
+//! #[derive(Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! struct EnvelopeV2<T> {
+//!     payload: T,
+//!     hops: u8,
+//! }
+//!
+//! type Envelope<T> = EnvelopeV2<T>;
+//!
+//! impl<T> FromVersion<EnvelopeV1<T>> for EnvelopeV2<T> {
+//!     fn from_version(v1: EnvelopeV1<T>) -> Self {
+//!         EnvelopeV2 { payload: v1.payload, hops: 0 }
+//!     }
+//! }
+//!
+//! impl<T: MessageId> MessageId for Envelope<T> {
+//!     const MSG_ID: u16 = 1000 + T::MSG_ID;
+//! }
+//! ```
+//!
+//! ### Tracing
+//!
+//! With the `tracing` feature, reading a message through a derived
+//! `UpgradeLatest` impl (which includes group dispatch and
+//! `expect_message`) opens a `read_message` span, and writing a message
+//! with [`CborData`](util::cbor::CborData) opens a `write_message` span.
+//! Each span carries `msg_id`, `ver`, and `msg_type` fields, and a failure
+//! is recorded as an event inside it. Span names must be static, so the
+//! message type is a field rather than part of the name. Without the
+//! feature, nothing is instrumented.
 
 #![warn(missing_docs)]
 #![forbid(unsafe_code)]
@@ -111,17 +166,31 @@
 
 pub mod group;
 mod id;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod trace;
 pub mod util;
 mod versioned;
 
 #[doc(inline)]
-pub use crate::versioned::{FromVersion, IntoVersion, Versioned};
+pub use crate::versioned::{
+    FromVersion, FromVersionRef, IntoVersion, IntoVersionRef, IsLatest, Version, Versioned,
+};
 
 #[doc(inline)]
-pub use crate::group::GroupDeserialize;
+pub use crate::group::{GroupDeserialize, GroupSerialize};
+
+// Items used by the derive macros. Not public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::trace::read_span;
+    pub use serde::de::DeserializeOwned;
+}
 
 #[doc(inline)]
-pub use aversion_macros::{GroupDeserialize, UpgradeLatest, Versioned};
+pub use aversion_macros::{
+    GroupDeserialize, GroupSerialize, RawVersions, UpgradeLatest, Versioned,
+};
 
 /// Implement `MessageId` for a bunch of types at once.
 ///
@@ -157,8 +226,12 @@ pub use aversion_macros::{GroupDeserialize, UpgradeLatest, Versioned};
 /// }
 /// ```
 ///
+/// A message id with a `u32` suffix, e.g. `Qux: 0x1_0000u32`, is a 32-bit
+/// id: it sets [`MessageId::WIDE_MSG_ID`], and `MSG_ID` is set to its low
+/// 16 bits.
+///
 #[doc(inline)]
 pub use aversion_macros::assign_message_ids;
 
 #[doc(inline)]
-pub use id::MessageId;
+pub use id::{MessageId, VersionedDyn};
@@ -0,0 +1,780 @@
+//! Helpers for testing code that uses `aversion`.
+//!
+//! This module is only available when the `test-util` feature is enabled.
+//! It is meant to be used from tests, e.g. by adding `aversion` to
+//! `[dev-dependencies]` with `features = ["test-util"]`.
+
+use crate::group::{DataSink, DataSource, GroupHeader, UpgradeLatest};
+use crate::util::cbor::{CborData, CborDataError, SliceSource};
+use crate::util::clock::Clock;
+use crate::util::BasicHeader;
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Write};
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Serialize a message using the default header and codec.
+///
+/// This produces the same bytes that [`CborData`] would write to a stream:
+/// a [`BasicHeader`] followed by the CBOR-encoded message.
+///
+/// [`BasicHeader`]: crate::util::BasicHeader
+pub fn wire_bytes<T>(msg: &T) -> Vec<u8>
+where
+    T: Serialize + Versioned,
+    T::Base: MessageId,
+{
+    let mut sink = CborData::new(Vec::<u8>::new());
+    sink.write_message(msg).unwrap();
+    sink.into_inner()
+}
+
+/// Assert that a message serializes to exactly the expected bytes.
+///
+/// This is the function behind [`assert_wire_golden!`]; see that macro for
+/// more information.
+///
+/// [`assert_wire_golden!`]: crate::assert_wire_golden
+#[track_caller]
+pub fn assert_wire_golden<T>(msg: &T, expected: &[u8])
+where
+    T: Serialize + Versioned,
+    T::Base: MessageId,
+{
+    let actual = wire_bytes(msg);
+    if actual != expected {
+        panic!(
+            "wire format of {} does not match golden bytes\n{}",
+            std::any::type_name::<T>(),
+            hex_diff(expected, &actual)
+        );
+    }
+}
+
+/// Format two byte strings side by side, 8 bytes per row.
+///
+/// Rows that differ are marked with `*`.
+fn hex_diff(expected: &[u8], actual: &[u8]) -> String {
+    const ROW: usize = 8;
+
+    fn hex_row(buf: &[u8], offset: usize) -> String {
+        let row = buf.iter().skip(offset).take(ROW);
+        let mut out = String::new();
+        for byte in row {
+            write!(out, "{:02x} ", byte).unwrap();
+        }
+        // Pad short rows so the columns line up.
+        format!("{:width$}", out, width = ROW * 3)
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "       expected ({} bytes)        actual ({} bytes)",
+        expected.len(),
+        actual.len()
+    )
+    .unwrap();
+    let len = expected.len().max(actual.len());
+    for offset in (0..len).step_by(ROW) {
+        let exp_row = expected.iter().skip(offset).take(ROW);
+        let act_row = actual.iter().skip(offset).take(ROW);
+        let marker = if exp_row.ne(act_row) { '*' } else { ' ' };
+        writeln!(
+            out,
+            "{} {:04x}: {}| {}",
+            marker,
+            offset,
+            hex_row(expected, offset),
+            hex_row(actual, offset)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Assert that a message serializes to a known sequence of bytes.
+///
+/// The message is serialized using the default header and codec (see
+/// [`wire_bytes`]), and compared to the expected bytes. If they differ,
+/// the test panics with a side-by-side hex dump of both.
+///
+/// This can be used to lock down the wire format of a message, so that
+/// a changed serde attribute or codec upgrade can't silently alter the
+/// bytes.
+///
+/// ```
+/// # use aversion::{assert_wire_golden, assign_message_ids, Versioned};
+/// # use serde::Serialize;
+/// #[derive(Versioned, Serialize)]
+/// struct FooV1 {
+///     foo: u32,
+/// }
+/// # type Foo = FooV1;
+/// # assign_message_ids! { Foo: 123 }
+///
+/// assert_wire_golden!(
+///     FooV1 { foo: 1234 },
+///     &[
+///         0x00, 0x7b, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, // header
+///         0xa1, 0x63, 0x66, 0x6f, 0x6f, 0x19, 0x04, 0xd2, // body
+///     ]
+/// );
+/// ```
+///
+/// [`wire_bytes`]: crate::test_util::wire_bytes
+#[macro_export]
+macro_rules! assert_wire_golden {
+    ($msg:expr, $expected:expr $(,)?) => {
+        $crate::test_util::assert_wire_golden(&$msg, $expected)
+    };
+}
+
+/// Assert that a message decodes and upgrades to an expected value.
+///
+/// `bytes` is a message in the default format (see [`wire_bytes`]), of
+/// any version of `T`. It's decoded and upgraded to the latest version,
+/// as [`expect_message`] would, and compared to `expected`. The version
+/// that was read from the header is returned, and included in the panic
+/// message if the values differ.
+///
+/// This can be used to lock down migrations: keep the bytes of an old
+/// message around, and check that it always upgrades to the same value.
+///
+/// ```
+/// # use aversion::test_util::{assert_upgrades_to, wire_bytes};
+/// # use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Versioned, Serialize, Deserialize)]
+/// struct FooV1 {
+///     foo: u32,
+/// }
+///
+/// #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// struct FooV2 {
+///     foo: u64,
+///     bar: bool,
+/// }
+///
+/// type Foo = FooV2;
+/// # assign_message_ids! { Foo: 1 }
+///
+/// impl FromVersion<FooV1> for FooV2 {
+///     fn from_version(v1: FooV1) -> Self {
+///         FooV2 { foo: v1.foo.into(), bar: false }
+///     }
+/// }
+///
+/// let v1_bytes = wire_bytes(&FooV1 { foo: 7 });
+/// let ver = assert_upgrades_to(&v1_bytes, Foo { foo: 7, bar: false });
+/// assert_eq!(ver, 1);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the message can't be decoded, if it has a different message
+/// id, or if the upgraded value isn't equal to `expected`.
+///
+/// [`expect_message`]: crate::group::DataSourceExt::expect_message
+#[track_caller]
+pub fn assert_upgrades_to<T>(bytes: &[u8], expected: T) -> u16
+where
+    T: MessageId + UpgradeLatest + PartialEq + Debug,
+{
+    let type_name = std::any::type_name::<T>();
+    let mut src = SliceSource::new(bytes);
+    let header = match src.read_header() {
+        Ok(header) => header,
+        Err(e) => panic!("failed to read {} header: {}", type_name, e),
+    };
+    let ver = header.msg_ver();
+    if header.msg_id() != T::MSG_ID {
+        panic!(
+            "expected {} (id {}), got message id {}",
+            type_name,
+            T::MSG_ID,
+            header.msg_id()
+        );
+    }
+    let actual = match T::upgrade_latest(&mut src, header) {
+        Ok(actual) => actual,
+        Err(e) => panic!("failed to decode {} version {}: {}", type_name, ver, e),
+    };
+    if actual != expected {
+        panic!(
+            "{} version {} did not upgrade to the expected value\n  expected: {:?}\n    actual: {:?}",
+            type_name, ver, expected, actual
+        );
+    }
+    ver
+}
+
+/// Assert that a message family has no gaps in its versions.
+///
+/// `assert_version_continuity!(Foo)` checks that
+/// [`Foo::SUPPORTED_VERSIONS`][UpgradeLatest::SUPPORTED_VERSIONS] is every
+/// version from 1 (or 0, for a `#[versioned(legacy)]` struct) up to the
+/// latest, and that [`upgrade_latest`][UpgradeLatest::upgrade_latest] can
+/// decode each one. A family that intentionally skips versions can list
+/// them instead: `assert_version_continuity!(Foo, [1, 3, 4])`.
+///
+/// The test panics, naming the missing version, if a check fails. Run it
+/// in CI to catch a new `FooV3` whose upgrade path wasn't wired up.
+///
+/// ```
+/// # use aversion::{assert_version_continuity, assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Versioned, Serialize, Deserialize)]
+/// struct FooV1 {
+///     foo: u32,
+/// }
+///
+/// #[derive(Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// struct FooV2 {
+///     foo: u64,
+/// }
+///
+/// type Foo = FooV2;
+/// # assign_message_ids! { Foo: 1 }
+///
+/// impl FromVersion<FooV1> for FooV2 {
+///     fn from_version(v1: FooV1) -> Self {
+///         FooV2 { foo: v1.foo.into() }
+///     }
+/// }
+///
+/// assert_version_continuity!(Foo);
+/// ```
+///
+/// With `#[derive(UpgradeLatest)]`, a missing upgrade step is already a
+/// compile error:
+///
+/// ```compile_fail
+/// # use aversion::{UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Versioned, Serialize, Deserialize)]
+/// struct FooV1 {
+///     foo: u32,
+/// }
+///
+/// // No `impl FromVersion<FooV1> for FooV2`.
+/// #[derive(Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// struct FooV2 {
+///     foo: u64,
+/// }
+/// ```
+///
+/// A hand-written [`UpgradeLatest`] impl has no such check, which is
+/// what this assertion is for.
+///
+/// [`UpgradeLatest`]: crate::group::UpgradeLatest
+/// [UpgradeLatest::SUPPORTED_VERSIONS]: crate::group::UpgradeLatest::SUPPORTED_VERSIONS
+/// [UpgradeLatest::upgrade_latest]: crate::group::UpgradeLatest::upgrade_latest
+#[macro_export]
+macro_rules! assert_version_continuity {
+    ($ty:ty $(,)?) => {
+        $crate::test_util::assert_version_continuity::<$ty>()
+    };
+    ($ty:ty, [$($ver:expr),* $(,)?] $(,)?) => {
+        $crate::test_util::assert_versions::<$ty>(&[$($ver),*])
+    };
+}
+
+/// Assert that `T` supports every version from the first to the latest.
+///
+/// This is the function behind [`assert_version_continuity!`]; see that
+/// macro for more information.
+///
+/// [`assert_version_continuity!`]: crate::assert_version_continuity
+#[track_caller]
+pub fn assert_version_continuity<T>()
+where
+    T: UpgradeLatest,
+{
+    let type_name = std::any::type_name::<T>();
+    let supported = T::SUPPORTED_VERSIONS;
+    let first = match supported.first() {
+        Some(&first) if first <= 1 => first,
+        Some(first) => panic!("{} versions start at {}, not 1", type_name, first),
+        None => panic!("{} has no supported versions", type_name),
+    };
+    let expected: Vec<u16> = (first..=T::VER).collect();
+    if let Some(missing) = expected.iter().find(|ver| !supported.contains(ver)) {
+        panic!(
+            "{} is missing version {} (supported versions: {:?}, latest: {})",
+            type_name,
+            missing,
+            supported,
+            T::VER
+        );
+    }
+    assert_versions::<T>(&expected);
+}
+
+/// Assert that `T` supports exactly the versions in `expected`.
+///
+/// This is the function behind the two-argument form of
+/// [`assert_version_continuity!`]. `expected` must be in increasing order,
+/// and end with the latest version.
+///
+/// [`assert_version_continuity!`]: crate::assert_version_continuity
+#[track_caller]
+pub fn assert_versions<T>(expected: &[u16])
+where
+    T: UpgradeLatest,
+{
+    let type_name = std::any::type_name::<T>();
+    let supported = T::SUPPORTED_VERSIONS;
+    if supported != expected {
+        panic!(
+            "{} supports versions {:?}, expected {:?}",
+            type_name, supported, expected
+        );
+    }
+    if supported.last() != Some(&T::VER) {
+        panic!(
+            "{} supported versions {:?} don't end with the latest version {}",
+            type_name,
+            supported,
+            T::VER
+        );
+    }
+    if let Some(pair) = supported.windows(2).find(|pair| pair[0] >= pair[1]) {
+        panic!(
+            "{} supported versions are out of order: {} before {}",
+            type_name, pair[0], pair[1]
+        );
+    }
+
+    // Check that each supported version, and no other, reaches a decode
+    // step in `upgrade_latest`.
+    let first = supported[0];
+    for ver in first..=T::VER {
+        let mut probe = VersionProbe;
+        let reached = match T::upgrade_latest(&mut probe, ProbeHeader(ver)) {
+            Err(ProbeResult::Decoded) => true,
+            Err(ProbeResult::UnknownVersion) => false,
+            Ok(_) => unreachable!("VersionProbe never returns a message"),
+        };
+        let listed = supported.contains(&ver);
+        if listed && !reached {
+            panic!(
+                "{} lists version {} as supported, but has no upgrade path from it",
+                type_name, ver
+            );
+        }
+        if reached && !listed {
+            panic!(
+                "{} can upgrade version {}, but doesn't list it as supported",
+                type_name, ver
+            );
+        }
+    }
+}
+
+/// The header passed to `upgrade_latest` by [`VersionProbe`].
+struct ProbeHeader(u16);
+
+impl GroupHeader for ProbeHeader {
+    fn msg_id(&self) -> u16 {
+        0
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.0
+    }
+}
+
+/// How far `upgrade_latest` got with a [`VersionProbe`].
+#[derive(Debug)]
+enum ProbeResult {
+    /// It tried to decode the message body.
+    Decoded,
+    /// It rejected the version.
+    UnknownVersion,
+}
+
+/// A `DataSource` that records whether a version would be decoded,
+/// without any message data.
+struct VersionProbe;
+
+impl DataSource for VersionProbe {
+    type Error = ProbeResult;
+    type Header = ProbeHeader;
+
+    fn read_header(&mut self) -> Result<ProbeHeader, ProbeResult> {
+        unreachable!("VersionProbe doesn't read headers");
+    }
+
+    fn read_message<T>(&mut self, _header: &ProbeHeader) -> Result<T, ProbeResult>
+    where
+        T: DeserializeOwned,
+    {
+        Err(ProbeResult::Decoded)
+    }
+
+    fn unknown_version<T>(&self, _ver: u16) -> ProbeResult
+    where
+        T: Versioned,
+    {
+        ProbeResult::UnknownVersion
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        u16::MAX
+    }
+}
+
+/// A reader that injects faults, for testing error handling.
+///
+/// `FaultSource` wraps another [`Read`] type, and follows a script of
+/// faults: it can stop returning data after a number of bytes, corrupt
+/// bytes at specific offsets, or return [`WouldBlock`] errors on specific
+/// calls to [`read`].
+///
+/// Wrap it in a [`DataSource`] that reads from a `Read` type, e.g.
+/// [`CborData`], to simulate faulty IO:
+/// ```
+/// # use aversion::test_util::FaultSource;
+/// # use aversion::util::cbor::CborData;
+/// # use std::io::Cursor;
+/// # let buf = Vec::<u8>::new();
+/// // Pretend that the data ends after 12 bytes, and flip every bit
+/// // of the first byte.
+/// let reader = FaultSource::new(Cursor::new(buf))
+///     .truncate_after(12)
+///     .corrupt_byte(0, 0xff);
+/// let src = CborData::new(reader);
+/// ```
+///
+/// [`read`]: Read::read
+/// [`WouldBlock`]: std::io::ErrorKind::WouldBlock
+/// [`DataSource`]: crate::group::DataSource
+#[derive(Debug)]
+pub struct FaultSource<R> {
+    inner: R,
+    /// The number of bytes returned so far.
+    pos: u64,
+    /// The number of calls to `read` so far.
+    reads: usize,
+    truncate_at: Option<u64>,
+    corruptions: Vec<(u64, u8)>,
+    would_block: Vec<usize>,
+}
+
+impl<R> FaultSource<R> {
+    /// Create a new `FaultSource` with no faults.
+    pub fn new(inner: R) -> Self {
+        FaultSource {
+            inner,
+            pos: 0,
+            reads: 0,
+            truncate_at: None,
+            corruptions: Vec::new(),
+            would_block: Vec::new(),
+        }
+    }
+
+    /// Signal EOF after `len` bytes have been read.
+    pub fn truncate_after(mut self, len: u64) -> Self {
+        self.truncate_at = Some(len);
+        self
+    }
+
+    /// Corrupt the byte at `offset`, by XORing it with `mask`.
+    pub fn corrupt_byte(mut self, offset: u64, mask: u8) -> Self {
+        self.corruptions.push((offset, mask));
+        self
+    }
+
+    /// Return a `WouldBlock` error from the `n`th call to `read`.
+    ///
+    /// Calls are counted starting at 1. The failed call still counts, so
+    /// the next call will proceed normally (unless it is also scripted to
+    /// fail).
+    pub fn would_block_on_read(mut self, n: usize) -> Self {
+        self.would_block.push(n);
+        self
+    }
+
+    /// Returns the number of bytes that have been read so far.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Consume the `FaultSource`, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Read for FaultSource<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads += 1;
+        if self.would_block.contains(&self.reads) {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let mut max_len = buf.len();
+        if let Some(truncate_at) = self.truncate_at {
+            let left = truncate_at.saturating_sub(self.pos);
+            max_len = usize::try_from(left).map_or(max_len, |left| left.min(max_len));
+        }
+        let len = self.inner.read(&mut buf[..max_len])?;
+
+        let range = self.pos..self.pos + len as u64;
+        for &(offset, mask) in &self.corruptions {
+            if range.contains(&offset) {
+                let index = usize::try_from(offset - self.pos).unwrap();
+                buf[index] ^= mask;
+            }
+        }
+        self.pos = range.end;
+        Ok(len)
+    }
+}
+
+/// A data source backed by a bounded ring buffer of frames.
+///
+/// `RingSource` simulates a socket that can run dry and later receive
+/// more data. Each frame pushed with [`push_frame`] should contain whole
+/// messages (a [`BasicHeader`] followed by the CBOR-encoded body), e.g.
+/// from [`wire_bytes`].
+///
+/// When there is no data left to read, [`read_header`] returns an IO
+/// error of kind [`WouldBlock`], rather than signalling the end of the
+/// stream. The error's [`kind`] is transient, so the read can be retried
+/// after more frames have been pushed.
+///
+/// The ring holds at most `capacity` frames that haven't been read yet.
+/// Pushing a frame into a full ring drops the oldest frame, as a lossy
+/// transport would; see [`dropped`].
+///
+/// ```
+/// # use aversion::assign_message_ids;
+/// # use aversion::group::DataSourceExt;
+/// # use aversion::test_util::{wire_bytes, RingSource};
+/// # use aversion::{UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// # struct PingV1 { seq: u32 }
+/// # type Ping = PingV1;
+/// # assign_message_ids! { Ping: 1 }
+/// let mut src = RingSource::new(4);
+/// assert!(src.expect_message::<Ping>().unwrap_err().kind().is_transient());
+///
+/// src.push_frame(&wire_bytes(&PingV1 { seq: 1 }));
+/// assert_eq!(src.expect_message::<Ping>().unwrap(), PingV1 { seq: 1 });
+/// ```
+///
+/// [`push_frame`]: Self::push_frame
+/// [`dropped`]: Self::dropped
+/// [`read_header`]: DataSource::read_header
+/// [`kind`]: CborDataError::kind
+/// [`BasicHeader`]: crate::util::BasicHeader
+/// [`WouldBlock`]: std::io::ErrorKind::WouldBlock
+#[derive(Debug)]
+pub struct RingSource {
+    frames: VecDeque<Vec<u8>>,
+    capacity: usize,
+    /// The frame that is currently being read.
+    current: Vec<u8>,
+    /// The number of bytes of `current` that have been read.
+    pos: usize,
+    dropped: usize,
+}
+
+impl RingSource {
+    /// Create an empty `RingSource` that holds up to `capacity` frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "RingSource capacity must be nonzero");
+        RingSource {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+            current: Vec::new(),
+            pos: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Add a frame to the ring.
+    ///
+    /// If the ring is full, the oldest unread frame is dropped.
+    pub fn push_frame(&mut self, frame: &[u8]) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+            self.dropped += 1;
+        }
+        self.frames.push_back(frame.to_vec());
+    }
+
+    /// Returns the number of frames that haven't been started yet.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns `true` if there is no data left to read.
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.current.len() && self.frames.is_empty()
+    }
+
+    /// Returns the number of frames that were dropped because the ring
+    /// was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Make sure that the current frame has unread data.
+    fn fill(&mut self) -> Result<(), CborDataError> {
+        while self.pos == self.current.len() {
+            match self.frames.pop_front() {
+                Some(frame) => {
+                    self.current = frame;
+                    self.pos = 0;
+                }
+                None => return Err(io::Error::from(io::ErrorKind::WouldBlock).into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a function on the unread part of the current frame.
+    fn with_slice<T>(
+        &mut self,
+        f: impl FnOnce(&mut SliceSource<'_>) -> Result<T, CborDataError>,
+    ) -> Result<T, CborDataError> {
+        let mut src = SliceSource::new(&self.current[self.pos..]);
+        let result = f(&mut src);
+        self.pos = self.current.len() - src.remaining().len();
+        result
+    }
+}
+
+impl DataSource for RingSource {
+    type Error = CborDataError;
+    type Header = BasicHeader;
+
+    fn read_header(&mut self) -> Result<BasicHeader, CborDataError> {
+        self.fill()?;
+        self.with_slice(|src| src.read_header())
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, CborDataError> {
+        self.fill()?;
+        self.with_slice(|src| src.peek_msg_id())
+    }
+
+    fn read_message<T>(&mut self, header: &BasicHeader) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        self.with_slice(|src| src.read_message(header))
+    }
+
+    fn read_raw(&mut self, header: &BasicHeader) -> Result<Vec<u8>, CborDataError> {
+        self.with_slice(|src| src.read_raw(header))
+    }
+
+    fn skip_message(&mut self, header: &BasicHeader) -> Result<(), CborDataError> {
+        self.with_slice(|src| src.skip_message(header))
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        SliceSource::new(&[]).unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        SliceSource::new(&[]).unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        SliceSource::new(&[]).unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        SliceSource::new(&[]).deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        SliceSource::new(&[]).deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        SliceSource::new(&[]).unknown_version::<T>(ver)
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        SliceSource::new(&[]).upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        SliceSource::new(&[]).unexpected_message::<T>(msg_id)
+    }
+}
+
+/// A [`Clock`] whose time is set by the test.
+///
+/// Clones share the same time, so a test can keep one handle and move
+/// the time forward while another is owned by the component under test:
+///
+/// ```
+/// # use aversion::test_util::MockClock;
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::util::expiry::ExpirySource;
+/// # use aversion::util::ExtendedHeader;
+/// # let buf = Vec::<u8>::new();
+/// let clock = MockClock::new(1000);
+/// let src = CborData::<_, ExtendedHeader>::with_header(&buf[..]);
+/// let src = ExpirySource::with_clock(src, clock.clone());
+/// clock.advance(60);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Create a `MockClock` that starts at `now`.
+    pub fn new(now: u64) -> Self {
+        MockClock {
+            now: Arc::new(AtomicU64::new(now)),
+        }
+    }
+
+    /// Set the current time.
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    /// Move the current time forward by `elapsed`.
+    pub fn advance(&self, elapsed: u64) {
+        self.now.fetch_add(elapsed, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
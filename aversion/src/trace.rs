@@ -0,0 +1,70 @@
+//! Optional `tracing` instrumentation.
+//!
+//! With the `tracing` feature, these helpers run a read or write inside a
+//! `tracing` span and record failures as events. Without it, they just
+//! call the closure, so they cost nothing.
+
+#[cfg(feature = "tracing")]
+use std::any::type_name;
+
+/// Run `f`, which reads a message of type `T`, inside a span.
+///
+/// The span is named `read_message`, and carries the message id, the
+/// version that was received, and the name of `T`. If `f` fails, an
+/// event is recorded within the span.
+#[cfg(feature = "tracing")]
+pub fn read_span<T, R, E, F>(msg_id: u32, ver: u16, f: F) -> Result<R, E>
+where
+    T: ?Sized,
+    F: FnOnce() -> Result<R, E>,
+{
+    let span = tracing::debug_span!("read_message", msg_id, ver, msg_type = type_name::<T>());
+    let _enter = span.enter();
+    let result = f();
+    if result.is_err() {
+        tracing::debug!("failed to read message");
+    }
+    result
+}
+
+/// Run `f`, which reads a message of type `T`.
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub fn read_span<T, R, E, F>(_msg_id: u32, _ver: u16, f: F) -> Result<R, E>
+where
+    T: ?Sized,
+    F: FnOnce() -> Result<R, E>,
+{
+    f()
+}
+
+/// Run `f`, which writes a message of type `T`, inside a span.
+///
+/// The span is named `write_message`, and carries the message id, the
+/// version, and the name of `T`. If `f` fails, an event is recorded
+/// within the span.
+#[cfg(feature = "tracing")]
+pub fn write_span<T, R, E, F>(msg_id: u32, ver: u16, f: F) -> Result<R, E>
+where
+    T: ?Sized,
+    F: FnOnce() -> Result<R, E>,
+{
+    let span = tracing::debug_span!("write_message", msg_id, ver, msg_type = type_name::<T>());
+    let _enter = span.enter();
+    let result = f();
+    if result.is_err() {
+        tracing::debug!("failed to write message");
+    }
+    result
+}
+
+/// Run `f`, which writes a message of type `T`.
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub fn write_span<T, R, E, F>(_msg_id: u32, _ver: u16, f: F) -> Result<R, E>
+where
+    T: ?Sized,
+    F: FnOnce() -> Result<R, E>,
+{
+    f()
+}
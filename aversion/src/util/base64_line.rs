@@ -0,0 +1,289 @@
+//! Provides a `DataSource` and `DataSink` that write one base64 line per
+//! message.
+//!
+//! This is a text transport, meant for debugging: each message frame (the
+//! serialized header, followed by the CBOR body) is base64-encoded and
+//! written on its own line, terminated by `\n`. The output can be read
+//! with any line reader, and pasted into logs or bug reports.
+//!
+//! [`Base64LineSource`] ignores blank lines, and whitespace around each
+//! line. A line that isn't valid base64, or whose header doesn't match
+//! the length of its body, is returned as an [`io::Error`] with kind
+//! [`InvalidData`][io::ErrorKind::InvalidData].
+//!
+//! ```
+//! # use aversion::group::{DataSink, DataSourceExt};
+//! # use aversion::util::base64_line::{Base64LineSink, Base64LineSource};
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! # struct FooV1 { foo: u32 }
+//! # type Foo = FooV1;
+//! # assign_message_ids! { Foo: 1 }
+//! let mut sink = Base64LineSink::new(Vec::new());
+//! sink.write_message(&FooV1 { foo: 1 }).unwrap();
+//! let text = String::from_utf8(sink.into_inner()).unwrap();
+//! assert_eq!(text, "AAEAAQAAAAahY2ZvbwE=\n");
+//!
+//! let mut src = Base64LineSource::new(text.as_bytes());
+//! let foo: Foo = src.expect_message().unwrap();
+//! assert_eq!(foo, FooV1 { foo: 1 });
+//! ```
+//!
+//! This is only available when the `base64` feature is enabled.
+
+use crate::group::{DataSink, DataSource};
+use crate::util::cbor::{CborCodec, CborDataError};
+use crate::util::protocol::ProtocolError;
+use crate::util::{BasicHeader, Codec, FramedHeader};
+use crate::{MessageId, Versioned};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::io::{self, BufRead, Write};
+use std::marker::PhantomData;
+
+/// A [`DataSink`] that writes each message as a base64 line.
+///
+/// See the [module documentation](self) for the format.
+pub struct Base64LineSink<W, H = BasicHeader> {
+    inner: W,
+    /// The header and body of the current message.
+    frame: Vec<u8>,
+    /// The base64 line of the current message.
+    line: String,
+    _header: PhantomData<fn() -> H>,
+}
+
+impl<W> Base64LineSink<W> {
+    /// Create a new `Base64LineSink`.
+    pub fn new(inner: W) -> Self {
+        Self::with_header(inner)
+    }
+}
+
+impl<W, H> Base64LineSink<W, H> {
+    /// Create a new `Base64LineSink` that uses a specific header type.
+    pub fn with_header(inner: W) -> Self {
+        Base64LineSink {
+            inner,
+            frame: Vec::new(),
+            line: String::new(),
+            _header: PhantomData,
+        }
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consume the `Base64LineSink`, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, H> DataSink for Base64LineSink<W, H>
+where
+    W: Write,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let mut body = Vec::new();
+        CborCodec.encode(msg, &mut body)?;
+        let msg_len: u32 = body.len().try_into().expect("usize to u32");
+
+        self.frame.clear();
+        H::for_msg(msg, msg_len).serialize_into(&mut self.frame)?;
+        self.frame.extend_from_slice(&body);
+
+        self.line.clear();
+        STANDARD.encode_string(&self.frame, &mut self.line);
+        self.line.push('\n');
+        self.inner.write_all(self.line.as_bytes())?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// A [`DataSource`] that reads messages written by [`Base64LineSink`].
+///
+/// Each header read consumes a whole line; the body is decoded from the
+/// same line.
+///
+/// See the [module documentation](self) for the format.
+pub struct Base64LineSource<R, H = BasicHeader> {
+    inner: R,
+    /// The current line.
+    line: String,
+    /// The header and body of the current message.
+    frame: Vec<u8>,
+    /// The offset of the body in `frame`.
+    body_start: usize,
+    _header: PhantomData<fn() -> H>,
+}
+
+impl<R> Base64LineSource<R> {
+    /// Create a new `Base64LineSource`.
+    pub fn new(inner: R) -> Self {
+        Self::with_header(inner)
+    }
+}
+
+impl<R, H> Base64LineSource<R, H> {
+    /// Create a new `Base64LineSource` that uses a specific header type.
+    pub fn with_header(inner: R) -> Self {
+        Base64LineSource {
+            inner,
+            line: String::new(),
+            frame: Vec::new(),
+            body_start: 0,
+            _header: PhantomData,
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the `Base64LineSource`, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// The body of the current message.
+    fn body(&self) -> &[u8] {
+        &self.frame[self.body_start..]
+    }
+}
+
+impl<R, H> Base64LineSource<R, H>
+where
+    R: BufRead,
+    H: FramedHeader,
+{
+    /// Read the next non-blank line, and decode its header.
+    ///
+    /// Returns `None` at EOF.
+    fn read_frame(&mut self) -> Result<Option<H>, CborDataError> {
+        let text = loop {
+            self.line.clear();
+            if self.inner.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            let text = self.line.trim();
+            if !text.is_empty() {
+                break text;
+            }
+        };
+
+        self.frame.clear();
+        STANDARD
+            .decode_vec(text, &mut self.frame)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let mut r = &self.frame[..];
+        let header = H::deserialize_from(&mut r)?;
+        if r.len() != header.msg_len() as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message length doesn't match the line",
+            )
+            .into());
+        }
+        self.body_start = self.frame.len() - r.len();
+        Ok(Some(header))
+    }
+}
+
+impl<R, H> DataSource for Base64LineSource<R, H>
+where
+    R: BufRead,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+    type Header = H;
+
+    fn read_header(&mut self) -> Result<H, CborDataError> {
+        self.read_frame()?.ok_or(CborDataError::Eof)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<H>, CborDataError> {
+        self.read_frame()
+    }
+
+    fn read_message<T>(&mut self, _header: &H) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        CborCodec.decode(self.body())
+    }
+
+    fn read_raw(&mut self, _header: &H) -> Result<Vec<u8>, CborDataError> {
+        Ok(self.body().to_vec())
+    }
+
+    fn skip_message(&mut self, _header: &H) -> Result<(), CborDataError> {
+        // The body was read along with the header.
+        Ok(())
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
+    }
+}
@@ -0,0 +1,78 @@
+//! Serialize a byte vector as a byte string.
+//!
+//! By default, serde serializes a `Vec<u8>` as a sequence of integers. In
+//! CBOR that takes one or two bytes per element, plus the array header. A
+//! byte string takes one byte per element. Mark the field with
+//! `#[serde(with = "aversion::util::bytes")]` to use a byte string:
+//!
+//! ```
+//! # use aversion::Versioned;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Versioned, Serialize, Deserialize)]
+//! struct BlobV1 {
+//!     #[serde(with = "aversion::util::bytes")]
+//!     data: Vec<u8>,
+//! }
+//! # type Blob = BlobV1;
+//! ```
+//!
+//! This does the same thing as the `serde_bytes` crate, which works too.
+//!
+//! The attribute changes the encoding, so it can't be added to a version
+//! that has already been written. Add it in a new version instead; an
+//! older version without it still decodes, and the upgrade produces a
+//! `Vec<u8>` that is then written as a byte string. The field in every
+//! later version needs the attribute too.
+
+use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+use serde::Serializer;
+use std::fmt;
+
+/// Serialize a byte slice as a byte string.
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]> + ?Sized,
+    S: Serializer,
+{
+    serializer.serialize_bytes(bytes.as_ref())
+}
+
+/// Deserialize a byte vector.
+///
+/// This accepts a byte string, or a sequence of integers, so data written
+/// without [`serialize`] can still be read.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_byte_buf(ByteBufVisitor)
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Vec<u8>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(bytes)
+    }
+}
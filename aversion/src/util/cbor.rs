@@ -1,12 +1,24 @@
 //! Provides a `DataSink` and `DataSource` using the CBOR format.
 
-use crate::group::{DataSink, DataSource};
-use crate::util::BasicHeader;
+use crate::group::{
+    DataSink, DataSource, GroupDeserialize, GroupErrorKind, IsTruncated, DEFAULT_MAX_UPGRADE_STEPS,
+};
+use crate::trace::write_span;
+use crate::util::chunked::{ChunkReader, ChunkWriter};
+use crate::util::codec::{format_id, Codec};
+use crate::util::compact::ReadAhead;
+use crate::util::protocol::{Protocol, ProtocolError, ProtocolId};
+use crate::util::{
+    read_body, try_read_first_byte, BasicHeader, FramedHeader, MultiHeader, PeekedId,
+};
 use crate::{MessageId, Versioned};
-use serde::de::DeserializeOwned;
+use serde::de::value::UnitDeserializer;
+use serde::de::{Deserialize, DeserializeOwned, IntoDeserializer};
 use serde::Serialize;
-use std::convert::TryInto;
-use std::io::{self, Cursor, Read, Write};
+use std::any::type_name;
+use std::convert::{TryFrom, TryInto};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
 use thiserror::Error;
 
 /// Errors that may occur while reading or writing CborData data.
@@ -21,6 +33,202 @@ pub enum CborDataError {
     /// An EOF happened while attempting to read data.
     #[error("Premature EOF")]
     Eof,
+    /// A message was received with an unknown message id.
+    #[error("Unknown message id {msg_id}")]
+    UnknownMessage {
+        /// The message id.
+        msg_id: u32,
+    },
+    /// A message was received with a version that can't be upgraded.
+    #[error("Expected {expected}, got version {got}, highest supported is {latest}")]
+    UnknownVersion {
+        /// The name of the expected message type.
+        expected: &'static str,
+        /// The message version that was received.
+        got: u16,
+        /// The highest message version that can be decoded.
+        latest: u16,
+    },
+    /// Upgrading a message to the latest version would take too many steps.
+    ///
+    /// See [`DataSource::max_upgrade_steps`].
+    #[error(
+        "Upgrading {expected} from version {got} to {latest} exceeds the limit of {max} steps"
+    )]
+    UpgradeTooDeep {
+        /// The name of the expected message type.
+        expected: &'static str,
+        /// The message version that was received.
+        got: u16,
+        /// The latest message version.
+        latest: u16,
+        /// The maximum number of upgrade steps allowed.
+        max: u16,
+    },
+    /// A different message was received than the one that was expected.
+    #[error("Expected {expected} (id {expected_id}), got message id {got}")]
+    UnexpectedMessage {
+        /// The name of the expected message type.
+        expected: &'static str,
+        /// The message id of the expected message type.
+        expected_id: u16,
+        /// The message id that was received.
+        got: u16,
+    },
+    /// A message footer didn't match the message.
+    ///
+    /// See [`FooterData`](crate::util::footer::FooterData).
+    #[error("Message footer verification failed")]
+    FooterVerifyFailed,
+    /// A message body didn't match the hash chain.
+    ///
+    /// See [`HashChainSource`](crate::util::hash_chain::HashChainSource).
+    #[error("Hash chain verification failed")]
+    HashChainBroken,
+    /// A message signature didn't verify against the public key.
+    ///
+    /// See the `signed` module, which requires the `signing` feature.
+    #[error("Message signature is invalid")]
+    SignatureInvalid,
+    /// A reply didn't carry the correlation id of its request.
+    ///
+    /// See the [`reply`](crate::util::reply) module.
+    #[error("Reply correlation id {found:?} does not match the request ({expected:?})")]
+    CorrelationMismatch {
+        /// The correlation id of the request.
+        expected: Option<u64>,
+        /// The correlation id of the reply.
+        found: Option<u64>,
+    },
+    /// A diff named a field that the message doesn't have.
+    ///
+    /// See the [`diff`](crate::util::diff) module.
+    #[error("Diff contains unknown field {field:?}")]
+    UnknownDiffField {
+        /// The field name.
+        field: String,
+    },
+    /// A message body was nested more deeply than allowed.
+    ///
+    /// See [`CborData::max_depth`].
+    #[error("Message nesting exceeds the limit of {max}")]
+    NestingTooDeep {
+        /// The maximum nesting depth allowed.
+        max: usize,
+    },
+    /// The data ended with an incomplete message.
+    ///
+    /// See [`CborData::read_until_eof_strict`].
+    #[error("Trailing garbage after {messages} complete messages")]
+    TrailingGarbage {
+        /// The number of complete messages before the garbage.
+        messages: usize,
+    },
+    /// The data was written with a different protocol.
+    ///
+    /// See [`check_protocol_id`](crate::util::protocol::check_protocol_id).
+    #[error("Protocol mismatch: expected {expected}, found {found}")]
+    ProtocolMismatch {
+        /// The protocol of the reader.
+        expected: ProtocolId,
+        /// The protocol recorded in the data.
+        found: ProtocolId,
+    },
+    /// A frame was longer than the decoder's limit.
+    ///
+    /// This is returned by the `tokio-codec` and `async` decoders, which
+    /// buffer a whole frame before decoding it. The rest of the stream
+    /// can't be decoded.
+    #[error("Frame length {len} exceeds the limit of {max}")]
+    FrameTooLong {
+        /// The length of the frame, including its header.
+        len: usize,
+        /// The maximum frame length allowed.
+        max: usize,
+    },
+    /// A reserved or deprecated message id was received.
+    #[error("Deprecated message id {msg_id} ({note})")]
+    DeprecatedMessage {
+        /// The message id.
+        msg_id: u32,
+        /// The reason the message id is no longer used.
+        note: &'static str,
+    },
+}
+
+impl CborDataError {
+    /// The error for a message version that can't be decoded as `T`.
+    ///
+    /// This is what [`DataSource::unknown_version`] returns for the
+    /// sources in this crate.
+    pub fn unknown_version<T>(ver: u16) -> Self
+    where
+        T: Versioned,
+    {
+        CborDataError::UnknownVersion {
+            expected: type_name::<T>(),
+            got: ver,
+            latest: T::VER,
+        }
+    }
+
+    /// The error for a message version that would take more than `max`
+    /// steps to upgrade to `T`.
+    ///
+    /// This is what [`DataSource::upgrade_too_deep`] returns for the
+    /// sources in this crate.
+    pub fn upgrade_too_deep<T>(ver: u16, max: u16) -> Self
+    where
+        T: Versioned,
+    {
+        CborDataError::UpgradeTooDeep {
+            expected: type_name::<T>(),
+            got: ver,
+            latest: T::VER,
+            max,
+        }
+    }
+
+    /// The error for a message with id `msg_id`, when `T` was expected.
+    ///
+    /// This is what [`DataSource::unexpected_message`] returns for the
+    /// sources in this crate.
+    pub fn unexpected_message<T>(msg_id: u16) -> Self
+    where
+        T: MessageId,
+    {
+        CborDataError::UnexpectedMessage {
+            expected: type_name::<T>(),
+            expected_id: T::MSG_ID,
+            got: msg_id,
+        }
+    }
+
+    /// The kind of error, e.g. to decide whether to retry.
+    pub fn kind(&self) -> GroupErrorKind {
+        match self {
+            CborDataError::Io(Some(e)) if e.kind() == io::ErrorKind::InvalidData => {
+                GroupErrorKind::Framing
+            }
+            CborDataError::Io(_) => GroupErrorKind::Io,
+            CborDataError::Serializer => GroupErrorKind::Codec,
+            CborDataError::Eof => GroupErrorKind::Framing,
+            CborDataError::TrailingGarbage { .. } => GroupErrorKind::Framing,
+            CborDataError::UnknownMessage { .. } => GroupErrorKind::UnknownMessage,
+            CborDataError::DeprecatedMessage { .. } => GroupErrorKind::UnknownMessage,
+            CborDataError::UnknownVersion { .. } => GroupErrorKind::UnknownVersion,
+            CborDataError::UpgradeTooDeep { .. } => GroupErrorKind::Validation,
+            CborDataError::UnexpectedMessage { .. } => GroupErrorKind::Validation,
+            CborDataError::FooterVerifyFailed => GroupErrorKind::Validation,
+            CborDataError::HashChainBroken => GroupErrorKind::Validation,
+            CborDataError::SignatureInvalid => GroupErrorKind::Validation,
+            CborDataError::NestingTooDeep { .. } => GroupErrorKind::Validation,
+            CborDataError::FrameTooLong { .. } => GroupErrorKind::Framing,
+            CborDataError::CorrelationMismatch { .. } => GroupErrorKind::Validation,
+            CborDataError::UnknownDiffField { .. } => GroupErrorKind::Validation,
+            CborDataError::ProtocolMismatch { .. } => GroupErrorKind::Validation,
+        }
+    }
 }
 
 impl From<serde_cbor::Error> for CborDataError {
@@ -36,12 +244,410 @@ impl From<serde_cbor::Error> for CborDataError {
     }
 }
 
+impl IsTruncated for CborDataError {
+    fn is_truncated(&self) -> bool {
+        match self {
+            CborDataError::Eof => true,
+            CborDataError::Io(Some(e)) => e.is_truncated(),
+            _ => false,
+        }
+    }
+}
+
 impl From<io::Error> for CborDataError {
     fn from(e: io::Error) -> Self {
         CborDataError::Io(Some(e))
     }
 }
 
+impl ProtocolError for CborDataError {
+    fn unknown_message(msg_id: u16) -> Self {
+        Self::unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(msg_id: u32) -> Self {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(msg_id: u16, note: &'static str) -> Self {
+        Self::deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(msg_id: u32, note: &'static str) -> Self {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version(expected: &'static str, got: u16, latest: u16) -> Self {
+        CborDataError::UnknownVersion {
+            expected,
+            got,
+            latest,
+        }
+    }
+
+    fn upgrade_too_deep(expected: &'static str, got: u16, latest: u16, max: u16) -> Self {
+        CborDataError::UpgradeTooDeep {
+            expected,
+            got,
+            latest,
+            max,
+        }
+    }
+
+    fn unexpected_message(expected: &'static str, expected_id: u16, got: u16) -> Self {
+        CborDataError::UnexpectedMessage {
+            expected,
+            expected_id,
+            got,
+        }
+    }
+
+    fn protocol_mismatch(expected: ProtocolId, found: ProtocolId) -> Self {
+        CborDataError::ProtocolMismatch { expected, found }
+    }
+}
+
+/// A [`Protocol`] using [`BasicHeader`] and [`CborCodec`].
+///
+/// This reads and writes the same format as [`CborData`].
+#[derive(Debug, Clone, Copy)]
+pub struct CborProtocol;
+
+impl Protocol for CborProtocol {
+    type Header = BasicHeader;
+    type Codec = CborCodec;
+    type Error = CborDataError;
+}
+
+/// A [`Codec`] using the CBOR serialization format.
+///
+/// Messages without a payload (unit structs like `struct Ping;`) are
+/// encoded as an empty body, so their header reports a length of 0.
+/// An empty body is decoded as a unit value. Note that an empty braced
+/// struct (`struct Ping {}`) is encoded as an empty map, not a unit
+/// value; use a unit struct for messages with no payload.
+///
+/// Only unit structs get an empty body. Other values that CBOR encodes as
+/// `null`, such as a newtype around `None`, are written as `null`, so
+/// they decode as the same value.
+///
+/// # Compatibility
+///
+/// Empty bodies are a change to the encoding: older readers expect a CBOR
+/// value in every body, so they can't decode a unit struct message. A
+/// unit struct written by an older writer, as a one-byte `null` body,
+/// still decodes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+/// Returns `true` if `msg` serializes as a unit struct.
+///
+/// These messages are written with an empty body.
+fn is_unit_struct<T>(msg: &T) -> bool
+where
+    T: Serialize + ?Sized,
+{
+    msg.serialize(UnitStructProbe).is_ok()
+}
+
+/// A `Serializer` that only accepts a unit struct.
+struct UnitStructProbe;
+
+/// The error from [`UnitStructProbe`], for anything but a unit struct.
+#[derive(Debug)]
+struct NotUnitStruct;
+
+impl std::fmt::Display for NotUnitStruct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("not a unit struct")
+    }
+}
+
+impl std::error::Error for NotUnitStruct {}
+
+impl serde::ser::Error for NotUnitStruct {
+    fn custom<M: std::fmt::Display>(_msg: M) -> Self {
+        NotUnitStruct
+    }
+}
+
+/// Implement `Serializer` methods that reject their input.
+macro_rules! reject {
+    ($($method:ident($($arg:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ret, NotUnitStruct> {
+                Err(NotUnitStruct)
+            }
+        )*
+    };
+}
+
+impl serde::Serializer for UnitStructProbe {
+    type Ok = ();
+    type Error = NotUnitStruct;
+    type SerializeSeq = serde::ser::Impossible<(), NotUnitStruct>;
+    type SerializeTuple = serde::ser::Impossible<(), NotUnitStruct>;
+    type SerializeTupleStruct = serde::ser::Impossible<(), NotUnitStruct>;
+    type SerializeTupleVariant = serde::ser::Impossible<(), NotUnitStruct>;
+    type SerializeMap = serde::ser::Impossible<(), NotUnitStruct>;
+    type SerializeStruct = serde::ser::Impossible<(), NotUnitStruct>;
+    type SerializeStructVariant = serde::ser::Impossible<(), NotUnitStruct>;
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), NotUnitStruct> {
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<(), NotUnitStruct>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(NotUnitStruct)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<(), NotUnitStruct>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(NotUnitStruct)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), NotUnitStruct>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(NotUnitStruct)
+    }
+
+    reject! {
+        serialize_bool(bool) -> ();
+        serialize_i8(i8) -> ();
+        serialize_i16(i16) -> ();
+        serialize_i32(i32) -> ();
+        serialize_i64(i64) -> ();
+        serialize_u8(u8) -> ();
+        serialize_u16(u16) -> ();
+        serialize_u32(u32) -> ();
+        serialize_u64(u64) -> ();
+        serialize_f32(f32) -> ();
+        serialize_f64(f64) -> ();
+        serialize_char(char) -> ();
+        serialize_str(&str) -> ();
+        serialize_bytes(&[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_variant(&'static str, u32, &'static str) -> ();
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+}
+
+/// Decode a message from an empty body.
+fn decode_empty<'de, T>() -> Result<T, CborDataError>
+where
+    T: Deserialize<'de>,
+{
+    let deserializer: UnitDeserializer<serde::de::value::Error> = ().into_deserializer();
+    T::deserialize(deserializer).map_err(|_| CborDataError::Serializer)
+}
+
+impl Codec for CborCodec {
+    const FORMAT_ID: u16 = format_id::CBOR;
+
+    type Error = CborDataError;
+
+    fn encode<T>(&self, msg: &T, buf: &mut Vec<u8>) -> Result<(), CborDataError>
+    where
+        T: Serialize,
+    {
+        if !is_unit_struct(msg) {
+            serde_cbor::to_writer(&mut *buf, msg)?;
+        }
+        Ok(())
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        if buf.is_empty() {
+            return decode_empty();
+        }
+        Ok(serde_cbor::from_slice(buf)?)
+    }
+}
+
+/// A [`Codec`] that produces canonical CBOR.
+///
+/// The same value always encodes to the same bytes, which makes this
+/// suitable for signing or hashing messages. This follows the canonical
+/// form from RFC 7049: map keys are sorted (shorter keys first, then
+/// bytewise), and floats use the shortest encoding that preserves their
+/// value, with a single NaN encoding.
+///
+/// Struct fields are map keys too, so they are sorted rather than written
+/// in declaration order. The output is ordinary CBOR, so it can be decoded
+/// by [`CborCodec`], and both codecs share a [`FORMAT_ID`][Codec::FORMAT_ID].
+///
+/// Canonical encoding is slower, because each message is converted to a
+/// [`serde_cbor::Value`] before it's written.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CanonicalCborCodec;
+
+impl Codec for CanonicalCborCodec {
+    const FORMAT_ID: u16 = format_id::CBOR;
+
+    type Error = CborDataError;
+
+    fn encode<T>(&self, msg: &T, buf: &mut Vec<u8>) -> Result<(), CborDataError>
+    where
+        T: Serialize,
+    {
+        if is_unit_struct(msg) {
+            return Ok(());
+        }
+        // Maps in a `Value` are stored in canonical order.
+        let value = serde_cbor::value::to_value(msg)?;
+        serde_cbor::to_writer(&mut *buf, &value)?;
+        Ok(())
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        CborCodec.decode(buf)
+    }
+}
+
+/// An open array or map, while checking the nesting depth.
+enum Nesting {
+    /// An array or map with this many items left.
+    Items(u64),
+    /// An indefinite-length array or map.
+    Indefinite,
+    /// The chunks of an indefinite-length byte or text string. These don't
+    /// count towards the depth.
+    Chunks,
+}
+
+/// Check that a CBOR item isn't nested more than `max_depth` levels deep.
+///
+/// Each array or map adds a level; a scalar at the top level is 0 levels
+/// deep. The item is scanned without recursion, so this is safe to call on
+/// untrusted data. Returns [`CborDataError::NestingTooDeep`] if the limit
+/// is exceeded, or [`CborDataError::Eof`] if the item is truncated.
+///
+/// Only the first item in `buf` is checked; any bytes after it are
+/// ignored.
+pub fn check_depth(buf: &[u8], max_depth: usize) -> Result<(), CborDataError> {
+    let mut stack: Vec<Nesting> = Vec::new();
+    let mut depth = 0;
+    let mut pos = 0;
+
+    loop {
+        let initial = *buf.get(pos).ok_or(CborDataError::Eof)?;
+        pos += 1;
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+
+        // Read the argument that follows the initial byte.
+        let arg_len = match info {
+            0..=23 => 0,
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 => 0,
+            _ => return Err(CborDataError::Serializer),
+        };
+        let arg_bytes = buf.get(pos..pos + arg_len).ok_or(CborDataError::Eof)?;
+        pos += arg_len;
+        let arg = match info {
+            0..=23 => u64::from(info),
+            _ => arg_bytes
+                .iter()
+                .fold(0u64, |arg, &byte| (arg << 8) | u64::from(byte)),
+        };
+        let indefinite = info == 31;
+
+        let mut container = None;
+        match (major, indefinite) {
+            // A break ends an indefinite-length item.
+            (7, true) => match stack.pop() {
+                Some(Nesting::Indefinite) => depth -= 1,
+                Some(Nesting::Chunks) => {}
+                _ => return Err(CborDataError::Serializer),
+            },
+            // A tag applies to the next item.
+            (6, false) => continue,
+            // A string.
+            (2, false) | (3, false) => {
+                let len = usize::try_from(arg).map_err(|_| CborDataError::Eof)?;
+                let end = pos.checked_add(len).ok_or(CborDataError::Eof)?;
+                if end > buf.len() {
+                    return Err(CborDataError::Eof);
+                }
+                pos = end;
+            }
+            (2, true) | (3, true) => {
+                stack.push(Nesting::Chunks);
+                continue;
+            }
+            (4, false) => container = Some(Nesting::Items(arg)),
+            (5, false) => {
+                let items = arg.checked_mul(2).ok_or(CborDataError::Serializer)?;
+                container = Some(Nesting::Items(items));
+            }
+            (4, true) | (5, true) => container = Some(Nesting::Indefinite),
+            // An integer, float, or simple value.
+            (0, false) | (1, false) | (7, false) => {}
+            _ => return Err(CborDataError::Serializer),
+        }
+
+        if let Some(container) = container {
+            if depth == max_depth {
+                return Err(CborDataError::NestingTooDeep { max: max_depth });
+            }
+            if !matches!(container, Nesting::Items(0)) {
+                depth += 1;
+                stack.push(container);
+                continue;
+            }
+        }
+
+        // An item is complete; count it against the enclosing container.
+        loop {
+            match stack.last_mut() {
+                Some(Nesting::Items(left)) => {
+                    *left -= 1;
+                    if *left > 0 {
+                        break;
+                    }
+                    stack.pop();
+                    depth -= 1;
+                }
+                Some(_) => break,
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
 /// A [`DataSource`] and/or [`DataSink`] using the CBOR serialization format.
 ///
 /// [`CborData`] works with any type that implements [`Read`] or [`Write`].
@@ -50,64 +656,610 @@ impl From<io::Error> for CborDataError {
 /// It implements the [`DataSource`] trait if the inner type implements [`Read`],
 /// and implements the [`DataSink`] trait if the inner type implements [`Write`].
 ///
+/// Each message is preceded by a [`BasicHeader`]. A different header can
+/// be chosen with the `H` type parameter; see [`with_header`][Self::with_header].
+///
+/// # Decoding a preview
+///
+/// CBOR structs are encoded as maps, and serde ignores map fields that a
+/// struct doesn't have. So [`read_message`][DataSource::read_message] can
+/// decode a message into a smaller "preview" struct that only contains
+/// some of the fields, e.g. to route a large message based on a few
+/// fields in it. The preview struct doesn't need to implement
+/// [`Versioned`]; it only needs to match the message version that was
+/// written.
+///
+/// The whole message body is always consumed, so the `CborData` is left
+/// at the start of the next message. This means that a preview is a
+/// terminal read: the full message can't be read afterwards. To decode
+/// both a preview and the full message, use
+/// [`SliceSource::peek_message_ref`].
+///
+/// # Canonical encoding
+///
+/// By default, messages are written with [`CborCodec`]. Call
+/// [`canonical`][Self::canonical] to write them with
+/// [`CanonicalCborCodec`] instead, so that the same message always
+/// produces the same bytes.
+///
+/// # Large messages
+///
+/// Each message body is encoded into a buffer before it's written. If the
+/// inner type also implements [`Seek`], use
+/// [`write_message_streamed`][Self::write_message_streamed] to encode the
+/// body directly into it instead.
+///
 /// [`Read`]: std::io::Read
 /// [`Write`]: std::io::Write
 ///
-pub struct CborData<RW> {
+pub struct CborData<RW, H = BasicHeader> {
     inner: RW,
+    canonical: bool,
+    max_upgrade_steps: u16,
+    max_depth: Option<usize>,
+    header_format: Option<u16>,
+    peeked: PeekedId,
+    /// The body buffer used by `read_message_reuse`.
+    body: Vec<u8>,
+    _header: PhantomData<fn() -> H>,
 }
 
 impl<RW> CborData<RW> {
     /// Create a new `CborData`.
     pub fn new(reader: RW) -> Self {
-        CborData { inner: reader }
+        Self::with_header(reader)
+    }
+}
+
+impl<RW, H> CborData<RW, H> {
+    /// Create a new `CborData` that uses a specific header type.
+    ///
+    /// ```
+    /// # use aversion::util::cbor::CborData;
+    /// # use aversion::util::SemverHeader;
+    /// let sink = CborData::<Vec<u8>, SemverHeader>::with_header(Vec::new());
+    /// ```
+    pub fn with_header(inner: RW) -> Self {
+        CborData {
+            inner,
+            canonical: false,
+            max_upgrade_steps: DEFAULT_MAX_UPGRADE_STEPS,
+            max_depth: None,
+            header_format: None,
+            peeked: PeekedId::default(),
+            body: Vec::new(),
+            _header: PhantomData,
+        }
+    }
+
+    /// Write messages using canonical CBOR.
+    ///
+    /// See [`CanonicalCborCodec`] for details. This has no effect on
+    /// reading.
+    pub fn canonical(mut self) -> Self {
+        self.canonical = true;
+        self
+    }
+
+    /// Limit the number of upgrade steps when reading a message.
+    ///
+    /// See [`DataSource::max_upgrade_steps`]. The default is
+    /// [`DEFAULT_MAX_UPGRADE_STEPS`].
+    pub fn upgrade_limit(mut self, max_steps: u16) -> Self {
+        self.max_upgrade_steps = max_steps;
+        self
+    }
+
+    /// Reject message bodies that are nested more than `depth` levels deep.
+    ///
+    /// Each array or map adds a level, so a struct with a `Vec` field is
+    /// 2 levels deep. A message that is nested too deeply is rejected with
+    /// [`CborDataError::NestingTooDeep`] before it's decoded, which guards
+    /// against stack exhaustion when decoding untrusted data. See
+    /// [`check_depth`].
+    ///
+    /// With a limit set, each message body is read into a buffer before
+    /// it's decoded. By default, there is no limit other than the
+    /// recursion limit of `serde_cbor` itself.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Read headers that were written in header format `format`.
+    ///
+    /// The format usually comes from the stream's preamble; see
+    /// [`read_preamble`][crate::util::preamble::read_preamble]. Each header
+    /// is read with [`FramedHeader::deserialize_format`], so a header type
+    /// that understands older layouts can read data written with any of
+    /// them. By default, headers are read with
+    /// [`FramedHeader::deserialize_from`].
+    pub fn header_format(mut self, format: u16) -> Self {
+        self.header_format = Some(format);
+        self
+    }
+
+    /// Get a reference to the inner data type.
+    pub fn get_ref(&self) -> &RW {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner data type.
+    pub fn get_mut(&mut self) -> &mut RW {
+        &mut self.inner
     }
 
     /// Consume the `CborData`, returning the inner data type.
+    ///
+    /// If a message id was peeked with
+    /// [`peek_msg_id`][DataSource::peek_msg_id], those bytes have already
+    /// been read from the inner data type, and are lost.
     pub fn into_inner(self) -> RW {
         self.inner
     }
 }
 
-impl<R> DataSource for CborData<R>
+/// Deserialize a header, in a specific header format if one is given.
+fn deserialize_header<H>(format: Option<u16>, r: &mut impl Read) -> io::Result<H>
+where
+    H: FramedHeader,
+{
+    match format {
+        Some(format) => H::deserialize_format(format, r),
+        None => H::deserialize_from(r),
+    }
+}
+
+impl<R, H> CborData<R, H>
+where
+    R: Read,
+    H: FramedHeader,
+{
+    /// Read every message until EOF, rejecting an incomplete last message.
+    ///
+    /// This expects the data to contain nothing but complete messages. If
+    /// it ends with a partial header, or with a header whose message body
+    /// is cut short, [`CborDataError::TrailingGarbage`] is returned, so
+    /// that a truncated or corrupted file is noticed. Other errors, e.g.
+    /// an unknown message id, are returned as usual.
+    ///
+    /// ```
+    /// # use aversion::group::DataSink;
+    /// # use aversion::util::cbor::{CborData, CborDataError};
+    /// # use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Versioned, UpgradeLatest, Serialize, Deserialize)]
+    /// # struct FooV1 { foo: u32 }
+    /// # type Foo = FooV1;
+    /// # assign_message_ids! { Foo: 1 }
+    /// #[derive(Debug, GroupDeserialize)]
+    /// enum MyGroup {
+    ///     Foo(Foo),
+    /// }
+    ///
+    /// let mut sink = CborData::new(Vec::new());
+    /// sink.write_message(&FooV1 { foo: 1 }).unwrap();
+    /// let mut buf = sink.into_inner();
+    ///
+    /// let msgs: Vec<MyGroup> = CborData::new(&buf[..]).read_until_eof_strict().unwrap();
+    /// assert_eq!(msgs.len(), 1);
+    ///
+    /// buf.push(0);
+    /// let result = CborData::new(&buf[..]).read_until_eof_strict::<MyGroup>();
+    /// assert!(matches!(result, Err(CborDataError::TrailingGarbage { messages: 1 })));
+    /// ```
+    pub fn read_until_eof_strict<G>(&mut self) -> Result<Vec<G>, CborDataError>
+    where
+        G: GroupDeserialize,
+    {
+        let mut msgs = Vec::new();
+        loop {
+            let garbage = CborDataError::TrailingGarbage {
+                messages: msgs.len(),
+            };
+            let header = match self.try_read_header() {
+                Ok(Some(header)) => header,
+                Ok(None) => return Ok(msgs),
+                Err(e) if e.is_truncated() => return Err(garbage),
+                Err(e) => return Err(e),
+            };
+            let mut src = ReadAhead {
+                inner: &mut *self,
+                header: Some(header),
+            };
+            match G::read_message(&mut src) {
+                Ok(msg) => msgs.push(msg),
+                Err(e) if e.is_truncated() => return Err(garbage),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Read a message body into an existing value, reusing its allocations.
+    ///
+    /// This works like [`read_message`][DataSource::read_message], but
+    /// decodes with serde's `deserialize_in_place`, so a decode loop can
+    /// keep one value around and avoid allocating for every message.
+    /// No upgrade is performed; `T` must be the version that was written.
+    ///
+    /// Whether anything is actually reused depends on `T`'s `Deserialize`
+    /// impl:
+    ///
+    /// - `Vec<T>` keeps its capacity, and decodes each element in place.
+    /// - `String` keeps its capacity.
+    /// - Tuples and arrays decode each element in place.
+    /// - Structs created with `#[derive(Deserialize)]` decode each field
+    ///   in place, but only if serde_derive's `deserialize_in_place`
+    ///   feature is enabled.
+    /// - Every other type (including `Option`, `Box`, maps, and structs
+    ///   derived without that feature) is decoded as usual and assigned
+    ///   to `existing`.
+    ///
+    /// If an error is returned, the contents of `existing` are
+    /// unspecified, though still valid.
+    ///
+    /// The message body is read into a buffer owned by the `CborData`,
+    /// which grows to fit the largest message read this way.
+    ///
+    /// ```
+    /// # use aversion::group::{DataSink, DataSource};
+    /// # use aversion::util::cbor::CborData;
+    /// # use aversion::{assign_message_ids, Versioned};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Default, Versioned, Serialize, Deserialize)]
+    /// struct SamplesV1 {
+    ///     values: Vec<u32>,
+    /// }
+    /// # type Samples = SamplesV1;
+    /// # assign_message_ids! { Samples: 1 }
+    ///
+    /// # let mut sink = CborData::new(Vec::new());
+    /// # for n in 0..3 {
+    /// #     sink.write_message(&SamplesV1 { values: vec![n; 8] }).unwrap();
+    /// # }
+    /// # let buf = sink.into_inner();
+    /// let mut src = CborData::new(&buf[..]);
+    /// let mut samples = SamplesV1::default();
+    /// while let Some(header) = src.try_read_header().unwrap() {
+    ///     src.read_message_reuse(&header, &mut samples).unwrap();
+    ///     assert_eq!(samples.values.len(), 8);
+    /// }
+    /// ```
+    pub fn read_message_reuse<T>(
+        &mut self,
+        header: &H,
+        existing: &mut T,
+    ) -> Result<(), CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        if header.msg_len() == 0 {
+            *existing = decode_empty()?;
+            return Ok(());
+        }
+        // The body is read into a buffer that is kept between calls, so
+        // that the deserializer can borrow strings from it rather than
+        // copying them into a scratch buffer of its own.
+        read_body(&mut self.inner, header.msg_len(), &mut self.body)?;
+        if let Some(max_depth) = self.max_depth {
+            check_depth(&self.body, max_depth)?;
+        }
+        let mut deserializer = serde_cbor::Deserializer::from_slice(&self.body);
+        T::deserialize_in_place(&mut deserializer, existing)?;
+        deserializer.end()?;
+        Ok(())
+    }
+}
+
+impl<R, H> DataSource for CborData<R, H>
 where
     R: Read,
+    H: FramedHeader,
 {
     type Error = CborDataError;
-    type Header = BasicHeader;
+    type Header = H;
 
-    fn read_header(&mut self) -> Result<BasicHeader, CborDataError> {
-        Ok(BasicHeader::deserialize_from(&mut self.inner)?)
+    fn read_header(&mut self) -> Result<H, CborDataError> {
+        let peeked = self.peeked.take();
+        let mut reader = peeked.as_slice().chain(&mut self.inner);
+        Ok(deserialize_header(self.header_format, &mut reader)?)
     }
 
-    fn read_message<T>(&mut self, header: &BasicHeader) -> Result<T, CborDataError>
+    fn read_header_into(&mut self, header: &mut H) -> Result<(), CborDataError> {
+        let peeked = self.peeked.take();
+        let mut reader = peeked.as_slice().chain(&mut self.inner);
+        match self.header_format {
+            Some(format) => *header = H::deserialize_format(format, &mut reader)?,
+            None => header.deserialize_into(&mut reader)?,
+        }
+        Ok(())
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<H>, CborDataError> {
+        let peeked = self.peeked.take();
+        if !peeked.is_empty() {
+            let mut reader = peeked.as_slice().chain(&mut self.inner);
+            return Ok(Some(deserialize_header(self.header_format, &mut reader)?));
+        }
+        let first = match try_read_first_byte(&mut self.inner)? {
+            Some(first) => [first],
+            None => return Ok(None),
+        };
+        let mut reader = (&first[..]).chain(&mut self.inner);
+        Ok(Some(deserialize_header(self.header_format, &mut reader)?))
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, CborDataError> {
+        Ok(self.peeked.peek::<H>(&mut self.inner)?)
+    }
+
+    fn read_message<T>(&mut self, header: &H) -> Result<T, CborDataError>
     where
         T: DeserializeOwned,
     {
+        if header.msg_len() == 0 {
+            return decode_empty();
+        }
+        if let Some(max_depth) = self.max_depth {
+            let body = self.read_raw(header)?;
+            check_depth(&body, max_depth)?;
+            return CborCodec.decode(&body);
+        }
         // Construct a reader over the exact message length specified
         // in the message header.
         let reader = &mut self.inner;
-        let mut subreader = reader.take(header.msg_len.into());
-        let msg: T = serde_cbor::from_reader(&mut subreader)?;
-        Ok(msg)
+        let mut subreader = reader.take(header.msg_len().into());
+        match serde_cbor::from_reader(&mut subreader) {
+            Ok(msg) => Ok(msg),
+            Err(e) => {
+                // Consume the rest of the body, so that the next header
+                // can still be read.
+                let _ = io::copy(&mut subreader, &mut io::sink());
+                Err(e.into())
+            }
+        }
+    }
+
+    fn read_raw(&mut self, header: &H) -> Result<Vec<u8>, CborDataError> {
+        let mut body = Vec::new();
+        read_body(&mut self.inner, header.msg_len(), &mut body)?;
+        Ok(body)
+    }
+
+    fn skip_message(&mut self, header: &H) -> Result<(), CborDataError> {
+        let msg_len = u64::from(header.msg_len());
+        let mut subreader = (&mut self.inner).take(msg_len);
+        let skipped = io::copy(&mut subreader, &mut io::sink())?;
+        if skipped < msg_len {
+            return Err(CborDataError::Eof);
+        }
+        Ok(())
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.max_upgrade_steps
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
+    }
+}
+
+/// A [`DataSource`] that reads messages with either a [`BasicHeader`] or a
+/// [`VarintHeader`][crate::util::VarintHeader].
+///
+/// This is a [`CborData`] using [`MultiHeader`], which detects the header
+/// type of each message; see [`MultiHeader`] for how detection works and
+/// its limits.
+///
+/// ```
+/// # use aversion::util::cbor::MultiHeaderSource;
+/// # let file: &[u8] = &[];
+/// let src = MultiHeaderSource::with_header(file);
+/// ```
+pub type MultiHeaderSource<R> = CborData<R, MultiHeader>;
+
+/// A [`DataSource`] that reads CBOR messages from a byte slice.
+///
+/// This uses the same format as [`CborData`], but because the data is
+/// already in memory, messages may borrow from it; see
+/// [`read_message_ref`][Self::read_message_ref].
+pub struct SliceSource<'de> {
+    remaining: &'de [u8],
+}
+
+impl<'de> SliceSource<'de> {
+    /// Create a new `SliceSource`.
+    pub fn new(buf: &'de [u8]) -> Self {
+        SliceSource { remaining: buf }
+    }
+
+    /// Returns the bytes that haven't been read yet.
+    pub fn remaining(&self) -> &'de [u8] {
+        self.remaining
+    }
+
+    /// Read a message that may borrow from the source data.
+    ///
+    /// This is like [`read_message`][DataSource::read_message], but `T`
+    /// doesn't need to be `DeserializeOwned`. Fields of type `&'de [u8]`
+    /// and `&'de str` will borrow from the source data.
+    ///
+    /// Fields of type `Cow<'de, [u8]>` or `Cow<'de, str>` must be marked
+    /// with `#[serde(borrow)]`; otherwise serde will always make a copy.
+    /// If they are, the field will borrow if the value is stored
+    /// contiguously. CBOR also allows byte and text strings to be split
+    /// into chunks (indefinite-length strings); those can't be borrowed,
+    /// so the chunks are joined into a `Cow::Owned` value.
+    ///
+    /// No upgrade is performed, because upgrading would need to consume
+    /// the message; `T` should be the message version that was written.
+    ///
+    /// The message body is consumed even if it fails to decode.
+    pub fn read_message_ref<T>(&mut self, header: &BasicHeader) -> Result<T, CborDataError>
+    where
+        T: Deserialize<'de>,
+    {
+        let msg = self.peek_message_ref(header);
+        if let Some(rest) = self.remaining.get(header.msg_len as usize..) {
+            self.remaining = rest;
+        }
+        msg
+    }
+
+    /// Decode a message without consuming it.
+    ///
+    /// This is like [`read_message_ref`][Self::read_message_ref], but the
+    /// message body is left in place, so it can be read again afterwards.
+    /// This is useful with a preview struct (see [`CborData`]): a router
+    /// can peek at a few fields, then read or skip the full message.
+    pub fn peek_message_ref<T>(&self, header: &BasicHeader) -> Result<T, CborDataError>
+    where
+        T: Deserialize<'de>,
+    {
+        let msg_len = header.msg_len as usize;
+        if self.remaining.len() < msg_len {
+            return Err(CborDataError::Eof);
+        }
+        let body = &self.remaining[..msg_len];
+        if body.is_empty() {
+            return decode_empty();
+        }
+        Ok(serde_cbor::from_slice(body)?)
+    }
+}
+
+impl<'de> DataSource for SliceSource<'de> {
+    type Error = CborDataError;
+    type Header = BasicHeader;
+
+    fn read_header(&mut self) -> Result<BasicHeader, CborDataError> {
+        Ok(BasicHeader::deserialize_from(&mut self.remaining)?)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<BasicHeader>, CborDataError> {
+        if self.remaining.is_empty() {
+            return Ok(None);
+        }
+        self.read_header().map(Some)
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, CborDataError> {
+        match self.remaining {
+            [hi, lo, ..] => Ok(u16::from_be_bytes([*hi, *lo])),
+            _ => Err(CborDataError::Eof),
+        }
+    }
+
+    fn read_message<T>(&mut self, header: &BasicHeader) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        self.read_message_ref(header)
+    }
+
+    fn read_raw(&mut self, header: &BasicHeader) -> Result<Vec<u8>, CborDataError> {
+        let msg_len = header.msg_len as usize;
+        if self.remaining.len() < msg_len {
+            return Err(CborDataError::Eof);
+        }
+        let (body, rest) = self.remaining.split_at(msg_len);
+        self.remaining = rest;
+        Ok(body.to_vec())
+    }
+
+    fn skip_message(&mut self, header: &BasicHeader) -> Result<(), CborDataError> {
+        let msg_len = header.msg_len as usize;
+        if self.remaining.len() < msg_len {
+            return Err(CborDataError::Eof);
+        }
+        self.remaining = &self.remaining[msg_len..];
+        Ok(())
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
     }
 
-    fn unknown_message(&self, _msg_id: u16) -> CborDataError {
-        CborDataError::Serializer
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
     }
 
-    fn unknown_version<T>(&self, _ver: u16) -> CborDataError {
-        CborDataError::Serializer
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
     }
 
-    fn unexpected_message<T>(&self, _msg_id: u16) -> CborDataError {
-        CborDataError::Serializer
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
     }
 }
 
-impl<W> DataSink for CborData<W>
+impl<W, H> DataSink for CborData<W, H>
 where
     W: Write,
+    H: FramedHeader,
 {
     type Error = CborDataError;
 
@@ -116,16 +1268,319 @@ where
         T: Serialize + Versioned,
         T::Base: MessageId,
     {
-        // Serialize the message first, then the header (which needs
-        // the serialized message length.
-        let msg_buf = Vec::<u8>::new();
-        let mut cursor = Cursor::new(msg_buf);
-        serde_cbor::to_writer(&mut cursor, msg)?;
-        let msg_buf = cursor.into_inner();
-        let msg_len: u32 = msg_buf.len().try_into().expect("usize to u32");
-        let header = BasicHeader::for_msg(msg, msg_len);
-        header.serialize_into(&mut self.inner)?;
-        self.inner.write_all(&msg_buf)?;
+        self.write_message_with_header(msg, |header| header)
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        self.inner.flush()?;
         Ok(())
     }
 }
+
+impl<W, H> CborData<W, H>
+where
+    W: Write,
+    H: FramedHeader,
+{
+    /// Write a message, with a header that was modified by `set_fields`.
+    ///
+    /// The header is created with [`FramedHeader::for_msg`], and then
+    /// passed to `set_fields`, which can fill in optional header fields.
+    pub(crate) fn write_message_with_header<T, F>(
+        &mut self,
+        msg: &T,
+        set_fields: F,
+    ) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+        F: FnOnce(H) -> H,
+    {
+        let mut msg_buf = Vec::<u8>::new();
+        self.write_message_with_buffer(msg, &mut msg_buf, set_fields)
+    }
+
+    /// Write a message, using `msg_buf` to hold the encoded frame.
+    ///
+    /// `msg_buf` is cleared first.
+    fn write_message_with_buffer<T, F>(
+        &mut self,
+        msg: &T,
+        msg_buf: &mut Vec<u8>,
+        set_fields: F,
+    ) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+        F: FnOnce(H) -> H,
+    {
+        write_span::<T, _, _, _>(T::Base::WIDE_MSG_ID, T::VER, || {
+            // Serialize the message first, then the header (which needs
+            // the serialized message length.
+            msg_buf.clear();
+            if self.canonical {
+                CanonicalCborCodec.encode(msg, msg_buf)?;
+            } else {
+                CborCodec.encode(msg, msg_buf)?;
+            }
+            let msg_len: u32 = msg_buf.len().try_into().expect("usize to u32");
+            let header = set_fields(H::for_msg(msg, msg_len));
+            // Write the whole frame at once, so that a writer shared with
+            // other threads never sees a header without its body. The
+            // header is appended to `msg_buf` and rotated to the front, so
+            // that a reused buffer doesn't need a second allocation.
+            let body_len = msg_buf.len();
+            header.serialize_into(msg_buf)?;
+            msg_buf.rotate_left(body_len);
+            self.inner.write_all(msg_buf)?;
+            Ok(())
+        })
+    }
+
+    /// Write a message, encoding the body into a reusable buffer.
+    ///
+    /// This produces the same output as
+    /// [`write_message`][DataSink::write_message], which allocates a new
+    /// buffer for each message body. When writing many messages in a loop,
+    /// e.g. for a bulk export, pass the same [`BatchEncoder`] each time, so
+    /// that its buffer is reused.
+    ///
+    /// ```
+    /// # use aversion::util::cbor::{BatchEncoder, CborData};
+    /// # use aversion::{assign_message_ids, Versioned};
+    /// # use serde::Serialize;
+    /// #[derive(Versioned, Serialize)]
+    /// struct RowV1 {
+    ///     id: u64,
+    /// }
+    /// # type Row = RowV1;
+    /// # assign_message_ids! { Row: 1 }
+    ///
+    /// let mut sink = CborData::new(Vec::new());
+    /// let mut encoder = BatchEncoder::new();
+    /// for id in 0..100 {
+    ///     sink.write_message_batched(&mut encoder, &RowV1 { id }).unwrap();
+    /// }
+    /// ```
+    pub fn write_message_batched<T>(
+        &mut self,
+        encoder: &mut BatchEncoder,
+        msg: &T,
+    ) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        self.write_message_with_buffer(msg, &mut encoder.scratch, |header| header)
+    }
+}
+
+/// A reusable buffer for encoding message bodies.
+///
+/// See [`CborData::write_message_batched`]. The buffer grows to fit the
+/// largest message written so far; create a new `BatchEncoder` to release
+/// that memory.
+#[derive(Debug, Default)]
+pub struct BatchEncoder {
+    scratch: Vec<u8>,
+}
+
+impl BatchEncoder {
+    /// Create a new `BatchEncoder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new `BatchEncoder`, with room for a frame (message body
+    /// and header) of `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        BatchEncoder {
+            scratch: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The size of the largest frame (message body and header) that fits
+    /// without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.scratch.capacity()
+    }
+}
+
+impl<W, H> CborData<W, H>
+where
+    W: Write,
+    H: FramedHeader,
+{
+    /// Write a message that is followed by a chunked payload.
+    ///
+    /// The message is written as usual; the payload is then written to
+    /// the returned [`ChunkWriter`], which must be
+    /// [`finish`][ChunkWriter::finish]ed before anything else is written.
+    /// See the [`chunked`][crate::util::chunked] module for the format.
+    pub fn write_chunked<T>(&mut self, msg: &T) -> Result<ChunkWriter<&mut W>, CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        self.write_message(msg)?;
+        Ok(ChunkWriter::new(&mut self.inner))
+    }
+}
+
+impl<R, H> CborData<R, H>
+where
+    R: Read,
+{
+    /// Read a chunked payload that follows the message just read.
+    ///
+    /// The returned [`ChunkReader`] reads the payload incrementally, so
+    /// it doesn't need to fit in memory. It should be read to the end,
+    /// or [`finish`][ChunkReader::finish]ed, before the next message is
+    /// read. See the [`chunked`][crate::util::chunked] module for the
+    /// format.
+    pub fn read_chunked_body(&mut self) -> ChunkReader<&mut R> {
+        ChunkReader::new(&mut self.inner)
+    }
+}
+
+impl<W, H> CborData<W, H>
+where
+    W: Write + Seek,
+    H: FramedHeader,
+{
+    /// Write a message, encoding the body directly into the writer.
+    ///
+    /// [`write_message`][DataSink::write_message] encodes the message body
+    /// into a buffer first, because the header needs the body length. This
+    /// function avoids that buffer, which matters for very large messages:
+    /// it writes a placeholder header, encodes the body into the writer,
+    /// then seeks back and overwrites the header with the real length.
+    /// The writer is left at the end of the message.
+    ///
+    /// The output is the same as `write_message`. This requires that the
+    /// size of the serialized header doesn't depend on its length field,
+    /// which is true of all the headers in this crate except
+    /// [`VarintHeader`][crate::util::VarintHeader]. If the size changes, an
+    /// error of kind [`InvalidInput`][io::ErrorKind::InvalidInput] is
+    /// returned, and the output is corrupt.
+    ///
+    /// A writer that can't seek (e.g. a socket) can't be backpatched, so
+    /// use `write_message` for those. Canonical encoding (see
+    /// [`canonical`][Self::canonical]) still converts the message to a
+    /// [`serde_cbor::Value`] first, but the encoded bytes aren't buffered.
+    pub fn write_message_streamed<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let header_start = self.inner.stream_position()?;
+        H::for_msg(msg, 0).serialize_into(&mut self.inner)?;
+        let body_start = self.inner.stream_position()?;
+
+        let mut body = BodyWriter::new(&mut self.inner);
+        if is_unit_struct(msg) {
+            // Written as an empty body, as CborCodec does.
+        } else if self.canonical {
+            let value = serde_cbor::value::to_value(msg)?;
+            serde_cbor::to_writer(&mut body, &value)?;
+        } else {
+            serde_cbor::to_writer(&mut body, msg)?;
+        }
+        let msg_len = body.len;
+        let msg_len_u32: u32 = msg_len.try_into().expect("u64 to u32");
+
+        let body_end = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(header_start))?;
+        H::for_msg(msg, msg_len_u32).serialize_into(&mut self.inner)?;
+        if self.inner.stream_position()? != body_start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "header size depends on the message length",
+            )
+            .into());
+        }
+        self.inner.seek(SeekFrom::Start(body_end))?;
+        Ok(())
+    }
+}
+
+/// A writer for a message body that counts the bytes written.
+struct BodyWriter<'a, W> {
+    inner: &'a mut W,
+    len: u64,
+}
+
+impl<'a, W: Write> BodyWriter<'a, W> {
+    fn new(inner: &'a mut W) -> Self {
+        BodyWriter { inner, len: 0 }
+    }
+}
+
+impl<W: Write> Write for BodyWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The default limit on the length of a frame, including its header, for
+/// the `tokio-codec` and `async` decoders.
+#[cfg(feature = "bytes")]
+pub const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// Decode one message from the front of a buffer, if a complete frame is
+/// available.
+///
+/// The frame is removed from the buffer even if it fails to deserialize,
+/// so that the caller remains in sync with the stream.
+///
+/// If the header describes a frame longer than `max_frame_len`,
+/// [`CborDataError::FrameTooLong`] is returned, and the buffer is left as
+/// it is. Otherwise, room is reserved for the rest of the frame.
+#[cfg(feature = "bytes")]
+pub(crate) fn decode_frame<G>(
+    buf: &mut bytes::BytesMut,
+    max_frame_len: usize,
+) -> Result<Option<G>, CborDataError>
+where
+    G: crate::GroupDeserialize,
+{
+    Ok(decode_frame_with_id(buf, max_frame_len)?.map(|(_, msg)| msg))
+}
+
+/// Like [`decode_frame`], but also returns the message id from the
+/// frame header.
+#[cfg(feature = "bytes")]
+pub(crate) fn decode_frame_with_id<G>(
+    buf: &mut bytes::BytesMut,
+    max_frame_len: usize,
+) -> Result<Option<(u32, G)>, CborDataError>
+where
+    G: crate::GroupDeserialize,
+{
+    if buf.len() < BasicHeader::SIZE {
+        return Ok(None);
+    }
+    let header = BasicHeader::deserialize_from(&mut &buf[..BasicHeader::SIZE])?;
+    let frame_len = BasicHeader::SIZE + header.msg_len as usize;
+    if frame_len > max_frame_len {
+        return Err(CborDataError::FrameTooLong {
+            len: frame_len,
+            max: max_frame_len,
+        });
+    }
+    if buf.len() < frame_len {
+        buf.reserve(frame_len - buf.len());
+        return Ok(None);
+    }
+
+    let frame = buf.split_to(frame_len);
+    let mut frame_src = CborData::new(&frame[..]);
+    let msg = G::read_message(&mut frame_src)?;
+    Ok(Some((header.msg_id.into(), msg)))
+}
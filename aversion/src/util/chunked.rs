@@ -0,0 +1,210 @@
+//! Stream a large payload as a series of chunks.
+//!
+//! A message body is normally read or written in one piece, so the whole
+//! body has to fit in memory. A payload that may be very large can
+//! instead follow its message as a series of chunk frames:
+//!
+//! | size | field |
+//! |------|-------|
+//! | 4    | chunk length (big-endian) |
+//! | n    | chunk data |
+//!
+//! A chunk with length 0 marks the end of the payload. The message
+//! itself carries any metadata, e.g. a file name, and is written and
+//! read as usual.
+//!
+//! [`ChunkWriter`] writes the chunk frames, and [`ChunkReader`] reads
+//! them back as a single byte stream. [`CborData`] has helpers that
+//! combine a message with its payload:
+//!
+//! ```
+//! # use aversion::group::{DataSource, DataSourceExt};
+//! # use aversion::util::cbor::CborData;
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # use std::io::{Read, Write};
+//! #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! struct UploadV1 {
+//!     name: String,
+//! }
+//! # type Upload = UploadV1;
+//! # assign_message_ids! { Upload: 1 }
+//!
+//! let mut sink = CborData::new(Vec::new());
+//! let mut chunks = sink.write_chunked(&UploadV1 { name: "a.txt".into() }).unwrap();
+//! chunks.write_all(b"hello world").unwrap();
+//! chunks.finish().unwrap();
+//! let buf = sink.into_inner();
+//!
+//! let mut src = CborData::new(&buf[..]);
+//! let upload: Upload = src.expect_message().unwrap();
+//! let mut payload = Vec::new();
+//! src.read_chunked_body().read_to_end(&mut payload).unwrap();
+//! assert_eq!(payload, b"hello world");
+//! ```
+//!
+//! The payload isn't part of the message, so anything that handles
+//! messages individually, e.g. [`skip_message`], doesn't know about it.
+//! The protocol must define which messages are followed by a payload.
+//!
+//! [`CborData`]: crate::util::cbor::CborData
+//! [`skip_message`]: crate::group::DataSource::skip_message
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::cmp::min;
+use std::convert::{TryFrom, TryInto};
+use std::io::{self, Read, Write};
+
+/// The default maximum chunk size used by [`ChunkWriter`].
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Writes a payload as a series of chunk frames.
+///
+/// Data is buffered until a full chunk is available. Call
+/// [`finish`][Self::finish] to write the last chunk and the end marker;
+/// if a `ChunkWriter` is dropped without calling `finish`, the payload
+/// is incomplete.
+///
+/// See the [module documentation](self) for the format.
+pub struct ChunkWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+    chunk_size: usize,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Create a new `ChunkWriter`.
+    pub fn new(inner: W) -> Self {
+        ChunkWriter {
+            inner,
+            buf: Vec::new(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Set the maximum chunk size.
+    ///
+    /// The default is [`DEFAULT_CHUNK_SIZE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0 or doesn't fit in a `u32`.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "chunk size must be nonzero");
+        assert!(u32::try_from(size).is_ok(), "chunk size must fit in a u32");
+        self.chunk_size = size;
+        self
+    }
+
+    fn write_chunk(inner: &mut W, chunk: &[u8]) -> io::Result<()> {
+        let len: u32 = chunk.len().try_into().expect("usize to u32");
+        inner.write_u32::<BigEndian>(len)?;
+        inner.write_all(chunk)
+    }
+
+    fn write_buffered(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            Self::write_chunk(&mut self.inner, &self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+
+    /// Write any buffered data, followed by the end marker.
+    ///
+    /// Returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_buffered()?;
+        self.inner.write_u32::<BigEndian>(0)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChunkWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.is_empty() && data.len() >= self.chunk_size {
+            // Write a full chunk without copying it.
+            let chunk = &data[..self.chunk_size];
+            Self::write_chunk(&mut self.inner, chunk)?;
+            return Ok(chunk.len());
+        }
+        let len = min(data.len(), self.chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+        if self.buf.len() == self.chunk_size {
+            self.write_buffered()?;
+        }
+        Ok(len)
+    }
+
+    /// Write any buffered data as a chunk, and flush the inner writer.
+    ///
+    /// This doesn't end the payload.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buffered()?;
+        self.inner.flush()
+    }
+}
+
+/// Reads a payload that was written as a series of chunk frames.
+///
+/// The payload is read incrementally; only the chunk headers are
+/// buffered. After the end marker, `read` returns 0. If the data ends
+/// before the end marker, an error of kind
+/// [`UnexpectedEof`][io::ErrorKind::UnexpectedEof] is returned.
+///
+/// If the payload isn't read to the end, call [`finish`][Self::finish]
+/// to skip the rest of it, so that the next message can be read.
+///
+/// See the [module documentation](self) for the format.
+pub struct ChunkReader<R> {
+    inner: R,
+    /// The number of bytes left in the current chunk.
+    remaining: u32,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Create a new `ChunkReader`.
+    pub fn new(inner: R) -> Self {
+        ChunkReader {
+            inner,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    /// Returns `true` if the end marker has been read.
+    pub fn is_finished(&self) -> bool {
+        self.done
+    }
+
+    /// Skip the rest of the payload, including the end marker.
+    ///
+    /// Returns the number of payload bytes that were skipped.
+    pub fn finish(mut self) -> io::Result<u64> {
+        io::copy(&mut self, &mut io::sink())
+    }
+}
+
+impl<R: Read> Read for ChunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            self.remaining = self.inner.read_u32::<BigEndian>()?;
+            if self.remaining == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let len = min(buf.len(), self.remaining as usize);
+        let count = self.inner.read(&mut buf[..len])?;
+        if count == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // `count` is no larger than `remaining`.
+        self.remaining -= u32::try_from(count).expect("usize to u32");
+        Ok(count)
+    }
+}
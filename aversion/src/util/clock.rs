@@ -0,0 +1,45 @@
+//! Provides the `Clock` trait, for components that check the time.
+//!
+//! Components that compare header fields against the current time, such
+//! as [`ExpirySource`] and [`RateLimitedSource`], read it from a `Clock`
+//! instead of calling [`SystemTime::now`] directly. They use
+//! [`SystemClock`] by default; tests can substitute a clock they control,
+//! e.g. `MockClock` from the `test_util` module (with the `test-util`
+//! feature), so that time-dependent decoding is deterministic.
+//!
+//! [`ExpirySource`]: crate::util::expiry::ExpirySource
+//! [`RateLimitedSource`]: crate::util::rate_limit::RateLimitedSource
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of the current time.
+///
+/// The time must use the same units as the times that are written into
+/// message headers, e.g. seconds for [`SystemClock`].
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> u64;
+}
+
+/// A [`Clock`] that returns the system time, in seconds since the Unix
+/// epoch.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        // A clock set before 1970 treats every message as live.
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+impl<C> Clock for &C
+where
+    C: Clock + ?Sized,
+{
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
@@ -0,0 +1,66 @@
+//! Provides the `Codec` trait, for encoding message bodies.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A serialization format for message bodies.
+///
+/// A codec only handles the message body; headers are written separately,
+/// by the [`DataSink`] or [`DataSource`].
+///
+/// Each codec has a [`FORMAT_ID`][Self::FORMAT_ID], which is recorded in
+/// the file [preamble] so that a reader can tell which codec produced a
+/// file.
+///
+/// # Canonical output
+///
+/// Some applications, such as signing messages, need the same value to
+/// always produce the same bytes. Of the codecs provided by this crate,
+/// only [`CanonicalCborCodec`] guarantees this. [`CborCodec`] and
+/// [`JsonCodec`] write map entries in iteration order, so a `HashMap`
+/// field may be encoded differently each time.
+///
+/// [`DataSink`]: crate::group::DataSink
+/// [`DataSource`]: crate::group::DataSource
+/// [preamble]: crate::util::preamble
+/// [`CanonicalCborCodec`]: crate::util::cbor::CanonicalCborCodec
+/// [`CborCodec`]: crate::util::cbor::CborCodec
+/// [`JsonCodec`]: crate::util::json::JsonCodec
+pub trait Codec {
+    /// A number that identifies this serialization format.
+    ///
+    /// Format ids below 0x100 are reserved for codecs provided by this
+    /// crate. Other codecs should use a value of 0x100 or higher.
+    const FORMAT_ID: u16;
+
+    /// The error returned when encoding or decoding fails.
+    type Error;
+
+    /// Serialize a message, appending the bytes to `buf`.
+    fn encode<T>(&self, msg: &T, buf: &mut Vec<u8>) -> Result<(), Self::Error>
+    where
+        T: Serialize;
+
+    /// Deserialize a message from a buffer.
+    ///
+    /// The buffer contains exactly one message body.
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, Self::Error>
+    where
+        T: DeserializeOwned;
+}
+
+/// Format ids for the codecs provided by this crate.
+pub mod format_id {
+    /// [`CborCodec`](crate::util::cbor::CborCodec).
+    pub const CBOR: u16 = 1;
+    /// [`JsonCodec`](crate::util::json::JsonCodec).
+    pub const JSON: u16 = 2;
+    /// [`FixedSizeCodec`](crate::util::fixed::FixedSizeCodec).
+    pub const FIXED_SIZE: u16 = 3;
+    /// [`PackedCodec<BigEndian>`](crate::util::fixed::PackedCodec).
+    pub const PACKED_BE: u16 = 4;
+    /// [`PackedCodec<LittleEndian, ReprC>`](crate::util::fixed::PackedCodec).
+    pub const REPR_C_LE: u16 = 5;
+    /// [`PackedCodec<BigEndian, ReprC>`](crate::util::fixed::PackedCodec).
+    pub const REPR_C_BE: u16 = 6;
+}
@@ -0,0 +1,207 @@
+//! Compact a message log, keeping only the latest message for each key.
+
+use crate::group::{DataSink, DataSource, GroupDeserialize, GroupHeader, GroupSerialize};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::hash::Hash;
+use thiserror::Error;
+
+/// An error returned by [`compact`].
+#[derive(Debug, Error)]
+pub enum CompactError<R, W> {
+    /// Reading from the source failed.
+    #[error("failed to read a message")]
+    Read(#[source] R),
+    /// Writing to the destination failed.
+    #[error("failed to write a message")]
+    Write(#[source] W),
+}
+
+/// Copy a message log, dropping messages that were superseded.
+///
+/// Every message is read from `src` until
+/// [`try_read_header`][DataSource::try_read_header] detects the end of the
+/// data. `key_fn` extracts a key from each message; only the last message
+/// with each key is kept. The surviving messages are written to `dst` in
+/// the order they were read, and then `dst` is flushed.
+///
+/// The messages are held in memory until the whole source has been read,
+/// so this is meant for logs that fit in memory.
+///
+/// Returns the number of messages written.
+///
+/// ```
+/// # use aversion::group::DataSinkExt;
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::util::compact::compact;
+/// # use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize};
+/// # use aversion::{UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// struct SetV1 {
+///     key: String,
+///     value: u32,
+/// }
+/// # type Set = SetV1;
+/// # assign_message_ids! { Set: 1 }
+///
+/// #[derive(GroupDeserialize, GroupSerialize)]
+/// enum Log {
+///     Set(Set),
+/// }
+///
+/// # let mut log = CborData::new(Vec::new());
+/// # log.write_all_messages(vec![
+/// #     Log::Set(SetV1 { key: "a".into(), value: 1 }),
+/// #     Log::Set(SetV1 { key: "a".into(), value: 2 }),
+/// # ]).unwrap();
+/// # let buf = log.into_inner();
+/// let mut src = CborData::new(&buf[..]);
+/// let mut dst = CborData::new(Vec::new());
+/// let count = compact(&mut src, &mut dst, |msg: &Log| match msg {
+///     Log::Set(set) => set.key.clone(),
+/// })
+/// .unwrap();
+/// assert_eq!(count, 1);
+/// ```
+pub fn compact<G, K, F, Src, Snk>(
+    src: &mut Src,
+    dst: &mut Snk,
+    mut key_fn: F,
+) -> Result<usize, CompactError<Src::Error, Snk::Error>>
+where
+    G: GroupDeserialize + GroupSerialize,
+    K: Eq + Hash,
+    F: FnMut(&G) -> K,
+    Src: DataSource,
+    Snk: DataSink,
+{
+    // Superseded messages are replaced by `None`.
+    let mut msgs: Vec<Option<G>> = Vec::new();
+    let mut latest = HashMap::<K, usize>::new();
+    while let Some(header) = src.try_read_header().map_err(CompactError::Read)? {
+        let mut src = ReadAhead {
+            inner: &mut *src,
+            header: Some(header),
+        };
+        let msg = G::read_message(&mut src).map_err(CompactError::Read)?;
+        if let Some(index) = latest.insert(key_fn(&msg), msgs.len()) {
+            msgs[index] = None;
+        }
+        msgs.push(Some(msg));
+    }
+
+    let mut count = 0;
+    for msg in msgs.iter().flatten() {
+        msg.write_message(dst).map_err(CompactError::Write)?;
+        count += 1;
+    }
+    dst.flush().map_err(CompactError::Write)?;
+    Ok(count)
+}
+
+/// A `DataSource` whose next header has already been read.
+///
+/// This lets a loop detect the end of the data with
+/// [`try_read_header`][DataSource::try_read_header], and then pass the
+/// header on to [`GroupDeserialize::read_message`].
+pub(crate) struct ReadAhead<'a, Src>
+where
+    Src: DataSource,
+{
+    pub(crate) inner: &'a mut Src,
+    pub(crate) header: Option<Src::Header>,
+}
+
+impl<Src> DataSource for ReadAhead<'_, Src>
+where
+    Src: DataSource,
+{
+    type Error = Src::Error;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Src::Error> {
+        match self.header.take() {
+            Some(header) => Ok(header),
+            None => self.inner.read_header(),
+        }
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Src::Error> {
+        match self.header.take() {
+            Some(next) => {
+                *header = next;
+                Ok(())
+            }
+            None => self.inner.read_header_into(header),
+        }
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Src::Error> {
+        match &self.header {
+            Some(header) => Ok(header.msg_id()),
+            None => self.inner.peek_msg_id(),
+        }
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Src::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.inner.read_message(header)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Src::Error> {
+        self.inner.skip_message(header)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Src::Error> {
+        self.inner.read_raw(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Src::Error {
+        self.inner.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Src::Error {
+        self.inner.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Src::Error {
+        self.inner.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Src::Error {
+        self.inner.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Src::Error {
+        self.inner.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Src::Error
+    where
+        T: MessageId,
+    {
+        self.inner.unexpected_message::<T>(msg_id)
+    }
+}
@@ -0,0 +1,247 @@
+//! Provides a `DataSource` that drops duplicated messages.
+
+use crate::group::{DataSource, GetSequence, GroupHeader};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeSet;
+
+/// How a [`DedupSource`] decides whether a message is a duplicate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Sequence numbers must always increase.
+    ///
+    /// Any message with a sequence number less than or equal to the
+    /// highest one seen so far is dropped. This also drops messages that
+    /// arrive out of order.
+    Strict,
+    /// Allow out-of-order delivery within a window.
+    ///
+    /// Messages with a sequence number within `n` of the highest one seen
+    /// so far are accepted, unless that sequence number was already seen.
+    /// Messages older than that are dropped.
+    Window(u64),
+}
+
+/// A [`DataSource`] that drops messages with already-seen sequence numbers.
+///
+/// This wraps another `DataSource` whose header implements [`GetSequence`].
+/// Messages without a sequence number are always accepted. When a
+/// duplicate header is read, the message body is skipped using
+/// [`DataSource::skip_message`], and the next header is read instead, so
+/// callers never see the duplicate.
+///
+/// [`peek_msg_id`][DataSource::peek_msg_id] reads the whole header, since
+/// the sequence number is needed to know whether the message will be
+/// dropped. The header is kept until the next call to `read_header`.
+pub struct DedupSource<Src>
+where
+    Src: DataSource,
+{
+    inner: Src,
+    /// A header that was read by `peek_msg_id`.
+    peeked: Option<Src::Header>,
+    mode: DedupMode,
+    highest: Option<u64>,
+    /// Sequence numbers seen within the window, in `Window` mode.
+    seen: BTreeSet<u64>,
+    dropped: u64,
+}
+
+impl<Src> DedupSource<Src>
+where
+    Src: DataSource,
+    Src::Header: GetSequence,
+{
+    /// Create a new `DedupSource`.
+    pub fn new(inner: Src, mode: DedupMode) -> Self {
+        DedupSource {
+            inner,
+            peeked: None,
+            mode,
+            highest: None,
+            seen: BTreeSet::new(),
+            dropped: 0,
+        }
+    }
+
+    /// The number of duplicate messages that have been dropped.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Consume the `DedupSource`, returning the inner `DataSource`.
+    ///
+    /// A header read by `peek_msg_id` is lost.
+    pub fn into_inner(self) -> Src {
+        self.inner
+    }
+
+    /// Record a sequence number, returning `false` if it's a duplicate.
+    fn accept(&mut self, seq: Option<u64>) -> bool {
+        let seq = match seq {
+            Some(seq) => seq,
+            None => return true,
+        };
+        let highest = match self.highest {
+            None => {
+                self.highest = Some(seq);
+                self.seen.insert(seq);
+                return true;
+            }
+            Some(highest) => highest,
+        };
+        match self.mode {
+            DedupMode::Strict => {
+                if seq <= highest {
+                    return false;
+                }
+                self.highest = Some(seq);
+                true
+            }
+            DedupMode::Window(window) => {
+                if seq.saturating_add(window) < highest || !self.seen.insert(seq) {
+                    return false;
+                }
+                if seq > highest {
+                    self.highest = Some(seq);
+                    // Forget sequence numbers that have left the window.
+                    let oldest = seq.saturating_sub(window);
+                    self.seen = self.seen.split_off(&oldest);
+                }
+                true
+            }
+        }
+    }
+
+    fn next_header(
+        &mut self,
+        header: Option<Src::Header>,
+    ) -> Result<Option<Src::Header>, Src::Error> {
+        let mut header = header;
+        while let Some(hdr) = header {
+            if self.accept(hdr.sequence()) {
+                return Ok(Some(hdr));
+            }
+            self.dropped += 1;
+            self.inner.skip_message(&hdr)?;
+            header = self.inner.try_read_header()?;
+        }
+        Ok(None)
+    }
+}
+
+impl<Src> DataSource for DedupSource<Src>
+where
+    Src: DataSource,
+    Src::Header: GetSequence,
+{
+    type Error = Src::Error;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Src::Error> {
+        if let Some(header) = self.peeked.take() {
+            return Ok(header);
+        }
+        loop {
+            let header = self.inner.read_header()?;
+            if self.accept(header.sequence()) {
+                return Ok(header);
+            }
+            self.dropped += 1;
+            self.inner.skip_message(&header)?;
+        }
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Src::Error> {
+        if let Some(peeked) = self.peeked.take() {
+            *header = peeked;
+            return Ok(());
+        }
+        loop {
+            self.inner.read_header_into(header)?;
+            if self.accept(header.sequence()) {
+                return Ok(());
+            }
+            self.dropped += 1;
+            self.inner.skip_message(header)?;
+        }
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<Src::Header>, Src::Error> {
+        if let Some(header) = self.peeked.take() {
+            return Ok(Some(header));
+        }
+        let header = self.inner.try_read_header()?;
+        self.next_header(header)
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Src::Error> {
+        let header = match self.peeked.take() {
+            Some(header) => header,
+            None => self.read_header()?,
+        };
+        let msg_id = header.msg_id();
+        self.peeked = Some(header);
+        Ok(msg_id)
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Src::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.inner.read_message(header)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Src::Error> {
+        self.inner.skip_message(header)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Src::Error> {
+        self.inner.read_raw(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Src::Error {
+        self.inner.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Src::Error {
+        self.inner.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Src::Error {
+        self.inner.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Src::Error {
+        self.inner.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Src::Error {
+        self.inner.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Src::Error
+    where
+        T: MessageId,
+    {
+        self.inner.unexpected_message::<T>(msg_id)
+    }
+}
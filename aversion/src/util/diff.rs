@@ -0,0 +1,221 @@
+//! Send only the fields of a message that changed.
+//!
+//! When a large message is sent repeatedly with small changes (e.g. a
+//! configuration that is propagated to many peers), sending only the
+//! changed fields saves space. A struct that derives [`Diffable`] can
+//! compute a [`FieldDiff`] against a newer value, and apply a `FieldDiff`
+//! to itself.
+//!
+//! [`write_diff`] writes a diff as a message, with the [`flags::DIFF`]
+//! flag set in its [`ExtendedHeader`]. [`read_and_apply_diff`] reads it
+//! back and applies it to the reader's copy.
+//!
+//! ```
+//! # use aversion::util::cbor::CborData;
+//! # use aversion::util::diff::{read_and_apply_diff, write_diff, Diffable};
+//! # use aversion::util::ExtendedHeader;
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # use std::io::Cursor;
+//! #[derive(Debug, Clone, PartialEq, Versioned, UpgradeLatest, Diffable, Serialize, Deserialize)]
+//! struct ConfigV1 {
+//!     name: String,
+//!     limit: u32,
+//! }
+//! # type Config = ConfigV1;
+//! # assign_message_ids! { Config: 1 }
+//!
+//! let old = ConfigV1 { name: "cache".into(), limit: 10 };
+//! let new = ConfigV1 { name: "cache".into(), limit: 20 };
+//!
+//! // Only `limit` is sent.
+//! let mut sink = CborData::<_, ExtendedHeader>::with_header(Vec::new());
+//! write_diff(&mut sink, &old, &new).unwrap();
+//!
+//! let mut src = CborData::<_, ExtendedHeader>::with_header(Cursor::new(sink.into_inner()));
+//! let mut config = old.clone();
+//! read_and_apply_diff(&mut src, &mut config).unwrap();
+//! assert_eq!(config, new);
+//! ```
+//!
+//! A diff doesn't include the values it was computed from, so the reader
+//! must already hold the same value as the writer's `base`. Diffs can't be
+//! upgraded: the reader must be using the same message version.
+//!
+//! [`flags::DIFF`]: crate::group::flags::DIFF
+
+use crate::group::{flags, DataSource, GroupHeader, UpgradeLatest};
+use crate::util::cbor::{CborData, CborDataError};
+use crate::util::ExtendedHeader;
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_cbor::Value;
+use std::collections::btree_map::{self, BTreeMap};
+use std::io::Write;
+use std::marker::PhantomData;
+
+#[doc(inline)]
+pub use aversion_macros::Diffable;
+
+/// A set of changed fields, with their new values.
+///
+/// This is produced by [`Diffable::diff`], and consumed by
+/// [`Diffable::apply_diff`]. When serialized, it's a map from field
+/// names to values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FieldDiff {
+    fields: BTreeMap<String, Value>,
+}
+
+impl FieldDiff {
+    /// Create an empty `FieldDiff`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the new value of a field.
+    pub fn insert<T>(&mut self, name: &str, value: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize,
+    {
+        let value = serde_cbor::value::to_value(value)?;
+        self.fields.insert(name.to_owned(), value);
+        Ok(())
+    }
+
+    /// Returns `true` if no fields changed.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The number of changed fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if the field `name` changed.
+    pub fn contains(&self, name: &str) -> bool {
+        self.fields.contains_key(name)
+    }
+}
+
+impl IntoIterator for FieldDiff {
+    type Item = (String, Value);
+    type IntoIter = btree_map::IntoIter<String, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.into_iter()
+    }
+}
+
+/// Decode the new value of a field.
+///
+/// This is used by `#[derive(Diffable)]`.
+pub fn decode_field<T>(value: Value) -> Result<T, CborDataError>
+where
+    T: DeserializeOwned,
+{
+    Ok(serde_cbor::value::from_value(value)?)
+}
+
+/// A structure that can compute and apply diffs between two values.
+///
+/// This is normally derived; see [`Diffable`](macro@Diffable).
+pub trait Diffable {
+    /// Find the fields of `target` that differ from `self`.
+    fn diff(&self, target: &Self) -> Result<FieldDiff, CborDataError>;
+
+    /// Overwrite the fields that are in `diff`.
+    ///
+    /// If `diff` names a field that doesn't exist, or a value can't be
+    /// decoded, an error is returned and `self` isn't modified.
+    fn apply_diff(&mut self, diff: FieldDiff) -> Result<(), CborDataError>;
+}
+
+/// A `FieldDiff` that is written with the header of `T`.
+struct DiffBody<'a, T> {
+    diff: &'a FieldDiff,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Versioned for DiffBody<'_, T>
+where
+    T: Versioned,
+{
+    const VER: u16 = T::VER;
+    type Base = T::Base;
+    const SCHEMA_HASH: u64 = T::SCHEMA_HASH;
+    const SCHEMA: &'static str = T::SCHEMA;
+    const MINOR_VER: u16 = T::MINOR_VER;
+}
+
+impl<T> Serialize for DiffBody<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.diff.serialize(serializer)
+    }
+}
+
+/// Write the changes from `base` to `target` as a message.
+///
+/// The message has the id and version of `T`, and the [`flags::DIFF`]
+/// flag is set. Its body is the [`FieldDiff`].
+///
+/// [`flags::DIFF`]: crate::group::flags::DIFF
+pub fn write_diff<W, T>(
+    sink: &mut CborData<W, ExtendedHeader>,
+    base: &T,
+    target: &T,
+) -> Result<(), CborDataError>
+where
+    W: Write,
+    T: Diffable + Versioned,
+    T::Base: MessageId,
+{
+    let diff = base.diff(target)?;
+    let body = DiffBody::<T> {
+        diff: &diff,
+        _marker: PhantomData,
+    };
+    sink.write_message_with_header(&body, |header| {
+        let flags = header.flags.unwrap_or(0) | flags::DIFF;
+        header.with_flags(flags)
+    })
+}
+
+/// Read a message of type `T`, and apply it to `target`.
+///
+/// If the [`flags::DIFF`] flag is set, the message is a [`FieldDiff`],
+/// and it's applied to `target`. It must have the same version as `T`;
+/// otherwise the data source's `unknown_version` error is returned.
+///
+/// If the flag isn't set, the message is a complete `T`, which is
+/// upgraded to the latest version and replaces `target`. This allows a
+/// writer to send a full value first, followed by diffs.
+///
+/// [`flags::DIFF`]: crate::group::flags::DIFF
+pub fn read_and_apply_diff<T, Src>(src: &mut Src, target: &mut T) -> Result<(), CborDataError>
+where
+    T: Diffable + MessageId + UpgradeLatest,
+    Src: DataSource<Error = CborDataError>,
+{
+    let header = src.read_header()?;
+    if header.wide_msg_id() != T::WIDE_MSG_ID {
+        return Err(src.unexpected_message::<T>(header.msg_id()));
+    }
+    if header.flags() & flags::DIFF == 0 {
+        *target = T::upgrade_latest(src, header)?;
+        return Ok(());
+    }
+    if header.msg_ver() != T::VER {
+        let ver = header.msg_ver();
+        src.skip_message(&header)?;
+        return Err(src.unknown_version::<T>(ver));
+    }
+    let diff: FieldDiff = src.read_message(&header)?;
+    target.apply_diff(diff)
+}
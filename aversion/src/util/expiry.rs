@@ -0,0 +1,235 @@
+//! Provides a `DataSource` that drops expired messages.
+
+use crate::group::{DataSource, GetExpiry, GroupHeader};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+
+#[doc(inline)]
+pub use crate::util::clock::{Clock, SystemClock};
+
+/// A [`DataSource`] that drops messages whose expiry time has passed.
+///
+/// This wraps another `DataSource` whose header implements [`GetExpiry`].
+/// When a header is read whose expiry time is earlier than the current
+/// time (less the skew tolerance), the message body is skipped using
+/// [`DataSource::skip_message`], and the next header is read instead, so
+/// callers (including [`GroupDeserialize::iter_filter`]) never see the expired
+/// message. Messages without an expiry time never expire.
+///
+/// [`peek_msg_id`][DataSource::peek_msg_id] reads the whole header, since
+/// the expiry time is needed to know whether the message will be dropped.
+/// The header is kept until the next call to `read_header`.
+///
+/// ```
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::util::expiry::ExpirySource;
+/// # use aversion::util::ExtendedHeader;
+/// # let buf = Vec::<u8>::new();
+/// // Accept messages that expired up to 5 seconds ago, in case the
+/// // writer's clock is behind ours.
+/// let src = CborData::<_, ExtendedHeader>::with_header(&buf[..]);
+/// let src = ExpirySource::new(src).skew_tolerance(5);
+/// ```
+///
+/// [`GroupDeserialize::iter_filter`]: crate::group::GroupDeserialize::iter_filter
+pub struct ExpirySource<Src, C = SystemClock>
+where
+    Src: DataSource,
+{
+    inner: Src,
+    /// A header that was read by `peek_msg_id`.
+    peeked: Option<Src::Header>,
+    clock: C,
+    tolerance: u64,
+    dropped: u64,
+}
+
+impl<Src> ExpirySource<Src>
+where
+    Src: DataSource,
+    Src::Header: GetExpiry,
+{
+    /// Create a new `ExpirySource` that uses the [`SystemClock`].
+    pub fn new(inner: Src) -> Self {
+        Self::with_clock(inner, SystemClock)
+    }
+}
+
+impl<Src, C> ExpirySource<Src, C>
+where
+    Src: DataSource,
+    Src::Header: GetExpiry,
+    C: Clock,
+{
+    /// Create a new `ExpirySource` that uses a custom [`Clock`].
+    pub fn with_clock(inner: Src, clock: C) -> Self {
+        ExpirySource {
+            inner,
+            peeked: None,
+            clock,
+            tolerance: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Allow for clock skew between the writer and the reader.
+    ///
+    /// A message is only dropped if its expiry time is more than
+    /// `tolerance` earlier than the current time. The default is 0.
+    pub fn skew_tolerance(mut self, tolerance: u64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The number of expired messages that have been dropped.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Consume the `ExpirySource`, returning the inner `DataSource`.
+    ///
+    /// A header read by `peek_msg_id` is lost.
+    pub fn into_inner(self) -> Src {
+        self.inner
+    }
+
+    fn is_expired(&self, header: &Src::Header) -> bool {
+        match header.expiry() {
+            Some(expiry) => expiry.saturating_add(self.tolerance) < self.clock.now(),
+            None => false,
+        }
+    }
+
+    fn next_header(
+        &mut self,
+        header: Option<Src::Header>,
+    ) -> Result<Option<Src::Header>, Src::Error> {
+        let mut header = header;
+        while let Some(hdr) = header {
+            if !self.is_expired(&hdr) {
+                return Ok(Some(hdr));
+            }
+            self.dropped += 1;
+            self.inner.skip_message(&hdr)?;
+            header = self.inner.try_read_header()?;
+        }
+        Ok(None)
+    }
+}
+
+impl<Src, C> DataSource for ExpirySource<Src, C>
+where
+    Src: DataSource,
+    Src::Header: GetExpiry,
+    C: Clock,
+{
+    type Error = Src::Error;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Src::Error> {
+        if let Some(header) = self.peeked.take() {
+            return Ok(header);
+        }
+        loop {
+            let header = self.inner.read_header()?;
+            if !self.is_expired(&header) {
+                return Ok(header);
+            }
+            self.dropped += 1;
+            self.inner.skip_message(&header)?;
+        }
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Src::Error> {
+        if let Some(peeked) = self.peeked.take() {
+            *header = peeked;
+            return Ok(());
+        }
+        loop {
+            self.inner.read_header_into(header)?;
+            if !self.is_expired(header) {
+                return Ok(());
+            }
+            self.dropped += 1;
+            self.inner.skip_message(header)?;
+        }
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<Src::Header>, Src::Error> {
+        if let Some(header) = self.peeked.take() {
+            return Ok(Some(header));
+        }
+        let header = self.inner.try_read_header()?;
+        self.next_header(header)
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Src::Error> {
+        let header = match self.peeked.take() {
+            Some(header) => header,
+            None => self.read_header()?,
+        };
+        let msg_id = header.msg_id();
+        self.peeked = Some(header);
+        Ok(msg_id)
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Src::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.inner.read_message(header)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Src::Error> {
+        self.inner.skip_message(header)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Src::Error> {
+        self.inner.read_raw(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Src::Error {
+        self.inner.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Src::Error {
+        self.inner.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Src::Error {
+        self.inner.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Src::Error {
+        self.inner.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Src::Error {
+        self.inner.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Src::Error
+    where
+        T: Versioned,
+    {
+        self.inner.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Src::Error
+    where
+        T: MessageId,
+    {
+        self.inner.unexpected_message::<T>(msg_id)
+    }
+}
@@ -0,0 +1,994 @@
+//! Provides `Codec`s for messages with a fixed binary layout.
+//!
+//! Some messages must always serialize to the same number of bytes, e.g.
+//! to be stored in fixed-size slots, or should be as small as possible.
+//! [`FixedSizeCodec`] packs each field in declaration order, as raw
+//! little-endian bytes, with no framing or field names. [`PackedCodec`]
+//! does the same, with a choice of byte order.
+//!
+//! Only fields with a fixed width are supported:
+//! - the integer types `u8` through `u128` and `i8` through `i128`,
+//! - `f32` and `f64`,
+//! - `bool` (one byte, 0 or 1),
+//! - arrays `[T; N]` of supported types,
+//! - structs that implement [`FixedSize`].
+//!
+//! Variable-length types (`String`, `Vec`, `Option`, enums, maps) can't
+//! be encoded, and return [`FixedSizeError::Unsupported`].
+//!
+//! The size of a message can be checked at compile time with
+//! `#[versioned(fixed_size = N)]`. This implements [`FixedSize`] for the
+//! struct, and fails to compile unless the fields add up to `N` bytes.
+//! Each field type must implement `FixedSize`, so a variable-length field
+//! is also a compile error.
+//!
+//! ```
+//! # use aversion::util::fixed::{FixedSize, FixedSizeCodec};
+//! # use aversion::util::Codec;
+//! # use aversion::Versioned;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+//! #[versioned(fixed_size = 16)]
+//! struct SlotV1 {
+//!     key: u64,
+//!     value: u32,
+//!     tag: [u8; 4],
+//! }
+//! # type Slot = SlotV1;
+//!
+//! assert_eq!(Slot::FIXED_SIZE, 16);
+//! let slot = SlotV1 { key: 1, value: 2, tag: *b"abcd" };
+//! let mut buf = Vec::new();
+//! FixedSizeCodec.encode(&slot, &mut buf).unwrap();
+//! assert_eq!(buf.len(), 16);
+//! assert_eq!(FixedSizeCodec.decode::<Slot>(&buf).unwrap(), slot);
+//! ```
+//!
+//! A size mismatch is a compile error:
+//! ```compile_fail
+//! # use aversion::Versioned;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Versioned, Serialize, Deserialize)]
+//! #[versioned(fixed_size = 16)]
+//! struct SlotV1 {
+//!     key: u64,
+//!     value: u32,
+//! }
+//! # type Slot = SlotV1;
+//! ```
+//!
+//! So is a field without a fixed size:
+//! ```compile_fail
+//! # use aversion::Versioned;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Versioned, Serialize, Deserialize)]
+//! #[versioned(fixed_size = 16)]
+//! struct SlotV1 {
+//!     key: u64,
+//!     name: String,
+//! }
+//! # type Slot = SlotV1;
+//! ```
+//!
+//! Fields are written in declaration order, so reordering fields, or
+//! skipping them with `#[serde(skip)]`, changes the layout.
+//!
+//! To exchange messages with C code, use `PackedCodec<B, ReprC>`, which
+//! inserts the same alignment padding as a `#[repr(C)]` struct; see
+//! [`ReprC`].
+
+use crate::util::codec::{format_id, Codec};
+use byteorder::ByteOrder;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, SeqAccess, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt::Display;
+use std::marker::PhantomData;
+use thiserror::Error;
+
+pub use byteorder::{BigEndian, LittleEndian};
+
+/// A type that always serializes to the same number of bytes with
+/// [`FixedSizeCodec`].
+///
+/// This is implemented for fixed-width primitives and arrays, and by
+/// `#[derive(Versioned)]` for structs marked `#[versioned(fixed_size = N)]`.
+pub trait FixedSize {
+    /// The serialized size, in bytes.
+    const FIXED_SIZE: usize;
+}
+
+macro_rules! impl_fixed_size {
+    ($($ty:ty),*) => {
+        $(
+            impl FixedSize for $ty {
+                const FIXED_SIZE: usize = std::mem::size_of::<$ty>();
+            }
+        )*
+    };
+}
+
+impl_fixed_size!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, bool);
+
+impl<T, const N: usize> FixedSize for [T; N]
+where
+    T: FixedSize,
+{
+    const FIXED_SIZE: usize = T::FIXED_SIZE * N;
+}
+
+/// An error from [`FixedSizeCodec`].
+#[derive(Debug, Error)]
+pub enum FixedSizeError {
+    /// The message contains a type that doesn't have a fixed size.
+    #[error("{0} is not supported by FixedSizeCodec")]
+    Unsupported(&'static str),
+    /// The message body is shorter than the message.
+    #[error("Premature EOF")]
+    Eof,
+    /// The message body is longer than the message.
+    #[error("{0} unexpected bytes after the message")]
+    TrailingBytes(usize),
+    /// A [`ReprC`] layout doesn't match the layout of a `#[repr(C)]` struct.
+    ///
+    /// See [`check_repr_c`].
+    #[error(
+        "C layout is {size} bytes with alignment {align}, expected {expected_size} bytes with alignment {expected_align}"
+    )]
+    LayoutMismatch {
+        /// The size of the computed layout.
+        size: usize,
+        /// The alignment of the computed layout.
+        align: usize,
+        /// The size of the struct.
+        expected_size: usize,
+        /// The alignment of the struct.
+        expected_align: usize,
+    },
+    /// A `bool` field contained a byte other than 0 or 1.
+    #[error("Invalid bool value {0}")]
+    InvalidBool(u8),
+    /// An error reported by a `Serialize` or `Deserialize` implementation.
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl ser::Error for FixedSizeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        FixedSizeError::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for FixedSizeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        FixedSizeError::Custom(msg.to_string())
+    }
+}
+
+/// A [`Codec`] that packs fields as raw little-endian bytes.
+///
+/// This is the same as `PackedCodec<LittleEndian>`.
+/// See the [module documentation](self) for the supported types.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedSizeCodec;
+
+impl Codec for FixedSizeCodec {
+    const FORMAT_ID: u16 = format_id::FIXED_SIZE;
+
+    type Error = FixedSizeError;
+
+    fn encode<T>(&self, msg: &T, buf: &mut Vec<u8>) -> Result<(), FixedSizeError>
+    where
+        T: Serialize,
+    {
+        PackedCodec::<LittleEndian>::new().encode(msg, buf)
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, FixedSizeError>
+    where
+        T: DeserializeOwned,
+    {
+        PackedCodec::<LittleEndian>::new().decode(buf)
+    }
+}
+
+/// The byte order of a [`PackedCodec`].
+///
+/// This is implemented for [`LittleEndian`] and [`BigEndian`].
+pub trait PackedByteOrder: ByteOrder {
+    /// The [`Codec::FORMAT_ID`] of a `PackedCodec` with this byte order.
+    const FORMAT_ID: u16;
+    /// The [`Codec::FORMAT_ID`] of a `PackedCodec` with this byte order
+    /// and the [`ReprC`] layout.
+    const REPR_C_FORMAT_ID: u16;
+}
+
+impl PackedByteOrder for LittleEndian {
+    const FORMAT_ID: u16 = format_id::FIXED_SIZE;
+    const REPR_C_FORMAT_ID: u16 = format_id::REPR_C_LE;
+}
+
+impl PackedByteOrder for BigEndian {
+    const FORMAT_ID: u16 = format_id::PACKED_BE;
+    const REPR_C_FORMAT_ID: u16 = format_id::REPR_C_BE;
+}
+
+/// The field layout of a [`PackedCodec`].
+///
+/// This is implemented for [`Packed`] and [`ReprC`].
+pub trait PackedLayout {
+    /// Whether fields are aligned like a `#[repr(C)]` struct.
+    const ALIGNED: bool;
+}
+
+/// Fields are packed with no padding. This is the default layout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Packed;
+
+impl PackedLayout for Packed {
+    const ALIGNED: bool = false;
+}
+
+/// Fields are laid out like a `#[repr(C)]` struct.
+///
+/// Each field is aligned to the alignment of its type, and each struct
+/// (or tuple) is padded to a multiple of its largest field alignment, so
+/// the bytes match the in-memory layout of the equivalent C struct on
+/// this platform. Padding bytes are written as zero, and ignored when
+/// reading.
+///
+/// ```
+/// # use aversion::util::fixed::{LittleEndian, PackedCodec, ReprC};
+/// # use aversion::util::Codec;
+/// # use serde::{Deserialize, Serialize};
+/// // struct header { uint8_t kind; uint32_t len; };
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// #[repr(C)]
+/// struct Header {
+///     kind: u8,
+///     len: u32,
+/// }
+///
+/// let codec = PackedCodec::<LittleEndian, ReprC>::new();
+/// let mut buf = Vec::new();
+/// codec.encode(&Header { kind: 1, len: 2 }, &mut buf).unwrap();
+/// assert_eq!(buf, [1, 0, 0, 0, 2, 0, 0, 0]);
+/// ```
+///
+/// Use [`check_repr_c`] to verify that a `#[repr(C)]` struct has the
+/// layout that this codec will use.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReprC;
+
+impl PackedLayout for ReprC {
+    const ALIGNED: bool = true;
+}
+
+/// A [`Codec`] that packs fields as raw bytes, in a chosen byte order.
+///
+/// Fields are written in declaration order, with fixed widths and no
+/// framing, so a message with two `u32` fields is always 8 bytes. There
+/// are no optional or variable-length fields; see the
+/// [module documentation](self) for the supported types. Mark the message
+/// with `#[versioned(fixed_size = N)]` to check its layout at compile time.
+///
+/// ```
+/// # use aversion::util::fixed::{BigEndian, PackedCodec};
+/// # use aversion::util::Codec;
+/// # use aversion::Versioned;
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+/// #[versioned(fixed_size = 4)]
+/// struct AckV1 {
+///     seq: u32,
+/// }
+/// # type Ack = AckV1;
+///
+/// let codec = PackedCodec::<BigEndian>::new();
+/// let mut buf = Vec::new();
+/// codec.encode(&AckV1 { seq: 1 }, &mut buf).unwrap();
+/// assert_eq!(buf, [0, 0, 0, 1]);
+/// ```
+///
+/// Little-endian packing is the same format as [`FixedSizeCodec`], and
+/// shares its [`FORMAT_ID`][Codec::FORMAT_ID].
+///
+/// The layout parameter `L` selects whether fields are packed with no
+/// padding ([`Packed`], the default) or aligned like a C struct
+/// ([`ReprC`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PackedCodec<B, L = Packed> {
+    _order: PhantomData<B>,
+    _layout: PhantomData<L>,
+}
+
+impl<B, L> PackedCodec<B, L>
+where
+    B: PackedByteOrder,
+    L: PackedLayout,
+{
+    /// Create a new `PackedCodec`.
+    pub fn new() -> Self {
+        PackedCodec {
+            _order: PhantomData,
+            _layout: PhantomData,
+        }
+    }
+}
+
+impl<B, L> Codec for PackedCodec<B, L>
+where
+    B: PackedByteOrder,
+    L: PackedLayout,
+{
+    const FORMAT_ID: u16 = if L::ALIGNED {
+        B::REPR_C_FORMAT_ID
+    } else {
+        B::FORMAT_ID
+    };
+
+    type Error = FixedSizeError;
+
+    fn encode<T>(&self, msg: &T, buf: &mut Vec<u8>) -> Result<(), FixedSizeError>
+    where
+        T: Serialize,
+    {
+        if !L::ALIGNED {
+            return msg.serialize(&mut FixedSerializer::<B> {
+                buf,
+                layout: None,
+                _order: PhantomData,
+            });
+        }
+
+        // Pack the fields, then move each one to its aligned offset.
+        let mut packed = Vec::new();
+        let mut ser = FixedSerializer::<B> {
+            buf: &mut packed,
+            layout: Some(Vec::new()),
+            _order: PhantomData,
+        };
+        msg.serialize(&mut ser)?;
+        let layout = CLayout::from_events(&ser.layout.unwrap_or_default());
+        let start = buf.len();
+        buf.resize(start + layout.size, 0);
+        let mut fields = &packed[..];
+        for &(offset, size) in &layout.fields {
+            let (field, rest) = fields.split_at(size);
+            buf[start + offset..][..size].copy_from_slice(field);
+            fields = rest;
+        }
+        Ok(())
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, FixedSizeError>
+    where
+        T: DeserializeOwned,
+    {
+        if L::ALIGNED {
+            // Gather the fields from their aligned offsets, then unpack.
+            let layout = c_layout::<T>()?;
+            if buf.len() < layout.size {
+                return Err(FixedSizeError::Eof);
+            }
+            if buf.len() > layout.size {
+                return Err(FixedSizeError::TrailingBytes(buf.len() - layout.size));
+            }
+            let mut packed = Vec::with_capacity(layout.size);
+            for &(offset, size) in &layout.fields {
+                packed.extend_from_slice(&buf[offset..][..size]);
+            }
+            return PackedCodec::<B>::new().decode(&packed);
+        }
+
+        let mut de = FixedDeserializer::<B> {
+            input: buf,
+            probe: None,
+            _order: PhantomData,
+        };
+        let msg = T::deserialize(&mut de)?;
+        if !de.input.is_empty() {
+            return Err(FixedSizeError::TrailingBytes(de.input.len()));
+        }
+        Ok(msg)
+    }
+}
+
+/// The layout of a message with the [`ReprC`] layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CLayout {
+    /// The total size, in bytes, including trailing padding.
+    pub size: usize,
+    /// The alignment of the outermost struct.
+    pub align: usize,
+    /// The offset and size of each primitive value, in the order they
+    /// are serialized. An array has one entry per element.
+    pub fields: Vec<(usize, usize)>,
+}
+
+/// A structural event, recorded while serializing or deserializing.
+enum LayoutEvent {
+    /// A primitive value.
+    Field { size: usize, align: usize },
+    /// The start of a struct, tuple, or array.
+    Open,
+    /// The end of a struct, tuple, or array.
+    Close,
+}
+
+impl CLayout {
+    fn from_events(events: &[LayoutEvent]) -> Self {
+        Self::group(&mut events.iter())
+    }
+
+    /// Lay out the values up to the end of the current group, with
+    /// offsets relative to the start of the group.
+    fn group<'a>(events: &mut impl Iterator<Item = &'a LayoutEvent>) -> Self {
+        let mut group = CLayout {
+            size: 0,
+            align: 1,
+            fields: Vec::new(),
+        };
+        while let Some(event) = events.next() {
+            let inner = match *event {
+                LayoutEvent::Field { size, align } => CLayout {
+                    size,
+                    align,
+                    fields: vec![(0, size)],
+                },
+                LayoutEvent::Open => Self::group(events),
+                LayoutEvent::Close => break,
+            };
+            let start = round_up(group.size, inner.align);
+            group.fields.extend(
+                inner
+                    .fields
+                    .into_iter()
+                    .map(|(offset, size)| (start + offset, size)),
+            );
+            group.size = start + inner.size;
+            group.align = group.align.max(inner.align);
+        }
+        group.size = round_up(group.size, group.align);
+        group
+    }
+}
+
+fn round_up(offset: usize, align: usize) -> usize {
+    offset.next_multiple_of(align)
+}
+
+/// Compute the [`ReprC`] layout of a message type.
+///
+/// The layout is found by deserializing a value of type `T` from zeroed
+/// fields, so `T` must accept zero in every field; e.g. a `NonZeroU32`
+/// field returns an error.
+pub fn c_layout<T>() -> Result<CLayout, FixedSizeError>
+where
+    T: DeserializeOwned,
+{
+    let mut de = FixedDeserializer::<LittleEndian> {
+        input: &[],
+        probe: Some(Vec::new()),
+        _order: PhantomData,
+    };
+    T::deserialize(&mut de)?;
+    Ok(CLayout::from_events(&de.probe.unwrap_or_default()))
+}
+
+/// Check that a `#[repr(C)]` struct has the [`ReprC`] layout.
+///
+/// The size and alignment computed by [`c_layout`] are compared with the
+/// compiler's layout of `T`, which should be marked `#[repr(C)]`. If
+/// they differ, e.g. because `T` isn't `#[repr(C)]`, or a field is
+/// serialized differently from how it's stored,
+/// [`FixedSizeError::LayoutMismatch`] is returned.
+pub fn check_repr_c<T>() -> Result<CLayout, FixedSizeError>
+where
+    T: DeserializeOwned,
+{
+    let layout = c_layout::<T>()?;
+    let expected_size = std::mem::size_of::<T>();
+    let expected_align = std::mem::align_of::<T>();
+    if layout.size != expected_size || layout.align != expected_align {
+        return Err(FixedSizeError::LayoutMismatch {
+            size: layout.size,
+            align: layout.align,
+            expected_size,
+            expected_align,
+        });
+    }
+    Ok(layout)
+}
+
+/// Decode a fixed-size message from a byte slice, without allocating.
+///
+/// The fields are read directly from `buf` in `B` byte order, and the
+/// message is returned by value, so no `Vec` or other heap allocation is
+/// used unless decoding fails. `buf` must be exactly
+/// [`T::FIXED_SIZE`][FixedSize::FIXED_SIZE] bytes long; this is checked
+/// before any fields are read.
+///
+/// This supports the same field types as [`PackedCodec`] (see the
+/// [module documentation](self)): fixed-width integers, floats, `bool`,
+/// arrays, and nested [`FixedSize`] structs. Note that serde only
+/// implements `Deserialize` for arrays of up to 32 elements.
+///
+/// ```
+/// # use aversion::util::fixed::{decode_fixed, BigEndian};
+/// # use aversion::Versioned;
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+/// #[versioned(fixed_size = 6)]
+/// struct SampleV1 {
+///     channel: u16,
+///     value: i32,
+/// }
+/// # type Sample = SampleV1;
+///
+/// let buf = [0, 3, 0xff, 0xff, 0xff, 0xfe];
+/// let sample: Sample = decode_fixed::<_, BigEndian>(&buf).unwrap();
+/// assert_eq!(sample, SampleV1 { channel: 3, value: -2 });
+/// ```
+pub fn decode_fixed<T, B>(buf: &[u8]) -> Result<T, FixedSizeError>
+where
+    T: FixedSize + DeserializeOwned,
+    B: PackedByteOrder,
+{
+    if buf.len() < T::FIXED_SIZE {
+        return Err(FixedSizeError::Eof);
+    }
+    if buf.len() > T::FIXED_SIZE {
+        return Err(FixedSizeError::TrailingBytes(buf.len() - T::FIXED_SIZE));
+    }
+    PackedCodec::<B>::new().decode(buf)
+}
+
+struct FixedSerializer<'a, B> {
+    buf: &'a mut Vec<u8>,
+    /// The structure of the message, if it's being recorded.
+    layout: Option<Vec<LayoutEvent>>,
+    _order: PhantomData<B>,
+}
+
+impl<B> FixedSerializer<'_, B> {
+    fn write(&mut self, bytes: &[u8], align: usize) {
+        self.buf.extend_from_slice(bytes);
+        self.record(LayoutEvent::Field {
+            size: bytes.len(),
+            align,
+        });
+    }
+
+    fn record(&mut self, event: LayoutEvent) {
+        if let Some(layout) = &mut self.layout {
+            layout.push(event);
+        }
+    }
+}
+
+macro_rules! serialize_ordered {
+    ($($method:ident: $ty:ty => $write:ident,)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), FixedSizeError> {
+                let mut bytes = [0; std::mem::size_of::<$ty>()];
+                B::$write(&mut bytes, v);
+                self.write(&bytes, std::mem::align_of::<$ty>());
+                Ok(())
+            }
+        )*
+    };
+}
+
+macro_rules! serialize_unsupported {
+    ($($method:ident($($arg:ty),*) -> $ok:ty: $name:expr,)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ok, FixedSizeError> {
+                Err(FixedSizeError::Unsupported($name))
+            }
+        )*
+    };
+}
+
+impl<'a, 'b, B> ser::Serializer for &'a mut FixedSerializer<'b, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = FixedSizeError;
+    type SerializeSeq = Impossible<(), FixedSizeError>;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), FixedSizeError>;
+    type SerializeMap = Impossible<(), FixedSizeError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), FixedSizeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), FixedSizeError> {
+        self.write(&[v.into()], 1);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), FixedSizeError> {
+        self.write(&[v], 1);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), FixedSizeError> {
+        self.write(&v.to_le_bytes(), 1);
+        Ok(())
+    }
+
+    serialize_ordered! {
+        serialize_u16: u16 => write_u16,
+        serialize_u32: u32 => write_u32,
+        serialize_u64: u64 => write_u64,
+        serialize_u128: u128 => write_u128,
+        serialize_i16: i16 => write_i16,
+        serialize_i32: i32 => write_i32,
+        serialize_i64: i64 => write_i64,
+        serialize_i128: i128 => write_i128,
+        serialize_f32: f32 => write_f32,
+        serialize_f64: f64 => write_f64,
+    }
+
+    serialize_unsupported! {
+        serialize_char(char) -> (): "char",
+        serialize_str(&str) -> (): "str",
+        serialize_bytes(&[u8]) -> (): "byte string",
+        serialize_none() -> (): "Option",
+        serialize_unit_variant(&'static str, u32, &'static str) -> (): "enum",
+        serialize_seq(Option<usize>) -> Self::SerializeSeq: "sequence",
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant: "enum",
+        serialize_map(Option<usize>) -> Self::SerializeMap: "map",
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant: "enum",
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<(), FixedSizeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(FixedSizeError::Unsupported("Option"))
+    }
+
+    fn serialize_unit(self) -> Result<(), FixedSizeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), FixedSizeError> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), FixedSizeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), FixedSizeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(FixedSizeError::Unsupported("enum"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, FixedSizeError> {
+        self.record(LayoutEvent::Open);
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self, FixedSizeError> {
+        self.record(LayoutEvent::Open);
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, FixedSizeError> {
+        self.record(LayoutEvent::Open);
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, 'b, B> ser::SerializeTuple for &'a mut FixedSerializer<'b, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = FixedSizeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), FixedSizeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), FixedSizeError> {
+        self.record(LayoutEvent::Close);
+        Ok(())
+    }
+}
+
+impl<'a, 'b, B> ser::SerializeTupleStruct for &'a mut FixedSerializer<'b, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = FixedSizeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), FixedSizeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), FixedSizeError> {
+        self.record(LayoutEvent::Close);
+        Ok(())
+    }
+}
+
+impl<'a, 'b, B> ser::SerializeStruct for &'a mut FixedSerializer<'b, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = FixedSizeError;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<(), FixedSizeError>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), FixedSizeError> {
+        self.record(LayoutEvent::Close);
+        Ok(())
+    }
+}
+
+struct FixedDeserializer<'de, B> {
+    input: &'de [u8],
+    /// If this is set, no input is read: each field is zero, and the
+    /// structure of the message is recorded instead.
+    probe: Option<Vec<LayoutEvent>>,
+    _order: PhantomData<B>,
+}
+
+/// The value of every field while probing.
+static ZEROS: [u8; 16] = [0; 16];
+
+impl<'de, B> FixedDeserializer<'de, B> {
+    fn take(&mut self, len: usize, align: usize) -> Result<&'de [u8], FixedSizeError> {
+        if let Some(probe) = &mut self.probe {
+            probe.push(LayoutEvent::Field { size: len, align });
+            return Ok(&ZEROS[..len]);
+        }
+        if self.input.len() < len {
+            return Err(FixedSizeError::Eof);
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    /// Visit a struct, tuple, or array with `len` fields.
+    fn fields<V>(&mut self, len: usize, visitor: V) -> Result<V::Value, FixedSizeError>
+    where
+        B: ByteOrder,
+        V: Visitor<'de>,
+    {
+        if let Some(probe) = &mut self.probe {
+            probe.push(LayoutEvent::Open);
+        }
+        let value = visitor.visit_seq(Fields {
+            de: &mut *self,
+            left: len,
+        })?;
+        if let Some(probe) = &mut self.probe {
+            probe.push(LayoutEvent::Close);
+        }
+        Ok(value)
+    }
+}
+
+macro_rules! deserialize_ordered {
+    ($($method:ident: $ty:ty => $read:ident, $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, FixedSizeError>
+            where
+                V: Visitor<'de>,
+            {
+                let bytes = self.take(std::mem::size_of::<$ty>(), std::mem::align_of::<$ty>())?;
+                visitor.$visit(B::$read(bytes))
+            }
+        )*
+    };
+}
+
+macro_rules! deserialize_unsupported {
+    ($($method:ident($($arg:ty),*): $name:expr,)*) => {
+        $(
+            fn $method<V>(self, $(_: $arg,)* _visitor: V) -> Result<V::Value, FixedSizeError>
+            where
+                V: Visitor<'de>,
+            {
+                Err(FixedSizeError::Unsupported($name))
+            }
+        )*
+    };
+}
+
+impl<'de, B> de::Deserializer<'de> for &mut FixedDeserializer<'de, B>
+where
+    B: ByteOrder,
+{
+    type Error = FixedSizeError;
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.take(1, 1)?[0] {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            other => Err(FixedSizeError::InvalidBool(other)),
+        }
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u8(self.take(1, 1)?[0])
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i8(i8::from_le_bytes([self.take(1, 1)?[0]]))
+    }
+
+    deserialize_ordered! {
+        deserialize_u16: u16 => read_u16, visit_u16,
+        deserialize_u32: u32 => read_u32, visit_u32,
+        deserialize_u64: u64 => read_u64, visit_u64,
+        deserialize_u128: u128 => read_u128, visit_u128,
+        deserialize_i16: i16 => read_i16, visit_i16,
+        deserialize_i32: i32 => read_i32, visit_i32,
+        deserialize_i64: i64 => read_i64, visit_i64,
+        deserialize_i128: i128 => read_i128, visit_i128,
+        deserialize_f32: f32 => read_f32, visit_f32,
+        deserialize_f64: f64 => read_f64, visit_f64,
+    }
+
+    // The layout isn't self-describing, so the type must be known.
+    deserialize_unsupported! {
+        deserialize_any(): "deserialize_any",
+        deserialize_ignored_any(): "deserialize_ignored_any",
+        deserialize_char(): "char",
+        deserialize_str(): "str",
+        deserialize_string(): "String",
+        deserialize_bytes(): "byte string",
+        deserialize_byte_buf(): "byte string",
+        deserialize_option(): "Option",
+        deserialize_seq(): "sequence",
+        deserialize_map(): "map",
+        deserialize_identifier(): "identifier",
+        deserialize_enum(&'static str, &'static [&'static str]): "enum",
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.fields(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.fields(len, visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, FixedSizeError>
+    where
+        V: Visitor<'de>,
+    {
+        self.fields(fields.len(), visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Gives access to a known number of fields, in order.
+struct Fields<'a, 'de, B> {
+    de: &'a mut FixedDeserializer<'de, B>,
+    left: usize,
+}
+
+impl<'a, 'de, B> SeqAccess<'de> for Fields<'a, 'de, B>
+where
+    B: ByteOrder,
+{
+    type Error = FixedSizeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, FixedSizeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.left == 0 {
+            return Ok(None);
+        }
+        self.left -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
@@ -0,0 +1,271 @@
+//! Provides a `DataSource` and `DataSink` with a trailing footer on each message.
+//!
+//! A footer is a fixed-size trailer, such as an HMAC tag, that is
+//! computed over the header and body of each message. [`FooterData`]
+//! writes the footer after each message body, and verifies it when the
+//! message is read.
+
+use crate::group::{DataSink, DataSource};
+use crate::util::cbor::{CborCodec, CborDataError};
+use crate::util::protocol::ProtocolError;
+use crate::util::{try_read_first_byte, BasicHeader, Codec, FramedHeader};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+/// A fixed-size trailer that authenticates a message.
+///
+/// The footer is computed over the serialized header and body of the
+/// message (the "frame"), so tampering with either one is detected.
+pub trait GroupFooter {
+    /// The size of the footer, in bytes.
+    fn size(&self) -> usize;
+
+    /// Compute the footer for a frame, appending it to `out`.
+    ///
+    /// This must append exactly [`size`][Self::size] bytes.
+    fn compute(&self, frame: &[u8], out: &mut Vec<u8>);
+
+    /// Check that `footer` is correct for a frame.
+    ///
+    /// The default implementation calls [`compute`][Self::compute], and
+    /// compares the result in constant time.
+    fn verify(&self, frame: &[u8], footer: &[u8]) -> bool {
+        let mut expected = Vec::with_capacity(self.size());
+        self.compute(frame, &mut expected);
+        expected.len() == footer.len()
+            && expected
+                .iter()
+                .zip(footer)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// A `Read` stream that records the bytes that are read.
+struct Recorder<'a, R> {
+    inner: &'a mut R,
+    buf: &'a mut Vec<u8>,
+}
+
+impl<R: Read> Read for Recorder<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.buf.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// A [`DataSource`] and/or [`DataSink`] that adds a footer to each message.
+///
+/// Messages are written in the same format as
+/// [`CborData`](crate::util::cbor::CborData), except that each message
+/// body is followed by a footer computed by `F`. When a message is read,
+/// the footer is verified before the body is decoded; if it doesn't
+/// match, [`CborDataError::FooterVerifyFailed`] is returned.
+///
+/// Since the footer follows the body, the header must record the body
+/// length, so `H` must implement [`FramedHeader`].
+///
+/// Skipping a message (e.g. in [`DedupSource`]) also skips its footer,
+/// without verifying it.
+///
+/// [`DedupSource`]: crate::util::dedup::DedupSource
+pub struct FooterData<RW, F, H = BasicHeader> {
+    inner: RW,
+    footer: F,
+    /// The header and body of the current message.
+    frame: Vec<u8>,
+    _header: PhantomData<fn() -> H>,
+}
+
+impl<RW, F> FooterData<RW, F> {
+    /// Create a new `FooterData`.
+    pub fn new(inner: RW, footer: F) -> Self {
+        Self::with_header(inner, footer)
+    }
+}
+
+impl<RW, F, H> FooterData<RW, F, H> {
+    /// Create a new `FooterData` that uses a specific header type.
+    pub fn with_header(inner: RW, footer: F) -> Self {
+        FooterData {
+            inner,
+            footer,
+            frame: Vec::new(),
+            _header: PhantomData,
+        }
+    }
+
+    /// Get a reference to the inner data type.
+    pub fn get_ref(&self) -> &RW {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner data type.
+    pub fn get_mut(&mut self) -> &mut RW {
+        &mut self.inner
+    }
+
+    /// Consume the `FooterData`, returning the inner data type.
+    pub fn into_inner(self) -> RW {
+        self.inner
+    }
+}
+
+impl<R, F, H> FooterData<R, F, H>
+where
+    R: Read,
+    F: GroupFooter,
+    H: FramedHeader,
+{
+    /// Read the body and footer that follow `header`, and verify them.
+    ///
+    /// On success, the body is at `self.frame[body_start..]`.
+    fn read_verified(&mut self, header: &H) -> Result<usize, CborDataError> {
+        let body_start = self.frame.len();
+        let body_len = header.msg_len() as usize;
+        self.frame.resize(body_start + body_len, 0);
+        self.inner.read_exact(&mut self.frame[body_start..])?;
+
+        let mut footer = vec![0u8; self.footer.size()];
+        self.inner.read_exact(&mut footer)?;
+        if !self.footer.verify(&self.frame, &footer) {
+            return Err(CborDataError::FooterVerifyFailed);
+        }
+        Ok(body_start)
+    }
+}
+
+impl<R, F, H> DataSource for FooterData<R, F, H>
+where
+    R: Read,
+    F: GroupFooter,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+    type Header = H;
+
+    fn read_header(&mut self) -> Result<H, CborDataError> {
+        self.frame.clear();
+        let mut recorder = Recorder {
+            inner: &mut self.inner,
+            buf: &mut self.frame,
+        };
+        Ok(H::deserialize_from(&mut recorder)?)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<H>, CborDataError> {
+        let first = match try_read_first_byte(&mut self.inner)? {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        self.frame.clear();
+        self.frame.push(first);
+        let mut recorder = Recorder {
+            inner: &mut self.inner,
+            buf: &mut self.frame,
+        };
+        Ok(Some(H::deserialize_from(&mut recorder)?))
+    }
+
+    fn read_message<T>(&mut self, header: &H) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        let body_start = self.read_verified(header)?;
+        CborCodec.decode(&self.frame[body_start..])
+    }
+
+    fn read_raw(&mut self, header: &H) -> Result<Vec<u8>, CborDataError> {
+        let body_start = self.read_verified(header)?;
+        Ok(self.frame[body_start..].to_vec())
+    }
+
+    fn skip_message(&mut self, header: &H) -> Result<(), CborDataError> {
+        let skip_len = u64::from(header.msg_len()) + self.footer.size() as u64;
+        let mut subreader = (&mut self.inner).take(skip_len);
+        let skipped = io::copy(&mut subreader, &mut io::sink())?;
+        if skipped < skip_len {
+            return Err(CborDataError::Eof);
+        }
+        Ok(())
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
+    }
+}
+
+impl<W, F, H> DataSink for FooterData<W, F, H>
+where
+    W: Write,
+    F: GroupFooter,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let mut body = Vec::new();
+        CborCodec.encode(msg, &mut body)?;
+        let msg_len: u32 = body.len().try_into().expect("usize to u32");
+
+        self.frame.clear();
+        H::for_msg(msg, msg_len).serialize_into(&mut self.frame)?;
+        self.frame.extend_from_slice(&body);
+        let mut footer = Vec::with_capacity(self.footer.size());
+        self.footer.compute(&self.frame, &mut footer);
+
+        self.inner.write_all(&self.frame)?;
+        self.inner.write_all(&footer)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        Ok(self.inner.flush()?)
+    }
+}
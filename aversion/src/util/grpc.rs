@@ -0,0 +1,283 @@
+//! Provides a `DataSink` and `DataSource` using gRPC-style length-prefixed frames.
+//!
+//! Many transports, including gRPC, frame each message as:
+//!
+//! | size | field |
+//! |------|-------|
+//! | 1    | compression flag (0 = uncompressed) |
+//! | 4    | body length (big-endian) |
+//! | n    | body |
+//!
+//! [`GrpcFramedSink`] and [`GrpcFramedSource`] use that framing, and put
+//! the message header and the CBOR-encoded message inside the body, in
+//! the same format as [`CborData`]. This lets versioned messages travel
+//! over a transport that only understands the outer frame.
+//!
+//! ```
+//! # use aversion::group::{DataSink, DataSourceExt};
+//! # use aversion::util::grpc::{GrpcFramedSink, GrpcFramedSource};
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! struct PingV1 {
+//!     seq: u32,
+//! }
+//! # type Ping = PingV1;
+//! # assign_message_ids! { Ping: 1 }
+//!
+//! let mut sink = GrpcFramedSink::new(Vec::new());
+//! sink.write_message(&PingV1 { seq: 1 }).unwrap();
+//! let buf = sink.into_inner();
+//! assert_eq!(buf[0], 0);
+//!
+//! let mut src = GrpcFramedSource::new(&buf[..]);
+//! let ping: Ping = src.expect_message().unwrap();
+//! assert_eq!(ping, PingV1 { seq: 1 });
+//! ```
+//!
+//! Compressed frames aren't supported; reading one returns an error of
+//! kind [`InvalidData`][io::ErrorKind::InvalidData].
+
+use crate::group::{DataSink, DataSource};
+use crate::util::cbor::{CborData, CborDataError};
+use crate::util::{try_read_first_byte, BasicHeader, FramedHeader};
+use crate::{MessageId, Versioned};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::io::{self, Cursor, Read, Write};
+
+/// The compression flag of an uncompressed frame.
+const UNCOMPRESSED: u8 = 0;
+
+/// A [`DataSink`] that writes each message in a gRPC-style frame.
+///
+/// See the [module documentation](self) for the format.
+pub struct GrpcFramedSink<W, H = BasicHeader> {
+    inner: W,
+    frame: CborData<Vec<u8>, H>,
+}
+
+impl<W> GrpcFramedSink<W> {
+    /// Create a new `GrpcFramedSink`.
+    pub fn new(writer: W) -> Self {
+        Self::with_header(writer)
+    }
+}
+
+impl<W, H> GrpcFramedSink<W, H> {
+    /// Create a new `GrpcFramedSink` that uses a specific header type.
+    pub fn with_header(inner: W) -> Self {
+        GrpcFramedSink {
+            inner,
+            frame: CborData::with_header(Vec::new()),
+        }
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Consume the `GrpcFramedSink`, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, H> DataSink for GrpcFramedSink<W, H>
+where
+    W: Write,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        // Encode the header and message first, because the frame needs
+        // their length.
+        self.frame.get_mut().clear();
+        self.frame.write_message(msg)?;
+        let body = self.frame.get_ref();
+        let body_len: u32 = body.len().try_into().expect("usize to u32");
+        self.inner.write_u8(UNCOMPRESSED)?;
+        self.inner.write_u32::<BigEndian>(body_len)?;
+        self.inner.write_all(body)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// A [`DataSource`] that reads messages from gRPC-style frames.
+///
+/// Each frame must contain exactly one header and message. See the
+/// [module documentation](self) for the format.
+pub struct GrpcFramedSource<R, H = BasicHeader> {
+    inner: R,
+    frame: CborData<Cursor<Vec<u8>>, H>,
+}
+
+impl<R> GrpcFramedSource<R> {
+    /// Create a new `GrpcFramedSource`.
+    pub fn new(reader: R) -> Self {
+        Self::with_header(reader)
+    }
+}
+
+impl<R, H> GrpcFramedSource<R, H> {
+    /// Create a new `GrpcFramedSource` that uses a specific header type.
+    pub fn with_header(inner: R) -> Self {
+        GrpcFramedSource {
+            inner,
+            frame: CborData::with_header(Cursor::new(Vec::new())),
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the `GrpcFramedSource`, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, H> GrpcFramedSource<R, H>
+where
+    R: Read,
+    H: FramedHeader,
+{
+    /// Read the rest of a frame whose compression flag has been read,
+    /// and the header inside it.
+    fn read_frame(&mut self, flag: u8) -> Result<H, CborDataError> {
+        if flag != UNCOMPRESSED {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "compressed gRPC frames are not supported",
+            )
+            .into());
+        }
+        let frame_len = self.inner.read_u32::<BigEndian>()?;
+
+        let cursor = self.frame.get_mut();
+        let body = cursor.get_mut();
+        body.clear();
+        (&mut self.inner).take(frame_len.into()).read_to_end(body)?;
+        if body.len() < frame_len as usize {
+            return Err(CborDataError::Eof);
+        }
+        cursor.set_position(0);
+
+        let header = self.frame.read_header()?;
+        let header_len = self.frame.get_ref().position();
+        if header_len + u64::from(header.msg_len()) != u64::from(frame_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "gRPC frame length doesn't match the message length",
+            )
+            .into());
+        }
+        Ok(header)
+    }
+}
+
+impl<R, H> DataSource for GrpcFramedSource<R, H>
+where
+    R: Read,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+    type Header = H;
+
+    fn read_header(&mut self) -> Result<H, CborDataError> {
+        let flag = self.inner.read_u8()?;
+        self.read_frame(flag)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<H>, CborDataError> {
+        match try_read_first_byte(&mut self.inner)? {
+            Some(flag) => self.read_frame(flag).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_message<T>(&mut self, header: &H) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        self.frame.read_message(header)
+    }
+
+    fn read_raw(&mut self, header: &H) -> Result<Vec<u8>, CborDataError> {
+        self.frame.read_raw(header)
+    }
+
+    fn skip_message(&mut self, header: &H) -> Result<(), CborDataError> {
+        self.frame.skip_message(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        self.frame.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.frame.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        self.frame.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.frame.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        self.frame.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        self.frame.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.frame.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        self.frame.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        self.frame.unexpected_message::<T>(msg_id)
+    }
+}
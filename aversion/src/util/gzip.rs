@@ -0,0 +1,419 @@
+//! Provides gzip compression of message bodies.
+//!
+//! [`GzipCodec`] wraps another [`Codec`], and gzip-compresses the bytes it
+//! produces. This is the format used by HTTP's `Content-Encoding: gzip`,
+//! so compressed bodies can be handled by web tooling.
+//!
+//! [`GzipSink`] and [`GzipSource`] choose the codec for each message, using
+//! the [`flags::COMPRESSED`] bit of a [`FlagsHeader`]. A sink compresses
+//! the bodies that are at least [`min_size`][GzipSink::min_size] bytes, and
+//! sets the flag on those messages; a source decompresses the bodies that
+//! have the flag set. Compressed and plain messages can be mixed in a
+//! single stream.
+//!
+//! The header's message length is the length of the body as written, i.e.
+//! after compression, so the stream can still be framed (or skipped) by
+//! any reader that understands the header.
+//!
+//! ```
+//! # use aversion::group::{DataSink, DataSourceExt};
+//! # use aversion::util::gzip::{GzipSink, GzipSource};
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! # struct TextV1 { text: String }
+//! # type Text = TextV1;
+//! # assign_message_ids! { Text: 1 }
+//! let text = TextV1 { text: "a".repeat(1000) };
+//! let mut sink = GzipSink::new(Vec::new());
+//! sink.write_message(&text).unwrap();
+//! let buf = sink.into_inner();
+//! assert!(buf.len() < 100);
+//!
+//! let mut src = GzipSource::new(&buf[..]);
+//! let msg: Text = src.expect_message().unwrap();
+//! assert_eq!(msg, text);
+//! ```
+//!
+//! This is only available when the `flate2` feature is enabled.
+//!
+//! [`flags::COMPRESSED`]: crate::group::flags::COMPRESSED
+
+use crate::group::{flags, DataSink, DataSource, GroupHeader};
+use crate::util::cbor::{CborCodec, CborDataError};
+use crate::util::protocol::ProtocolError;
+use crate::util::transform::BodyTransform;
+use crate::util::{try_read_first_byte, Codec, FlagsHeader};
+use crate::{MessageId, Versioned};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+
+/// The largest decompressed body accepted by default, in bytes.
+///
+/// See [`GzipCodec::max_decompressed_len`].
+pub const DEFAULT_MAX_DECOMPRESSED_LEN: u64 = 64 * 1024 * 1024;
+
+/// Gzip compression for the bodies produced by another [`Codec`].
+///
+/// This isn't a `Codec` itself. Compression is recorded per message, with
+/// the [`flags::COMPRESSED`] header flag, rather than in the file
+/// [preamble](crate::util::preamble), so a reader checking the preamble
+/// sees the inner codec's format id. Use it through [`GzipSink`] and
+/// [`GzipSource`], or as a [`BodyTransform`].
+///
+/// [`flags::COMPRESSED`]: crate::group::flags::COMPRESSED
+#[derive(Debug, Clone, Copy)]
+pub struct GzipCodec<C = CborCodec> {
+    inner: C,
+    level: Compression,
+    max_decompressed_len: u64,
+}
+
+impl<C> GzipCodec<C> {
+    /// Create a new `GzipCodec`, using the default compression level.
+    pub fn new(inner: C) -> Self {
+        GzipCodec {
+            inner,
+            level: Compression::default(),
+            max_decompressed_len: DEFAULT_MAX_DECOMPRESSED_LEN,
+        }
+    }
+
+    /// Set the compression level, from 0 (none) to 9 (best).
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Compression::new(level);
+        self
+    }
+
+    /// Limit the size of a decompressed body, in bytes.
+    ///
+    /// A small compressed body can expand to a very large one, so a body
+    /// that would decompress to more than `len` bytes is rejected with an
+    /// [`io::Error`] of kind [`InvalidData`][io::ErrorKind::InvalidData],
+    /// without decompressing the rest of it. The default is
+    /// [`DEFAULT_MAX_DECOMPRESSED_LEN`].
+    pub fn max_decompressed_len(mut self, len: u64) -> Self {
+        self.max_decompressed_len = len;
+        self
+    }
+
+    /// Get a reference to the inner codec.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Compress `body`, appending the result to `buf`.
+    fn compress(&self, body: &[u8], buf: &mut Vec<u8>) -> io::Result<()> {
+        let mut encoder = GzEncoder::new(buf, self.level);
+        encoder.write_all(body)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Decompress `buf`, appending the result to `body`.
+    fn decompress(&self, buf: &[u8], body: &mut Vec<u8>) -> io::Result<()> {
+        let limit = self.max_decompressed_len;
+        // Read one byte past the limit, to tell whether it was exceeded.
+        let read = GzDecoder::new(buf)
+            .take(limit.saturating_add(1))
+            .read_to_end(body)?;
+        if read as u64 > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("decompressed body exceeds the limit of {} bytes", limit),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<C> Default for GzipCodec<C>
+where
+    C: Default,
+{
+    fn default() -> Self {
+        Self::new(C::default())
+    }
+}
+
+/// Compresses message bodies in a
+/// [`TransformPipeline`](crate::util::transform::TransformPipeline).
+///
+/// Only the compression level and the
+/// [decompressed length limit](GzipCodec::max_decompressed_len) are used;
+/// the inner codec is ignored.
+impl<C> BodyTransform for GzipCodec<C> {
+    fn flag(&self) -> u8 {
+        flags::COMPRESSED
+    }
+
+    fn apply(&self, body: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.compress(&body, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn reverse(&self, body: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.decompress(&body, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// A [`DataSink`] that gzip-compresses large message bodies.
+///
+/// See the [module documentation](self) for the format.
+pub struct GzipSink<W, C = CborCodec> {
+    inner: W,
+    codec: GzipCodec<C>,
+    min_size: usize,
+    /// The body of the current message.
+    body: Vec<u8>,
+}
+
+impl<W> GzipSink<W> {
+    /// Create a new `GzipSink` that compresses every message body.
+    pub fn new(inner: W) -> Self {
+        Self::with_codec(inner, CborCodec)
+    }
+}
+
+impl<W, C> GzipSink<W, C> {
+    /// Create a new `GzipSink` that uses a specific codec.
+    pub fn with_codec(inner: W, codec: C) -> Self {
+        GzipSink {
+            inner,
+            codec: GzipCodec::new(codec),
+            min_size: 0,
+            body: Vec::new(),
+        }
+    }
+
+    /// Only compress message bodies of at least `min_size` bytes.
+    ///
+    /// Compressing a small body can make it larger; smaller bodies are
+    /// written as-is, without the [`flags::COMPRESSED`] flag.
+    ///
+    /// [`flags::COMPRESSED`]: crate::group::flags::COMPRESSED
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Set the compression level, from 0 (none) to 9 (best).
+    pub fn level(mut self, level: u32) -> Self {
+        self.codec = self.codec.level(level);
+        self
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consume the `GzipSink`, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, C> DataSink for GzipSink<W, C>
+where
+    W: Write,
+    C: Codec<Error = CborDataError>,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        self.body.clear();
+        self.codec.get_ref().encode(msg, &mut self.body)?;
+        let mut msg_flags = 0;
+        if self.body.len() >= self.min_size {
+            let mut compressed = Vec::new();
+            self.codec.compress(&self.body, &mut compressed)?;
+            self.body = compressed;
+            msg_flags |= flags::COMPRESSED;
+        }
+
+        let msg_len: u32 = self.body.len().try_into().expect("usize to u32");
+        FlagsHeader::for_msg(msg, msg_flags, msg_len).serialize_into(&mut self.inner)?;
+        self.inner.write_all(&self.body)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// A [`DataSource`] that decompresses message bodies written by
+/// [`GzipSink`].
+///
+/// Bodies without the [`flags::COMPRESSED`] flag are decoded as-is.
+///
+/// [`flags::COMPRESSED`]: crate::group::flags::COMPRESSED
+pub struct GzipSource<R, C = CborCodec> {
+    inner: R,
+    codec: GzipCodec<C>,
+    /// The body of the current message.
+    body: Vec<u8>,
+}
+
+impl<R> GzipSource<R> {
+    /// Create a new `GzipSource`.
+    pub fn new(inner: R) -> Self {
+        Self::with_codec(inner, CborCodec)
+    }
+}
+
+impl<R, C> GzipSource<R, C> {
+    /// Create a new `GzipSource` that uses a specific codec.
+    pub fn with_codec(inner: R, codec: C) -> Self {
+        GzipSource {
+            inner,
+            codec: GzipCodec::new(codec),
+            body: Vec::new(),
+        }
+    }
+
+    /// Limit the size of a decompressed body, in bytes.
+    ///
+    /// See [`GzipCodec::max_decompressed_len`].
+    pub fn max_decompressed_len(mut self, len: u64) -> Self {
+        self.codec = self.codec.max_decompressed_len(len);
+        self
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the `GzipSource`, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, C> GzipSource<R, C>
+where
+    R: Read,
+{
+    /// Read the body of the current message, as written.
+    fn read_body(&mut self, header: &FlagsHeader) -> Result<(), CborDataError> {
+        self.body.clear();
+        let read = (&mut self.inner)
+            .take(header.msg_len.into())
+            .read_to_end(&mut self.body)?;
+        if read < header.msg_len as usize {
+            return Err(CborDataError::Eof);
+        }
+        Ok(())
+    }
+
+    /// Read the body of the current message, decompressing it if the
+    /// header says it's compressed.
+    fn read_plain_body(&mut self, header: &FlagsHeader) -> Result<(), CborDataError> {
+        self.read_body(header)?;
+        if header.flags() & flags::COMPRESSED != 0 {
+            let mut body = Vec::new();
+            self.codec.decompress(&self.body, &mut body)?;
+            self.body = body;
+        }
+        Ok(())
+    }
+}
+
+impl<R, C> DataSource for GzipSource<R, C>
+where
+    R: Read,
+    C: Codec<Error = CborDataError>,
+{
+    type Error = CborDataError;
+    type Header = FlagsHeader;
+
+    fn read_header(&mut self) -> Result<FlagsHeader, CborDataError> {
+        Ok(FlagsHeader::deserialize_from(&mut self.inner)?)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<FlagsHeader>, CborDataError> {
+        let first = match try_read_first_byte(&mut self.inner)? {
+            Some(first) => [first],
+            None => return Ok(None),
+        };
+        let mut reader = (&first[..]).chain(&mut self.inner);
+        Ok(Some(FlagsHeader::deserialize_from(&mut reader)?))
+    }
+
+    fn read_message<T>(&mut self, header: &FlagsHeader) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        self.read_plain_body(header)?;
+        self.codec.get_ref().decode(&self.body)
+    }
+
+    fn read_raw(&mut self, header: &FlagsHeader) -> Result<Vec<u8>, CborDataError> {
+        self.read_plain_body(header)?;
+        Ok(self.body.clone())
+    }
+
+    fn skip_message(&mut self, header: &FlagsHeader) -> Result<(), CborDataError> {
+        self.read_body(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
+    }
+}
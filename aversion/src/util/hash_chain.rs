@@ -0,0 +1,250 @@
+//! Provides a `DataSink` and `DataSource` that link messages with a hash chain.
+//!
+//! Each message's [`ChainHeader`] contains `H(prev_hash || body)`, where
+//! `prev_hash` is the hash from the previous message's header. The first
+//! message uses a genesis hash. Changing, removing, or reordering any
+//! message body breaks the chain from that point on, which makes an
+//! append-only log tamper-evident.
+//!
+//! The hash only covers the message bodies and the previous hash; the
+//! other header fields aren't covered.
+//!
+//! The hash function is supplied by implementing [`ChainHasher`], so this
+//! crate doesn't depend on any particular hash implementation.
+
+use crate::group::{DataSink, DataSource};
+use crate::util::cbor::{CborCodec, CborDataError};
+use crate::util::protocol::ProtocolError;
+use crate::util::{try_read_first_byte, ChainHeader, Codec, ExtendedHeader};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+/// The hash used by the first message in a chain, unless another one is
+/// chosen.
+pub const GENESIS_HASH: [u8; 32] = [0; 32];
+
+/// A hash function for a [`HashChainSink`] or [`HashChainSource`].
+pub trait ChainHasher {
+    /// Compute the hash of `prev` followed by `body`.
+    fn chain(&self, prev: &[u8; 32], body: &[u8]) -> [u8; 32];
+}
+
+/// A [`DataSink`] that writes CBOR messages linked by a hash chain.
+///
+/// See the [module documentation](self) for details. The messages can be
+/// read back with [`HashChainSource`].
+pub struct HashChainSink<W, C> {
+    inner: W,
+    hasher: C,
+    prev: [u8; 32],
+}
+
+impl<W, C> HashChainSink<W, C> {
+    /// Create a new `HashChainSink`, starting from [`GENESIS_HASH`].
+    pub fn new(inner: W, hasher: C) -> Self {
+        HashChainSink {
+            inner,
+            hasher,
+            prev: GENESIS_HASH,
+        }
+    }
+
+    /// Start the chain from a different hash.
+    ///
+    /// This can be used to continue a chain from an earlier log.
+    pub fn with_genesis(mut self, genesis: [u8; 32]) -> Self {
+        self.prev = genesis;
+        self
+    }
+
+    /// The hash of the last message that was written.
+    ///
+    /// Before any messages are written, this is the genesis hash.
+    pub fn last_hash(&self) -> [u8; 32] {
+        self.prev
+    }
+
+    /// Get a reference to the inner data type.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consume the `HashChainSink`, returning the inner data type.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, C> DataSink for HashChainSink<W, C>
+where
+    W: Write,
+    C: ChainHasher,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let mut body = Vec::new();
+        CborCodec.encode(msg, &mut body)?;
+        let msg_len: u32 = body.len().try_into().expect("usize to u32");
+        let hash = self.hasher.chain(&self.prev, &body);
+        let header = ChainHeader::new(ExtendedHeader::for_msg(msg, msg_len), hash);
+        header.serialize_into(&mut self.inner)?;
+        self.inner.write_all(&body)?;
+        self.prev = hash;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// A [`DataSource`] that reads CBOR messages and verifies their hash chain.
+///
+/// Each message body is checked against the hash in its header before it
+/// is decoded. If they don't match, [`CborDataError::HashChainBroken`] is
+/// returned. The chain can't be repaired after that, so every later
+/// message will fail too.
+///
+/// Skipping a message still reads its body, to check the chain.
+pub struct HashChainSource<R, C> {
+    inner: R,
+    hasher: C,
+    prev: [u8; 32],
+    body: Vec<u8>,
+}
+
+impl<R, C> HashChainSource<R, C> {
+    /// Create a new `HashChainSource`, starting from [`GENESIS_HASH`].
+    pub fn new(inner: R, hasher: C) -> Self {
+        HashChainSource {
+            inner,
+            hasher,
+            prev: GENESIS_HASH,
+            body: Vec::new(),
+        }
+    }
+
+    /// Start the chain from a different hash.
+    pub fn with_genesis(mut self, genesis: [u8; 32]) -> Self {
+        self.prev = genesis;
+        self
+    }
+
+    /// The hash of the last message that was verified.
+    ///
+    /// Before any messages are read, this is the genesis hash.
+    pub fn last_hash(&self) -> [u8; 32] {
+        self.prev
+    }
+
+    /// Consume the `HashChainSource`, returning the inner data type.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, C> HashChainSource<R, C>
+where
+    R: Read,
+    C: ChainHasher,
+{
+    /// Read the body that follows `header` into `self.body`, and check
+    /// the chain.
+    fn read_verified(&mut self, header: &ChainHeader) -> Result<(), CborDataError> {
+        self.body.resize(header.ext.msg_len as usize, 0);
+        self.inner.read_exact(&mut self.body)?;
+        if self.hasher.chain(&self.prev, &self.body) != header.hash {
+            return Err(CborDataError::HashChainBroken);
+        }
+        self.prev = header.hash;
+        Ok(())
+    }
+}
+
+impl<R, C> DataSource for HashChainSource<R, C>
+where
+    R: Read,
+    C: ChainHasher,
+{
+    type Error = CborDataError;
+    type Header = ChainHeader;
+
+    fn read_header(&mut self) -> Result<ChainHeader, CborDataError> {
+        Ok(ChainHeader::deserialize_from(&mut self.inner)?)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<ChainHeader>, CborDataError> {
+        let first = match try_read_first_byte(&mut self.inner)? {
+            Some(first) => [first],
+            None => return Ok(None),
+        };
+        let mut reader = (&first[..]).chain(&mut self.inner);
+        Ok(Some(ChainHeader::deserialize_from(&mut reader)?))
+    }
+
+    fn read_message<T>(&mut self, header: &ChainHeader) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        self.read_verified(header)?;
+        CborCodec.decode(&self.body)
+    }
+
+    fn read_raw(&mut self, header: &ChainHeader) -> Result<Vec<u8>, CborDataError> {
+        self.read_verified(header)?;
+        Ok(self.body.clone())
+    }
+
+    fn skip_message(&mut self, header: &ChainHeader) -> Result<(), CborDataError> {
+        self.read_verified(header)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
+    }
+}
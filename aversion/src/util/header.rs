@@ -1,7 +1,227 @@
-use crate::group::GroupHeader;
-use crate::{MessageId, Versioned};
+use crate::group::{GetCorrelationId, GetExpiry, GetSequence, GetTimestamp, GroupHeader};
+use crate::util::preamble::DEFAULT_HEADER_FORMAT;
+use crate::{MessageId, Version, Versioned};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::convert::TryFrom;
 use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::mem;
+
+/// A header that records the length of the message that follows it.
+///
+/// Headers that implement this trait can be used with
+/// [`CborData`][crate::util::cbor::CborData].
+pub trait FramedHeader: GroupHeader + Sized {
+    /// Create a header that corresponds to a type.
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId;
+
+    /// The length of the message, in bytes.
+    fn msg_len(&self) -> u32;
+
+    /// Deserialize a header from a `Read` stream.
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error>;
+
+    /// Deserialize a header from a `Read` stream into an existing value.
+    ///
+    /// Header types that own heap data can override this to reuse their
+    /// allocations. The default implementation calls
+    /// [`deserialize_from`][Self::deserialize_from].
+    fn deserialize_into(&mut self, r: &mut impl Read) -> Result<(), io::Error> {
+        *self = Self::deserialize_from(r)?;
+        Ok(())
+    }
+
+    /// Serialize a header into a `Write` stream.
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error>;
+
+    /// The header format written by [`serialize_into`][Self::serialize_into].
+    ///
+    /// This identifies both the header type and its layout, so that a
+    /// reader can tell which one was used; see
+    /// [`write_preamble_with_header`]. Each header in this crate has its
+    /// own number, listed in [`header_format`]. A header type whose layout
+    /// changes over time gives each layout a new number.
+    ///
+    /// The default is [`DEFAULT_HEADER_FORMAT`], which is the format of
+    /// [`BasicHeader`]. Other header types should override it, with a
+    /// number of 0x100 or higher.
+    ///
+    /// [`write_preamble_with_header`]: crate::util::preamble::write_preamble_with_header
+    const HEADER_FORMAT: u16 = DEFAULT_HEADER_FORMAT;
+
+    /// Whether [`for_msg`][Self::for_msg] records the full
+    /// [`WIDE_MSG_ID`][MessageId::WIDE_MSG_ID] of a message.
+    ///
+    /// Most headers only have room for a 16-bit message id. Writing a
+    /// message whose id doesn't fit with one of those headers is a compile
+    /// error, rather than silently writing the low 16 bits:
+    ///
+    /// ```compile_fail
+    /// # use aversion::group::DataSink;
+    /// # use aversion::util::cbor::CborData;
+    /// # use aversion::{assign_message_ids, Versioned};
+    /// # use serde::Serialize;
+    /// #[derive(Versioned, Serialize)]
+    /// struct WideV1 {
+    ///     x: u32,
+    /// }
+    /// # type Wide = WideV1;
+    /// assign_message_ids! { Wide: 0x1_0001u32 }
+    ///
+    /// // `CborData` uses a `BasicHeader` by default.
+    /// let mut sink = CborData::new(Vec::new());
+    /// sink.write_message(&WideV1 { x: 42 }).unwrap();
+    /// ```
+    ///
+    /// The default is `false`. [`WideHeader`] sets it to `true`.
+    const WIDE_IDS: bool = false;
+
+    /// Find the message id at the start of a serialized header.
+    ///
+    /// This is used to implement [`DataSource::peek_msg_id`]. `prefix`
+    /// holds the first bytes of a header; this returns `Ok(None)` if more
+    /// bytes are needed. The message id must be found within the first
+    /// [`MAX_PEEK_LEN`] bytes. The id is the same one that
+    /// [`msg_id`][GroupHeader::msg_id] will return once the header is read.
+    ///
+    /// The default implementation reads a big-endian `u16` from the first
+    /// two bytes, which is where most of the headers in this crate put the
+    /// message id. A header type with a different layout should override
+    /// this, or return an error of kind
+    /// [`Unsupported`][io::ErrorKind::Unsupported].
+    ///
+    /// [`DataSource::peek_msg_id`]: crate::group::DataSource::peek_msg_id
+    fn peek_id(prefix: &[u8]) -> Result<Option<u16>, io::Error> {
+        match prefix {
+            [hi, lo, ..] => Ok(Some(u16::from_be_bytes([*hi, *lo]))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Deserialize a header that was written in header format `format`.
+    ///
+    /// Header types that understand older layouts override this, and
+    /// branch on `format`. The default implementation calls
+    /// [`deserialize_from`][Self::deserialize_from] if `format` is
+    /// [`HEADER_FORMAT`][Self::HEADER_FORMAT], and returns an error of kind
+    /// [`InvalidData`][io::ErrorKind::InvalidData] otherwise.
+    fn deserialize_format(format: u16, r: &mut impl Read) -> Result<Self, io::Error> {
+        if format != Self::HEADER_FORMAT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported header format",
+            ));
+        }
+        Self::deserialize_from(r)
+    }
+}
+
+/// Header formats for the headers provided by this crate.
+///
+/// See [`FramedHeader::HEADER_FORMAT`]. Header formats below 0x100 are
+/// reserved for this crate.
+pub mod header_format {
+    /// [`BasicHeader`](crate::util::BasicHeader).
+    ///
+    /// This is also [`DEFAULT_HEADER_FORMAT`], the format assumed for a
+    /// preamble that doesn't record one.
+    ///
+    /// [`DEFAULT_HEADER_FORMAT`]: crate::util::preamble::DEFAULT_HEADER_FORMAT
+    pub const BASIC: u16 = 1;
+    /// [`FlagsHeader`](crate::util::FlagsHeader).
+    pub const FLAGS: u16 = 2;
+    /// [`SemverHeader`](crate::util::SemverHeader).
+    pub const SEMVER: u16 = 3;
+    /// [`ExtendedHeader`](crate::util::ExtendedHeader).
+    pub const EXTENDED: u16 = 4;
+    /// [`ChainHeader`](crate::util::ChainHeader).
+    pub const CHAIN: u16 = 5;
+    /// [`WideHeader`](crate::util::WideHeader).
+    pub const WIDE: u16 = 6;
+    /// [`VarintHeader`](crate::util::VarintHeader).
+    pub const VARINT: u16 = 7;
+    /// [`MultiHeader`](crate::util::MultiHeader).
+    pub const MULTI: u16 = 8;
+    /// [`SequencedHeader`](crate::util::SequencedHeader).
+    pub const SEQUENCED: u16 = 9;
+}
+
+/// The message id of `T`, for a header that stores a 16-bit id.
+///
+/// Using [`MSG_ID`][Self::MSG_ID] fails to compile if `T` has a 32-bit id
+/// that doesn't fit; see [`FramedHeader::WIDE_IDS`].
+struct NarrowId<T>(PhantomData<T>);
+
+impl<T> NarrowId<T>
+where
+    T: MessageId,
+{
+    const MSG_ID: u16 = {
+        assert!(
+            T::WIDE_MSG_ID <= u16::MAX as u32,
+            "message id doesn't fit in a 16-bit header; use a WideHeader"
+        );
+        T::MSG_ID
+    };
+}
+
+/// The most header bytes that [`FramedHeader::peek_id`] may need.
+pub const MAX_PEEK_LEN: usize = 8;
+
+/// The header bytes that were read ahead of a header.
+///
+/// This is used to implement [`DataSource::peek_msg_id`] for readers that
+/// can't seek: the bytes are kept here, and put back in front of the
+/// reader when the full header is read.
+///
+/// [`DataSource::peek_msg_id`]: crate::group::DataSource::peek_msg_id
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct PeekedId {
+    bytes: [u8; MAX_PEEK_LEN],
+    len: usize,
+}
+
+impl PeekedId {
+    /// Read enough of a header from `r` to find the message id.
+    ///
+    /// Bytes that were already peeked aren't read again.
+    pub(crate) fn peek<H>(&mut self, r: &mut impl Read) -> io::Result<u16>
+    where
+        H: FramedHeader,
+    {
+        loop {
+            if let Some(msg_id) = H::peek_id(self.as_slice())? {
+                return Ok(msg_id);
+            }
+            if self.len == MAX_PEEK_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "message id not found in header prefix",
+                ));
+            }
+            r.read_exact(&mut self.bytes[self.len..=self.len])?;
+            self.len += 1;
+        }
+    }
+
+    /// Remove the peeked bytes, returning them.
+    pub(crate) fn take(&mut self) -> PeekedId {
+        mem::take(self)
+    }
+
+    /// Returns `true` if no bytes have been peeked.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The peeked bytes, which belong in front of the next header.
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
 
 /// A header that can be serialized into a fixed-size buffer.
 ///
@@ -16,6 +236,9 @@ pub struct TinyHeader {
 }
 
 impl TinyHeader {
+    /// The size of the header when serialized, in bytes.
+    pub const SIZE: usize = 4;
+
     /// Create a new `TinyHeader`
     pub fn new(msg_id: u16, msg_ver: u16) -> Self {
         TinyHeader { msg_id, msg_ver }
@@ -32,7 +255,7 @@ impl TinyHeader {
         T::Base: MessageId,
     {
         TinyHeader {
-            msg_id: T::Base::MSG_ID,
+            msg_id: NarrowId::<T::Base>::MSG_ID,
             msg_ver: T::VER,
         }
     }
@@ -98,6 +321,9 @@ pub struct BasicHeader {
 }
 
 impl BasicHeader {
+    /// The size of the header when serialized, in bytes.
+    pub const SIZE: usize = 8;
+
     /// Create a new `BasicHeader`.
     pub fn new(msg_id: u16, msg_ver: u16, msg_len: u32) -> Self {
         BasicHeader {
@@ -118,7 +344,7 @@ impl BasicHeader {
         T::Base: MessageId,
     {
         BasicHeader {
-            msg_id: T::Base::MSG_ID,
+            msg_id: NarrowId::<T::Base>::MSG_ID,
             msg_ver: T::VER,
             msg_len,
         }
@@ -172,3 +398,1223 @@ impl GroupHeader for BasicHeader {
         self.msg_ver
     }
 }
+
+impl FramedHeader for BasicHeader {
+    const HEADER_FORMAT: u16 = header_format::BASIC;
+
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        BasicHeader::for_msg(msg, msg_len)
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        BasicHeader::deserialize_from(r)
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        BasicHeader::serialize_into(self, w)
+    }
+}
+
+/// A header that can be serialized into a fixed-size buffer.
+///
+/// This header does not use serde; it serializes to a binary
+/// (big-endian) array of 9 bytes.
+///
+/// This is the same as [`BasicHeader`], with an extra flags byte
+/// between the message version and the message length. See the
+/// [`flags`] module for the meaning of each bit.
+///
+/// [`flags`]: crate::group::flags
+#[derive(Debug, Clone, Copy)]
+pub struct FlagsHeader {
+    /// The message id.
+    pub msg_id: u16,
+    /// The message version.
+    pub msg_ver: u16,
+    /// The message flags.
+    pub flags: u8,
+    /// The length of the message when serialized.
+    pub msg_len: u32,
+}
+
+impl FlagsHeader {
+    /// The size of the header when serialized, in bytes.
+    pub const SIZE: usize = 9;
+
+    /// Create a new `FlagsHeader`.
+    pub fn new(msg_id: u16, msg_ver: u16, flags: u8, msg_len: u32) -> Self {
+        FlagsHeader {
+            msg_id,
+            msg_ver,
+            flags,
+            msg_len,
+        }
+    }
+
+    /// Create a new `FlagsHeader` that corresponds to a type.
+    ///
+    /// The version and message id values will be filled in from
+    /// the type's [`Versioned`] and [`MessageId`] associated
+    /// constants.
+    pub fn for_msg<T>(_msg: &T, flags: u8, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        FlagsHeader {
+            msg_id: NarrowId::<T::Base>::MSG_ID,
+            msg_ver: T::VER,
+            flags,
+            msg_len,
+        }
+    }
+
+    /// Deserialize a header from a `Read` stream.
+    pub fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let msg_id = r.read_u16::<BigEndian>()?;
+        let msg_ver = r.read_u16::<BigEndian>()?;
+        let flags = r.read_u8()?;
+        let msg_len = r.read_u32::<BigEndian>()?;
+        Ok(FlagsHeader {
+            msg_id,
+            msg_ver,
+            flags,
+            msg_len,
+        })
+    }
+
+    /// Deserialize a header from a 9-byte slice.
+    pub fn deserialize(buf: &[u8; 9]) -> Self {
+        // Use a &[u8] as the Read stream.
+        let mut buf: &[u8] = buf;
+        // No io::Error is possible, since we're doing no actual IO.
+        Self::deserialize_from(&mut buf).unwrap()
+    }
+
+    /// Serialize a header into a `Write` stream.
+    pub fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        w.write_u16::<BigEndian>(self.msg_id)?;
+        w.write_u16::<BigEndian>(self.msg_ver)?;
+        w.write_u8(self.flags)?;
+        w.write_u32::<BigEndian>(self.msg_len)?;
+        Ok(())
+    }
+
+    /// Serialize a header into a 9-byte array.
+    pub fn serialize(self) -> [u8; 9] {
+        let mut buf = [0u8; 9];
+        // Use a &[u8] as the Write stream.
+        let mut cursor: &mut [u8] = buf.as_mut();
+        // No io::Error is possible, since we're doing no actual IO.
+        self.serialize_into(&mut cursor).unwrap();
+        buf
+    }
+}
+
+impl GroupHeader for FlagsHeader {
+    fn msg_id(&self) -> u16 {
+        self.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.msg_ver
+    }
+
+    fn flags(&self) -> u8 {
+        self.flags
+    }
+}
+
+impl FramedHeader for FlagsHeader {
+    const HEADER_FORMAT: u16 = header_format::FLAGS;
+
+    /// Create a header with no flags set.
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        FlagsHeader::for_msg(msg, 0, msg_len)
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        FlagsHeader::deserialize_from(r)
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        FlagsHeader::serialize_into(self, w)
+    }
+}
+
+/// A header that can be serialized into a fixed-size buffer.
+///
+/// This header does not use serde; it serializes to a binary
+/// (big-endian) array of 10 bytes.
+///
+/// This is the same as [`BasicHeader`], with an extra minor version
+/// between the message version and the message length. See
+/// [`Versioned::MINOR_VER`] for how minor versions are used.
+#[derive(Debug, Clone, Copy)]
+pub struct SemverHeader {
+    /// The message id.
+    pub msg_id: u16,
+    /// The message (major) version.
+    pub msg_ver: u16,
+    /// The message minor version.
+    pub msg_minor_ver: u16,
+    /// The length of the message when serialized.
+    pub msg_len: u32,
+}
+
+impl SemverHeader {
+    /// The size of the header when serialized, in bytes.
+    pub const SIZE: usize = 10;
+
+    /// Create a new `SemverHeader`.
+    pub fn new(msg_id: u16, version: Version, msg_len: u32) -> Self {
+        SemverHeader {
+            msg_id,
+            msg_ver: version.major,
+            msg_minor_ver: version.minor,
+            msg_len,
+        }
+    }
+
+    /// Create a new `SemverHeader` that corresponds to a type.
+    ///
+    /// The version and message id values will be filled in from
+    /// the type's [`Versioned`] and [`MessageId`] associated
+    /// constants.
+    pub fn for_msg<T>(_msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        Self::new(NarrowId::<T::Base>::MSG_ID, T::VERSION, msg_len)
+    }
+
+    /// The full message version.
+    pub fn version(&self) -> Version {
+        Version {
+            major: self.msg_ver,
+            minor: self.msg_minor_ver,
+        }
+    }
+
+    /// Deserialize a header from a `Read` stream.
+    pub fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let msg_id = r.read_u16::<BigEndian>()?;
+        let msg_ver = r.read_u16::<BigEndian>()?;
+        let msg_minor_ver = r.read_u16::<BigEndian>()?;
+        let msg_len = r.read_u32::<BigEndian>()?;
+        Ok(SemverHeader {
+            msg_id,
+            msg_ver,
+            msg_minor_ver,
+            msg_len,
+        })
+    }
+
+    /// Deserialize a header from a 10-byte slice.
+    pub fn deserialize(buf: &[u8; 10]) -> Self {
+        // Use a &[u8] as the Read stream.
+        let mut buf: &[u8] = buf;
+        // No io::Error is possible, since we're doing no actual IO.
+        Self::deserialize_from(&mut buf).unwrap()
+    }
+
+    /// Serialize a header into a `Write` stream.
+    pub fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        w.write_u16::<BigEndian>(self.msg_id)?;
+        w.write_u16::<BigEndian>(self.msg_ver)?;
+        w.write_u16::<BigEndian>(self.msg_minor_ver)?;
+        w.write_u32::<BigEndian>(self.msg_len)?;
+        Ok(())
+    }
+
+    /// Serialize a header into a 10-byte array.
+    pub fn serialize(self) -> [u8; 10] {
+        let mut buf = [0u8; 10];
+        // Use a &[u8] as the Write stream.
+        let mut cursor: &mut [u8] = buf.as_mut();
+        // No io::Error is possible, since we're doing no actual IO.
+        self.serialize_into(&mut cursor).unwrap();
+        buf
+    }
+}
+
+impl GroupHeader for SemverHeader {
+    fn msg_id(&self) -> u16 {
+        self.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.msg_ver
+    }
+
+    fn msg_minor_ver(&self) -> u16 {
+        self.msg_minor_ver
+    }
+}
+
+impl FramedHeader for SemverHeader {
+    const HEADER_FORMAT: u16 = header_format::SEMVER;
+
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        SemverHeader::for_msg(msg, msg_len)
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        SemverHeader::deserialize_from(r)
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        SemverHeader::serialize_into(self, w)
+    }
+}
+
+/// A header that can be serialized into a fixed-size buffer.
+///
+/// This header does not use serde; it serializes to a binary
+/// (big-endian) array of 16 bytes.
+///
+/// This is the same as [`BasicHeader`], with an extra 64-bit sequence
+/// number between the message version and the message length.
+#[derive(Debug, Clone, Copy)]
+pub struct SequencedHeader {
+    /// The message id.
+    pub msg_id: u16,
+    /// The message version.
+    pub msg_ver: u16,
+    /// The message sequence number.
+    pub seq: u64,
+    /// The length of the message when serialized.
+    pub msg_len: u32,
+}
+
+impl SequencedHeader {
+    /// The size of the header when serialized, in bytes.
+    pub const SIZE: usize = 16;
+
+    /// Create a new `SequencedHeader`.
+    pub fn new(msg_id: u16, msg_ver: u16, seq: u64, msg_len: u32) -> Self {
+        SequencedHeader {
+            msg_id,
+            msg_ver,
+            seq,
+            msg_len,
+        }
+    }
+
+    /// Create a new `SequencedHeader` that corresponds to a type.
+    ///
+    /// The version and message id values will be filled in from
+    /// the type's [`Versioned`] and [`MessageId`] associated
+    /// constants.
+    pub fn for_msg<T>(_msg: &T, seq: u64, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        SequencedHeader {
+            msg_id: NarrowId::<T::Base>::MSG_ID,
+            msg_ver: T::VER,
+            seq,
+            msg_len,
+        }
+    }
+
+    /// Deserialize a header from a `Read` stream.
+    pub fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let msg_id = r.read_u16::<BigEndian>()?;
+        let msg_ver = r.read_u16::<BigEndian>()?;
+        let seq = r.read_u64::<BigEndian>()?;
+        let msg_len = r.read_u32::<BigEndian>()?;
+        Ok(SequencedHeader {
+            msg_id,
+            msg_ver,
+            seq,
+            msg_len,
+        })
+    }
+
+    /// Deserialize a header from a 16-byte slice.
+    pub fn deserialize(buf: &[u8; 16]) -> Self {
+        // Use a &[u8] as the Read stream.
+        let mut buf: &[u8] = buf;
+        // No io::Error is possible, since we're doing no actual IO.
+        Self::deserialize_from(&mut buf).unwrap()
+    }
+
+    /// Serialize a header into a `Write` stream.
+    pub fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        w.write_u16::<BigEndian>(self.msg_id)?;
+        w.write_u16::<BigEndian>(self.msg_ver)?;
+        w.write_u64::<BigEndian>(self.seq)?;
+        w.write_u32::<BigEndian>(self.msg_len)?;
+        Ok(())
+    }
+
+    /// Serialize a header into a 16-byte array.
+    pub fn serialize(self) -> [u8; 16] {
+        let mut buf = [0u8; 16];
+        // Use a &[u8] as the Write stream.
+        let mut cursor: &mut [u8] = buf.as_mut();
+        // No io::Error is possible, since we're doing no actual IO.
+        self.serialize_into(&mut cursor).unwrap();
+        buf
+    }
+}
+
+impl GroupHeader for SequencedHeader {
+    fn msg_id(&self) -> u16 {
+        self.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.msg_ver
+    }
+}
+
+impl GetSequence for SequencedHeader {
+    fn sequence(&self) -> Option<u64> {
+        Some(self.seq)
+    }
+}
+
+impl FramedHeader for SequencedHeader {
+    const HEADER_FORMAT: u16 = header_format::SEQUENCED;
+
+    /// Create a header with sequence number 0.
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        SequencedHeader::for_msg(msg, 0, msg_len)
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        SequencedHeader::deserialize_from(r)
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        SequencedHeader::serialize_into(self, w)
+    }
+}
+
+/// A header with optional timestamp, flags, sequence number, expiry, and
+/// correlation id fields.
+///
+/// This header does not use serde; it serializes to a binary (big-endian)
+/// layout of 9 to 42 bytes, depending on which optional fields are set:
+///
+/// | size | field |
+/// |------|-------|
+/// | 2    | message id |
+/// | 2    | message version |
+/// | 1    | which optional fields are present (see below) |
+/// | 8    | timestamp, if bit 0x01 is set |
+/// | 1    | flags, if bit 0x02 is set |
+/// | 8    | sequence number, if bit 0x04 is set |
+/// | 8    | expiry time, if bit 0x08 is set |
+/// | 8    | correlation id, if bit 0x10 is set |
+/// | 4    | message length |
+///
+/// Because the presence byte is part of the header, readers don't need
+/// to know in advance which fields a writer uses.
+///
+/// Optional fields are set with the `with_` methods:
+/// ```
+/// # use aversion::util::ExtendedHeader;
+/// let header = ExtendedHeader::new(0x12, 1, 100)
+///     .with_timestamp(1_600_000_000)
+///     .with_seq(7);
+/// assert_eq!(header.size(), 25);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedHeader {
+    /// The message id.
+    pub msg_id: u16,
+    /// The message version.
+    pub msg_ver: u16,
+    /// The message timestamp.
+    ///
+    /// The meaning of the value (e.g. seconds since the Unix epoch) is up
+    /// to the application.
+    pub timestamp: Option<u64>,
+    /// The message flags. See the [`flags`] module.
+    ///
+    /// [`flags`]: crate::group::flags
+    pub flags: Option<u8>,
+    /// The message sequence number.
+    pub seq: Option<u64>,
+    /// The time after which the message should be discarded.
+    ///
+    /// This uses the same units as the timestamp. See
+    /// [`ExpirySource`](crate::util::expiry::ExpirySource).
+    pub expiry: Option<u64>,
+    /// The id that links a reply to its request.
+    ///
+    /// See the [`reply`](crate::util::reply) module.
+    pub correlation_id: Option<u64>,
+    /// The length of the message when serialized.
+    pub msg_len: u32,
+}
+
+impl ExtendedHeader {
+    /// The size of the header with no optional fields, in bytes.
+    pub const MIN_SIZE: usize = 9;
+    /// The size of the header with all optional fields, in bytes.
+    pub const MAX_SIZE: usize = 42;
+
+    const HAS_TIMESTAMP: u8 = 0x01;
+    const HAS_FLAGS: u8 = 0x02;
+    const HAS_SEQ: u8 = 0x04;
+    const HAS_EXPIRY: u8 = 0x08;
+    const HAS_CORRELATION_ID: u8 = 0x10;
+
+    /// Create a new `ExtendedHeader`, with no optional fields.
+    pub fn new(msg_id: u16, msg_ver: u16, msg_len: u32) -> Self {
+        ExtendedHeader {
+            msg_id,
+            msg_ver,
+            timestamp: None,
+            flags: None,
+            seq: None,
+            expiry: None,
+            correlation_id: None,
+            msg_len,
+        }
+    }
+
+    /// Create a new `ExtendedHeader` that corresponds to a type.
+    ///
+    /// The version and message id values will be filled in from
+    /// the type's [`Versioned`] and [`MessageId`] associated
+    /// constants.
+    pub fn for_msg<T>(_msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        Self::new(NarrowId::<T::Base>::MSG_ID, T::VER, msg_len)
+    }
+
+    /// Set the timestamp.
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Set the flags.
+    pub fn with_flags(mut self, flags: u8) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    /// Set the sequence number.
+    pub fn with_seq(mut self, seq: u64) -> Self {
+        self.seq = Some(seq);
+        self
+    }
+
+    /// Set the expiry time.
+    pub fn with_expiry(mut self, expiry: u64) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    /// Set the correlation id.
+    pub fn with_correlation_id(mut self, correlation_id: u64) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self
+    }
+
+    /// The size of the header when serialized, in bytes.
+    pub fn size(&self) -> usize {
+        let mut size = Self::MIN_SIZE;
+        if self.timestamp.is_some() {
+            size += 8;
+        }
+        if self.flags.is_some() {
+            size += 1;
+        }
+        if self.seq.is_some() {
+            size += 8;
+        }
+        if self.expiry.is_some() {
+            size += 8;
+        }
+        if self.correlation_id.is_some() {
+            size += 8;
+        }
+        size
+    }
+
+    /// Deserialize a header from a `Read` stream.
+    ///
+    /// Returns an error of kind [`InvalidData`][io::ErrorKind::InvalidData]
+    /// if the presence byte contains unknown bits.
+    pub fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let msg_id = r.read_u16::<BigEndian>()?;
+        let msg_ver = r.read_u16::<BigEndian>()?;
+        let present = r.read_u8()?;
+        let known = Self::HAS_TIMESTAMP
+            | Self::HAS_FLAGS
+            | Self::HAS_SEQ
+            | Self::HAS_EXPIRY
+            | Self::HAS_CORRELATION_ID;
+        if present & !known != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown ExtendedHeader fields",
+            ));
+        }
+        let timestamp = if present & Self::HAS_TIMESTAMP != 0 {
+            Some(r.read_u64::<BigEndian>()?)
+        } else {
+            None
+        };
+        let flags = if present & Self::HAS_FLAGS != 0 {
+            Some(r.read_u8()?)
+        } else {
+            None
+        };
+        let seq = if present & Self::HAS_SEQ != 0 {
+            Some(r.read_u64::<BigEndian>()?)
+        } else {
+            None
+        };
+        let expiry = if present & Self::HAS_EXPIRY != 0 {
+            Some(r.read_u64::<BigEndian>()?)
+        } else {
+            None
+        };
+        let correlation_id = if present & Self::HAS_CORRELATION_ID != 0 {
+            Some(r.read_u64::<BigEndian>()?)
+        } else {
+            None
+        };
+        let msg_len = r.read_u32::<BigEndian>()?;
+        Ok(ExtendedHeader {
+            msg_id,
+            msg_ver,
+            timestamp,
+            flags,
+            seq,
+            expiry,
+            correlation_id,
+            msg_len,
+        })
+    }
+
+    /// Serialize a header into a `Write` stream.
+    pub fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        let mut present = 0;
+        if self.timestamp.is_some() {
+            present |= Self::HAS_TIMESTAMP;
+        }
+        if self.flags.is_some() {
+            present |= Self::HAS_FLAGS;
+        }
+        if self.seq.is_some() {
+            present |= Self::HAS_SEQ;
+        }
+        if self.expiry.is_some() {
+            present |= Self::HAS_EXPIRY;
+        }
+        if self.correlation_id.is_some() {
+            present |= Self::HAS_CORRELATION_ID;
+        }
+        w.write_u16::<BigEndian>(self.msg_id)?;
+        w.write_u16::<BigEndian>(self.msg_ver)?;
+        w.write_u8(present)?;
+        if let Some(timestamp) = self.timestamp {
+            w.write_u64::<BigEndian>(timestamp)?;
+        }
+        if let Some(flags) = self.flags {
+            w.write_u8(flags)?;
+        }
+        if let Some(seq) = self.seq {
+            w.write_u64::<BigEndian>(seq)?;
+        }
+        if let Some(expiry) = self.expiry {
+            w.write_u64::<BigEndian>(expiry)?;
+        }
+        if let Some(correlation_id) = self.correlation_id {
+            w.write_u64::<BigEndian>(correlation_id)?;
+        }
+        w.write_u32::<BigEndian>(self.msg_len)?;
+        Ok(())
+    }
+
+    /// Serialize a header into a `Vec`.
+    pub fn serialize(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size());
+        // No io::Error is possible, since we're doing no actual IO.
+        self.serialize_into(&mut buf).unwrap();
+        buf
+    }
+}
+
+impl GroupHeader for ExtendedHeader {
+    fn msg_id(&self) -> u16 {
+        self.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.msg_ver
+    }
+
+    fn flags(&self) -> u8 {
+        self.flags.unwrap_or(0)
+    }
+}
+
+impl GetTimestamp for ExtendedHeader {
+    fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+}
+
+impl GetSequence for ExtendedHeader {
+    fn sequence(&self) -> Option<u64> {
+        self.seq
+    }
+}
+
+impl GetExpiry for ExtendedHeader {
+    fn expiry(&self) -> Option<u64> {
+        self.expiry
+    }
+}
+
+impl GetCorrelationId for ExtendedHeader {
+    fn correlation_id(&self) -> Option<u64> {
+        self.correlation_id
+    }
+}
+
+impl FramedHeader for ExtendedHeader {
+    const HEADER_FORMAT: u16 = header_format::EXTENDED;
+
+    /// Create a header with no optional fields.
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        ExtendedHeader::for_msg(msg, msg_len)
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        ExtendedHeader::deserialize_from(r)
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        ExtendedHeader::serialize_into(self, w)
+    }
+}
+
+/// An [`ExtendedHeader`] followed by a hash that links it to the
+/// previous message.
+///
+/// This header does not use serde; it serializes to the bytes of the
+/// [`ExtendedHeader`], followed by the 32-byte hash. See
+/// [`HashChainSink`] for how the hash is computed.
+///
+/// [`HashChainSink`]: crate::util::hash_chain::HashChainSink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainHeader {
+    /// The rest of the header.
+    pub ext: ExtendedHeader,
+    /// The hash of the previous message's hash and this message's body.
+    pub hash: [u8; 32],
+}
+
+impl ChainHeader {
+    /// The size of the hash, in bytes.
+    pub const HASH_SIZE: usize = 32;
+
+    /// Create a new `ChainHeader`.
+    pub fn new(ext: ExtendedHeader, hash: [u8; 32]) -> Self {
+        ChainHeader { ext, hash }
+    }
+
+    /// Deserialize a header from a `Read` stream.
+    pub fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let ext = ExtendedHeader::deserialize_from(r)?;
+        let mut hash = [0u8; Self::HASH_SIZE];
+        r.read_exact(&mut hash)?;
+        Ok(ChainHeader { ext, hash })
+    }
+
+    /// Serialize a header into a `Write` stream.
+    pub fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        self.ext.serialize_into(w)?;
+        w.write_all(&self.hash)
+    }
+}
+
+impl GroupHeader for ChainHeader {
+    fn msg_id(&self) -> u16 {
+        self.ext.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.ext.msg_ver
+    }
+
+    fn flags(&self) -> u8 {
+        self.ext.flags()
+    }
+}
+
+impl GetExpiry for ChainHeader {
+    fn expiry(&self) -> Option<u64> {
+        self.ext.expiry
+    }
+}
+
+impl FramedHeader for ChainHeader {
+    const HEADER_FORMAT: u16 = header_format::CHAIN;
+
+    /// Create a header with no optional fields, and an all-zero hash.
+    ///
+    /// To write a valid chain, use [`HashChainSink`].
+    ///
+    /// [`HashChainSink`]: crate::util::hash_chain::HashChainSink
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        ChainHeader::new(ExtendedHeader::for_msg(msg, msg_len), [0; 32])
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.ext.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        ChainHeader::deserialize_from(r)
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        ChainHeader::serialize_into(self, w)
+    }
+}
+
+/// A header with a 32-bit message id.
+///
+/// This header does not use serde; it serializes to a binary
+/// (big-endian) array of 10 bytes. It's the same as [`BasicHeader`], but
+/// the message id is 32 bits wide; see [`MessageId::WIDE_MSG_ID`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WideHeader {
+    /// The message id.
+    pub msg_id: u32,
+    /// The message version.
+    pub msg_ver: u16,
+    /// The length of the message when serialized.
+    pub msg_len: u32,
+}
+
+impl WideHeader {
+    /// The size of the header when serialized, in bytes.
+    pub const SIZE: usize = 10;
+
+    /// Create a new `WideHeader`.
+    pub fn new(msg_id: u32, msg_ver: u16, msg_len: u32) -> Self {
+        WideHeader {
+            msg_id,
+            msg_ver,
+            msg_len,
+        }
+    }
+
+    /// Create a new `WideHeader` that corresponds to a type.
+    ///
+    /// The version and message id values will be filled in from
+    /// the type's [`Versioned`] and [`MessageId`] associated
+    /// constants.
+    pub fn for_msg<T>(_msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        Self::new(T::Base::WIDE_MSG_ID, T::VER, msg_len)
+    }
+
+    /// Deserialize a header from a `Read` stream.
+    pub fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let msg_id = r.read_u32::<BigEndian>()?;
+        let msg_ver = r.read_u16::<BigEndian>()?;
+        let msg_len = r.read_u32::<BigEndian>()?;
+        Ok(WideHeader {
+            msg_id,
+            msg_ver,
+            msg_len,
+        })
+    }
+
+    /// Serialize a header into a `Write` stream.
+    pub fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        w.write_u32::<BigEndian>(self.msg_id)?;
+        w.write_u16::<BigEndian>(self.msg_ver)?;
+        w.write_u32::<BigEndian>(self.msg_len)?;
+        Ok(())
+    }
+}
+
+impl GroupHeader for WideHeader {
+    /// The low 16 bits of the message id.
+    #[allow(clippy::cast_possible_truncation)]
+    fn msg_id(&self) -> u16 {
+        self.msg_id as u16
+    }
+
+    fn wide_msg_id(&self) -> u32 {
+        self.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.msg_ver
+    }
+}
+
+impl FramedHeader for WideHeader {
+    const HEADER_FORMAT: u16 = header_format::WIDE;
+    const WIDE_IDS: bool = true;
+
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        WideHeader::for_msg(msg, msg_len)
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        WideHeader::deserialize_from(r)
+    }
+
+    /// The low 16 bits of the message id, as returned by
+    /// [`msg_id`][GroupHeader::msg_id].
+    fn peek_id(prefix: &[u8]) -> Result<Option<u16>, io::Error> {
+        match prefix {
+            [_, _, hi, lo, ..] => Ok(Some(u16::from_be_bytes([*hi, *lo]))),
+            _ => Ok(None),
+        }
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        WideHeader::serialize_into(self, w)
+    }
+}
+
+/// A header that stores each field as a variable-length integer.
+///
+/// This header does not use serde. It serializes to a marker byte
+/// ([`MARKER`][Self::MARKER]), followed by the message id, the message
+/// version, and the message length, each as an unsigned LEB128 varint.
+/// Small values take fewer bytes, so a typical header is 4 or 5 bytes,
+/// and never more than [`MAX_SIZE`][Self::MAX_SIZE].
+///
+/// The marker byte allows a reader to tell this header apart from a
+/// [`BasicHeader`]; see [`MultiHeader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VarintHeader {
+    /// The message id.
+    pub msg_id: u16,
+    /// The message version.
+    pub msg_ver: u16,
+    /// The length of the message when serialized.
+    pub msg_len: u32,
+}
+
+impl VarintHeader {
+    /// The first byte of every serialized `VarintHeader`.
+    pub const MARKER: u8 = 0xff;
+    /// The largest possible size of the header when serialized, in bytes.
+    pub const MAX_SIZE: usize = 12;
+
+    /// Create a new `VarintHeader`.
+    pub fn new(msg_id: u16, msg_ver: u16, msg_len: u32) -> Self {
+        VarintHeader {
+            msg_id,
+            msg_ver,
+            msg_len,
+        }
+    }
+
+    /// Create a new `VarintHeader` that corresponds to a type.
+    ///
+    /// The version and message id values will be filled in from
+    /// the type's [`Versioned`] and [`MessageId`] associated
+    /// constants.
+    pub fn for_msg<T>(_msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        Self::new(NarrowId::<T::Base>::MSG_ID, T::VER, msg_len)
+    }
+
+    /// Deserialize a header from a `Read` stream.
+    ///
+    /// Returns an error of kind [`InvalidData`][io::ErrorKind::InvalidData]
+    /// if the marker byte is missing, or a field is out of range.
+    pub fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        if r.read_u8()? != Self::MARKER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "missing VarintHeader marker",
+            ));
+        }
+        let msg_id = read_varint(r)?;
+        let msg_ver = read_varint(r)?;
+        let msg_len = read_varint(r)?;
+        Ok(VarintHeader {
+            msg_id,
+            msg_ver,
+            msg_len,
+        })
+    }
+
+    /// Serialize a header into a `Write` stream.
+    pub fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        w.write_u8(Self::MARKER)?;
+        write_varint(w, self.msg_id.into())?;
+        write_varint(w, self.msg_ver.into())?;
+        write_varint(w, self.msg_len.into())?;
+        Ok(())
+    }
+}
+
+/// Read an unsigned LEB128 varint, which must fit in a `T`.
+fn read_varint<T>(r: &mut impl Read) -> Result<T, io::Error>
+where
+    T: TryFrom<u64>,
+{
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = r.read_u8()?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "varint too long",
+            ));
+        }
+    }
+    T::try_from(value)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "varint out of range"))
+}
+
+/// Write an unsigned LEB128 varint.
+fn write_varint(w: &mut impl Write, mut value: u64) -> Result<(), io::Error> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return w.write_u8(byte);
+        }
+        w.write_u8(byte | 0x80)?;
+    }
+}
+
+impl GroupHeader for VarintHeader {
+    fn msg_id(&self) -> u16 {
+        self.msg_id
+    }
+
+    fn msg_ver(&self) -> u16 {
+        self.msg_ver
+    }
+}
+
+impl FramedHeader for VarintHeader {
+    const HEADER_FORMAT: u16 = header_format::VARINT;
+
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        VarintHeader::for_msg(msg, msg_len)
+    }
+
+    fn msg_len(&self) -> u32 {
+        self.msg_len
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        VarintHeader::deserialize_from(r)
+    }
+
+    fn peek_id(prefix: &[u8]) -> Result<Option<u16>, io::Error> {
+        let rest = match prefix {
+            [] => return Ok(None),
+            [marker, rest @ ..] if *marker == Self::MARKER => rest,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "missing VarintHeader marker",
+                ))
+            }
+        };
+        match read_varint(&mut &rest[..]) {
+            Ok(msg_id) => Ok(Some(msg_id)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        VarintHeader::serialize_into(self, w)
+    }
+}
+
+/// Either a [`BasicHeader`] or a [`VarintHeader`], detected per message.
+///
+/// This eases a migration from `BasicHeader` to `VarintHeader`: a reader
+/// using `MultiHeader` can read a stream in which old and new writers
+/// have mixed both kinds of header.
+///
+/// # Detection
+///
+/// The first byte of each header decides how it's read. If it's
+/// [`VarintHeader::MARKER`] (`0xff`), the header is a `VarintHeader`.
+/// Otherwise it's the high byte of a `BasicHeader` message id.
+///
+/// This is ambiguous for a `BasicHeader` with a message id of `0xff00` or
+/// higher, which would be read as a `VarintHeader`. Don't use those
+/// message ids in a group that has ever been written with a `BasicHeader`.
+///
+/// [`DataSource::peek_msg_id`] uses the same detection, so it works with
+/// both kinds of header.
+///
+/// New messages are written with a `VarintHeader`.
+///
+/// [`DataSource::peek_msg_id`]: crate::group::DataSource::peek_msg_id
+#[derive(Debug, Clone, Copy)]
+pub enum MultiHeader {
+    /// A fixed-size header.
+    Basic(BasicHeader),
+    /// A variable-size header.
+    Varint(VarintHeader),
+}
+
+impl GroupHeader for MultiHeader {
+    fn msg_id(&self) -> u16 {
+        match self {
+            MultiHeader::Basic(h) => h.msg_id,
+            MultiHeader::Varint(h) => h.msg_id,
+        }
+    }
+
+    fn msg_ver(&self) -> u16 {
+        match self {
+            MultiHeader::Basic(h) => h.msg_ver,
+            MultiHeader::Varint(h) => h.msg_ver,
+        }
+    }
+}
+
+impl FramedHeader for MultiHeader {
+    const HEADER_FORMAT: u16 = header_format::MULTI;
+
+    /// Create a [`VarintHeader`].
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        MultiHeader::Varint(VarintHeader::for_msg(msg, msg_len))
+    }
+
+    fn msg_len(&self) -> u32 {
+        match self {
+            MultiHeader::Basic(h) => h.msg_len,
+            MultiHeader::Varint(h) => h.msg_len,
+        }
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        let first = [r.read_u8()?];
+        let mut r = (&first[..]).chain(r);
+        if first[0] == VarintHeader::MARKER {
+            VarintHeader::deserialize_from(&mut r).map(MultiHeader::Varint)
+        } else {
+            BasicHeader::deserialize_from(&mut r).map(MultiHeader::Basic)
+        }
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        match self {
+            MultiHeader::Basic(h) => h.serialize_into(w),
+            MultiHeader::Varint(h) => h.serialize_into(w),
+        }
+    }
+
+    fn peek_id(prefix: &[u8]) -> Result<Option<u16>, io::Error> {
+        match prefix.first() {
+            None => Ok(None),
+            Some(&VarintHeader::MARKER) => VarintHeader::peek_id(prefix),
+            Some(_) => BasicHeader::peek_id(prefix),
+        }
+    }
+
+    /// Accepts a stream written with [`BasicHeader`], [`VarintHeader`] or
+    /// `MultiHeader`, since each header is detected as it's read.
+    fn deserialize_format(format: u16, r: &mut impl Read) -> Result<Self, io::Error> {
+        match format {
+            header_format::BASIC | header_format::VARINT | header_format::MULTI => {
+                Self::deserialize_from(r)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported header format",
+            )),
+        }
+    }
+}
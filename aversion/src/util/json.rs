@@ -0,0 +1,153 @@
+//! Provides a JSON `Codec`, and a `DataSink` that writes newline-delimited JSON.
+
+use crate::group::DataSink;
+use crate::util::codec::{format_id, Codec};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::io::Write;
+
+/// A [`Codec`] using the JSON serialization format.
+///
+/// This is only available when the `json` feature is enabled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    const FORMAT_ID: u16 = format_id::JSON;
+
+    type Error = serde_json::Error;
+
+    fn encode<T>(&self, msg: &T, buf: &mut Vec<u8>) -> Result<(), serde_json::Error>
+    where
+        T: Serialize,
+    {
+        serde_json::to_writer(buf, msg)
+    }
+
+    fn decode<T>(&self, buf: &[u8]) -> Result<T, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(buf)
+    }
+}
+
+/// A [`DataSink`] that writes messages as newline-delimited JSON.
+///
+/// This is meant for debugging: it writes one JSON object per line, which
+/// can then be inspected with tools like `jq`. Each object contains the
+/// message id, the message version, and the message itself:
+/// ```text
+/// {"id":112,"ver":1,"body":{"foo":1234}}
+/// ```
+///
+/// There is no matching `DataSource`; this format is not meant for
+/// exchanging data.
+///
+/// This is only available when the `json` feature is enabled.
+pub struct DebugJsonSink<W> {
+    inner: W,
+}
+
+impl<W> DebugJsonSink<W> {
+    /// Create a new `DebugJsonSink`.
+    pub fn new(writer: W) -> Self {
+        DebugJsonSink { inner: writer }
+    }
+
+    /// Consume the `DebugJsonSink`, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// The JSON object written for each message.
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    id: u16,
+    ver: u16,
+    body: &'a T,
+}
+
+impl<W> DataSink for DebugJsonSink<W>
+where
+    W: Write,
+{
+    type Error = serde_json::Error;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), serde_json::Error>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let envelope = Envelope {
+            id: T::Base::MSG_ID,
+            ver: T::VER,
+            body: msg,
+        };
+        serde_json::to_writer(&mut self.inner, &envelope)?;
+        self.inner.write_all(b"\n").map_err(serde_json::Error::io)
+    }
+
+    fn flush(&mut self) -> Result<(), serde_json::Error> {
+        self.inner.flush().map_err(serde_json::Error::io)
+    }
+}
+
+/// A [`DataSink`] that captures messages as canonical JSON.
+///
+/// This is used by [`GroupSerialize::to_canonical_json`]. Each message is
+/// written as an object with `body`, `msg_id` and `version` keys, and the
+/// keys of every object (including those inside the body) are sorted, so
+/// that the same message always produces the same string.
+///
+/// [`GroupSerialize::to_canonical_json`]: crate::group::GroupSerialize::to_canonical_json
+#[derive(Default)]
+pub(crate) struct CanonicalJsonSink {
+    pub(crate) output: String,
+}
+
+impl DataSink for CanonicalJsonSink {
+    type Error = serde_json::Error;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), serde_json::Error>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let mut envelope = Map::new();
+        envelope.insert("body".into(), serde_json::to_value(msg)?);
+        envelope.insert("msg_id".into(), T::Base::WIDE_MSG_ID.into());
+        envelope.insert("version".into(), T::VER.into());
+        let envelope = sort_keys(Value::Object(envelope));
+        self.output.push_str(&serde_json::to_string(&envelope)?);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), serde_json::Error> {
+        Ok(())
+    }
+}
+
+/// Sort the keys of every object in `value`.
+///
+/// `serde_json::Map` is already sorted unless the `preserve_order`
+/// feature is enabled somewhere in the dependency graph, so this doesn't
+/// rely on it.
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let map = entries
+                .into_iter()
+                .map(|(key, value)| (key, sort_keys(value)))
+                .collect();
+            Value::Object(map)
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
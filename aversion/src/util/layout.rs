@@ -0,0 +1,267 @@
+//! Describes the wire layout of header types.
+//!
+//! [`HeaderLayout`] lists the fields of a serialized header, in order,
+//! with their offsets, widths, and encodings. This is meant for tools
+//! that generate documentation, e.g. a layout table for each header:
+//!
+//! ```
+//! # use aversion::util::layout::HeaderLayout;
+//! # use aversion::util::BasicHeader;
+//! let mut table = String::from("| offset | size | field |\n|---|---|---|\n");
+//! for field in BasicHeader::layout() {
+//!     table += &format!(
+//!         "| {} | {} | {} |\n",
+//!         field.offset.unwrap(),
+//!         field.width.unwrap(),
+//!         field.name
+//!     );
+//! }
+//! assert!(table.ends_with("| 4 | 4 | msg_len |\n"));
+//! ```
+
+use crate::group::GroupHeader;
+use crate::util::{
+    BasicHeader, ChainHeader, ExtendedHeader, FlagsHeader, SemverHeader, SequencedHeader,
+    TinyHeader, VarintHeader, WideHeader,
+};
+use std::fmt;
+
+/// How a header field is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldEncoding {
+    /// An unsigned integer, most significant byte first.
+    BigEndian,
+    /// A single byte, or an array of bytes with no byte order.
+    Bytes,
+    /// An unsigned LEB128 varint.
+    Leb128,
+}
+
+impl fmt::Display for FieldEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldEncoding::BigEndian => "BE",
+            FieldEncoding::Bytes => "bytes",
+            FieldEncoding::Leb128 => "LEB128",
+        })
+    }
+}
+
+/// One field of a serialized header.
+///
+/// This displays in a compact form, e.g. `msg_id@0:2 BE`. An offset or
+/// width that isn't fixed is shown as `?`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderField {
+    /// The field name. This matches the struct field, if there is one.
+    pub name: &'static str,
+    /// The offset from the start of the header, in bytes.
+    ///
+    /// This is `None` if it depends on the size of earlier fields.
+    pub offset: Option<usize>,
+    /// The size of the field, in bytes.
+    ///
+    /// This is `None` if the field has a variable size.
+    pub width: Option<usize>,
+    /// How the field is encoded.
+    pub encoding: FieldEncoding,
+    /// If the field is optional, a description of when it's present.
+    pub present_if: Option<&'static str>,
+}
+
+impl HeaderField {
+    /// A field with a fixed offset and width.
+    pub const fn fixed(
+        name: &'static str,
+        offset: usize,
+        width: usize,
+        encoding: FieldEncoding,
+    ) -> Self {
+        HeaderField {
+            name,
+            offset: Some(offset),
+            width: Some(width),
+            encoding,
+            present_if: None,
+        }
+    }
+
+    /// A field whose offset or width isn't fixed.
+    pub const fn variable(
+        name: &'static str,
+        offset: Option<usize>,
+        width: Option<usize>,
+        encoding: FieldEncoding,
+    ) -> Self {
+        HeaderField {
+            name,
+            offset,
+            width,
+            encoding,
+            present_if: None,
+        }
+    }
+
+    /// Mark this field as optional.
+    pub const fn present_if(mut self, condition: &'static str) -> Self {
+        self.present_if = Some(condition);
+        self
+    }
+}
+
+impl fmt::Display for HeaderField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        match self.offset {
+            Some(offset) => write!(f, "@{}", offset)?,
+            None => f.write_str("@?")?,
+        }
+        match self.width {
+            Some(width) => write!(f, ":{}", width)?,
+            None => f.write_str(":?")?,
+        }
+        write!(f, " {}", self.encoding)?;
+        if let Some(condition) = self.present_if {
+            write!(f, " if {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+/// A header type that can describe its serialized layout.
+///
+/// ```
+/// # use aversion::util::layout::HeaderLayout;
+/// # use aversion::util::TinyHeader;
+/// let fields: Vec<String> = TinyHeader::layout().iter().map(|f| f.to_string()).collect();
+/// assert_eq!(fields, ["msg_id@0:2 BE", "msg_ver@2:2 BE"]);
+/// ```
+///
+/// [`MultiHeader`](crate::util::MultiHeader) doesn't implement this
+/// trait, since it's serialized as one of two different layouts.
+pub trait HeaderLayout: GroupHeader {
+    /// The fields of the serialized header, in the order they're written.
+    fn layout() -> &'static [HeaderField];
+}
+
+use FieldEncoding::{BigEndian, Bytes, Leb128};
+
+impl HeaderLayout for TinyHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = &[
+            HeaderField::fixed("msg_id", 0, 2, BigEndian),
+            HeaderField::fixed("msg_ver", 2, 2, BigEndian),
+        ];
+        LAYOUT
+    }
+}
+
+impl HeaderLayout for BasicHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = &[
+            HeaderField::fixed("msg_id", 0, 2, BigEndian),
+            HeaderField::fixed("msg_ver", 2, 2, BigEndian),
+            HeaderField::fixed("msg_len", 4, 4, BigEndian),
+        ];
+        LAYOUT
+    }
+}
+
+impl HeaderLayout for FlagsHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = &[
+            HeaderField::fixed("msg_id", 0, 2, BigEndian),
+            HeaderField::fixed("msg_ver", 2, 2, BigEndian),
+            HeaderField::fixed("flags", 4, 1, Bytes),
+            HeaderField::fixed("msg_len", 5, 4, BigEndian),
+        ];
+        LAYOUT
+    }
+}
+
+impl HeaderLayout for SemverHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = &[
+            HeaderField::fixed("msg_id", 0, 2, BigEndian),
+            HeaderField::fixed("msg_ver", 2, 2, BigEndian),
+            HeaderField::fixed("msg_minor_ver", 4, 2, BigEndian),
+            HeaderField::fixed("msg_len", 6, 4, BigEndian),
+        ];
+        LAYOUT
+    }
+}
+
+impl HeaderLayout for SequencedHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = &[
+            HeaderField::fixed("msg_id", 0, 2, BigEndian),
+            HeaderField::fixed("msg_ver", 2, 2, BigEndian),
+            HeaderField::fixed("seq", 4, 8, BigEndian),
+            HeaderField::fixed("msg_len", 12, 4, BigEndian),
+        ];
+        LAYOUT
+    }
+}
+
+impl HeaderLayout for WideHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = &[
+            HeaderField::fixed("msg_id", 0, 4, BigEndian),
+            HeaderField::fixed("msg_ver", 4, 2, BigEndian),
+            HeaderField::fixed("msg_len", 6, 4, BigEndian),
+        ];
+        LAYOUT
+    }
+}
+
+/// The fields of an `ExtendedHeader`, which are also the start of a
+/// `ChainHeader`.
+macro_rules! extended_fields {
+    ($($extra:expr),*) => {
+        &[
+            HeaderField::fixed("msg_id", 0, 2, BigEndian),
+            HeaderField::fixed("msg_ver", 2, 2, BigEndian),
+            HeaderField::fixed("present", 4, 1, Bytes),
+            HeaderField::fixed("timestamp", 5, 8, BigEndian).present_if("present & 0x01"),
+            HeaderField::variable("flags", None, Some(1), Bytes).present_if("present & 0x02"),
+            HeaderField::variable("seq", None, Some(8), BigEndian).present_if("present & 0x04"),
+            HeaderField::variable("expiry", None, Some(8), BigEndian)
+                .present_if("present & 0x08"),
+            HeaderField::variable("correlation_id", None, Some(8), BigEndian)
+                .present_if("present & 0x10"),
+            HeaderField::variable("msg_len", None, Some(4), BigEndian),
+            $($extra),*
+        ]
+    };
+}
+
+impl HeaderLayout for ExtendedHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = extended_fields!();
+        LAYOUT
+    }
+}
+
+impl HeaderLayout for ChainHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = extended_fields!(HeaderField::variable(
+            "hash",
+            None,
+            Some(ChainHeader::HASH_SIZE),
+            Bytes
+        ));
+        LAYOUT
+    }
+}
+
+impl HeaderLayout for VarintHeader {
+    fn layout() -> &'static [HeaderField] {
+        const LAYOUT: &[HeaderField] = &[
+            HeaderField::fixed("marker", 0, 1, Bytes),
+            HeaderField::variable("msg_id", Some(1), None, Leb128),
+            HeaderField::variable("msg_ver", None, None, Leb128),
+            HeaderField::variable("msg_len", None, None, Leb128),
+        ];
+        LAYOUT
+    }
+}
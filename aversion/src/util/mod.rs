@@ -14,10 +14,117 @@
 //! [`GroupHeader`]: crate::group::GroupHeader
 //! [`CborData`]: crate::util::cbor::CborData
 
+pub mod bytes;
+pub mod chunked;
+pub mod clock;
+pub mod codec;
+pub mod compact;
+pub mod dedup;
+pub mod expiry;
+pub mod fixed;
 mod header;
+pub mod layout;
+pub mod monotonic;
+pub mod preamble;
+pub mod protocol;
+pub mod rate_limit;
+pub mod sync;
+pub mod transcode;
+pub mod unknown_fields;
 
 #[doc(inline)]
-pub use header::{BasicHeader, TinyHeader};
+pub use codec::Codec;
+#[doc(inline)]
+pub use header::{
+    BasicHeader, ChainHeader, ExtendedHeader, FlagsHeader, FramedHeader, MultiHeader, SemverHeader,
+    SequencedHeader, TinyHeader, VarintHeader, WideHeader, MAX_PEEK_LEN,
+};
+
+#[doc(inline)]
+pub use header::header_format;
+
+pub(crate) use header::PeekedId;
+
+use std::io::{self, Read};
+
+/// Read a message body of `len` bytes into `body`, replacing its contents.
+///
+/// `len` usually comes from a header, which may be untrusted, so the
+/// buffer grows as the data arrives instead of being allocated up front.
+/// If the reader ends early, an error of kind
+/// [`UnexpectedEof`][io::ErrorKind::UnexpectedEof] is returned.
+pub(crate) fn read_body(r: &mut impl Read, len: u32, body: &mut Vec<u8>) -> io::Result<()> {
+    body.clear();
+    let read = r.take(len.into()).read_to_end(body)?;
+    if read < len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Read one byte, or return `None` at a clean end of file.
+///
+/// Sources read the first byte of a header this way, so that a clean EOF
+/// can be distinguished from a truncated header.
+pub(crate) fn try_read_first_byte(r: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut first = [0u8; 1];
+    loop {
+        match r.read(&mut first) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(first[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "base64")]
+pub mod base64_line;
 
 #[cfg(feature = "serde_cbor")]
 pub mod cbor;
+
+#[cfg(feature = "serde_cbor")]
+pub mod diff;
+
+#[cfg(feature = "serde_cbor")]
+pub mod footer;
+
+#[cfg(feature = "serde_cbor")]
+pub mod grpc;
+
+#[cfg(feature = "flate2")]
+pub mod gzip;
+
+#[cfg(feature = "serde_cbor")]
+pub mod hash_chain;
+
+#[cfg(feature = "json")]
+pub mod json;
+
+#[cfg(feature = "rayon")]
+pub mod parallel;
+
+#[cfg(feature = "serde_cbor")]
+pub mod priority;
+
+#[cfg(feature = "serde_cbor")]
+pub mod reply;
+
+#[cfg(feature = "serde_cbor")]
+pub mod rotating;
+
+#[cfg(feature = "signing")]
+pub mod signed;
+
+#[cfg(feature = "async")]
+pub mod stream;
+
+#[cfg(feature = "tokio-codec")]
+pub mod tokio_codec;
+
+#[cfg(feature = "serde_cbor")]
+pub mod trailer;
+
+#[cfg(feature = "serde_cbor")]
+pub mod transform;
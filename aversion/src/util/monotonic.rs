@@ -0,0 +1,205 @@
+//! Provides a `DataSource` that rejects messages whose timestamps go backwards.
+
+use crate::group::{DataSource, GetTimestamp};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// An error returned by [`MonotonicSource`].
+#[derive(Debug, Error)]
+pub enum MonotonicError<E> {
+    /// The inner `DataSource` returned an error.
+    #[error("failed to read a message")]
+    Source(#[source] E),
+    /// A message's timestamp was earlier than the previous message's.
+    ///
+    /// If equal timestamps aren't allowed, this is also returned when the
+    /// timestamp didn't change.
+    #[error("message timestamp {found} is out of order (previous {previous})")]
+    OutOfOrder {
+        /// The timestamp of the last message that was accepted.
+        previous: u64,
+        /// The timestamp of the rejected message.
+        found: u64,
+    },
+}
+
+/// A [`DataSource`] that rejects messages whose timestamps go backwards.
+///
+/// This wraps another `DataSource` whose header implements [`GetTimestamp`].
+/// Each header's timestamp is compared with the last one that was accepted;
+/// if it's earlier, [`MonotonicError::OutOfOrder`] is returned instead of the
+/// header. The message body is left unread, so the caller can
+/// [`skip_message`][DataSource::skip_message] on the inner source (via
+/// [`get_mut`][Self::get_mut]) if it wants to continue.
+///
+/// Messages with the same timestamp as the previous message are accepted
+/// by default; see [`allow_equal`][Self::allow_equal]. Messages without a
+/// timestamp are always accepted, and don't affect later checks.
+///
+/// ```
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::util::monotonic::MonotonicSource;
+/// # use aversion::util::ExtendedHeader;
+/// # let buf = Vec::<u8>::new();
+/// // Every message must be strictly later than the one before it.
+/// let src = CborData::<_, ExtendedHeader>::with_header(&buf[..]);
+/// let src = MonotonicSource::new(src).allow_equal(false);
+/// ```
+pub struct MonotonicSource<Src> {
+    inner: Src,
+    allow_equal: bool,
+    last: Option<u64>,
+}
+
+impl<Src> MonotonicSource<Src>
+where
+    Src: DataSource,
+    Src::Header: GetTimestamp,
+{
+    /// Create a new `MonotonicSource`.
+    pub fn new(inner: Src) -> Self {
+        MonotonicSource {
+            inner,
+            allow_equal: true,
+            last: None,
+        }
+    }
+
+    /// Choose whether a message may have the same timestamp as the
+    /// previous one. The default is `true`.
+    pub fn allow_equal(mut self, allow: bool) -> Self {
+        self.allow_equal = allow;
+        self
+    }
+
+    /// The timestamp of the last message that was accepted, if any.
+    pub fn last_timestamp(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// Get a mutable reference to the inner `DataSource`.
+    pub fn get_mut(&mut self) -> &mut Src {
+        &mut self.inner
+    }
+
+    /// Consume the `MonotonicSource`, returning the inner `DataSource`.
+    pub fn into_inner(self) -> Src {
+        self.inner
+    }
+
+    fn check(&mut self, header: &Src::Header) -> Result<(), MonotonicError<Src::Error>> {
+        let found = match header.timestamp() {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+        if let Some(previous) = self.last {
+            if found < previous || (found == previous && !self.allow_equal) {
+                return Err(MonotonicError::OutOfOrder { previous, found });
+            }
+        }
+        self.last = Some(found);
+        Ok(())
+    }
+}
+
+impl<Src> DataSource for MonotonicSource<Src>
+where
+    Src: DataSource,
+    Src::Header: GetTimestamp,
+{
+    type Error = MonotonicError<Src::Error>;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Self::Error> {
+        let header = self.inner.read_header().map_err(MonotonicError::Source)?;
+        self.check(&header)?;
+        Ok(header)
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Self::Error> {
+        self.inner
+            .read_header_into(header)
+            .map_err(MonotonicError::Source)?;
+        self.check(header)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<Src::Header>, Self::Error> {
+        let header = self
+            .inner
+            .try_read_header()
+            .map_err(MonotonicError::Source)?;
+        if let Some(header) = &header {
+            self.check(header)?;
+        }
+        Ok(header)
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Self::Error> {
+        self.inner.peek_msg_id().map_err(MonotonicError::Source)
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.inner
+            .read_message(header)
+            .map_err(MonotonicError::Source)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Self::Error> {
+        self.inner
+            .skip_message(header)
+            .map_err(MonotonicError::Source)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Self::Error> {
+        self.inner.read_raw(header).map_err(MonotonicError::Source)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Self::Error {
+        MonotonicError::Source(self.inner.unsupported_operation(operation))
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Self::Error {
+        MonotonicError::Source(self.inner.unknown_message(msg_id))
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Self::Error {
+        MonotonicError::Source(self.inner.unknown_wide_message(msg_id))
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Self::Error {
+        MonotonicError::Source(self.inner.deprecated_message(msg_id, note))
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Self::Error {
+        MonotonicError::Source(self.inner.deprecated_wide_message(msg_id, note))
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Self::Error
+    where
+        T: Versioned,
+    {
+        MonotonicError::Source(self.inner.unknown_version::<T>(ver))
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Self::Error
+    where
+        T: Versioned,
+    {
+        MonotonicError::Source(self.inner.upgrade_too_deep::<T>(ver))
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Self::Error
+    where
+        T: MessageId,
+    {
+        MonotonicError::Source(self.inner.unexpected_message::<T>(msg_id))
+    }
+}
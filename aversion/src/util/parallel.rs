@@ -0,0 +1,61 @@
+//! Decodes independently framed messages on a thread pool.
+//!
+//! This is only available when the `rayon` feature is enabled.
+
+use crate::group::GroupDeserialize;
+use crate::util::cbor::{CborData, CborDataError};
+use rayon::prelude::*;
+
+/// Decode a batch of frames in parallel, on the global `rayon` pool.
+///
+/// Each frame must contain exactly one message: a [`BasicHeader`]
+/// followed by a CBOR body, as written by [`CborData`] or
+/// [`GroupSerialize::to_vec`]. The results are in the same order as
+/// `frames`. A frame that can't be decoded doesn't affect the others. A
+/// frame with bytes left over after its message returns
+/// [`CborDataError::TrailingGarbage`].
+///
+/// ```
+/// # use aversion::util::parallel::decode_parallel;
+/// # use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// # struct FooV1 { foo: u32 }
+/// # type Foo = FooV1;
+/// # assign_message_ids! { Foo: 1 }
+/// #[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+/// enum MyGroup {
+///     Foo(Foo),
+/// }
+///
+/// let frames: Vec<Vec<u8>> = (0..4)
+///     .map(|foo| MyGroup::Foo(FooV1 { foo }).to_vec().unwrap())
+///     .collect();
+/// let msgs = decode_parallel::<MyGroup>(frames);
+/// assert_eq!(msgs[3].as_ref().unwrap(), &MyGroup::Foo(FooV1 { foo: 3 }));
+/// ```
+///
+/// [`BasicHeader`]: crate::util::BasicHeader
+/// [`GroupSerialize::to_vec`]: crate::group::GroupSerialize::to_vec
+pub fn decode_parallel<G>(frames: Vec<Vec<u8>>) -> Vec<Result<G, CborDataError>>
+where
+    G: GroupDeserialize + Send,
+{
+    frames
+        .into_par_iter()
+        .map(|frame| decode_frame(&frame))
+        .collect()
+}
+
+/// Decode the single message in `frame`.
+fn decode_frame<G>(frame: &[u8]) -> Result<G, CborDataError>
+where
+    G: GroupDeserialize,
+{
+    let mut src = CborData::new(frame);
+    let msg = G::read_message(&mut src)?;
+    if !src.get_ref().is_empty() {
+        return Err(CborDataError::TrailingGarbage { messages: 1 });
+    }
+    Ok(msg)
+}
@@ -0,0 +1,675 @@
+//! Provides a preamble that identifies the format of a file.
+//!
+//! A preamble is written once at the start of a file or stream, before any
+//! messages. It contains a magic number, the preamble version, and the
+//! [`FORMAT_ID`] of the [`Codec`] used for message bodies. Checking it
+//! prevents a reader from silently misdecoding data written with a
+//! different codec.
+//!
+//! The preamble is 8 bytes, big-endian:
+//!
+//! | bytes | field |
+//! |-------|-------|
+//! | 0..4  | magic number, `b"AVER"` |
+//! | 4..6  | preamble version (currently 1) |
+//! | 6..8  | codec format id |
+//!
+//! A version 5 preamble adds a set of [`flags`], each of which says that
+//! an optional field is present. The fields follow the flags, in the
+//! order of their flag bits, so any combination of them can be recorded:
+//!
+//! | size | field | flag |
+//! |------|-------|------|
+//! | 4    | magic number, `b"AVER"` | |
+//! | 2    | preamble version (5) | |
+//! | 2    | codec format id | |
+//! | 2    | flags | |
+//! | 2    | header format (see [`FramedHeader::HEADER_FORMAT`]) | [`flags::HEADER_FORMAT`] |
+//! | 2    | group version (see [`GroupDeserialize::GROUP_VERSION`]) | [`flags::GROUP_VERSION`] |
+//!
+//! A preamble that doesn't record a header format is read as
+//! [`DEFAULT_HEADER_FORMAT`], and one that doesn't record a group version
+//! is read as group version 0. A reader rejects a preamble with a flag it
+//! doesn't know, since it can't tell how long the field is.
+//!
+//! If [`flags::SCHEMA_MANIFEST`] is set, the preamble is followed by a
+//! [`SchemaManifest`], which describes the messages in the file. Use
+//! [`read_schema_manifest`] to read it, after [`read_preamble`]. The
+//! manifest starts with its own length, so a reader that doesn't need it
+//! can skip it with [`skip_schema_manifest`]. It is laid out as:
+//!
+//! | size | field |
+//! |------|-------|
+//! | 4    | length of the rest of the manifest, in bytes |
+//! | 2    | manifest version (currently 2) |
+//! | 2    | number of entries |
+//!
+//! followed by each entry:
+//!
+//! | size | field |
+//! |------|-------|
+//! | 4    | message id |
+//! | 2    | message version |
+//! | 2    | length of the name |
+//! | *    | name, UTF-8 |
+//! | 4    | length of the schema |
+//! | *    | schema, UTF-8 (see [`Versioned::SCHEMA`]) |
+//!
+//! Versions 2 to 4 each added a single one of these fields, and are
+//! still read, but are no longer written:
+//!
+//! | version | size | contents |
+//! |---------|------|----------|
+//! | 2       | 10   | version 1, then the header format |
+//! | 3       | 10   | the same as version 2, followed by a schema manifest |
+//! | 4       | 12   | version 2, then the group version |
+//!
+//! [`FORMAT_ID`]: Codec::FORMAT_ID
+
+use crate::group::GroupDeserialize;
+use crate::util::{read_body, Codec, FramedHeader};
+use crate::{MessageId, Versioned};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+/// The magic number at the start of every preamble.
+pub const MAGIC: [u8; 4] = *b"AVER";
+
+/// The preamble version written by [`write_preamble`].
+pub const PREAMBLE_VERSION: u16 = 1;
+
+/// A preamble version that records the header format.
+///
+/// This is no longer written, but can still be read.
+pub const PREAMBLE_VERSION_2: u16 = 2;
+
+/// A preamble version that records the header format, followed by a
+/// schema manifest.
+///
+/// This is no longer written, but can still be read.
+pub const PREAMBLE_VERSION_3: u16 = 3;
+
+/// A preamble version that records the header format and group version.
+///
+/// This is no longer written, but can still be read.
+pub const PREAMBLE_VERSION_4: u16 = 4;
+
+/// The preamble version written by [`Preamble::write`].
+///
+/// This version records a set of [`flags`], so it can carry any
+/// combination of optional fields.
+pub const PREAMBLE_VERSION_5: u16 = 5;
+
+/// Flags that say which optional fields a version 5 preamble contains.
+pub mod flags {
+    /// The preamble records a header format.
+    pub const HEADER_FORMAT: u16 = 0x1;
+    /// The preamble records a group version.
+    pub const GROUP_VERSION: u16 = 0x2;
+    /// The preamble is followed by a schema manifest.
+    pub const SCHEMA_MANIFEST: u16 = 0x4;
+
+    /// All of the flags that this version of the crate understands.
+    pub const ALL: u16 = HEADER_FORMAT | GROUP_VERSION | SCHEMA_MANIFEST;
+}
+
+/// The schema manifest version written by [`write_preamble_with_manifest`].
+///
+/// Version 2 stores each message id as a `u32`; see
+/// [`MessageId::WIDE_MSG_ID`]. Version 1, which stored a `u16`, can still
+/// be read.
+pub const MANIFEST_VERSION: u16 = 2;
+
+/// The first schema manifest version, with 16-bit message ids.
+const MANIFEST_VERSION_1: u16 = 1;
+
+/// The size of the preamble when serialized, in bytes.
+pub const SIZE: usize = 8;
+
+/// The size of a version 2 or 3 preamble when serialized, in bytes.
+pub const SIZE_V2: usize = 10;
+
+/// The size of a version 4 preamble when serialized, in bytes.
+pub const SIZE_V4: usize = 12;
+
+/// The size of a version 5 preamble with no flags set, in bytes.
+///
+/// Each optional field adds two bytes; see [`Preamble::size`].
+pub const SIZE_V5: usize = 10;
+
+/// The header format of [`BasicHeader`][crate::util::BasicHeader].
+///
+/// A version 1 preamble is read with this header format, and it's the
+/// default [`FramedHeader::HEADER_FORMAT`].
+pub const DEFAULT_HEADER_FORMAT: u16 = crate::util::header_format::BASIC;
+
+/// Errors that may occur while reading a preamble.
+#[derive(Debug, Error)]
+pub enum PreambleError {
+    /// A `std::io::Error` occurred while reading data.
+    #[error("IO Error")]
+    Io(#[from] io::Error),
+    /// The data doesn't start with the preamble magic number.
+    #[error("Missing preamble")]
+    BadMagic,
+    /// The preamble version is not supported.
+    #[error("Unsupported preamble version {0}")]
+    UnsupportedVersion(u16),
+    /// The preamble has flags that are not supported.
+    #[error("Unsupported preamble flags {0:#x}")]
+    UnsupportedFlags(u16),
+    /// The schema manifest version is not supported.
+    #[error("Unsupported schema manifest version {0}")]
+    UnsupportedManifestVersion(u16),
+    /// The data was written with a different codec.
+    #[error("Codec mismatch: expected format id {expected}, got {found}")]
+    CodecMismatch {
+        /// The format id of the reader's codec.
+        expected: u16,
+        /// The format id recorded in the preamble.
+        found: u16,
+    },
+    /// The data was written with a later version of the message group.
+    #[error("Unsupported group version {found}: reader supports up to {supported}")]
+    UnsupportedGroupVersion {
+        /// The group version of the reader.
+        supported: u16,
+        /// The group version recorded in the preamble.
+        found: u16,
+    },
+}
+
+/// The contents of a preamble.
+///
+/// A preamble can be built for writing with [`Preamble::new`], and the
+/// `with_*` methods for each optional field:
+///
+/// ```
+/// # use aversion::util::cbor::CborCodec;
+/// # use aversion::util::preamble::{self, Preamble};
+/// # use aversion::util::WideHeader;
+/// let mut buf = Vec::new();
+/// Preamble::new::<CborCodec>()
+///     .with_header_format::<WideHeader>()
+///     .with_group_version(3)
+///     .write(&mut buf)
+///     .unwrap();
+///
+/// let found = preamble::read_preamble::<CborCodec>(&mut &buf[..]).unwrap();
+/// assert_eq!(found.header_format, aversion::util::header_format::WIDE);
+/// assert_eq!(found.group_version, 3);
+/// assert_eq!(buf.len(), found.size());
+/// ```
+///
+/// More fields may be added in later versions of the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Preamble {
+    /// The preamble version.
+    pub version: u16,
+    /// The format id of the codec used for message bodies.
+    pub format_id: u16,
+    /// The optional fields that are present; see [`flags`].
+    ///
+    /// For a preamble older than version 5, this is set from the fields
+    /// that version records.
+    pub flags: u16,
+    /// The header format of the message headers.
+    ///
+    /// This is [`DEFAULT_HEADER_FORMAT`] if the preamble doesn't record
+    /// one.
+    pub header_format: u16,
+    /// The version of the message group.
+    ///
+    /// This is 0 if the preamble doesn't record one.
+    pub group_version: u16,
+}
+
+impl Preamble {
+    /// Create a version 5 preamble for codec `C`, with no optional fields.
+    pub fn new<C>() -> Self
+    where
+        C: Codec,
+    {
+        Preamble {
+            version: PREAMBLE_VERSION_5,
+            format_id: C::FORMAT_ID,
+            flags: 0,
+            header_format: DEFAULT_HEADER_FORMAT,
+            group_version: 0,
+        }
+    }
+
+    /// Record [`H::HEADER_FORMAT`][FramedHeader::HEADER_FORMAT], so that
+    /// a reader can pass it to
+    /// [`CborData::header_format`][crate::util::cbor::CborData::header_format].
+    pub fn with_header_format<H>(mut self) -> Self
+    where
+        H: FramedHeader,
+    {
+        self.flags |= flags::HEADER_FORMAT;
+        self.header_format = H::HEADER_FORMAT;
+        self
+    }
+
+    /// Record the version of a message group.
+    ///
+    /// `group_version` is usually the
+    /// [`GROUP_VERSION`][GroupDeserialize::GROUP_VERSION] of the group
+    /// being written. A reader can check it with [`check_group`].
+    ///
+    /// [`check_group`]: Self::check_group
+    pub fn with_group_version(mut self, group_version: u16) -> Self {
+        self.flags |= flags::GROUP_VERSION;
+        self.group_version = group_version;
+        self
+    }
+
+    /// The size of the preamble when serialized, in bytes.
+    ///
+    /// This doesn't include a schema manifest that follows it.
+    pub fn size(&self) -> usize {
+        match self.version {
+            PREAMBLE_VERSION => SIZE,
+            PREAMBLE_VERSION_2 | PREAMBLE_VERSION_3 => SIZE_V2,
+            PREAMBLE_VERSION_4 => SIZE_V4,
+            _ => SIZE_V5 + 2 * (self.flags & !flags::SCHEMA_MANIFEST).count_ones() as usize,
+        }
+    }
+
+    /// Write the preamble, as version 5.
+    ///
+    /// This clears [`flags::SCHEMA_MANIFEST`]; use
+    /// [`write_with_manifest`][Self::write_with_manifest] to write a
+    /// manifest as well.
+    pub fn write(&self, w: &mut impl Write) -> Result<(), io::Error> {
+        self.write_flags(w, self.flags & !flags::SCHEMA_MANIFEST)
+    }
+
+    /// Write the preamble, as version 5, followed by a schema manifest.
+    pub fn write_with_manifest(
+        &self,
+        w: &mut impl Write,
+        manifest: &SchemaManifest,
+    ) -> Result<(), io::Error> {
+        let manifest = manifest.serialize()?;
+        self.write_flags(w, self.flags | flags::SCHEMA_MANIFEST)?;
+        w.write_all(&manifest)
+    }
+
+    fn write_flags(&self, w: &mut impl Write, bits: u16) -> Result<(), io::Error> {
+        w.write_all(&MAGIC)?;
+        w.write_u16::<BigEndian>(PREAMBLE_VERSION_5)?;
+        w.write_u16::<BigEndian>(self.format_id)?;
+        w.write_u16::<BigEndian>(bits)?;
+        if bits & flags::HEADER_FORMAT != 0 {
+            w.write_u16::<BigEndian>(self.header_format)?;
+        }
+        if bits & flags::GROUP_VERSION != 0 {
+            w.write_u16::<BigEndian>(self.group_version)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if a [`SchemaManifest`] follows the preamble.
+    pub fn has_schema_manifest(&self) -> bool {
+        self.flags & flags::SCHEMA_MANIFEST != 0
+    }
+
+    /// Check that the data can be read as group `G`.
+    ///
+    /// Returns [`PreambleError::UnsupportedGroupVersion`] if the data was
+    /// written with a later [`GROUP_VERSION`] than `G`'s. A preamble that
+    /// doesn't record a group version is always accepted.
+    ///
+    /// [`GROUP_VERSION`]: GroupDeserialize::GROUP_VERSION
+    pub fn check_group<G>(&self) -> Result<(), PreambleError>
+    where
+        G: GroupDeserialize,
+    {
+        if self.group_version > G::GROUP_VERSION {
+            return Err(PreambleError::UnsupportedGroupVersion {
+                supported: G::GROUP_VERSION,
+                found: self.group_version,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The description of one message version, in a [`SchemaManifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaEntry {
+    /// The message id.
+    ///
+    /// This is the full id, [`MessageId::WIDE_MSG_ID`].
+    pub msg_id: u32,
+    /// The message version.
+    pub msg_ver: u16,
+    /// The name of the message type, e.g. `FooV1`.
+    pub name: String,
+    /// The field names and types; see [`Versioned::SCHEMA`].
+    pub schema: String,
+}
+
+/// A description of the messages in a file.
+///
+/// This can be written after the preamble, so that future tools can
+/// describe the messages in a file without the original Rust types.
+///
+/// ```
+/// # use aversion::util::cbor::CborCodec;
+/// # use aversion::util::preamble::{self, SchemaManifest};
+/// # use aversion::util::BasicHeader;
+/// # use aversion::{assign_message_ids, Versioned};
+/// #[derive(Versioned)]
+/// struct FooV1 {
+///     foo: u32,
+/// }
+/// # type Foo = FooV1;
+/// # assign_message_ids! { Foo: 7 }
+///
+/// let manifest = SchemaManifest::new().with::<FooV1>();
+/// let mut buf = Vec::new();
+/// preamble::write_preamble_with_manifest::<CborCodec, BasicHeader>(&mut buf, &manifest)
+///     .unwrap();
+///
+/// let mut r = &buf[..];
+/// let preamble = preamble::read_preamble::<CborCodec>(&mut r).unwrap();
+/// assert!(preamble.has_schema_manifest());
+/// let manifest = preamble::read_schema_manifest(&mut r).unwrap();
+/// assert_eq!(manifest.entries[0].name, "FooV1");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaManifest {
+    /// The message descriptions.
+    pub entries: Vec<SchemaEntry>,
+}
+
+impl SchemaManifest {
+    /// Create an empty `SchemaManifest`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a description of the message type `T`.
+    ///
+    /// The name is taken from [`std::any::type_name`], without the module
+    /// path. It's only meant for people to read.
+    pub fn with<T>(mut self) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        let name = std::any::type_name::<T>();
+        let name = name.rsplit("::").next().unwrap_or(name);
+        self.entries.push(SchemaEntry {
+            msg_id: T::Base::WIDE_MSG_ID,
+            msg_ver: T::VER,
+            name: name.to_owned(),
+            schema: T::SCHEMA.to_owned(),
+        });
+        self
+    }
+
+    /// Look up the description of a message version.
+    pub fn find(&self, msg_id: u32, msg_ver: u16) -> Option<&SchemaEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.msg_id == msg_id && entry.msg_ver == msg_ver)
+    }
+
+    /// Serialize the manifest, including its length prefix.
+    fn serialize(&self) -> Result<Vec<u8>, io::Error> {
+        fn too_long(what: &str) -> io::Error {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("schema manifest {} is too long", what),
+            )
+        }
+
+        let mut body = Vec::new();
+        body.write_u16::<BigEndian>(MANIFEST_VERSION)?;
+        let count = u16::try_from(self.entries.len()).map_err(|_| too_long("entry list"))?;
+        body.write_u16::<BigEndian>(count)?;
+        for entry in &self.entries {
+            body.write_u32::<BigEndian>(entry.msg_id)?;
+            body.write_u16::<BigEndian>(entry.msg_ver)?;
+            let name_len = u16::try_from(entry.name.len()).map_err(|_| too_long("name"))?;
+            body.write_u16::<BigEndian>(name_len)?;
+            body.write_all(entry.name.as_bytes())?;
+            let schema_len = u32::try_from(entry.schema.len()).map_err(|_| too_long("schema"))?;
+            body.write_u32::<BigEndian>(schema_len)?;
+            body.write_all(entry.schema.as_bytes())?;
+        }
+        let len = u32::try_from(body.len()).map_err(|_| too_long("body"))?;
+        let mut buf = Vec::with_capacity(body.len() + 4);
+        buf.write_u32::<BigEndian>(len)?;
+        buf.extend_from_slice(&body);
+        Ok(buf)
+    }
+}
+
+/// Read a UTF-8 string of `len` bytes from the body of a manifest.
+///
+/// A length that runs past the end of the manifest is rejected before
+/// anything is read.
+fn read_string<R: Read>(body: &mut io::Take<R>, len: u32) -> Result<String, io::Error> {
+    if u64::from(len) > body.limit() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "schema manifest string is longer than the manifest",
+        ));
+    }
+    let mut buf = Vec::new();
+    read_body(body, len, &mut buf)?;
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write a preamble for codec `C`.
+///
+/// This writes a version 1 preamble, with no optional fields.
+pub fn write_preamble<C>(w: &mut impl Write) -> Result<(), io::Error>
+where
+    C: Codec,
+{
+    w.write_all(&MAGIC)?;
+    w.write_u16::<BigEndian>(PREAMBLE_VERSION)?;
+    w.write_u16::<BigEndian>(C::FORMAT_ID)?;
+    Ok(())
+}
+
+/// Write a preamble for codec `C` and header type `H`.
+///
+/// This records [`H::HEADER_FORMAT`][FramedHeader::HEADER_FORMAT]; see
+/// [`Preamble::with_header_format`].
+pub fn write_preamble_with_header<C, H>(w: &mut impl Write) -> Result<(), io::Error>
+where
+    C: Codec,
+    H: FramedHeader,
+{
+    Preamble::new::<C>().with_header_format::<H>().write(w)
+}
+
+/// Write a preamble for codec `C` and header type `H`, recording
+/// the version of a message group.
+///
+/// `group_version` is usually the
+/// [`GROUP_VERSION`][GroupDeserialize::GROUP_VERSION] of the group being
+/// written. A reader can check it with [`Preamble::check_group`].
+///
+/// ```
+/// # use aversion::util::cbor::CborCodec;
+/// # use aversion::util::preamble::{self, PreambleError};
+/// # use aversion::util::BasicHeader;
+/// # use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize, Versioned, UpgradeLatest)]
+/// # struct FooV1 { foo: u32 }
+/// # type Foo = FooV1;
+/// # assign_message_ids! { Foo: 1 }
+/// #[derive(GroupDeserialize)]
+/// #[group(version = 2)]
+/// enum NewGroup {
+///     Foo(Foo),
+/// }
+///
+/// #[derive(GroupDeserialize)]
+/// #[group(version = 1)]
+/// enum OldGroup {
+///     Foo(Foo),
+/// }
+///
+/// let mut buf = Vec::new();
+/// preamble::write_preamble_with_group::<CborCodec, BasicHeader>(
+///     &mut buf,
+///     NewGroup::GROUP_VERSION,
+/// )
+/// .unwrap();
+///
+/// let found = preamble::read_preamble::<CborCodec>(&mut &buf[..]).unwrap();
+/// assert_eq!(found.group_version, 2);
+/// assert!(found.check_group::<NewGroup>().is_ok());
+/// assert!(matches!(
+///     found.check_group::<OldGroup>(),
+///     Err(PreambleError::UnsupportedGroupVersion { supported: 1, found: 2 })
+/// ));
+/// ```
+pub fn write_preamble_with_group<C, H>(
+    w: &mut impl Write,
+    group_version: u16,
+) -> Result<(), io::Error>
+where
+    C: Codec,
+    H: FramedHeader,
+{
+    Preamble::new::<C>()
+        .with_header_format::<H>()
+        .with_group_version(group_version)
+        .write(w)
+}
+
+/// Write a preamble for codec `C` and header type `H`, followed
+/// by a schema manifest.
+pub fn write_preamble_with_manifest<C, H>(
+    w: &mut impl Write,
+    manifest: &SchemaManifest,
+) -> Result<(), io::Error>
+where
+    C: Codec,
+    H: FramedHeader,
+{
+    Preamble::new::<C>()
+        .with_header_format::<H>()
+        .write_with_manifest(w, manifest)
+}
+
+/// Read the schema manifest that follows a preamble.
+///
+/// This should be called right after [`read_preamble`] (or
+/// [`read_preamble_any`]), if [`Preamble::has_schema_manifest`] is true.
+pub fn read_schema_manifest(r: &mut impl Read) -> Result<SchemaManifest, PreambleError> {
+    let len = r.read_u32::<BigEndian>()?;
+    let mut body = r.take(len.into());
+    let version = body.read_u16::<BigEndian>()?;
+    if version != MANIFEST_VERSION && version != MANIFEST_VERSION_1 {
+        return Err(PreambleError::UnsupportedManifestVersion(version));
+    }
+    let count = body.read_u16::<BigEndian>()?;
+    let mut entries = Vec::with_capacity(count.into());
+    for _ in 0..count {
+        let msg_id = match version {
+            MANIFEST_VERSION_1 => body.read_u16::<BigEndian>()?.into(),
+            _ => body.read_u32::<BigEndian>()?,
+        };
+        let msg_ver = body.read_u16::<BigEndian>()?;
+        let name_len = body.read_u16::<BigEndian>()?;
+        let name = read_string(&mut body, name_len.into())?;
+        let schema_len = body.read_u32::<BigEndian>()?;
+        let schema = read_string(&mut body, schema_len)?;
+        entries.push(SchemaEntry {
+            msg_id,
+            msg_ver,
+            name,
+            schema,
+        });
+    }
+    // Leave the reader positioned after the manifest.
+    io::copy(&mut body, &mut io::sink())?;
+    Ok(SchemaManifest { entries })
+}
+
+/// Skip over the schema manifest that follows a preamble.
+pub fn skip_schema_manifest(r: &mut impl Read) -> Result<(), PreambleError> {
+    let len = r.read_u32::<BigEndian>()?;
+    let skipped = io::copy(&mut r.take(len.into()), &mut io::sink())?;
+    if skipped < u64::from(len) {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+/// Read a preamble, without checking the codec.
+///
+/// This is useful for choosing a codec based on the preamble's
+/// `format_id`. Use [`read_preamble`] if the codec is already known.
+pub fn read_preamble_any(r: &mut impl Read) -> Result<Preamble, PreambleError> {
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(PreambleError::BadMagic);
+    }
+    let version = r.read_u16::<BigEndian>()?;
+    let format_id = r.read_u16::<BigEndian>()?;
+    let flags = match version {
+        PREAMBLE_VERSION => 0,
+        PREAMBLE_VERSION_2 => flags::HEADER_FORMAT,
+        PREAMBLE_VERSION_3 => flags::HEADER_FORMAT | flags::SCHEMA_MANIFEST,
+        PREAMBLE_VERSION_4 => flags::HEADER_FORMAT | flags::GROUP_VERSION,
+        PREAMBLE_VERSION_5 => {
+            let bits = r.read_u16::<BigEndian>()?;
+            if bits & !flags::ALL != 0 {
+                return Err(PreambleError::UnsupportedFlags(bits & !flags::ALL));
+            }
+            bits
+        }
+        _ => return Err(PreambleError::UnsupportedVersion(version)),
+    };
+    let header_format = if flags & flags::HEADER_FORMAT != 0 {
+        r.read_u16::<BigEndian>()?
+    } else {
+        DEFAULT_HEADER_FORMAT
+    };
+    let group_version = if flags & flags::GROUP_VERSION != 0 {
+        r.read_u16::<BigEndian>()?
+    } else {
+        0
+    };
+    Ok(Preamble {
+        version,
+        format_id,
+        flags,
+        header_format,
+        group_version,
+    })
+}
+
+/// Read a preamble, and check that it was written for codec `C`.
+///
+/// Returns [`PreambleError::CodecMismatch`] if the data was written with
+/// a different codec.
+pub fn read_preamble<C>(r: &mut impl Read) -> Result<Preamble, PreambleError>
+where
+    C: Codec,
+{
+    let preamble = read_preamble_any(r)?;
+    if preamble.format_id != C::FORMAT_ID {
+        return Err(PreambleError::CodecMismatch {
+            expected: C::FORMAT_ID,
+            found: preamble.format_id,
+        });
+    }
+    Ok(preamble)
+}
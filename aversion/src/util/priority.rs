@@ -0,0 +1,101 @@
+//! Provides a `DataSink` that writes urgent messages first.
+
+use crate::group::{DataSink, GroupDeserialize};
+use crate::util::cbor::{CborData, CborDataError};
+use crate::util::{BasicHeader, FramedHeader};
+use crate::{MessageId, Versioned};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::io::Write;
+use std::marker::PhantomData;
+
+/// A [`DataSink`] that buffers messages, and writes them in priority order.
+///
+/// Each message is encoded as soon as it's written, using the same format
+/// as [`CborData`], but nothing reaches the inner writer until
+/// [`flush`][DataSink::flush] is called. Then the buffered messages are
+/// written with the highest [`Priority`] first. Messages with the same
+/// priority are written in the order they were buffered (FIFO).
+///
+/// Priorities are looked up by message id in the group `G`; see
+/// [`GroupDeserialize::priority`]. Messages that aren't part of `G` have
+/// priority 0.
+///
+/// Messages are never reordered across a flush, so each flush is a
+/// boundary: a low priority message that was flushed is always written
+/// before a high priority message that is buffered afterwards. Messages
+/// that haven't been flushed are lost when the sink is dropped.
+///
+/// [`Priority`]: crate::group::Priority
+pub struct PriorityQueueSink<W, G, H = BasicHeader> {
+    inner: W,
+    /// Encoded messages (header and body), with their priority.
+    queue: Vec<(u8, Vec<u8>)>,
+    _group: PhantomData<fn() -> (G, H)>,
+}
+
+impl<W, G> PriorityQueueSink<W, G> {
+    /// Create a new `PriorityQueueSink`.
+    pub fn new(inner: W) -> Self {
+        Self::with_header(inner)
+    }
+}
+
+impl<W, G, H> PriorityQueueSink<W, G, H> {
+    /// Create a new `PriorityQueueSink` that uses a specific header type.
+    pub fn with_header(inner: W) -> Self {
+        PriorityQueueSink {
+            inner,
+            queue: Vec::new(),
+            _group: PhantomData,
+        }
+    }
+
+    /// The number of messages waiting to be flushed.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consume the `PriorityQueueSink`, returning the inner writer.
+    ///
+    /// Messages that haven't been flushed are lost.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, G, H> DataSink for PriorityQueueSink<W, G, H>
+where
+    W: Write,
+    G: GroupDeserialize,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let mut frame = CborData::<_, H>::with_header(Vec::new());
+        frame.write_message(msg)?;
+        let priority = G::priority(T::Base::WIDE_MSG_ID);
+        self.queue.push((priority, frame.into_inner()));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        // A stable sort keeps messages of equal priority in FIFO order.
+        self.queue.sort_by_key(|(priority, _)| Reverse(*priority));
+        for (_, frame) in self.queue.drain(..) {
+            self.inner.write_all(&frame)?;
+        }
+        self.inner.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,471 @@
+//! Provides the `Protocol` trait, which bundles a header, codec and error type.
+//!
+//! An application usually uses the same header and codec everywhere. A
+//! [`Protocol`] names that choice once, and [`ReadSource`] and
+//! [`WriteSink`] use it to read and write messages, so both ends of a
+//! connection stay consistent.
+//!
+//! ```
+//! use aversion::group::{DataSink, DataSourceExt};
+//! use aversion::util::cbor::{CborCodec, CborDataError};
+//! use aversion::util::protocol::{Protocol, ReadSource, WriteSink};
+//! use aversion::util::SemverHeader;
+//! use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! use serde::{Deserialize, Serialize};
+//!
+//! /// The protocol used by this application.
+//! struct MyProtocol;
+//!
+//! impl Protocol for MyProtocol {
+//!     type Header = SemverHeader;
+//!     type Codec = CborCodec;
+//!     type Error = CborDataError;
+//! }
+//!
+//! type MySource<R> = ReadSource<R, MyProtocol>;
+//! type MySink<W> = WriteSink<W, MyProtocol>;
+//!
+//! #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! struct PingV1 {
+//!     seq: u32,
+//! }
+//! type Ping = PingV1;
+//!
+//! assign_message_ids! {
+//!     Ping: 1,
+//! }
+//!
+//! let mut sink = MySink::new(Vec::new());
+//! sink.write_message(&Ping { seq: 1 }).unwrap();
+//!
+//! let buf = sink.into_inner();
+//! let mut src = MySource::new(&buf[..]);
+//! let ping: Ping = src.expect_message().unwrap();
+//! assert_eq!(ping, Ping { seq: 1 });
+//! ```
+//!
+//! # Sharing a protocol
+//!
+//! The simplest way to keep a client and server consistent is to define
+//! the `Protocol` type once, in a crate or module that both of them use,
+//! and to name it (e.g. through type aliases like `MySource` and `MySink`
+//! above) wherever data is read or written. Then a mismatch can't compile.
+//!
+//! When the two ends are built separately, the data can carry the
+//! protocol's identity instead: [`protocol_id`] combines the
+//! codec's [`FORMAT_ID`][Codec::FORMAT_ID] and the header's
+//! [`HEADER_FORMAT`][FramedHeader::HEADER_FORMAT]. [`to_writer_with`]
+//! writes it in a [preamble](crate::util::preamble) before the message,
+//! and [`from_reader_with`] checks it, returning
+//! [`ProtocolError::protocol_mismatch`] if the reader's protocol is
+//! different. For a stream of messages, call [`write_protocol_id`] and
+//! [`check_protocol_id`] before creating the [`WriteSink`] and
+//! [`ReadSource`].
+//!
+//! [`to_writer_with`]: crate::group::to_writer_with
+//! [`from_reader_with`]: crate::group::from_reader_with
+
+use crate::group::{DataSink, DataSource, DEFAULT_MAX_UPGRADE_STEPS};
+use crate::util::preamble::{
+    read_preamble_any, write_preamble_with_header, Preamble, PreambleError,
+};
+use crate::util::{read_body, try_read_first_byte, Codec, FramedHeader, PeekedId};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::type_name;
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+/// The header, codec and error types used by an application.
+///
+/// This is a marker trait; it's usually implemented on an empty struct.
+/// See the [module documentation](self) for an example.
+pub trait Protocol {
+    /// The header written before each message.
+    type Header: FramedHeader;
+    /// The codec used for message bodies.
+    type Codec: Codec + Default;
+    /// The error returned by [`ReadSource`] and [`WriteSink`].
+    type Error: ProtocolError + From<<Self::Codec as Codec>::Error>;
+}
+
+/// Identifies the codec and header format of the protocol `P`.
+///
+/// Two protocols with the same `ProtocolId` read and write the same
+/// bytes. Every header in this crate has its own
+/// [`HEADER_FORMAT`][FramedHeader::HEADER_FORMAT], so protocols that only
+/// differ by header type have different ids.
+pub const fn protocol_id<P>() -> ProtocolId
+where
+    P: Protocol,
+{
+    ProtocolId {
+        format_id: <P::Codec as Codec>::FORMAT_ID,
+        header_format: <P::Header as FramedHeader>::HEADER_FORMAT,
+    }
+}
+
+/// The codec and header format used by a [`Protocol`].
+///
+/// See [`protocol_id`], and the
+/// [module documentation](self#sharing-a-protocol).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProtocolId {
+    /// The [`FORMAT_ID`][Codec::FORMAT_ID] of the codec.
+    pub format_id: u16,
+    /// The [`HEADER_FORMAT`][FramedHeader::HEADER_FORMAT] of the header.
+    pub header_format: u16,
+}
+
+impl ProtocolId {
+    /// The `ProtocolId` of the protocol `P`.
+    pub fn of<P: Protocol>() -> Self {
+        protocol_id::<P>()
+    }
+}
+
+impl fmt::Display for ProtocolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "codec format {}, header format {}",
+            self.format_id, self.header_format
+        )
+    }
+}
+
+impl From<Preamble> for ProtocolId {
+    fn from(preamble: Preamble) -> Self {
+        ProtocolId {
+            format_id: preamble.format_id,
+            header_format: preamble.header_format,
+        }
+    }
+}
+
+/// Write a [preamble](crate::util::preamble) that records
+/// [`protocol_id::<P>()`][protocol_id].
+pub fn write_protocol_id<P>(w: &mut impl Write) -> Result<(), io::Error>
+where
+    P: Protocol,
+{
+    write_preamble_with_header::<P::Codec, P::Header>(w)
+}
+
+/// Read a preamble, and check that it matches
+/// [`protocol_id::<P>()`][protocol_id].
+///
+/// If the data was written with a different protocol,
+/// [`ProtocolError::protocol_mismatch`] is returned. A missing or
+/// unsupported preamble is returned as an [`io::Error`] with kind
+/// [`InvalidData`][io::ErrorKind::InvalidData].
+pub fn check_protocol_id<P>(r: &mut impl Read) -> Result<(), P::Error>
+where
+    P: Protocol,
+{
+    let preamble = read_preamble_any(r).map_err(|e| match e {
+        PreambleError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    })?;
+    let expected = protocol_id::<P>();
+    let found = ProtocolId::from(preamble);
+    if found != expected {
+        return Err(P::Error::protocol_mismatch(expected, found));
+    }
+    Ok(())
+}
+
+/// Errors that a [`Protocol`] error type must be able to represent.
+///
+/// These are used to implement the [`DataSource`] error hooks for
+/// [`ReadSource`].
+pub trait ProtocolError: From<io::Error> {
+    /// An unknown message id was received.
+    fn unknown_message(msg_id: u16) -> Self;
+
+    /// An unknown message id was received, in a group with 32-bit ids.
+    ///
+    /// The default implementation calls [`unknown_message`][Self::unknown_message]
+    /// with the low 16 bits of the id.
+    #[allow(clippy::cast_possible_truncation)]
+    fn unknown_wide_message(msg_id: u32) -> Self {
+        Self::unknown_message(msg_id as u16)
+    }
+
+    /// A reserved or deprecated message id was received.
+    ///
+    /// The default implementation calls [`unknown_message`][Self::unknown_message].
+    fn deprecated_message(msg_id: u16, note: &'static str) -> Self {
+        let _ = note;
+        Self::unknown_message(msg_id)
+    }
+
+    /// A reserved or deprecated message id was received, in a group with
+    /// 32-bit ids.
+    ///
+    /// The default implementation calls [`deprecated_message`][Self::deprecated_message]
+    /// with the low 16 bits of the id.
+    #[allow(clippy::cast_possible_truncation)]
+    fn deprecated_wide_message(msg_id: u32, note: &'static str) -> Self {
+        Self::deprecated_message(msg_id as u16, note)
+    }
+
+    /// An unknown version of the message type `expected` was received.
+    fn unknown_version(expected: &'static str, got: u16, latest: u16) -> Self;
+
+    /// Upgrading version `got` of the message type `expected` to version
+    /// `latest` would take more than `max` steps.
+    ///
+    /// The default implementation calls [`unknown_version`][Self::unknown_version].
+    fn upgrade_too_deep(expected: &'static str, got: u16, latest: u16, max: u16) -> Self {
+        let _ = max;
+        Self::unknown_version(expected, got, latest)
+    }
+
+    /// A different message id was received than the one that was expected.
+    fn unexpected_message(expected: &'static str, expected_id: u16, got: u16) -> Self;
+
+    /// The data source doesn't support `operation`.
+    ///
+    /// The default implementation returns an [`io::Error`] with kind
+    /// [`Unsupported`][io::ErrorKind::Unsupported].
+    fn unsupported_operation(operation: &'static str) -> Self {
+        Self::from(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} is not supported", operation),
+        ))
+    }
+
+    /// The data was written with a different protocol.
+    ///
+    /// The default implementation returns an [`io::Error`] with kind
+    /// [`InvalidData`][io::ErrorKind::InvalidData].
+    fn protocol_mismatch(expected: ProtocolId, found: ProtocolId) -> Self {
+        Self::from(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("protocol mismatch: expected {}, found {}", expected, found),
+        ))
+    }
+}
+
+/// A [`DataSource`] that reads messages using a [`Protocol`].
+pub struct ReadSource<R, P: Protocol> {
+    inner: R,
+    codec: P::Codec,
+    buf: Vec<u8>,
+    max_upgrade_steps: u16,
+    peeked: PeekedId,
+}
+
+impl<R, P: Protocol> ReadSource<R, P> {
+    /// Create a new `ReadSource`.
+    pub fn new(inner: R) -> Self {
+        ReadSource {
+            inner,
+            codec: P::Codec::default(),
+            buf: Vec::new(),
+            max_upgrade_steps: DEFAULT_MAX_UPGRADE_STEPS,
+            peeked: PeekedId::default(),
+        }
+    }
+
+    /// Limit the number of upgrade steps when reading a message.
+    ///
+    /// See [`DataSource::max_upgrade_steps`]. The default is
+    /// [`DEFAULT_MAX_UPGRADE_STEPS`].
+    pub fn upgrade_limit(mut self, max_steps: u16) -> Self {
+        self.max_upgrade_steps = max_steps;
+        self
+    }
+
+    /// Get a reference to the inner data type.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner data type.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the `ReadSource`, returning the inner data type.
+    ///
+    /// If a message id was peeked with
+    /// [`peek_msg_id`][DataSource::peek_msg_id], those bytes have already
+    /// been read from the inner data type, and are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, P> DataSource for ReadSource<R, P>
+where
+    R: Read,
+    P: Protocol,
+{
+    type Error = P::Error;
+    type Header = P::Header;
+
+    fn read_header(&mut self) -> Result<P::Header, P::Error> {
+        let peeked = self.peeked.take();
+        let mut reader = peeked.as_slice().chain(&mut self.inner);
+        Ok(P::Header::deserialize_from(&mut reader)?)
+    }
+
+    fn read_header_into(&mut self, header: &mut P::Header) -> Result<(), P::Error> {
+        let peeked = self.peeked.take();
+        let mut reader = peeked.as_slice().chain(&mut self.inner);
+        Ok(header.deserialize_into(&mut reader)?)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<P::Header>, P::Error> {
+        let peeked = self.peeked.take();
+        if !peeked.is_empty() {
+            let mut reader = peeked.as_slice().chain(&mut self.inner);
+            return Ok(Some(P::Header::deserialize_from(&mut reader)?));
+        }
+        let first = match try_read_first_byte(&mut self.inner)? {
+            Some(first) => [first],
+            None => return Ok(None),
+        };
+        let mut reader = (&first[..]).chain(&mut self.inner);
+        Ok(Some(P::Header::deserialize_from(&mut reader)?))
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, P::Error> {
+        Ok(self.peeked.peek::<P::Header>(&mut self.inner)?)
+    }
+
+    fn read_message<T>(&mut self, header: &P::Header) -> Result<T, P::Error>
+    where
+        T: DeserializeOwned,
+    {
+        read_body(&mut self.inner, header.msg_len(), &mut self.buf)?;
+        Ok(self.codec.decode(&self.buf)?)
+    }
+
+    fn read_raw(&mut self, header: &P::Header) -> Result<Vec<u8>, P::Error> {
+        let mut body = Vec::new();
+        read_body(&mut self.inner, header.msg_len(), &mut body)?;
+        Ok(body)
+    }
+
+    fn skip_message(&mut self, header: &P::Header) -> Result<(), P::Error> {
+        let msg_len = u64::from(header.msg_len());
+        let mut subreader = (&mut self.inner).take(msg_len);
+        let skipped = io::copy(&mut subreader, &mut io::sink())?;
+        if skipped < msg_len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(())
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> P::Error {
+        P::Error::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> P::Error {
+        P::Error::unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> P::Error {
+        P::Error::unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> P::Error {
+        P::Error::deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> P::Error {
+        P::Error::deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> P::Error
+    where
+        T: Versioned,
+    {
+        P::Error::unknown_version(type_name::<T>(), ver, T::VER)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.max_upgrade_steps
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> P::Error
+    where
+        T: Versioned,
+    {
+        P::Error::upgrade_too_deep(type_name::<T>(), ver, T::VER, self.max_upgrade_steps)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> P::Error
+    where
+        T: MessageId,
+    {
+        P::Error::unexpected_message(type_name::<T>(), T::MSG_ID, msg_id)
+    }
+}
+
+/// A [`DataSink`] that writes messages using a [`Protocol`].
+pub struct WriteSink<W, P: Protocol> {
+    inner: W,
+    codec: P::Codec,
+    buf: Vec<u8>,
+    _protocol: PhantomData<fn() -> P>,
+}
+
+impl<W, P: Protocol> WriteSink<W, P> {
+    /// Create a new `WriteSink`.
+    pub fn new(inner: W) -> Self {
+        WriteSink {
+            inner,
+            codec: P::Codec::default(),
+            buf: Vec::new(),
+            _protocol: PhantomData,
+        }
+    }
+
+    /// Get a reference to the inner data type.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner data type.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Consume the `WriteSink`, returning the inner data type.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, P> DataSink for WriteSink<W, P>
+where
+    W: Write,
+    P: Protocol,
+{
+    type Error = P::Error;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), P::Error>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        self.buf.clear();
+        self.codec.encode(msg, &mut self.buf)?;
+        let msg_len: u32 = self.buf.len().try_into().expect("usize to u32");
+        let header = P::Header::for_msg(msg, msg_len);
+        header.serialize_into(&mut self.inner)?;
+        self.inner.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), P::Error> {
+        Ok(self.inner.flush()?)
+    }
+}
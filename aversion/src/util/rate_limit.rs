@@ -0,0 +1,405 @@
+//! Provides a `DataSource` that limits how fast messages can be read.
+//!
+//! [`RateLimitedSource`] keeps one connection from monopolizing a shared
+//! decoder, by capping the number of messages and/or message bytes that
+//! can be read in each time window.
+//!
+//! # Sync and async use
+//!
+//! A `DataSource` can't block without stalling the whole thread, so when
+//! the limit is reached, [`RateLimitError::RateLimited`] is returned
+//! instead. It says how long to wait before a retry can succeed. Nothing
+//! is lost: the next read picks up where the rejected one stopped.
+//!
+//! A synchronous caller can sleep for that long and retry. In async code,
+//! await your runtime's timer instead (e.g. `tokio::time::sleep`), so that
+//! other tasks keep running while this one waits:
+//!
+//! ```
+//! # use aversion::group::DataSource;
+//! # use aversion::util::rate_limit::{RateLimitError, RateLimitedSource};
+//! # use aversion::util::FramedHeader;
+//! # use std::time::Duration;
+//! fn next_header<Src>(
+//!     src: &mut RateLimitedSource<Src>,
+//! ) -> Result<Src::Header, Src::Error>
+//! where
+//!     Src: DataSource,
+//!     Src::Header: FramedHeader,
+//! {
+//!     loop {
+//!         match src.read_header() {
+//!             Ok(header) => return Ok(header),
+//!             Err(RateLimitError::RateLimited { retry_after }) => {
+//!                 // Or, in async code: `sleep(..).await`.
+//!                 std::thread::sleep(Duration::from_secs(retry_after));
+//!             }
+//!             Err(RateLimitError::Source(e)) => return Err(e),
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::group::{DataSource, GroupHeader};
+use crate::util::clock::{Clock, SystemClock};
+use crate::util::FramedHeader;
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use std::convert::TryFrom;
+use thiserror::Error;
+
+/// An error returned by [`RateLimitedSource`].
+#[derive(Debug, Error)]
+pub enum RateLimitError<E> {
+    /// The inner `DataSource` returned an error.
+    #[error("failed to read a message")]
+    Source(#[source] E),
+    /// The rate limit was reached.
+    ///
+    /// No data was consumed; the read can be retried later.
+    #[error("rate limit reached, retry after {retry_after}")]
+    RateLimited {
+        /// How long to wait before retrying, in clock units.
+        retry_after: u64,
+    },
+}
+
+/// A token bucket that refills continuously.
+///
+/// To avoid rounding, credit is counted in token-ticks: each clock tick
+/// adds `rate` credit, and each token costs `window` credit.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    /// The number of tokens per window.
+    rate: u64,
+    credit: u128,
+}
+
+impl Bucket {
+    fn full(rate: u64, window: u64) -> Self {
+        Bucket {
+            rate,
+            credit: u128::from(rate) * u128::from(window),
+        }
+    }
+
+    fn refill(&mut self, elapsed: u64, window: u64) {
+        let max = u128::from(self.rate) * u128::from(window);
+        let added = u128::from(elapsed) * u128::from(self.rate);
+        self.credit = max.min(self.credit.saturating_add(added));
+    }
+
+    /// The cost of `tokens`, capped at a full bucket so that a message
+    /// that is larger than the limit can still be read.
+    fn cost(&self, tokens: u64, window: u64) -> u128 {
+        u128::from(tokens.min(self.rate)) * u128::from(window)
+    }
+
+    /// The number of clock ticks until `cost` credit is available.
+    fn wait(&self, cost: u128) -> u64 {
+        if cost <= self.credit {
+            return 0;
+        }
+        let rate = u128::from(self.rate);
+        let ticks = (cost - self.credit).div_ceil(rate);
+        u64::try_from(ticks).unwrap_or(u64::MAX)
+    }
+}
+
+/// A [`DataSource`] that limits the rate at which messages are read.
+///
+/// Limits are set with [`max_messages`][Self::max_messages] and
+/// [`max_bytes`][Self::max_bytes], per window of clock time. Each limit is
+/// a token bucket that starts full and refills continuously, so a burst
+/// of up to a full window's worth of messages is allowed, followed by a
+/// steady rate.
+///
+/// A message is charged when its header is read: one message token, and
+/// one byte token for each byte of the body (as reported by
+/// [`FramedHeader::msg_len`]). A message that is larger than the byte
+/// limit can be read once the bucket is full, which empties it.
+///
+/// When a limit is reached, [`RateLimitError::RateLimited`] is returned;
+/// see the [module documentation](self) for how to handle it. If the
+/// header had already been read, it's held, and returned by the next call
+/// to [`read_header`][DataSource::read_header] that is within the limit.
+///
+/// ```
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::util::rate_limit::RateLimitedSource;
+/// # let buf = Vec::<u8>::new();
+/// // Allow 100 messages, or 64 KiB, per second.
+/// let src = CborData::new(&buf[..]);
+/// let src = RateLimitedSource::new(src, 1)
+///     .max_messages(100)
+///     .max_bytes(64 * 1024);
+/// ```
+pub struct RateLimitedSource<Src, C = SystemClock>
+where
+    Src: DataSource,
+{
+    inner: Src,
+    clock: C,
+    window: u64,
+    last_refill: u64,
+    messages: Option<Bucket>,
+    bytes: Option<Bucket>,
+    /// A header that was read, but rejected by the byte limit.
+    pending: Option<Src::Header>,
+}
+
+impl<Src> RateLimitedSource<Src>
+where
+    Src: DataSource,
+{
+    /// Create a new `RateLimitedSource` that uses the [`SystemClock`].
+    ///
+    /// `window` is in seconds. No limits are set until
+    /// [`max_messages`][Self::max_messages] or
+    /// [`max_bytes`][Self::max_bytes] is called.
+    pub fn new(inner: Src, window: u64) -> Self {
+        Self::with_clock(inner, window, SystemClock)
+    }
+}
+
+impl<Src, C> RateLimitedSource<Src, C>
+where
+    Src: DataSource,
+    C: Clock,
+{
+    /// Create a new `RateLimitedSource` that uses a custom [`Clock`].
+    ///
+    /// `window` is in the clock's units.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0.
+    pub fn with_clock(inner: Src, window: u64, clock: C) -> Self {
+        assert!(window > 0, "rate limit window must not be 0");
+        let last_refill = clock.now();
+        RateLimitedSource {
+            inner,
+            clock,
+            window,
+            last_refill,
+            messages: None,
+            bytes: None,
+            pending: None,
+        }
+    }
+
+    /// Allow at most `count` messages per window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0.
+    pub fn max_messages(mut self, count: u64) -> Self {
+        assert!(count > 0, "rate limit must not be 0");
+        self.messages = Some(Bucket::full(count, self.window));
+        self
+    }
+
+    /// Allow at most `count` bytes of message bodies per window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0.
+    pub fn max_bytes(mut self, count: u64) -> Self {
+        assert!(count > 0, "rate limit must not be 0");
+        self.bytes = Some(Bucket::full(count, self.window));
+        self
+    }
+
+    /// Consume the `RateLimitedSource`, returning the inner `DataSource`.
+    ///
+    /// A header that was held because of the byte limit is lost.
+    pub fn into_inner(self) -> Src {
+        self.inner
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_refill);
+        self.last_refill = self.last_refill.max(now);
+        let window = self.window;
+        for bucket in self.messages.iter_mut().chain(self.bytes.iter_mut()) {
+            bucket.refill(elapsed, window);
+        }
+    }
+
+    /// Returns the number of clock ticks until a message is allowed.
+    fn message_wait(&self) -> u64 {
+        match &self.messages {
+            Some(bucket) => bucket.wait(bucket.cost(1, self.window)),
+            None => 0,
+        }
+    }
+
+    /// Returns the number of clock ticks until a body of `len` bytes is
+    /// allowed.
+    fn bytes_wait(&self, len: u64) -> u64 {
+        match &self.bytes {
+            Some(bucket) => bucket.wait(bucket.cost(len, self.window)),
+            None => 0,
+        }
+    }
+
+    fn charge(&mut self, len: u64) {
+        let window = self.window;
+        if let Some(bucket) = &mut self.messages {
+            bucket.credit -= bucket.cost(1, window);
+        }
+        if let Some(bucket) = &mut self.bytes {
+            bucket.credit -= bucket.cost(len, window);
+        }
+    }
+}
+
+impl<Src, C> RateLimitedSource<Src, C>
+where
+    Src: DataSource,
+    Src::Header: FramedHeader,
+    C: Clock,
+{
+    /// Admit a header, or hold it if the byte limit is reached.
+    fn admit(&mut self, header: Src::Header) -> Result<Src::Header, RateLimitError<Src::Error>> {
+        let len = u64::from(header.msg_len());
+        let retry_after = self.message_wait().max(self.bytes_wait(len));
+        if retry_after > 0 {
+            self.pending = Some(header);
+            return Err(RateLimitError::RateLimited { retry_after });
+        }
+        self.charge(len);
+        Ok(header)
+    }
+
+    /// Check the message limit before a header is read from the source.
+    fn check_messages(&mut self) -> Result<(), RateLimitError<Src::Error>> {
+        self.refill();
+        match self.message_wait() {
+            0 => Ok(()),
+            retry_after => Err(RateLimitError::RateLimited { retry_after }),
+        }
+    }
+}
+
+impl<Src, C> DataSource for RateLimitedSource<Src, C>
+where
+    Src: DataSource,
+    Src::Header: FramedHeader,
+    C: Clock,
+{
+    type Error = RateLimitError<Src::Error>;
+    type Header = Src::Header;
+
+    fn read_header(&mut self) -> Result<Src::Header, Self::Error> {
+        self.check_messages()?;
+        let header = match self.pending.take() {
+            Some(header) => header,
+            None => self.inner.read_header().map_err(RateLimitError::Source)?,
+        };
+        self.admit(header)
+    }
+
+    fn read_header_into(&mut self, header: &mut Src::Header) -> Result<(), Self::Error> {
+        // Without a byte limit, a header is never held, so it can be read
+        // in place. Otherwise it may need to be moved into `pending`.
+        if self.bytes.is_some() || self.pending.is_some() {
+            *header = self.read_header()?;
+            return Ok(());
+        }
+        self.check_messages()?;
+        self.inner
+            .read_header_into(header)
+            .map_err(RateLimitError::Source)?;
+        self.charge(0);
+        Ok(())
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<Src::Header>, Self::Error> {
+        self.check_messages()?;
+        let header = match self.pending.take() {
+            Some(header) => header,
+            None => match self
+                .inner
+                .try_read_header()
+                .map_err(RateLimitError::Source)?
+            {
+                Some(header) => header,
+                None => return Ok(None),
+            },
+        };
+        self.admit(header).map(Some)
+    }
+
+    fn peek_msg_id(&mut self) -> Result<u16, Self::Error> {
+        match &self.pending {
+            Some(header) => Ok(header.msg_id()),
+            None => self.inner.peek_msg_id().map_err(RateLimitError::Source),
+        }
+    }
+
+    fn read_message<T>(&mut self, header: &Src::Header) -> Result<T, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.inner
+            .read_message(header)
+            .map_err(RateLimitError::Source)
+    }
+
+    fn skip_message(&mut self, header: &Src::Header) -> Result<(), Self::Error> {
+        self.inner
+            .skip_message(header)
+            .map_err(RateLimitError::Source)
+    }
+
+    fn read_raw(&mut self, header: &Src::Header) -> Result<Vec<u8>, Self::Error> {
+        self.inner.read_raw(header).map_err(RateLimitError::Source)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> Self::Error {
+        RateLimitError::Source(self.inner.unsupported_operation(operation))
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> Self::Error {
+        RateLimitError::Source(self.inner.unknown_message(msg_id))
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> Self::Error {
+        RateLimitError::Source(self.inner.unknown_wide_message(msg_id))
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> Self::Error {
+        RateLimitError::Source(self.inner.deprecated_message(msg_id, note))
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> Self::Error {
+        RateLimitError::Source(self.inner.deprecated_wide_message(msg_id, note))
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> Self::Error
+    where
+        T: Versioned,
+    {
+        RateLimitError::Source(self.inner.unknown_version::<T>(ver))
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> Self::Error
+    where
+        T: Versioned,
+    {
+        RateLimitError::Source(self.inner.upgrade_too_deep::<T>(ver))
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> Self::Error
+    where
+        T: MessageId,
+    {
+        RateLimitError::Source(self.inner.unexpected_message::<T>(msg_id))
+    }
+}
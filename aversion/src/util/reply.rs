@@ -0,0 +1,94 @@
+//! Match replies to requests, using a correlation id.
+//!
+//! When requests and replies share a connection, a reply must say which
+//! request it answers. The requester sets a correlation id in the
+//! [`ExtendedHeader`] of each request; [`write_reply`] copies that id into
+//! the header of the reply, and [`expect_reply_to`] checks it when the
+//! reply is read.
+//!
+//! ```
+//! # use aversion::util::cbor::CborData;
+//! # use aversion::util::reply::{expect_reply_to, write_reply};
+//! # use aversion::util::ExtendedHeader;
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # use std::io::Cursor;
+//! #[derive(Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! struct PingV1 {}
+//! # type Ping = PingV1;
+//!
+//! #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! struct PongV1 {}
+//! # type Pong = PongV1;
+//! # assign_message_ids! { Ping: 1, Pong: 2 }
+//!
+//! // The requester sends a request with a correlation id.
+//! let request = ExtendedHeader::for_msg(&PingV1 {}, 0).with_correlation_id(7);
+//!
+//! // The responder copies the id into the reply.
+//! let mut sink = CborData::<_, ExtendedHeader>::with_header(Vec::new());
+//! write_reply(&mut sink, &request, &PongV1 {}).unwrap();
+//!
+//! // The requester checks the id when reading the reply.
+//! let mut src = CborData::<_, ExtendedHeader>::with_header(Cursor::new(sink.into_inner()));
+//! let pong: Pong = expect_reply_to(&mut src, &request).unwrap();
+//! assert_eq!(pong, PongV1 {});
+//! ```
+
+use crate::group::{DataSource, GetCorrelationId, GroupHeader, UpgradeLatest};
+use crate::util::cbor::{CborData, CborDataError};
+use crate::util::ExtendedHeader;
+use crate::{MessageId, Versioned};
+use serde::Serialize;
+use std::io::Write;
+
+/// Write a reply to a request.
+///
+/// The reply header gets the correlation id from the request header. If
+/// the request has no correlation id, neither does the reply.
+pub fn write_reply<W, T>(
+    sink: &mut CborData<W, ExtendedHeader>,
+    request: &ExtendedHeader,
+    reply: &T,
+) -> Result<(), CborDataError>
+where
+    W: Write,
+    T: Serialize + Versioned,
+    T::Base: MessageId,
+{
+    sink.write_message_with_header(reply, |mut header| {
+        header.correlation_id = request.correlation_id;
+        header
+    })
+}
+
+/// Read the reply to a request.
+///
+/// This is like [`expect_message`], but it also checks that the reply has
+/// the same correlation id as `request`. If it doesn't, the reply body is
+/// skipped, and [`CorrelationMismatch`] is returned; the caller can keep
+/// reading, e.g. to discard a late reply to an earlier request.
+///
+/// [`expect_message`]: crate::group::DataSourceExt::expect_message
+/// [`CorrelationMismatch`]: CborDataError::CorrelationMismatch
+pub fn expect_reply_to<T, Src>(
+    src: &mut Src,
+    request: &impl GetCorrelationId,
+) -> Result<T, CborDataError>
+where
+    T: MessageId + UpgradeLatest,
+    Src: DataSource<Error = CborDataError>,
+    Src::Header: GetCorrelationId,
+{
+    let header = src.read_header()?;
+    let expected = request.correlation_id();
+    let found = header.correlation_id();
+    if found != expected {
+        src.skip_message(&header)?;
+        return Err(CborDataError::CorrelationMismatch { expected, found });
+    }
+    if header.wide_msg_id() != T::WIDE_MSG_ID {
+        return Err(src.unexpected_message::<T>(header.msg_id()));
+    }
+    T::upgrade_latest(src, header)
+}
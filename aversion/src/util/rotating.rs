@@ -0,0 +1,352 @@
+//! Provides a `DataSink` that rotates between log files.
+
+use crate::group::DataSink;
+use crate::util::cbor::{CborData, CborDataError};
+use crate::util::{BasicHeader, FramedHeader};
+use crate::{MessageId, Versioned};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// When a [`RotatingSink`] should start a new file.
+///
+/// A file is rotated once any of the configured limits has been reached.
+/// With no limits set, all messages go to the first file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RotationPolicy {
+    max_bytes: Option<u64>,
+    max_messages: Option<u64>,
+}
+
+impl RotationPolicy {
+    /// Create a policy with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rotate once a file contains at least `max_bytes` bytes.
+    ///
+    /// Messages are never split, so a file may grow past this size by
+    /// up to one message.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Rotate once a file contains `max_messages` messages.
+    pub fn max_messages(mut self, max_messages: u64) -> Self {
+        self.max_messages = Some(max_messages);
+        self
+    }
+
+    fn is_full(&self, bytes: u64, messages: u64) -> bool {
+        let bytes_full = matches!(self.max_bytes, Some(max) if bytes >= max);
+        let messages_full = matches!(self.max_messages, Some(max) if messages >= max);
+        bytes_full || messages_full
+    }
+}
+
+/// A `Write` stream that counts the bytes written.
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct CurrentFile<H> {
+    path: PathBuf,
+    sink: CborData<CountingWriter<BufWriter<File>>, H>,
+    messages: u64,
+}
+
+/// A [`DataSink`] that writes to a sequence of files.
+///
+/// Messages are written in the same format as [`CborData`]. When the
+/// current file reaches a limit set by the [`RotationPolicy`], the next
+/// message is written to a new file. A message is never split across
+/// two files, so each file can be decoded on its own.
+///
+/// File names are created from a template: `{}` is replaced by the file
+/// number, starting at 0. Files are created when the first message is
+/// written to them, and existing files are overwritten. To continue
+/// writing an existing file instead, use [`append`][Self::append].
+///
+/// ```no_run
+/// # use aversion::util::rotating::{RotatingSink, RotationPolicy};
+/// let policy = RotationPolicy::new().max_bytes(64 * 1024 * 1024);
+/// let sink = RotatingSink::new("messages-{}.log", policy);
+/// ```
+pub struct RotatingSink<H = BasicHeader> {
+    template: String,
+    policy: RotationPolicy,
+    preamble: Vec<u8>,
+    truncate_partial: bool,
+    next_index: u64,
+    current: Option<CurrentFile<H>>,
+}
+
+impl RotatingSink {
+    /// Create a new `RotatingSink`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` does not contain `{}`.
+    pub fn new(template: impl Into<String>, policy: RotationPolicy) -> Self {
+        Self::with_header(template, policy)
+    }
+}
+
+impl<H> RotatingSink<H> {
+    /// Create a new `RotatingSink` that uses a specific header type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` does not contain `{}`.
+    pub fn with_header(template: impl Into<String>, policy: RotationPolicy) -> Self {
+        let template = template.into();
+        assert!(
+            template.contains("{}"),
+            "RotatingSink template must contain {}",
+            "{}"
+        );
+        RotatingSink {
+            template,
+            policy,
+            preamble: Vec::new(),
+            truncate_partial: false,
+            next_index: 0,
+            current: None,
+        }
+    }
+
+    /// Write these bytes at the start of each new file.
+    ///
+    /// The preamble counts towards the size limit.
+    pub fn with_preamble(mut self, preamble: impl Into<Vec<u8>>) -> Self {
+        self.preamble = preamble.into();
+        self
+    }
+
+    /// When appending, remove a partial message at the end of the file.
+    ///
+    /// A writer that crashed may leave the last message of a file partly
+    /// written. By default, [`append`][Self::append] returns an error if
+    /// it finds one; with this option, the partial message is truncated
+    /// and new messages are written in its place.
+    pub fn truncate_partial(mut self, truncate: bool) -> Self {
+        self.truncate_partial = truncate;
+        self
+    }
+
+    /// The path of the file currently being written.
+    ///
+    /// Returns `None` if no message has been written yet.
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.as_ref().map(|current| current.path.as_path())
+    }
+
+    fn next_path(&self) -> PathBuf {
+        let name = self.template.replace("{}", &self.next_index.to_string());
+        PathBuf::from(name)
+    }
+
+    /// Flush the current file and open the next one.
+    fn rotate(&mut self) -> Result<(), CborDataError> {
+        if let Some(mut current) = self.current.take() {
+            current.sink.get_mut().flush()?;
+        }
+        let path = self.next_path();
+        let mut writer = CountingWriter {
+            inner: BufWriter::new(File::create(&path)?),
+            count: 0,
+        };
+        writer.write_all(&self.preamble)?;
+        self.next_index += 1;
+        self.current = Some(CurrentFile {
+            path,
+            sink: CborData::with_header(writer),
+            messages: 0,
+        });
+        Ok(())
+    }
+}
+
+impl<H> RotatingSink<H>
+where
+    H: FramedHeader,
+{
+    /// Continue writing an existing file, instead of starting a new one.
+    ///
+    /// The next message is appended to file number `index`; later files
+    /// are numbered from `index + 1`. If the file doesn't exist or is
+    /// empty, it's created and the preamble is written, as for a new file.
+    ///
+    /// Otherwise the file must start with the preamble set by
+    /// [`with_preamble`][Self::with_preamble]; if it doesn't, an
+    /// [`io::Error`] of kind [`InvalidData`][io::ErrorKind::InvalidData]
+    /// is returned. The message headers are then read to find the end of
+    /// the last message, skipping over the message bodies. If the file
+    /// ends with a partial message, an [`io::Error`] of kind
+    /// [`UnexpectedEof`][io::ErrorKind::UnexpectedEof] is returned, unless
+    /// [`truncate_partial`][Self::truncate_partial] is set.
+    ///
+    /// The existing messages count towards the [`RotationPolicy`] limits.
+    ///
+    /// ```no_run
+    /// # use aversion::util::rotating::{RotatingSink, RotationPolicy};
+    /// let sink = RotatingSink::new("messages-{}.log", RotationPolicy::new())
+    ///     .with_preamble(*b"LOG1")
+    ///     .truncate_partial(true)
+    ///     .append(3)
+    ///     .unwrap();
+    /// ```
+    pub fn append(mut self, index: u64) -> Result<Self, CborDataError> {
+        self.next_index = index;
+        let path = self.next_path();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let len = file.metadata()?.len();
+        let tail = if len == 0 {
+            file.write_all(&self.preamble)?;
+            Tail {
+                end: self.preamble.len() as u64,
+                messages: 0,
+                complete: true,
+            }
+        } else {
+            find_tail::<H>(&mut file, len, &self.preamble)?
+        };
+        if !tail.complete {
+            if !self.truncate_partial {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "log file ends with a partial message",
+                )
+                .into());
+            }
+            file.set_len(tail.end)?;
+        }
+        file.seek(SeekFrom::Start(tail.end))?;
+
+        let writer = CountingWriter {
+            inner: BufWriter::new(file),
+            count: tail.end,
+        };
+        self.next_index = index + 1;
+        self.current = Some(CurrentFile {
+            path,
+            sink: CborData::with_header(writer),
+            messages: tail.messages,
+        });
+        Ok(self)
+    }
+}
+
+/// The end of the complete messages in an existing file.
+struct Tail {
+    /// The offset just past the last complete message.
+    end: u64,
+    /// The number of complete messages.
+    messages: u64,
+    /// `false` if a partial message follows `end`.
+    complete: bool,
+}
+
+/// Check the preamble of an existing file, and find the end of its last
+/// complete message.
+fn find_tail<H>(file: &mut File, len: u64, preamble: &[u8]) -> Result<Tail, CborDataError>
+where
+    H: FramedHeader,
+{
+    let mut reader = BufReader::new(file);
+    let mut found = Vec::new();
+    (&mut reader)
+        .take(preamble.len() as u64)
+        .read_to_end(&mut found)?;
+    if found != preamble {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "log file preamble doesn't match",
+        )
+        .into());
+    }
+
+    let mut tail = Tail {
+        end: preamble.len() as u64,
+        messages: 0,
+        complete: true,
+    };
+    while tail.end < len {
+        let header = match H::deserialize_from(&mut reader) {
+            Ok(header) => header,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                tail.complete = false;
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let body_len = header.msg_len();
+        let end = reader.stream_position()? + u64::from(body_len);
+        if end > len {
+            tail.complete = false;
+            break;
+        }
+        reader.seek_relative(i64::from(body_len))?;
+        tail.end = end;
+        tail.messages += 1;
+    }
+    Ok(tail)
+}
+
+impl<H> DataSink for RotatingSink<H>
+where
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let needs_rotation = match &self.current {
+            None => true,
+            Some(current) => {
+                let bytes = current.sink.get_ref().count;
+                self.policy.is_full(bytes, current.messages)
+            }
+        };
+        if needs_rotation {
+            self.rotate()?;
+        }
+        // rotate() always leaves a current file open.
+        let current = self.current.as_mut().unwrap();
+        current.sink.write_message(msg)?;
+        current.messages += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        match &mut self.current {
+            Some(current) => current.sink.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Provides a `DataSink` that signs messages, and a `DataSource` that
+//! verifies them.
+//!
+//! Each message is followed by an Ed25519 signature over its serialized
+//! header and body. [`SignedSink`] signs with a private key, and
+//! [`SignedSource`] checks the signature with the matching public key
+//! before the body is decoded, returning
+//! [`CborDataError::SignatureInvalid`] if it doesn't verify.
+//!
+//! The signature covers the bytes as they were written, so the reader
+//! never needs to re-encode a message to check it, and the encoding
+//! doesn't need to be canonical. The header must record the body length,
+//! so that the signature can be found; `H` must implement
+//! [`FramedHeader`].
+//!
+//! The framing is the same as [`FooterData`] with a 64-byte footer.
+//!
+//! ```
+//! # use aversion::group::{DataSink, DataSourceExt};
+//! # use aversion::util::signed::{SignedSink, SignedSource, SigningKey};
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! # struct FooV1 { foo: u32 }
+//! # type Foo = FooV1;
+//! # assign_message_ids! { Foo: 1 }
+//! let key = SigningKey::from_bytes(&[7; 32]);
+//! let mut sink = SignedSink::new(Vec::new(), key.clone());
+//! sink.write_message(&FooV1 { foo: 1 }).unwrap();
+//! let buf = sink.into_inner();
+//!
+//! let mut src = SignedSource::new(&buf[..], key.verifying_key());
+//! let foo: Foo = src.expect_message().unwrap();
+//! assert_eq!(foo, FooV1 { foo: 1 });
+//! ```
+//!
+//! This is only available when the `signing` feature is enabled.
+//!
+//! [`FooterData`]: crate::util::footer::FooterData
+
+use crate::group::{DataSink, DataSource};
+use crate::util::cbor::CborDataError;
+use crate::util::footer::{FooterData, GroupFooter};
+use crate::util::{BasicHeader, FramedHeader};
+use crate::{MessageId, Versioned};
+use ed25519_dalek::{Signature, Signer as _, SIGNATURE_LENGTH};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Read, Write};
+
+#[doc(no_inline)]
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// Signs each frame with a private key.
+struct Signer(SigningKey);
+
+impl GroupFooter for Signer {
+    fn size(&self) -> usize {
+        SIGNATURE_LENGTH
+    }
+
+    fn compute(&self, frame: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0.sign(frame).to_bytes());
+    }
+}
+
+/// Verifies each frame with a public key.
+struct Verifier(VerifyingKey);
+
+impl GroupFooter for Verifier {
+    fn size(&self) -> usize {
+        SIGNATURE_LENGTH
+    }
+
+    fn compute(&self, _frame: &[u8], _out: &mut Vec<u8>) {
+        unreachable!("SignedSource doesn't write messages");
+    }
+
+    fn verify(&self, frame: &[u8], footer: &[u8]) -> bool {
+        match Signature::from_slice(footer) {
+            Ok(signature) => self.0.verify_strict(frame, &signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Report a failed footer check as an invalid signature.
+fn signature_error(e: CborDataError) -> CborDataError {
+    match e {
+        CborDataError::FooterVerifyFailed => CborDataError::SignatureInvalid,
+        e => e,
+    }
+}
+
+/// A [`DataSink`] that signs each message with an Ed25519 private key.
+///
+/// See the [module documentation](self) for the format.
+pub struct SignedSink<W, H = BasicHeader> {
+    inner: FooterData<W, Signer, H>,
+}
+
+impl<W> SignedSink<W> {
+    /// Create a new `SignedSink`.
+    pub fn new(inner: W, key: SigningKey) -> Self {
+        Self::with_header(inner, key)
+    }
+}
+
+impl<W, H> SignedSink<W, H> {
+    /// Create a new `SignedSink` that uses a specific header type.
+    pub fn with_header(inner: W, key: SigningKey) -> Self {
+        SignedSink {
+            inner: FooterData::with_header(inner, Signer(key)),
+        }
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Consume the `SignedSink`, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+}
+
+impl<W, H> DataSink for SignedSink<W, H>
+where
+    W: Write,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        self.inner.write_message(msg)
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        self.inner.flush()
+    }
+}
+
+/// A [`DataSource`] that verifies each message with an Ed25519 public key.
+///
+/// The signature is checked before the body is decoded. If it doesn't
+/// match, [`CborDataError::SignatureInvalid`] is returned; the message has
+/// still been consumed, so the next message can be read.
+///
+/// Skipping a message skips its signature without verifying it.
+///
+/// See the [module documentation](self) for the format.
+pub struct SignedSource<R, H = BasicHeader> {
+    inner: FooterData<R, Verifier, H>,
+}
+
+impl<R> SignedSource<R> {
+    /// Create a new `SignedSource`.
+    pub fn new(inner: R, key: VerifyingKey) -> Self {
+        Self::with_header(inner, key)
+    }
+}
+
+impl<R, H> SignedSource<R, H> {
+    /// Create a new `SignedSource` that uses a specific header type.
+    pub fn with_header(inner: R, key: VerifyingKey) -> Self {
+        SignedSource {
+            inner: FooterData::with_header(inner, Verifier(key)),
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Consume the `SignedSource`, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R, H> DataSource for SignedSource<R, H>
+where
+    R: Read,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+    type Header = H;
+
+    fn read_header(&mut self) -> Result<H, CborDataError> {
+        self.inner.read_header()
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<H>, CborDataError> {
+        self.inner.try_read_header()
+    }
+
+    fn read_message<T>(&mut self, header: &H) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        self.inner.read_message(header).map_err(signature_error)
+    }
+
+    fn skip_message(&mut self, header: &H) -> Result<(), CborDataError> {
+        self.inner.skip_message(header)
+    }
+
+    fn read_raw(&mut self, header: &H) -> Result<Vec<u8>, CborDataError> {
+        self.inner.read_raw(header).map_err(signature_error)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        self.inner.unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.inner.unknown_message(msg_id)
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        self.inner.unknown_wide_message(msg_id)
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.inner.deprecated_message(msg_id, note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        self.inner.deprecated_wide_message(msg_id, note)
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        self.inner.unknown_version::<T>(ver)
+    }
+
+    fn max_upgrade_steps(&self) -> u16 {
+        self.inner.max_upgrade_steps()
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        self.inner.upgrade_too_deep::<T>(ver)
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        self.inner.unexpected_message::<T>(msg_id)
+    }
+}
@@ -0,0 +1,181 @@
+//! Provides an adapter from a `Stream` of bytes to a `Stream` of messages.
+
+use crate::group::GroupDeserialize;
+use crate::util::cbor::{decode_frame_with_id, CborDataError, DEFAULT_MAX_FRAME_LEN};
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Decode a stream of byte chunks into a stream of group messages.
+///
+/// See [`DecodeStream`] for more information.
+pub fn decode_stream<G, S>(stream: S) -> DecodeStream<G, S>
+where
+    G: GroupDeserialize,
+    S: Stream<Item = Bytes> + Unpin,
+{
+    DecodeStream {
+        inner: stream,
+        buf: BytesMut::new(),
+        max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        done: false,
+        _group: PhantomData,
+    }
+}
+
+/// A `Stream` of group messages, decoded from a `Stream` of byte chunks.
+///
+/// This uses the same framing as [`CborData`]: each message is a
+/// [`BasicHeader`] followed by the CBOR-encoded message. Chunks are
+/// buffered until a complete frame is available, so chunk boundaries can
+/// fall anywhere, including in the middle of a header.
+///
+/// If a complete frame fails to deserialize, the error is returned and the
+/// frame is discarded; the stream can continue to be polled. If the inner
+/// stream ends in the middle of a frame, [`CborDataError::Eof`] is
+/// returned, and then the stream ends.
+///
+/// A frame is only buffered if it's no longer than
+/// [`max_frame_len`][Self::max_frame_len]. If a header describes a longer
+/// frame, [`CborDataError::FrameTooLong`] is returned, and then the stream
+/// ends, since the rest of the data can't be decoded.
+///
+/// To receive each message's priority as well, use
+/// [`with_priority`][Self::with_priority].
+///
+/// # Cancellation
+///
+/// Polling is cancellation safe: a `next()` future can be dropped at any
+/// point, e.g. by losing a `select!`, without losing data. Bytes are only
+/// decoded once a whole frame has been buffered, and the buffer belongs to
+/// the `DecodeStream`, not to the future. A frame that was partly received
+/// when a read was cancelled is completed by the next read.
+///
+/// This is only available when the `async` feature is enabled.
+///
+/// [`CborData`]: crate::util::cbor::CborData
+/// [`BasicHeader`]: crate::util::BasicHeader
+pub struct DecodeStream<G, S> {
+    inner: S,
+    buf: BytesMut,
+    max_frame_len: usize,
+    done: bool,
+    _group: PhantomData<fn() -> G>,
+}
+
+impl<G, S> DecodeStream<G, S> {
+    /// Consume the `DecodeStream`, returning the inner stream.
+    ///
+    /// Any buffered bytes that haven't been decoded are lost.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Set the longest frame that will be decoded, including its header.
+    ///
+    /// The default is [`DEFAULT_MAX_FRAME_LEN`].
+    pub fn max_frame_len(mut self, len: usize) -> Self {
+        self.max_frame_len = len;
+        self
+    }
+
+    /// Report each message's priority along with the message.
+    ///
+    /// See [`PriorityDecodeStream`] for more information.
+    pub fn with_priority(self) -> PriorityDecodeStream<G, S> {
+        PriorityDecodeStream { inner: self }
+    }
+}
+
+impl<G, S> DecodeStream<G, S>
+where
+    G: GroupDeserialize,
+    S: Stream<Item = Bytes> + Unpin,
+{
+    /// Poll for the next message, along with its message id.
+    fn poll_frame(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<(u32, G), CborDataError>>> {
+        loop {
+            match decode_frame_with_id(&mut self.buf, self.max_frame_len) {
+                Ok(None) => {}
+                Err(e @ CborDataError::FrameTooLong { .. }) => {
+                    self.done = true;
+                    self.buf.clear();
+                    return Poll::Ready(Some(Err(e)));
+                }
+                result => return Poll::Ready(result.transpose()),
+            }
+            if self.done {
+                return Poll::Ready(None);
+            }
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(chunk)) => self.buf.extend_from_slice(&chunk),
+                Poll::Ready(None) => {
+                    self.done = true;
+                    if !self.buf.is_empty() {
+                        self.buf.clear();
+                        return Poll::Ready(Some(Err(CborDataError::Eof)));
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<G, S> Stream for DecodeStream<G, S>
+where
+    G: GroupDeserialize,
+    S: Stream<Item = Bytes> + Unpin,
+{
+    type Item = Result<G, CborDataError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .poll_frame(cx)
+            .map(|item| item.map(|result| result.map(|(_, msg)| msg)))
+    }
+}
+
+/// A `Stream` of group messages, each paired with its priority.
+///
+/// This is created by [`DecodeStream::with_priority`]. It decodes
+/// messages in the same way, and yields `(priority, message)` pairs, so
+/// that a downstream scheduler can handle urgent messages first.
+///
+/// The priority is looked up by message id with
+/// [`GroupDeserialize::priority`], so it's the message type's
+/// [`Priority`], or 0 if the type doesn't implement it.
+///
+/// [`Priority`]: crate::group::Priority
+pub struct PriorityDecodeStream<G, S> {
+    inner: DecodeStream<G, S>,
+}
+
+impl<G, S> PriorityDecodeStream<G, S> {
+    /// Consume the `PriorityDecodeStream`, returning the inner stream.
+    ///
+    /// Any buffered bytes that haven't been decoded are lost.
+    pub fn into_inner(self) -> S {
+        self.inner.into_inner()
+    }
+}
+
+impl<G, S> Stream for PriorityDecodeStream<G, S>
+where
+    G: GroupDeserialize,
+    S: Stream<Item = Bytes> + Unpin,
+{
+    type Item = Result<(u8, G), CborDataError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .inner
+            .poll_frame(cx)
+            .map(|item| item.map(|result| result.map(|(msg_id, msg)| (G::priority(msg_id), msg))))
+    }
+}
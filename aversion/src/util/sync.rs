@@ -0,0 +1,124 @@
+//! Provides a `DataSink` that can be shared between threads.
+
+use crate::group::DataSink;
+use crate::{MessageId, Versioned};
+use serde::Serialize;
+use std::sync::{Mutex, MutexGuard};
+
+/// A [`DataSink`] that can be written to from many threads at once.
+///
+/// A `SyncSink` wraps another `DataSink` behind a mutex. Each message is
+/// written while holding the lock, so the header and body of one message
+/// are never interleaved with bytes from another. [`CborData`], for
+/// example, encodes the header and body into one buffer and writes it
+/// with a single `write_all` call, all under the same lock.
+///
+/// Because only one thread can write at a time, this serializes
+/// throughput: producers spend time waiting for the lock, and a slow
+/// writer (e.g. a blocking socket) holds up all of them.
+///
+/// `SyncSink` has methods that take `&self`, and `&SyncSink` implements
+/// `DataSink`, so it can be shared using an `Arc` or a scoped thread:
+/// ```
+/// # use aversion::group::DataSink;
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::util::sync::SyncSink;
+/// # use aversion::{assign_message_ids, Versioned};
+/// # use serde::Serialize;
+/// # use std::sync::Arc;
+/// # #[derive(Versioned, Serialize)]
+/// # struct PingV1 { seq: u32 }
+/// # type Ping = PingV1;
+/// # assign_message_ids! { Ping: 1 }
+/// let sink = Arc::new(SyncSink::new(CborData::new(Vec::new())));
+/// let threads = (0..4)
+///     .map(|seq| {
+///         let sink = Arc::clone(&sink);
+///         std::thread::spawn(move || sink.write_message(&Ping { seq }).unwrap())
+///     })
+///     .collect::<Vec<_>>();
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// ```
+///
+/// If a thread panics while writing a message, the stream may contain a
+/// partial message, and all later writes will panic. A sink that writes
+/// the header and body with separate calls can also leave a partial
+/// message behind if the second write fails; `CborData` avoids this, but
+/// `write_all` may still stop partway through the frame.
+///
+/// [`CborData`]: crate::util::cbor::CborData
+pub struct SyncSink<D> {
+    inner: Mutex<D>,
+}
+
+impl<D> SyncSink<D> {
+    /// Create a new `SyncSink`.
+    pub fn new(inner: D) -> Self {
+        SyncSink {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Consume the `SyncSink`, returning the inner `DataSink`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while writing a message.
+    pub fn into_inner(self) -> D {
+        self.inner.into_inner().expect("SyncSink poisoned")
+    }
+
+    fn lock(&self) -> MutexGuard<'_, D> {
+        self.inner.lock().expect("SyncSink poisoned")
+    }
+}
+
+impl<D> SyncSink<D>
+where
+    D: DataSink,
+{
+    /// Write a message to the inner `DataSink`.
+    ///
+    /// This waits until no other thread is writing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while writing a message.
+    pub fn write_message<T>(&self, msg: &T) -> Result<(), D::Error>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        self.lock().write_message(msg)
+    }
+
+    /// Flush the inner `DataSink`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while writing a message.
+    pub fn flush(&self) -> Result<(), D::Error> {
+        self.lock().flush()
+    }
+}
+
+impl<D> DataSink for &SyncSink<D>
+where
+    D: DataSink,
+{
+    type Error = D::Error;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), D::Error>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        SyncSink::write_message(*self, msg)
+    }
+
+    fn flush(&mut self) -> Result<(), D::Error> {
+        SyncSink::flush(*self)
+    }
+}
@@ -0,0 +1,84 @@
+//! Provides a `tokio-util` codec for message groups.
+
+use crate::group::{GroupDeserialize, GroupSerialize};
+use crate::util::cbor::{decode_frame, CborData, CborDataError, DEFAULT_MAX_FRAME_LEN};
+use bytes::{BufMut, BytesMut};
+use std::marker::PhantomData;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A [`Decoder`] and [`Encoder`] for a message group.
+///
+/// `GroupCodec` uses the same framing as [`CborData`]: each message is a
+/// [`BasicHeader`] followed by the CBOR-encoded message. It can be used with
+/// [`tokio_util::codec::Framed`] to turn an async byte stream into a
+/// `Stream` and `Sink` of group messages.
+///
+/// The decoder buffers incoming bytes until a complete frame (header plus
+/// message body) is available, so frames may be split across any number of
+/// reads. Backpressure is handled by `Framed`.
+///
+/// If a complete frame fails to deserialize, the whole frame is discarded,
+/// so the decoder remains in sync with the stream.
+///
+/// A frame is only buffered if it's no longer than
+/// [`max_frame_len`][Self::max_frame_len]. A longer frame is an error,
+/// [`CborDataError::FrameTooLong`], and the stream can't be decoded any
+/// further.
+///
+/// This is only available when the `tokio-codec` feature is enabled.
+///
+/// [`BasicHeader`]: crate::util::BasicHeader
+/// [`Decoder`]: tokio_util::codec::Decoder
+/// [`Encoder`]: tokio_util::codec::Encoder
+pub struct GroupCodec<G> {
+    max_frame_len: usize,
+    _group: PhantomData<fn() -> G>,
+}
+
+impl<G> GroupCodec<G> {
+    /// Create a new `GroupCodec`.
+    pub fn new() -> Self {
+        GroupCodec {
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            _group: PhantomData,
+        }
+    }
+
+    /// Set the longest frame that will be decoded, including its header.
+    ///
+    /// The default is [`DEFAULT_MAX_FRAME_LEN`].
+    pub fn max_frame_len(mut self, len: usize) -> Self {
+        self.max_frame_len = len;
+        self
+    }
+}
+
+impl<G> Default for GroupCodec<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> Decoder for GroupCodec<G>
+where
+    G: GroupDeserialize,
+{
+    type Item = G;
+    type Error = CborDataError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<G>, CborDataError> {
+        decode_frame(src, self.max_frame_len)
+    }
+}
+
+impl<G> Encoder<G> for GroupCodec<G>
+where
+    G: GroupSerialize,
+{
+    type Error = CborDataError;
+
+    fn encode(&mut self, item: G, dst: &mut BytesMut) -> Result<(), CborDataError> {
+        let mut sink = CborData::new(dst.writer());
+        item.write_message(&mut sink)
+    }
+}
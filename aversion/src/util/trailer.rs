@@ -0,0 +1,298 @@
+//! Provides a `DataSource` and `DataSink` for frames with the header at
+//! the end.
+//!
+//! Some formats put the message type and version after the message body,
+//! as a trailer. Each frame is laid out as:
+//!
+//! | size | field |
+//! |------|-------|
+//! | 4    | body length, big-endian |
+//! | *    | body, CBOR |
+//! | *    | header, e.g. a [`BasicHeader`] |
+//!
+//! The header's message length must match the length prefix.
+//!
+//! # Buffering
+//!
+//! A message can't be decoded until its header has been read, so
+//! [`TrailerHeaderSource`] reads each body into memory first, and keeps it
+//! until the next header is read. This means a whole message body is
+//! always buffered, whatever the reader. Use
+//! [`max_body_len`][TrailerHeaderSource::max_body_len] to limit the size of
+//! that buffer when reading untrusted data.
+//!
+//! ```
+//! # use aversion::group::{DataSink, DataSourceExt};
+//! # use aversion::util::trailer::{TrailerHeaderSink, TrailerHeaderSource};
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! # struct FooV1 { foo: u32 }
+//! # type Foo = FooV1;
+//! # assign_message_ids! { Foo: 1 }
+//! let mut sink = TrailerHeaderSink::new(Vec::new());
+//! sink.write_message(&FooV1 { foo: 1 }).unwrap();
+//! let buf = sink.into_inner();
+//! // The header comes last.
+//! assert_eq!(&buf[buf.len() - 8..], &[0, 1, 0, 1, 0, 0, 0, 6]);
+//!
+//! let mut src = TrailerHeaderSource::new(&buf[..]).max_body_len(1024);
+//! let foo: Foo = src.expect_message().unwrap();
+//! assert_eq!(foo, FooV1 { foo: 1 });
+//! ```
+
+use crate::group::{DataSink, DataSource};
+use crate::util::cbor::{CborCodec, CborDataError};
+use crate::util::protocol::ProtocolError;
+use crate::util::{try_read_first_byte, BasicHeader, Codec, FramedHeader};
+use crate::{MessageId, Versioned};
+use byteorder::{BigEndian, ReadBytesExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+/// A [`DataSink`] that writes each header after its message body.
+///
+/// See the [module documentation](self) for the format.
+pub struct TrailerHeaderSink<W, H = BasicHeader> {
+    inner: W,
+    /// The length prefix and body of the current message.
+    frame: Vec<u8>,
+    _header: PhantomData<fn() -> H>,
+}
+
+impl<W> TrailerHeaderSink<W> {
+    /// Create a new `TrailerHeaderSink`.
+    pub fn new(inner: W) -> Self {
+        Self::with_header(inner)
+    }
+}
+
+impl<W, H> TrailerHeaderSink<W, H> {
+    /// Create a new `TrailerHeaderSink` that uses a specific header type.
+    pub fn with_header(inner: W) -> Self {
+        TrailerHeaderSink {
+            inner,
+            frame: Vec::new(),
+            _header: PhantomData,
+        }
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consume the `TrailerHeaderSink`, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, H> DataSink for TrailerHeaderSink<W, H>
+where
+    W: Write,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        // Leave room for the length prefix.
+        self.frame.clear();
+        self.frame.extend_from_slice(&[0; 4]);
+        CborCodec.encode(msg, &mut self.frame)?;
+        let msg_len: u32 = (self.frame.len() - 4).try_into().expect("usize to u32");
+        self.frame[..4].copy_from_slice(&msg_len.to_be_bytes());
+
+        H::for_msg(msg, msg_len).serialize_into(&mut self.frame)?;
+        self.inner.write_all(&self.frame)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// A [`DataSource`] that reads frames with the header after the body.
+///
+/// Reading a header also reads the body that precedes it, which is
+/// buffered until the message is read or skipped; see the
+/// [module documentation](self#buffering).
+pub struct TrailerHeaderSource<R, H = BasicHeader> {
+    inner: R,
+    /// The body of the current message.
+    body: Vec<u8>,
+    max_body_len: Option<u32>,
+    _header: PhantomData<fn() -> H>,
+}
+
+impl<R> TrailerHeaderSource<R> {
+    /// Create a new `TrailerHeaderSource`.
+    pub fn new(inner: R) -> Self {
+        Self::with_header(inner)
+    }
+}
+
+impl<R, H> TrailerHeaderSource<R, H> {
+    /// Create a new `TrailerHeaderSource` that uses a specific header type.
+    pub fn with_header(inner: R) -> Self {
+        TrailerHeaderSource {
+            inner,
+            body: Vec::new(),
+            max_body_len: None,
+            _header: PhantomData,
+        }
+    }
+
+    /// Limit the size of a message body, in bytes.
+    ///
+    /// A frame whose length prefix is larger is rejected with an
+    /// [`io::Error`] of kind [`InvalidData`][io::ErrorKind::InvalidData],
+    /// before its body is read. By default there is no limit.
+    pub fn max_body_len(mut self, len: u32) -> Self {
+        self.max_body_len = Some(len);
+        self
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the `TrailerHeaderSource`, returning the inner reader.
+    ///
+    /// A body that was buffered for the current message is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, H> TrailerHeaderSource<R, H>
+where
+    R: Read,
+    H: FramedHeader,
+{
+    /// Read the body that follows a length prefix, and the header after it.
+    fn read_frame(&mut self, msg_len: u32) -> Result<H, CborDataError> {
+        if let Some(max) = self.max_body_len {
+            if msg_len > max {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("message body of {} bytes exceeds the limit", msg_len),
+                )
+                .into());
+            }
+        }
+        self.body.clear();
+        let read = (&mut self.inner)
+            .take(msg_len.into())
+            .read_to_end(&mut self.body)?;
+        if read < msg_len as usize {
+            return Err(CborDataError::Eof);
+        }
+
+        let header = H::deserialize_from(&mut self.inner)?;
+        if header.msg_len() != msg_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trailing header length doesn't match the frame",
+            )
+            .into());
+        }
+        Ok(header)
+    }
+}
+
+impl<R, H> DataSource for TrailerHeaderSource<R, H>
+where
+    R: Read,
+    H: FramedHeader,
+{
+    type Error = CborDataError;
+    type Header = H;
+
+    fn read_header(&mut self) -> Result<H, CborDataError> {
+        let msg_len = self.inner.read_u32::<BigEndian>()?;
+        self.read_frame(msg_len)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<H>, CborDataError> {
+        let mut prefix = [0u8; 4];
+        prefix[0] = match try_read_first_byte(&mut self.inner)? {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        self.inner.read_exact(&mut prefix[1..])?;
+        let msg_len = (&prefix[..]).read_u32::<BigEndian>()?;
+        self.read_frame(msg_len).map(Some)
+    }
+
+    fn read_message<T>(&mut self, _header: &H) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        CborCodec.decode(&self.body)
+    }
+
+    fn read_raw(&mut self, _header: &H) -> Result<Vec<u8>, CborDataError> {
+        Ok(self.body.clone())
+    }
+
+    fn skip_message(&mut self, _header: &H) -> Result<(), CborDataError> {
+        // The body was read along with the header.
+        Ok(())
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
+    }
+}
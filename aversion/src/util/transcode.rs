@@ -0,0 +1,151 @@
+//! Check a message log migration without writing any output.
+
+use crate::group::{DataSink, DataSource, GroupDeserialize, GroupSerialize, MessageKey};
+use crate::util::compact::ReadAhead;
+use crate::{MessageId, Versioned};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Display;
+
+/// A message that couldn't be transcoded, from [`transcode_dry_run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeFailure {
+    /// The position of the message in the source, counting from 0.
+    pub index: usize,
+    /// A description of the error.
+    pub error: String,
+}
+
+/// The result of [`transcode_dry_run`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranscodeReport {
+    /// The number of messages that were read, including failures.
+    pub read: usize,
+    /// The number of output messages that would be written, by message
+    /// id and version.
+    pub counts: BTreeMap<MessageKey, usize>,
+    /// The messages that couldn't be decoded or mapped.
+    pub failures: Vec<TranscodeFailure>,
+}
+
+impl TranscodeReport {
+    /// The total number of output messages that would be written.
+    pub fn written(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+/// A `DataSink` that counts messages instead of writing them.
+struct CountingSink<'a> {
+    counts: &'a mut BTreeMap<MessageKey, usize>,
+}
+
+impl DataSink for CountingSink<'_> {
+    type Error = Infallible;
+
+    fn write_message<T>(&mut self, _msg: &T) -> Result<(), Infallible>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let key = MessageKey {
+            msg_id: T::Base::WIDE_MSG_ID,
+            msg_ver: T::VER,
+        };
+        *self.counts.entry(key).or_default() += 1;
+        Ok(())
+    }
+}
+
+/// Report what a transcode from `Gin` to `Gout` messages would produce.
+///
+/// Every message is read from `src` (and upgraded to the latest version)
+/// until [`try_read_header`][DataSource::try_read_header] detects the end
+/// of the data, and passed to `mapper`. The output messages are counted,
+/// but nothing is serialized or written.
+///
+/// If `mapper` returns an error, the failure is recorded and the dry run
+/// continues with the next message. If a message can't be decoded, the
+/// failure is recorded and the dry run stops, since the position of the
+/// next message isn't known.
+///
+/// ```
+/// # use aversion::group::DataSink;
+/// # use aversion::util::cbor::CborData;
+/// # use aversion::util::transcode::transcode_dry_run;
+/// # use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize};
+/// # use aversion::{UpgradeLatest, Versioned};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Versioned, UpgradeLatest, Serialize, Deserialize)]
+/// # struct PingV1 { seq: u32 }
+/// # type Ping = PingV1;
+/// # assign_message_ids! { Ping: 1 }
+/// #[derive(GroupDeserialize, GroupSerialize)]
+/// enum Log {
+///     Ping(Ping),
+/// }
+///
+/// # let mut sink = CborData::new(Vec::new());
+/// # sink.write_message(&PingV1 { seq: 1 }).unwrap();
+/// # let buf = sink.into_inner();
+/// let mut src = CborData::new(&buf[..]);
+/// let report = transcode_dry_run(&mut src, |msg: Log| Ok::<Log, String>(msg));
+/// assert_eq!(report.read, 1);
+/// assert_eq!(report.written(), 1);
+/// assert!(report.failures.is_empty());
+/// ```
+pub fn transcode_dry_run<Gin, Gout, E, Src, F>(src: &mut Src, mut mapper: F) -> TranscodeReport
+where
+    Gin: GroupDeserialize,
+    Gout: GroupSerialize,
+    E: Display,
+    Src: DataSource,
+    Src::Error: Display,
+    F: FnMut(Gin) -> Result<Gout, E>,
+{
+    let mut report = TranscodeReport::default();
+    loop {
+        let index = report.read;
+        let header = match src.try_read_header() {
+            Ok(Some(header)) => header,
+            Ok(None) => break,
+            Err(e) => {
+                report.failures.push(TranscodeFailure {
+                    index,
+                    error: e.to_string(),
+                });
+                break;
+            }
+        };
+        report.read += 1;
+        let mut src = ReadAhead {
+            inner: &mut *src,
+            header: Some(header),
+        };
+        let msg = match Gin::read_message(&mut src) {
+            Ok(msg) => msg,
+            Err(e) => {
+                report.failures.push(TranscodeFailure {
+                    index,
+                    error: e.to_string(),
+                });
+                break;
+            }
+        };
+        match mapper(msg) {
+            Ok(out) => {
+                let mut sink = CountingSink {
+                    counts: &mut report.counts,
+                };
+                // Counting can't fail.
+                out.write_message(&mut sink).unwrap_or_else(|e| match e {});
+            }
+            Err(e) => report.failures.push(TranscodeFailure {
+                index,
+                error: e.to_string(),
+            }),
+        }
+    }
+    report
+}
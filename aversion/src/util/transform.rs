@@ -0,0 +1,425 @@
+//! Provides a pipeline of transforms applied to message bodies.
+//!
+//! A [`BodyTransform`] changes the bytes of a message body, e.g. by
+//! compressing or encrypting them, and can reverse the change. Each
+//! transform has a [header flag](crate::group::flags), which is set on
+//! the messages it was applied to.
+//!
+//! A [`TransformPipeline`] applies a list of transforms in the order they
+//! were added, and reverses them in the opposite order. Order matters:
+//! encrypted data doesn't compress, so compression must come before
+//! encryption, and a checksum added last covers the bytes as written.
+//! [`TransformSink`] and [`TransformSource`] write and read messages with
+//! a [`FlagsHeader`], so a reader knows which transforms to reverse for
+//! each message, and transformed and plain messages can be mixed in a
+//! single stream.
+//!
+//! ```
+//! # use aversion::group::{flags, DataSink, DataSourceExt};
+//! # use aversion::util::transform::{BodyTransform, Checksum, TransformPipeline, TransformSink, TransformSource};
+//! # use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! # use std::io;
+//! # #[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+//! # struct FooV1 { foo: u32 }
+//! # type Foo = FooV1;
+//! # assign_message_ids! { Foo: 1 }
+//! /// A toy cipher, standing in for real encryption.
+//! struct Xor(u8);
+//!
+//! impl BodyTransform for Xor {
+//!     fn flag(&self) -> u8 {
+//!         flags::ENCRYPTED
+//!     }
+//!
+//!     fn apply(&self, body: Vec<u8>) -> io::Result<Vec<u8>> {
+//!         Ok(body.into_iter().map(|b| b ^ self.0).collect())
+//!     }
+//!
+//!     fn reverse(&self, body: Vec<u8>) -> io::Result<Vec<u8>> {
+//!         self.apply(body)
+//!     }
+//! }
+//!
+//! let pipeline = || TransformPipeline::new().then(Xor(0x5a)).then(Checksum);
+//! let mut sink = TransformSink::new(Vec::new(), pipeline());
+//! sink.write_message(&FooV1 { foo: 1 }).unwrap();
+//! let buf = sink.into_inner();
+//!
+//! let mut src = TransformSource::new(&buf[..], pipeline());
+//! let foo: Foo = src.expect_message().unwrap();
+//! assert_eq!(foo, FooV1 { foo: 1 });
+//! ```
+
+use crate::group::{flags, DataSink, DataSource, GroupHeader};
+use crate::util::cbor::{CborCodec, CborDataError};
+use crate::util::protocol::ProtocolError;
+use crate::util::{try_read_first_byte, Codec, FlagsHeader};
+use crate::{MessageId, Versioned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+
+/// The flags of the transforms defined by this crate.
+///
+/// A message with one of these flags can't be decoded unless the
+/// pipeline has a transform for it. Other flags, e.g. [`flags::DIFF`],
+/// are left for the caller to handle.
+const TRANSFORM_FLAGS: u8 = flags::COMPRESSED | flags::ENCRYPTED | flags::CHECKSUM;
+
+/// A reversible transformation of a message body.
+pub trait BodyTransform {
+    /// The header flag that marks a body with this transform applied.
+    ///
+    /// This must be a single bit; see the [`flags`] module.
+    fn flag(&self) -> u8;
+
+    /// Transform a message body.
+    fn apply(&self, body: Vec<u8>) -> io::Result<Vec<u8>>;
+
+    /// Undo [`apply`][Self::apply].
+    ///
+    /// Data that can't have been produced by `apply` should return an
+    /// [`io::Error`] of kind [`InvalidData`][io::ErrorKind::InvalidData].
+    fn reverse(&self, body: Vec<u8>) -> io::Result<Vec<u8>>;
+}
+
+/// A transform that appends a checksum to the body.
+///
+/// The checksum is a 64-bit FNV-1a hash, big-endian. It detects
+/// accidental corruption, but not tampering; use
+/// [`FooterData`](crate::util::footer::FooterData) to authenticate
+/// messages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Checksum;
+
+impl Checksum {
+    /// The size of the checksum, in bytes.
+    pub const SIZE: usize = 8;
+
+    fn hash(body: &[u8]) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+        body.iter().fold(FNV_OFFSET, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+    }
+}
+
+impl BodyTransform for Checksum {
+    fn flag(&self) -> u8 {
+        flags::CHECKSUM
+    }
+
+    fn apply(&self, mut body: Vec<u8>) -> io::Result<Vec<u8>> {
+        let hash = Self::hash(&body);
+        body.extend_from_slice(&hash.to_be_bytes());
+        Ok(body)
+    }
+
+    fn reverse(&self, mut body: Vec<u8>) -> io::Result<Vec<u8>> {
+        if body.len() < Self::SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message body is too short for a checksum",
+            ));
+        }
+        let split = body.len() - Self::SIZE;
+        let expected = u64::from_be_bytes(body[split..].try_into().unwrap());
+        body.truncate(split);
+        if Self::hash(&body) != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message checksum mismatch",
+            ));
+        }
+        Ok(body)
+    }
+}
+
+/// An ordered list of [`BodyTransform`]s.
+///
+/// See the [module documentation](self).
+#[derive(Default)]
+pub struct TransformPipeline {
+    transforms: Vec<Box<dyn BodyTransform>>,
+}
+
+impl TransformPipeline {
+    /// Create an empty pipeline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a transform, which is applied after the ones already added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transform's flag isn't a single bit, or is already
+    /// used by another transform in the pipeline, or if a compression
+    /// transform is added after an encryption transform.
+    pub fn then<T>(mut self, transform: T) -> Self
+    where
+        T: BodyTransform + 'static,
+    {
+        let flag = transform.flag();
+        assert!(
+            flag.is_power_of_two(),
+            "transform flag {:#04x} must be a single bit",
+            flag
+        );
+        assert!(
+            self.flags() & flag == 0,
+            "two transforms use flag {:#04x}",
+            flag
+        );
+        assert!(
+            !(flag == flags::COMPRESSED && self.flags() & flags::ENCRYPTED != 0),
+            "compression must come before encryption"
+        );
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// The flags of all of the transforms in the pipeline.
+    pub fn flags(&self) -> u8 {
+        self.transforms.iter().fold(0, |acc, t| acc | t.flag())
+    }
+
+    /// Apply the transforms whose flags are in `enabled`, in order.
+    ///
+    /// Returns the flags of the transforms that were applied, and the
+    /// transformed body.
+    pub fn apply(&self, enabled: u8, mut body: Vec<u8>) -> io::Result<(u8, Vec<u8>)> {
+        let mut applied = 0;
+        for transform in &self.transforms {
+            if transform.flag() & enabled != 0 {
+                body = transform.apply(body)?;
+                applied |= transform.flag();
+            }
+        }
+        Ok((applied, body))
+    }
+
+    /// Reverse the transforms marked in `msg_flags`, in reverse order.
+    ///
+    /// Returns an [`io::Error`] of kind
+    /// [`InvalidData`][io::ErrorKind::InvalidData] if `msg_flags` marks a
+    /// compression, encryption, or checksum transform that isn't in the
+    /// pipeline.
+    pub fn reverse(&self, msg_flags: u8, mut body: Vec<u8>) -> io::Result<Vec<u8>> {
+        let missing = msg_flags & TRANSFORM_FLAGS & !self.flags();
+        if missing != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "message flags {:#04x} include transforms that aren't in the pipeline",
+                    missing
+                ),
+            ));
+        }
+        for transform in self.transforms.iter().rev() {
+            if transform.flag() & msg_flags != 0 {
+                body = transform.reverse(body)?;
+            }
+        }
+        Ok(body)
+    }
+}
+
+/// A [`DataSink`] that applies a [`TransformPipeline`] to each message
+/// body.
+///
+/// See the [module documentation](self) for the format.
+pub struct TransformSink<W> {
+    inner: W,
+    pipeline: TransformPipeline,
+    enabled: u8,
+}
+
+impl<W> TransformSink<W> {
+    /// Create a new `TransformSink`.
+    ///
+    /// Every transform in the pipeline is enabled.
+    pub fn new(inner: W, pipeline: TransformPipeline) -> Self {
+        TransformSink {
+            inner,
+            pipeline,
+            enabled: 0xff,
+        }
+    }
+
+    /// Choose which transforms are applied to the following messages.
+    ///
+    /// Only the transforms whose flags are in `enabled` are applied; pass
+    /// 0 to write plain messages.
+    pub fn set_enabled(&mut self, enabled: u8) {
+        self.enabled = enabled;
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consume the `TransformSink`, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> DataSink for TransformSink<W>
+where
+    W: Write,
+{
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let mut body = Vec::new();
+        CborCodec.encode(msg, &mut body)?;
+        let (msg_flags, body) = self.pipeline.apply(self.enabled, body)?;
+
+        let msg_len: u32 = body.len().try_into().expect("usize to u32");
+        FlagsHeader::for_msg(msg, msg_flags, msg_len).serialize_into(&mut self.inner)?;
+        self.inner.write_all(&body)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CborDataError> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// A [`DataSource`] that reverses a [`TransformPipeline`] on each message
+/// body.
+///
+/// Only the transforms marked in each message's header flags are
+/// reversed, so plain messages are decoded as-is.
+pub struct TransformSource<R> {
+    inner: R,
+    pipeline: TransformPipeline,
+}
+
+impl<R> TransformSource<R> {
+    /// Create a new `TransformSource`.
+    pub fn new(inner: R, pipeline: TransformPipeline) -> Self {
+        TransformSource { inner, pipeline }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the `TransformSource`, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> TransformSource<R>
+where
+    R: Read,
+{
+    /// Read the body of the current message, as written.
+    fn read_body(&mut self, header: &FlagsHeader) -> Result<Vec<u8>, CborDataError> {
+        let mut body = Vec::new();
+        let read = (&mut self.inner)
+            .take(header.msg_len.into())
+            .read_to_end(&mut body)?;
+        if read < header.msg_len as usize {
+            return Err(CborDataError::Eof);
+        }
+        Ok(body)
+    }
+}
+
+impl<R> DataSource for TransformSource<R>
+where
+    R: Read,
+{
+    type Error = CborDataError;
+    type Header = FlagsHeader;
+
+    fn read_header(&mut self) -> Result<FlagsHeader, CborDataError> {
+        Ok(FlagsHeader::deserialize_from(&mut self.inner)?)
+    }
+
+    fn try_read_header(&mut self) -> Result<Option<FlagsHeader>, CborDataError> {
+        let first = match try_read_first_byte(&mut self.inner)? {
+            Some(first) => [first],
+            None => return Ok(None),
+        };
+        let mut reader = (&first[..]).chain(&mut self.inner);
+        Ok(Some(FlagsHeader::deserialize_from(&mut reader)?))
+    }
+
+    fn read_message<T>(&mut self, header: &FlagsHeader) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        let body = self.read_raw(header)?;
+        CborCodec.decode(&body)
+    }
+
+    fn read_raw(&mut self, header: &FlagsHeader) -> Result<Vec<u8>, CborDataError> {
+        let body = self.read_body(header)?;
+        Ok(self.pipeline.reverse(header.flags(), body)?)
+    }
+
+    fn skip_message(&mut self, header: &FlagsHeader) -> Result<(), CborDataError> {
+        self.read_body(header)?;
+        Ok(())
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        CborDataError::unsupported_operation(operation)
+    }
+
+    fn unknown_message(&self, msg_id: u16) -> CborDataError {
+        self.unknown_wide_message(msg_id.into())
+    }
+
+    fn unknown_wide_message(&self, msg_id: u32) -> CborDataError {
+        CborDataError::UnknownMessage { msg_id }
+    }
+
+    fn deprecated_message(&self, msg_id: u16, note: &'static str) -> CborDataError {
+        self.deprecated_wide_message(msg_id.into(), note)
+    }
+
+    fn deprecated_wide_message(&self, msg_id: u32, note: &'static str) -> CborDataError {
+        CborDataError::DeprecatedMessage { msg_id, note }
+    }
+
+    fn unknown_version<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::unknown_version::<T>(ver)
+    }
+
+    fn upgrade_too_deep<T>(&self, ver: u16) -> CborDataError
+    where
+        T: Versioned,
+    {
+        CborDataError::upgrade_too_deep::<T>(ver, self.max_upgrade_steps())
+    }
+
+    fn unexpected_message<T>(&self, msg_id: u16) -> CborDataError
+    where
+        T: MessageId,
+    {
+        CborDataError::unexpected_message::<T>(msg_id)
+    }
+}
@@ -0,0 +1,71 @@
+//! Capture message fields that the reader doesn't recognize.
+//!
+//! serde normally ignores fields that a struct doesn't declare, so a
+//! message from a newer writer decodes without any sign that it carried
+//! extra data. To find out what was dropped, read the message as
+//! [`UnknownFields<T, V>`] instead of `T`. Any field that isn't part of
+//! `T` is kept in [`unknown_fields`][UnknownFields::unknown_fields], as a
+//! value of type `V`, e.g. [`serde_cbor::Value`] or `serde_json::Value`.
+//!
+//! ```
+//! # use aversion::group::{DataSink, DataSource};
+//! # use aversion::util::cbor::CborData;
+//! # use aversion::util::unknown_fields::UnknownFields;
+//! # use aversion::{assign_message_ids, Versioned};
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Versioned, Serialize, Deserialize)]
+//! struct FooV1 {
+//!     foo: u32,
+//! }
+//! # type Foo = FooV1;
+//! # assign_message_ids! { Foo: 1 }
+//!
+//! # let mut sink = CborData::new(Vec::new());
+//! # sink.write_message(&FooV1 { foo: 1 }).unwrap();
+//! # let buf = sink.into_inner();
+//! let mut src = CborData::new(&buf[..]);
+//! let header = src.read_header().unwrap();
+//! let msg: UnknownFields<FooV1, serde_cbor::Value> = src.read_message(&header).unwrap();
+//! assert_eq!(msg.msg.foo, 1);
+//! assert!(msg.unknown_fields.is_empty());
+//! ```
+//!
+//! This relies on `#[serde(flatten)]`, so it has the same limitations:
+//!
+//! - It only works with self-describing codecs, such as CBOR and JSON.
+//!   It doesn't work with [`FixedSizeCodec`].
+//! - `T` must be a struct with named fields.
+//! - `T` can't use `#[serde(deny_unknown_fields)]`.
+//!
+//! No upgrade is performed; `T` should be the message version that was
+//! written, which is normally the latest version known to the reader.
+//!
+//! [`FixedSizeCodec`]: crate::util::fixed::FixedSizeCodec
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A message, along with any fields that it didn't recognize.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnknownFields<T, V> {
+    /// The decoded message.
+    #[serde(flatten)]
+    pub msg: T,
+    /// The fields that aren't part of `T`, by name.
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, V>,
+}
+
+impl<T, V> UnknownFields<T, V> {
+    /// Returns `true` if the message contained any unknown fields.
+    pub fn has_unknown_fields(&self) -> bool {
+        !self.unknown_fields.is_empty()
+    }
+
+    /// Discard the unknown fields, returning the message.
+    pub fn into_inner(self) -> T {
+        self.msg
+    }
+}
@@ -5,6 +5,47 @@
 /// created from the older version using the [`FromVersion`] or
 /// [`IntoVersion`] traits.
 ///
+/// # Adding fields without a version bump
+///
+/// A field can be added to a struct without creating a new version, as
+/// long as old readers can ignore it and new readers can do without it.
+/// Serde already ignores unknown fields when decoding a self-describing
+/// format like CBOR, and `#[serde(default)]` fills in a field that's
+/// missing. Mark the struct with `#[versioned(additive)]` and each new
+/// field with `#[versioned(added)]`, and the derive macro will check that
+/// every added field has a default:
+///
+/// ```
+/// # use aversion::Versioned;
+/// # use serde::{Deserialize, Serialize};
+/// # type Foo = FooV1;
+/// #[derive(Versioned, Serialize, Deserialize)]
+/// #[versioned(additive)]
+/// struct FooV1 {
+///     foo: u32,
+///     #[versioned(added)]
+///     #[serde(default)]
+///     count: u32,
+///     #[versioned(added)]
+///     note: Option<String>,
+/// }
+/// ```
+///
+/// An added field with no default is a compile error:
+///
+/// ```compile_fail
+/// # use aversion::Versioned;
+/// # use serde::{Deserialize, Serialize};
+/// # type Foo = FooV1;
+/// #[derive(Versioned, Serialize, Deserialize)]
+/// #[versioned(additive)]
+/// struct FooV1 {
+///     foo: u32,
+///     #[versioned(added)]
+///     count: u32,
+/// }
+/// ```
+///
 pub trait Versioned {
     /// The data structure version.
     ///
@@ -15,6 +56,12 @@ pub trait Versioned {
     ///
     /// The [`UpgradeLatest`] trait can be derived, to automatically
     /// upgrade from any old version to the latest version.
+    ///
+    /// Version 0 is reserved for legacy data, written before the message
+    /// was versioned. A struct named `FooV0` describes that format; mark
+    /// the latest version with `#[versioned(legacy)]` so that the derived
+    /// `UpgradeLatest` decodes a version 0 header as `FooV0` and upgrades
+    /// it using `FromVersion<FooV0> for FooV1`.
     const VER: u16;
     /// The data structure base type.
     ///
@@ -23,6 +70,103 @@ pub trait Versioned {
     /// and [`MessageId`]. Those two traits should not be needed on older
     /// versions.
     type Base: Versioned;
+    /// A hash of the data structure's layout.
+    ///
+    /// `#[derive(Versioned)]` computes this from the name and type of
+    /// each field, as they are written in the source code. Two builds
+    /// that disagree about the contents of a particular version will
+    /// (almost certainly) have different hashes, so peers can compare
+    /// hashes to detect incompatible builds.
+    ///
+    /// The hash is stable across compilations and platforms, and isn't
+    /// affected by formatting or comments. It is a purely syntactic
+    /// check: changing a type alias that a field refers to, or changing
+    /// serde attributes, won't change the hash.
+    ///
+    /// Implementations that don't set this get the value 0.
+    const SCHEMA_HASH: u64 = 0;
+    /// A description of the data structure's layout.
+    ///
+    /// This is the canonical string of field names and types that
+    /// [`SCHEMA_HASH`][Self::SCHEMA_HASH] is computed from, e.g.
+    /// `"foo:u32 ;bar:Option < String > ;"`. It can be recorded alongside
+    /// the data, so that tools can describe messages without the original
+    /// Rust types; see [`SchemaManifest`].
+    ///
+    /// Implementations that don't set this get an empty string.
+    ///
+    /// [`SchemaManifest`]: crate::util::preamble::SchemaManifest
+    const SCHEMA: &'static str = "";
+    /// The data structure minor version.
+    ///
+    /// Minor versions describe backward-compatible changes that don't
+    /// need a new type, e.g. adding a field that older readers can ignore
+    /// and newer readers can fill in with `#[serde(default)]`. The derive
+    /// macro sets this from the `#[versioned(minor = N)]` attribute.
+    ///
+    /// Only [`VER`][Self::VER] (the major version) is used to select which
+    /// type to deserialize, and the upgrade chain only steps between major
+    /// versions. A message with any minor version is decoded by the type
+    /// with the matching major version. The latest type's `VER` is the
+    /// highest major version that can be decoded; there is no upper limit
+    /// on the minor version.
+    ///
+    /// Implementations that don't set this get the value 0.
+    const MINOR_VER: u16 = 0;
+    /// The full version, made up of [`VER`][Self::VER] and
+    /// [`MINOR_VER`][Self::MINOR_VER].
+    const VERSION: Version = Version {
+        major: Self::VER,
+        minor: Self::MINOR_VER,
+    };
+}
+
+/// A marker for the latest version of a data structure.
+///
+/// This is implemented by `#[derive(Versioned)]` for structs marked with
+/// `#[versioned(latest)]`. It makes the choice of latest version explicit,
+/// rather than only implied by the type alias, and the derive checks that
+/// the type alias points at the marked struct:
+/// ```
+/// # use aversion::{IsLatest, Versioned};
+/// #[derive(Versioned)]
+/// struct FooV1 {}
+///
+/// #[derive(Versioned)]
+/// #[versioned(latest)]
+/// struct FooV2 {}
+///
+/// type Foo = FooV2;
+///
+/// fn check<T: IsLatest>() {}
+/// check::<Foo>();
+/// ```
+///
+/// Marking two versions in the same family as latest is an error, since
+/// the type alias can't point at both of them:
+/// ```compile_fail
+/// # use aversion::Versioned;
+/// #[derive(Versioned)]
+/// #[versioned(latest)]
+/// struct FooV1 {}
+///
+/// #[derive(Versioned)]
+/// #[versioned(latest)]
+/// struct FooV2 {}
+///
+/// type Foo = FooV2;
+/// ```
+pub trait IsLatest: Versioned {}
+
+/// A two-part version number.
+///
+/// See [`Versioned::MINOR_VER`] for how the two parts are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// The major version.
+    pub major: u16,
+    /// The minor version.
+    pub minor: u16,
 }
 
 /// Convert an older message version to a newer message version.
@@ -74,6 +218,59 @@ where
     }
 }
 
+/// Convert a borrowed older message version to a newer message version.
+///
+/// [`FromVersion`] consumes the old value, which is what an upgrade
+/// usually wants: a freshly decoded message isn't needed afterwards.
+/// When the old value must be kept, e.g. during a migration that writes
+/// the upgraded copy alongside the original, `FromVersionRef` upgrades
+/// it without a clone.
+///
+/// This is implemented for any `U: From<&T>`, so a single-step upgrade
+/// only needs a standard `From` impl. For a struct marked
+/// `#[versioned(by_ref)]`, `#[derive(UpgradeLatest)]` also implements it
+/// for each older version, by chaining those `From` impls.
+///
+/// As with [`IntoVersion`], the corresponding [`IntoVersionRef`] is
+/// provided by a blanket implementation.
+pub trait FromVersionRef<T>: Versioned
+where
+    T: Versioned,
+{
+    /// Convert from a reference to an older `Versioned` type to a newer
+    /// `Versioned` type.
+    fn from_version_ref(t: &T) -> Self;
+}
+
+impl<T, U> FromVersionRef<T> for U
+where
+    T: Versioned,
+    U: Versioned + for<'a> From<&'a T>,
+{
+    fn from_version_ref(t: &T) -> Self {
+        U::from(t)
+    }
+}
+
+/// Convert a borrowed older message version to a newer message version.
+///
+/// This is the inverse of [`FromVersionRef`]; see its documentation for more.
+pub trait IntoVersionRef<T> {
+    /// Convert from a reference to an older `Versioned` type to a newer
+    /// `Versioned` type, leaving the original in place.
+    fn to_version(&self) -> T;
+}
+
+impl<T, U> IntoVersionRef<U> for T
+where
+    T: Versioned,
+    U: FromVersionRef<T>,
+{
+    fn to_version(&self) -> U {
+        U::from_version_ref(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,95 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use std::io::Cursor;
+
+/// The struct as it was first released.
+mod old {
+    use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+    pub struct FooV1 {
+        pub foo: u32,
+    }
+
+    pub type Foo = FooV1;
+
+    assign_message_ids! {
+        Foo: 1,
+    }
+}
+
+/// The same version, after fields were added without a version bump.
+mod new {
+    use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+    use serde::{Deserialize, Serialize};
+
+    fn default_limit() -> u32 {
+        100
+    }
+
+    #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+    #[versioned(additive)]
+    pub struct FooV1 {
+        pub foo: u32,
+        #[versioned(added)]
+        #[serde(default)]
+        pub count: u32,
+        #[versioned(added)]
+        #[serde(default = "default_limit")]
+        pub limit: u32,
+        #[versioned(added)]
+        pub note: Option<String>,
+    }
+
+    pub type Foo = FooV1;
+
+    assign_message_ids! {
+        Foo: 1,
+    }
+}
+
+fn encode<T>(msg: &T) -> Vec<u8>
+where
+    T: serde::Serialize + aversion::Versioned,
+    T::Base: aversion::MessageId,
+{
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(msg).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_new_reader_old_message() {
+    let buf = encode(&old::Foo { foo: 7 });
+    let mut src = CborData::new(Cursor::new(buf));
+    let msg: new::Foo = src.expect_message().unwrap();
+    assert_eq!(
+        msg,
+        new::Foo {
+            foo: 7,
+            count: 0,
+            limit: 100,
+            note: None,
+        }
+    );
+}
+
+#[test]
+fn test_old_reader_new_message() {
+    let buf = encode(&new::Foo {
+        foo: 7,
+        count: 3,
+        limit: 5,
+        note: Some("hello".to_owned()),
+    });
+    let mut src = CborData::new(Cursor::new(buf));
+    let msg: old::Foo = src.expect_message().unwrap();
+    assert_eq!(msg, old::Foo { foo: 7 });
+}
+
+#[test]
+fn test_same_version() {
+    use aversion::Versioned;
+    assert_eq!(old::FooV1::VER, new::FooV1::VER);
+}
@@ -0,0 +1,59 @@
+use aversion::group::DataSink;
+use aversion::util::cbor::CborData;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct SnapshotV1 {
+    rows: Vec<u64>,
+}
+
+type Snapshot = SnapshotV1;
+
+assign_message_ids! {
+    Snapshot: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Broadcast {
+    Snapshot(Snapshot),
+}
+
+/// A consumer that sums the rows of each snapshot it receives.
+fn consumer(rx: mpsc::Receiver<Arc<Broadcast>>) -> thread::JoinHandle<(u64, Arc<Broadcast>)> {
+    thread::spawn(move || {
+        let msg = rx.recv().unwrap();
+        let Broadcast::Snapshot(snapshot) = &*msg;
+        (snapshot.rows.iter().sum(), msg)
+    })
+}
+
+#[test]
+fn test_read_message_arc() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&SnapshotV1 {
+        rows: (1..=100).collect(),
+    })
+    .unwrap();
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+
+    let msg = Broadcast::read_message_arc(&mut src).unwrap();
+
+    let (tx1, rx1) = mpsc::channel();
+    let (tx2, rx2) = mpsc::channel();
+    let consumers = [consumer(rx1), consumer(rx2)];
+    tx1.send(Arc::clone(&msg)).unwrap();
+    tx2.send(Arc::clone(&msg)).unwrap();
+
+    for handle in consumers {
+        let (sum, received) = handle.join().unwrap();
+        assert_eq!(sum, 5050);
+        // Both consumers got the same allocation, not a copy.
+        assert!(Arc::ptr_eq(&received, &msg));
+    }
+    assert_eq!(Arc::strong_count(&msg), 1);
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "base64")]
+
+use aversion::group::{DataSink, DataSource, GroupErrorKind};
+use aversion::util::base64_line::{Base64LineSink, Base64LineSource};
+use aversion::util::cbor::CborDataError;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn encoded() -> String {
+    let mut sink = Base64LineSink::new(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Bar {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    sink.write_message(&Foo { foo: 3 }).unwrap();
+    sink.flush().unwrap();
+    String::from_utf8(sink.into_inner()).unwrap()
+}
+
+#[test]
+fn test_round_trip() {
+    let text = encoded();
+    assert_eq!(text.as_bytes().lines().count(), 3);
+    assert!(text.ends_with('\n'));
+
+    let mut src = Base64LineSource::new(text.as_bytes());
+    let msgs: Vec<MyGroup> = (0..3)
+        .map(|_| MyGroup::read_message(&mut src).unwrap())
+        .collect();
+    assert_eq!(
+        msgs,
+        vec![
+            MyGroup::Foo(FooV1 { foo: 1 }),
+            MyGroup::Bar(BarV1 {
+                bar: "hello".to_owned()
+            }),
+            MyGroup::Foo(FooV1 { foo: 3 }),
+        ]
+    );
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_pasted_lines() {
+    // Blank lines, indentation, and CRLF line endings are tolerated.
+    let text: String = encoded()
+        .lines()
+        .map(|line| format!("  {}\r\n\n", line))
+        .collect();
+    let mut src = Base64LineSource::new(text.as_bytes());
+    for _ in 0..3 {
+        MyGroup::read_message(&mut src).unwrap();
+    }
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_malformed_base64() {
+    let mut text = encoded();
+    text.insert_str(0, "not base64!\n");
+
+    let mut src = Base64LineSource::new(text.as_bytes());
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(err, CborDataError::Io(Some(_))));
+    assert_eq!(err.kind(), GroupErrorKind::Framing);
+
+    // The bad line was consumed; the next one can be read.
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(FooV1 { foo: 1 })
+    );
+}
+
+#[test]
+fn test_length_mismatch() {
+    // A valid base64 line that is missing the end of its body.
+    let mut sink = Base64LineSink::new(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    let text = String::from_utf8(sink.into_inner()).unwrap();
+    let truncated = &text[..text.len() - 5];
+
+    let mut src = Base64LineSource::new(truncated.as_bytes());
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert_eq!(err.kind(), GroupErrorKind::Framing);
+}
@@ -0,0 +1,68 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{BatchEncoder, CborData};
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct RowV1 {
+    id: u64,
+    name: String,
+}
+
+type Row = RowV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct EndV1;
+
+type End = EndV1;
+
+assign_message_ids! {
+    Row: 1,
+    End: 2,
+}
+
+fn rows() -> Vec<RowV1> {
+    // Vary the body size, so that the buffer shrinks and grows.
+    (0..20)
+        .map(|id| RowV1 {
+            id,
+            name: "x".repeat((id as usize * 7) % 50),
+        })
+        .collect()
+}
+
+#[test]
+fn test_batched_matches_write_message() {
+    let mut plain = CborData::new(Vec::new());
+    let mut batched = CborData::new(Vec::new());
+    let mut encoder = BatchEncoder::new();
+    for row in rows() {
+        plain.write_message(&row).unwrap();
+        batched.write_message_batched(&mut encoder, &row).unwrap();
+    }
+    plain.write_message(&EndV1).unwrap();
+    batched.write_message_batched(&mut encoder, &EndV1).unwrap();
+    assert!(encoder.capacity() > 0);
+
+    let buf = batched.into_inner();
+    assert_eq!(buf, plain.into_inner());
+
+    let mut src = CborData::new(&buf[..]);
+    for row in rows() {
+        let msg: Row = src.expect_message().unwrap();
+        assert_eq!(msg, row);
+    }
+    let _: End = src.expect_message().unwrap();
+}
+
+#[test]
+fn test_batched_canonical() {
+    let mut plain = CborData::new(Vec::new()).canonical();
+    let mut batched = CborData::new(Vec::new()).canonical();
+    let mut encoder = BatchEncoder::with_capacity(64);
+    for row in rows() {
+        plain.write_message(&row).unwrap();
+        batched.write_message_batched(&mut encoder, &row).unwrap();
+    }
+    assert_eq!(batched.into_inner(), plain.into_inner());
+}
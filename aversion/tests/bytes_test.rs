@@ -0,0 +1,74 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct BlobV1 {
+    data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BlobV2 {
+    #[serde(with = "aversion::util::bytes")]
+    data: Vec<u8>,
+}
+
+type Blob = BlobV2;
+
+assign_message_ids! {
+    Blob: 1,
+}
+
+impl FromVersion<BlobV1> for BlobV2 {
+    fn from_version(v1: BlobV1) -> Self {
+        BlobV2 { data: v1.data }
+    }
+}
+
+fn data() -> Vec<u8> {
+    (0..=255).cycle().take(1000).collect()
+}
+
+fn encode<T>(msg: &T) -> Vec<u8>
+where
+    T: Serialize + Versioned,
+    T::Base: aversion::MessageId,
+{
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(msg).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_bytes_size() {
+    let plain = encode(&BlobV1 { data: data() });
+    let bytes = encode(&BlobV2 { data: data() });
+    // Values 24 and up take 2 bytes each as integers, but 1 byte in a
+    // byte string.
+    assert!(plain.len() > 1800);
+    assert!(bytes.len() < 1020);
+}
+
+#[test]
+fn test_bytes_upgrade() {
+    let mut buf = encode(&BlobV1 { data: data() });
+    buf.extend(encode(&BlobV2 { data: data() }));
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let old: Blob = src.expect_message().unwrap();
+    let new: Blob = src.expect_message().unwrap();
+    assert_eq!(old, new);
+
+    // The upgraded message is written as a byte string.
+    assert_eq!(encode(&old), encode(&new));
+}
+
+#[test]
+fn test_bytes_reads_sequence() {
+    // A field written without the attribute can still be decoded.
+    let buf = serde_cbor::to_vec(&BlobV1 { data: data() }).unwrap();
+    let msg: BlobV2 = serde_cbor::from_slice(&buf).unwrap();
+    assert_eq!(msg.data, data());
+}
@@ -0,0 +1,95 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CanonicalCborCodec, CborData};
+use aversion::util::{BasicHeader, Codec};
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct SignedV1 {
+    zeta: u32,
+    alpha: String,
+    ratio: f64,
+    counts: HashMap<String, u32>,
+}
+
+type Signed = SignedV1;
+
+assign_message_ids! {
+    Signed: 1,
+}
+
+fn signed(keys: &[&str]) -> Signed {
+    Signed {
+        zeta: 7,
+        alpha: "hello".to_owned(),
+        ratio: 1.5,
+        counts: keys
+            .iter()
+            .map(|k| (k.to_string(), k.len() as u32))
+            .collect(),
+    }
+}
+
+fn encode(msg: &Signed) -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new()).canonical();
+    sink.write_message(msg).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_canonical_is_deterministic() {
+    let keys: Vec<String> = (0..50).map(|n| format!("key{}", n)).collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    let mut reversed = keys.clone();
+    reversed.reverse();
+
+    // Each HashMap has its own random iteration order.
+    let first = encode(&signed(&keys));
+    let second = encode(&signed(&keys));
+    assert_eq!(first, second);
+
+    // Insertion order doesn't matter either.
+    assert_eq!(encode(&signed(&reversed)), first);
+
+    // The output can be read normally.
+    let mut src = CborData::new(Cursor::new(first));
+    let decoded: Signed = src.expect_message().unwrap();
+    assert_eq!(decoded, signed(&keys));
+}
+
+#[test]
+fn test_canonical_map_order() {
+    let mut map = HashMap::new();
+    map.insert("bb", 1u8);
+    map.insert("c", 2);
+    map.insert("a", 3);
+
+    let mut buf = Vec::new();
+    CanonicalCborCodec.encode(&map, &mut buf).unwrap();
+    // Shorter keys sort first, then keys of the same length sort bytewise.
+    let expected = [
+        0xa3, // map(3)
+        0x61, b'a', 0x03, // "a": 3
+        0x61, b'c', 0x02, // "c": 2
+        0x62, b'b', b'b', 0x01, // "bb": 1
+    ];
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn test_canonical_struct_fields() {
+    let buf = encode(&signed(&[]));
+    let body = &buf[BasicHeader::SIZE..];
+    let expected = [
+        0xa4, // map(4)
+        0x64, b'z', b'e', b't', b'a', 0x07, // "zeta": 7
+        0x65, b'a', b'l', b'p', b'h', b'a', // "alpha"
+        0x65, b'h', b'e', b'l', b'l', b'o', // "hello"
+        0x65, b'r', b'a', b't', b'i', b'o', // "ratio"
+        0xf9, 0x3e, 0x00, // 1.5, as a half-precision float
+        0x66, b'c', b'o', b'u', b'n', b't', b's', 0xa0, // "counts": {}
+    ];
+    assert_eq!(body, expected);
+}
@@ -0,0 +1,139 @@
+use aversion::group::DataSourceExt;
+use aversion::util::cbor::CborData;
+use aversion::util::chunked::{ChunkReader, ChunkWriter};
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct UploadV1 {
+    name: String,
+    size: u64,
+}
+
+type Upload = UploadV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct DoneV1 {}
+
+type Done = DoneV1;
+
+assign_message_ids! {
+    Upload: 1,
+    Done: 2,
+}
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|ii| (ii % 251) as u8).collect()
+}
+
+#[test]
+fn test_chunk_framing() {
+    let mut writer = ChunkWriter::new(Vec::new()).chunk_size(4);
+    writer.write_all(b"hello").unwrap();
+    writer.write_all(b" world").unwrap();
+    let buf = writer.finish().unwrap();
+
+    let expected: Vec<u8> = [
+        &[0, 0, 0, 4][..],
+        b"hell",
+        &[0, 0, 0, 4],
+        b"o wo",
+        &[0, 0, 0, 3],
+        b"rld",
+        &[0, 0, 0, 0],
+    ]
+    .concat();
+    assert_eq!(buf, expected);
+
+    let mut reader = ChunkReader::new(&buf[..]);
+    let mut out = String::new();
+    reader.read_to_string(&mut out).unwrap();
+    assert_eq!(out, "hello world");
+    assert!(reader.is_finished());
+}
+
+#[test]
+fn test_chunked_message() {
+    let data = payload(200_000);
+    let upload = UploadV1 {
+        name: "big.bin".into(),
+        size: data.len() as u64,
+    };
+
+    let mut sink = CborData::new(Vec::new());
+    let mut chunks = sink.write_chunked(&upload).unwrap().chunk_size(1000);
+    for piece in data.chunks(777) {
+        chunks.write_all(piece).unwrap();
+    }
+    chunks.finish().unwrap();
+    sink.write_chunked(&DoneV1 {}).unwrap().finish().unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(&buf[..]);
+    let msg: Upload = src.expect_message().unwrap();
+    assert_eq!(msg, upload);
+
+    // Read the payload incrementally, in small pieces.
+    let mut body = src.read_chunked_body();
+    let mut received = Vec::new();
+    let mut piece = [0u8; 300];
+    loop {
+        let count = body.read(&mut piece).unwrap();
+        if count == 0 {
+            break;
+        }
+        received.extend_from_slice(&piece[..count]);
+    }
+    assert!(body.is_finished());
+    assert_eq!(received, data);
+
+    // The empty payload is skipped, and the stream stays in sync.
+    let done: Done = src.expect_message().unwrap();
+    assert_eq!(done, DoneV1 {});
+    assert_eq!(src.read_chunked_body().finish().unwrap(), 0);
+}
+
+#[test]
+fn test_chunked_skip() {
+    let mut sink = CborData::new(Vec::new());
+    let upload = UploadV1 {
+        name: "skipped".into(),
+        size: 10_000,
+    };
+    let mut chunks = sink.write_chunked(&upload).unwrap().chunk_size(64);
+    chunks.write_all(&payload(10_000)).unwrap();
+    chunks.finish().unwrap();
+    sink.write_chunked(&DoneV1 {}).unwrap().finish().unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(&buf[..]);
+    let _: Upload = src.expect_message().unwrap();
+    let mut body = src.read_chunked_body();
+    let mut first = [0u8; 10];
+    body.read_exact(&mut first).unwrap();
+    assert_eq!(body.finish().unwrap(), 9_990);
+    let _: Done = src.expect_message().unwrap();
+}
+
+#[test]
+fn test_chunked_truncated() {
+    let mut writer = ChunkWriter::new(Vec::new()).chunk_size(8);
+    writer.write_all(&payload(20)).unwrap();
+    let mut buf = writer.finish().unwrap();
+
+    // Missing end marker.
+    buf.truncate(buf.len() - 4);
+    let mut out = Vec::new();
+    let err = ChunkReader::new(&buf[..])
+        .read_to_end(&mut out)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    // Truncated in the middle of a chunk.
+    buf.truncate(buf.len() - 2);
+    let err = ChunkReader::new(&buf[..])
+        .read_to_end(&mut out)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,104 @@
+use aversion::group::{DataSinkExt, DataSource};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::compact::{compact, CompactError};
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, Clone, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PutV1 {
+    key: u32,
+    value: String,
+}
+
+type Put = PutV1;
+
+#[derive(Debug, Clone, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct DeleteV1 {
+    key: u32,
+}
+
+type Delete = DeleteV1;
+
+assign_message_ids! {
+    Put: 1,
+    Delete: 2,
+}
+
+#[derive(Debug, Clone, PartialEq, GroupDeserialize, GroupSerialize)]
+enum State {
+    Put(Put),
+    Delete(Delete),
+}
+
+fn key(msg: &State) -> u32 {
+    match msg {
+        State::Put(put) => put.key,
+        State::Delete(delete) => delete.key,
+    }
+}
+
+fn put(key: u32, value: &str) -> State {
+    State::Put(PutV1 {
+        key,
+        value: value.to_owned(),
+    })
+}
+
+fn read_all(buf: Vec<u8>) -> Vec<State> {
+    let mut src = CborData::new(Cursor::new(buf));
+    let mut msgs = Vec::new();
+    while src.peek_msg_id().is_ok() {
+        msgs.push(State::read_message(&mut src).unwrap());
+    }
+    msgs
+}
+
+#[test]
+fn test_compact_keeps_latest() {
+    let log = vec![
+        put(1, "a"),
+        put(2, "b"),
+        put(1, "c"),
+        put(3, "d"),
+        State::Delete(DeleteV1 { key: 2 }),
+        put(3, "e"),
+    ];
+    let mut sink = CborData::new(Vec::new());
+    sink.write_all_messages(&log).unwrap();
+
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+    let mut dst = CborData::new(Vec::new());
+    let count = compact(&mut src, &mut dst, key).unwrap();
+    assert_eq!(count, 3);
+
+    // Survivors stay in the order of their last occurrence.
+    assert_eq!(
+        read_all(dst.into_inner()),
+        [put(1, "c"), State::Delete(DeleteV1 { key: 2 }), put(3, "e"),]
+    );
+}
+
+#[test]
+fn test_compact_empty() {
+    let mut src = CborData::new(Cursor::new(Vec::new()));
+    let mut dst = CborData::new(Vec::new());
+    assert_eq!(compact(&mut src, &mut dst, key).unwrap(), 0);
+    assert!(dst.into_inner().is_empty());
+}
+
+#[test]
+fn test_compact_truncated() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_all_messages(&[put(1, "a"), put(2, "b")])
+        .unwrap();
+    let mut buf = sink.into_inner();
+    buf.pop();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let mut dst = CborData::new(Vec::new());
+    let err = compact(&mut src, &mut dst, key).unwrap_err();
+    assert!(matches!(err, CompactError::Read(CborDataError::Eof)));
+    // Nothing is written unless the whole source was read.
+    assert!(dst.into_inner().is_empty());
+}
@@ -0,0 +1,111 @@
+use aversion::util::fixed::{decode_fixed, BigEndian, FixedSizeError, LittleEndian, PackedCodec};
+use aversion::util::Codec;
+use aversion::Versioned;
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// An allocator that counts the allocations made by each thread.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Ignore allocations made while the thread is being torn down.
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|count| count.get())
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Versioned)]
+#[versioned(fixed_size = 5)]
+struct PointV1 {
+    x: i16,
+    y: i16,
+    visible: bool,
+}
+
+type Point = PointV1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Versioned)]
+#[versioned(fixed_size = 28)]
+struct TelemetryV1 {
+    seq: u32,
+    temperature: f32,
+    origin: Point,
+    readings: [u16; 4],
+    id: [u8; 7],
+}
+
+type Telemetry = TelemetryV1;
+
+fn telemetry() -> TelemetryV1 {
+    TelemetryV1 {
+        seq: 0x0102_0304,
+        temperature: -1.5,
+        origin: PointV1 {
+            x: -3,
+            y: 4,
+            visible: true,
+        },
+        readings: [10, 20, 30, 40],
+        id: *b"sensor1",
+    }
+}
+
+#[test]
+fn test_decode_fixed_no_alloc() {
+    let msg = telemetry();
+    let mut le = Vec::new();
+    PackedCodec::<LittleEndian>::new()
+        .encode(&msg, &mut le)
+        .unwrap();
+    let mut be = Vec::new();
+    PackedCodec::<BigEndian>::new()
+        .encode(&msg, &mut be)
+        .unwrap();
+
+    let before = allocations();
+    let from_le: Telemetry = decode_fixed::<_, LittleEndian>(&le).unwrap();
+    let from_be: Telemetry = decode_fixed::<_, BigEndian>(&be).unwrap();
+    assert_eq!(allocations(), before);
+
+    assert_eq!(from_le, msg);
+    assert_eq!(from_be, msg);
+}
+
+#[test]
+fn test_decode_fixed_wrong_size() {
+    let mut buf = Vec::new();
+    PackedCodec::<LittleEndian>::new()
+        .encode(&telemetry(), &mut buf)
+        .unwrap();
+
+    let err = decode_fixed::<Telemetry, LittleEndian>(&buf[..27]).unwrap_err();
+    assert!(matches!(err, FixedSizeError::Eof));
+
+    buf.push(0);
+    let err = decode_fixed::<Telemetry, LittleEndian>(&buf).unwrap_err();
+    assert!(matches!(err, FixedSizeError::TrailingBytes(1)));
+}
+
+#[test]
+fn test_decode_fixed_invalid() {
+    let buf = [0, 0, 0, 0, 2];
+    let err = decode_fixed::<Point, LittleEndian>(&buf).unwrap_err();
+    assert!(matches!(err, FixedSizeError::InvalidBool(2)));
+}
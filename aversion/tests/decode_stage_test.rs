@@ -0,0 +1,129 @@
+use aversion::group::{DecodeError, GroupHeader};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+assign_message_ids! {
+    Foo: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[deprecated_msg(3, "use Foo instead")]
+enum MyGroup {
+    Foo(Foo),
+}
+
+/// A frame with a `BasicHeader` and the given body.
+fn frame(msg_id: u16, msg_ver: u16, body: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    BasicHeader::new(msg_id, msg_ver, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(body);
+    buf
+}
+
+fn read_staged(buf: &[u8]) -> Result<MyGroup, DecodeError<BasicHeader, CborDataError>> {
+    let mut src = CborData::new(buf);
+    MyGroup::read_message_staged(&mut src)
+}
+
+#[test]
+fn test_staged_ok() {
+    let body = serde_cbor::to_vec(&FooV1 { foo: 5 }).unwrap();
+    let msg = read_staged(&frame(1, 1, &body)).unwrap();
+    assert_eq!(msg, MyGroup::Foo(FooV1 { foo: 5 }));
+}
+
+#[test]
+fn test_stage_header() {
+    // A truncated header.
+    let err = read_staged(&[0, 1, 0]).unwrap_err();
+    assert!(err.header().is_none());
+    assert!(matches!(err, DecodeError::Header(_)));
+    assert!(err.to_string().starts_with("failed to read header"));
+}
+
+#[test]
+fn test_stage_unknown_message() {
+    let err = read_staged(&frame(9, 1, &[0xa0])).unwrap_err();
+    match err {
+        DecodeError::UnknownMessage { header, error } => {
+            assert_eq!(header.msg_id(), 9);
+            assert_eq!(header.msg_ver(), 1);
+            assert!(matches!(error, CborDataError::UnknownMessage { msg_id: 9 }));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_stage_deprecated_message() {
+    let err = read_staged(&frame(3, 1, &[0xa0])).unwrap_err();
+    match err {
+        DecodeError::UnknownMessage { header, error } => {
+            assert_eq!(header.msg_id(), 3);
+            assert!(matches!(
+                error,
+                CborDataError::DeprecatedMessage {
+                    msg_id: 3,
+                    note: "use Foo instead"
+                }
+            ));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_stage_unsupported_version() {
+    let err = read_staged(&frame(1, 7, &[0xa0])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("unsupported version 7 of message id 1: {}", err.error())
+    );
+    match err {
+        DecodeError::UnsupportedVersion { header, error } => {
+            assert_eq!(header.msg_id(), 1);
+            assert_eq!(header.msg_ver(), 7);
+            assert!(matches!(
+                error,
+                CborDataError::UnknownVersion {
+                    got: 7,
+                    latest: 1,
+                    ..
+                }
+            ));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_stage_body() {
+    // A CBOR string, where a map is expected.
+    let err = read_staged(&frame(1, 1, &[0x61, b'x'])).unwrap_err();
+    match err {
+        DecodeError::Body { header, error } => {
+            assert_eq!(header.msg_id(), 1);
+            assert_eq!(header.msg_len, 2);
+            assert!(matches!(error, CborDataError::Serializer));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_into_error() {
+    let err = read_staged(&frame(9, 1, &[0xa0])).unwrap_err();
+    let inner = err.into_error();
+    assert!(matches!(inner, CborDataError::UnknownMessage { msg_id: 9 }));
+}
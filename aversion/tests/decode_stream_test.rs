@@ -0,0 +1,178 @@
+#![cfg(feature = "async")]
+
+use aversion::group::{DataSink, Priority};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::stream::decode_stream;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::executor::block_on;
+use futures::poll;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+impl Priority for Bar {
+    const PRIORITY: u8 = 5;
+}
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn encoded() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Bar {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    sink.write_message(&Foo { foo: 12345 }).unwrap();
+    sink.into_inner()
+}
+
+fn expected() -> Vec<MyGroup> {
+    vec![
+        MyGroup::Foo(Foo { foo: 1 }),
+        MyGroup::Bar(Bar {
+            bar: "hello".to_owned(),
+        }),
+        MyGroup::Foo(Foo { foo: 12345 }),
+    ]
+}
+
+/// Split `buf` into chunks at the given offsets.
+fn chunks(buf: &[u8], offsets: &[usize]) -> Vec<Bytes> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for &end in offsets.iter().chain(Some(&buf.len())) {
+        chunks.push(Bytes::copy_from_slice(&buf[start..end]));
+        start = end;
+    }
+    chunks
+}
+
+fn decode_all(chunks: Vec<Bytes>) -> Vec<Result<MyGroup, CborDataError>> {
+    block_on(decode_stream::<MyGroup, _>(stream::iter(chunks)).collect())
+}
+
+#[test]
+fn test_decode_stream() {
+    let buf = encoded();
+
+    let splits: &[&[usize]] = &[
+        // Everything in one chunk.
+        &[],
+        // Inside the first header, at a frame boundary, and inside a body.
+        &[3, 13, 20],
+        // An empty chunk, and a chunk holding the end of one frame and the
+        // start of the next.
+        &[0, 0, 11, 25],
+    ];
+    for offsets in splits {
+        let msgs: Vec<MyGroup> = decode_all(chunks(&buf, offsets))
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(msgs, expected(), "split at {:?}", offsets);
+    }
+
+    // One byte at a time.
+    let offsets: Vec<usize> = (1..buf.len()).collect();
+    let msgs: Vec<MyGroup> = decode_all(chunks(&buf, &offsets))
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(msgs, expected());
+}
+
+#[test]
+fn test_decode_stream_truncated() {
+    let buf = encoded();
+    let truncated = &buf[..buf.len() - 2];
+
+    let mut results = decode_all(chunks(truncated, &[5]));
+    assert_eq!(results.len(), 3);
+    assert!(matches!(results.pop(), Some(Err(CborDataError::Eof))));
+    let msgs: Vec<MyGroup> = results.into_iter().map(Result::unwrap).collect();
+    assert_eq!(msgs, &expected()[..2]);
+}
+
+#[test]
+fn test_decode_stream_max_frame_len() {
+    let buf = encoded();
+    let stream = decode_stream::<MyGroup, _>(stream::iter(chunks(&buf, &[])));
+    let results: Vec<_> = block_on(stream.max_frame_len(8).collect());
+
+    // The first frame is too long, and the stream ends there.
+    assert_eq!(results.len(), 1);
+    assert!(matches!(
+        results[0],
+        Err(CborDataError::FrameTooLong { max: 8, .. })
+    ));
+}
+
+#[test]
+fn test_decode_stream_cancelled() {
+    let buf = encoded();
+    let (tx, rx) = mpsc::unbounded();
+    let mut msgs = decode_stream::<MyGroup, _>(rx);
+
+    block_on(async {
+        // Half of the first frame arrives, and then the read is cancelled,
+        // as if another branch of a `select!` had completed first.
+        tx.unbounded_send(Bytes::copy_from_slice(&buf[..5]))
+            .unwrap();
+        assert!(poll!(msgs.next()).is_pending());
+
+        // The partial frame was kept, so a new read picks up where the
+        // cancelled one stopped.
+        tx.unbounded_send(Bytes::copy_from_slice(&buf[5..]))
+            .unwrap();
+        drop(tx);
+        let mut decoded = Vec::new();
+        while let Some(msg) = msgs.next().await {
+            decoded.push(msg.unwrap());
+        }
+        assert_eq!(decoded, expected());
+    });
+}
+
+#[test]
+fn test_decode_stream_priority() {
+    let buf = encoded();
+    let msgs = decode_stream::<MyGroup, _>(stream::iter(chunks(&buf, &[7]))).with_priority();
+    let msgs: Vec<(u8, MyGroup)> = block_on(msgs.map(Result::unwrap).collect());
+
+    // Foo doesn't implement `Priority`, so it gets priority 0.
+    let priorities: Vec<u8> = msgs.iter().map(|(priority, _)| *priority).collect();
+    assert_eq!(priorities, [0, 5, 0]);
+    let msgs: Vec<MyGroup> = msgs.into_iter().map(|(_, msg)| msg).collect();
+    assert_eq!(msgs, expected());
+
+    let truncated = &buf[..buf.len() - 2];
+    let msgs = decode_stream::<MyGroup, _>(stream::iter(chunks(truncated, &[]))).with_priority();
+    let mut results: Vec<_> = block_on(msgs.collect());
+    assert!(matches!(results.pop(), Some(Err(CborDataError::Eof))));
+    assert!(matches!(results.pop(), Some(Ok((5, MyGroup::Bar(_))))));
+}
@@ -0,0 +1,127 @@
+use aversion::group::{DataSource, GroupHeader};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::dedup::{DedupMode, DedupSource};
+use aversion::util::{ExtendedHeader, SequencedHeader};
+use aversion::{assign_message_ids, GroupDeserialize, MessageId, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct EventV1 {
+    value: u32,
+}
+
+type Event = EventV1;
+
+assign_message_ids! {
+    Event: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Events {
+    Event(Event),
+}
+
+type SequencedSource = CborData<Cursor<Vec<u8>>, SequencedHeader>;
+
+/// Write events with the given sequence numbers; the value is `seq * 10`.
+fn source(seqs: &[u64]) -> SequencedSource {
+    let mut buf = Vec::new();
+    for &seq in seqs {
+        let msg = Event {
+            value: (seq * 10).try_into().unwrap(),
+        };
+        let body = serde_cbor::to_vec(&msg).unwrap();
+        let header = SequencedHeader::for_msg(&msg, seq, body.len().try_into().unwrap());
+        header.serialize_into(&mut buf).unwrap();
+        buf.extend_from_slice(&body);
+    }
+    CborData::with_header(Cursor::new(buf))
+}
+
+/// Read all of the events, returning the values.
+fn read_all<H>(src: &mut impl DataSource<Error = CborDataError, Header = H>) -> Vec<u32>
+where
+    H: GroupHeader,
+{
+    let mut values = Vec::new();
+    while let Some(header) = src.try_read_header().unwrap() {
+        assert_eq!(header.msg_id(), Event::MSG_ID);
+        let Event { value } = src.read_message(&header).unwrap();
+        values.push(value);
+    }
+    values
+}
+
+#[test]
+fn test_drop_duplicate() {
+    let mut src = DedupSource::new(source(&[1, 2, 2, 3]), DedupMode::Strict);
+    assert_eq!(read_all(&mut src), vec![10, 20, 30]);
+    assert_eq!(src.dropped(), 1);
+}
+
+#[test]
+fn test_strict_drops_out_of_order() {
+    let mut src = DedupSource::new(source(&[1, 3, 2, 4]), DedupMode::Strict);
+    assert_eq!(read_all(&mut src), vec![10, 30, 40]);
+    assert_eq!(src.dropped(), 1);
+}
+
+#[test]
+fn test_window() {
+    // 2 arrives late but within the window; the second 3 is a duplicate;
+    // 1 is too old once 5 has been seen.
+    let mut src = DedupSource::new(source(&[1, 3, 2, 3, 5, 1, 4]), DedupMode::Window(2));
+    assert_eq!(read_all(&mut src), vec![10, 30, 20, 50, 40]);
+    assert_eq!(src.dropped(), 2);
+}
+
+#[test]
+fn test_dedup_group() {
+    let mut src = DedupSource::new(source(&[1, 1, 2]), DedupMode::Strict);
+    assert_eq!(
+        Events::read_message(&mut src).unwrap(),
+        Events::Event(Event { value: 10 })
+    );
+    assert_eq!(
+        Events::read_message(&mut src).unwrap(),
+        Events::Event(Event { value: 20 })
+    );
+    assert_eq!(src.dropped(), 1);
+}
+
+#[test]
+fn test_peek() {
+    let mut src = DedupSource::new(source(&[1, 1, 2]), DedupMode::Strict);
+    let header = src.read_header().unwrap();
+    src.skip_message(&header).unwrap();
+
+    // Peeking finds the next message that won't be dropped.
+    assert_eq!(src.peek_msg_id().unwrap(), Event::MSG_ID);
+    assert_eq!(src.dropped(), 1);
+    assert_eq!(read_all(&mut src), vec![20]);
+}
+
+#[test]
+fn test_extended_header() {
+    // Messages without a sequence number are never dropped.
+    let mut buf = Vec::new();
+    for (value, seq) in [
+        (10, Some(1)),
+        (11, Some(1)),
+        (20, None),
+        (21, None),
+        (30, Some(3)),
+    ] {
+        let body = serde_cbor::to_vec(&Event { value }).unwrap();
+        let mut header = ExtendedHeader::new(Event::MSG_ID, 1, body.len().try_into().unwrap());
+        header.seq = seq;
+        header.serialize_into(&mut buf).unwrap();
+        buf.extend_from_slice(&body);
+    }
+    let src = CborData::<_, ExtendedHeader>::with_header(Cursor::new(buf));
+    let mut src = DedupSource::new(src, DedupMode::Strict);
+    assert_eq!(read_all(&mut src), vec![10, 20, 21, 30]);
+    assert_eq!(src.dropped(), 1);
+}
@@ -0,0 +1,148 @@
+use aversion::group::{flags, DataSink, DataSource, GroupHeader};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::diff::{read_and_apply_diff, write_diff, Diffable, FieldDiff};
+use aversion::util::ExtendedHeader;
+use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+#[derive(Debug, Clone, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Versioned, UpgradeLatest, Diffable, Serialize, Deserialize)]
+struct FooV2 {
+    name: String,
+    limit: u32,
+    tags: Vec<String>,
+    owners: BTreeMap<String, u8>,
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 {
+            name: v1.name,
+            limit: 0,
+            tags: Vec::new(),
+            owners: BTreeMap::new(),
+        }
+    }
+}
+
+type Foo = FooV2;
+
+assign_message_ids! {
+    Foo: 1,
+}
+
+type ExtSink = CborData<Vec<u8>, ExtendedHeader>;
+type ExtSource = CborData<Cursor<Vec<u8>>, ExtendedHeader>;
+
+fn base() -> FooV2 {
+    let mut owners = BTreeMap::new();
+    owners.insert("alice".to_string(), 1);
+    FooV2 {
+        name: "primary".into(),
+        limit: 100,
+        tags: vec!["a".into(), "b".into()],
+        owners,
+    }
+}
+
+fn target() -> FooV2 {
+    let mut target = base();
+    target.limit = 200;
+    target.tags.push("c".into());
+    target
+}
+
+#[test]
+fn test_diff_apply() {
+    let base = base();
+    let target = target();
+
+    let diff = base.diff(&target).unwrap();
+    assert_eq!(diff.len(), 2);
+    assert!(diff.contains("limit"));
+    assert!(diff.contains("tags"));
+    assert!(!diff.contains("name"));
+
+    let mut copy = base.clone();
+    copy.apply_diff(diff).unwrap();
+    assert_eq!(copy, target);
+
+    // Equal values produce an empty diff.
+    assert!(target.diff(&target).unwrap().is_empty());
+}
+
+#[test]
+fn test_diff_roundtrip() {
+    let base = base();
+    let target = target();
+
+    let mut sink = ExtSink::with_header(Vec::new());
+    write_diff(&mut sink, &base, &target).unwrap();
+    let diff_buf = sink.into_inner();
+
+    let mut sink = ExtSink::with_header(Vec::new());
+    sink.write_message(&target).unwrap();
+    let full_buf = sink.into_inner();
+    assert!(diff_buf.len() < full_buf.len());
+
+    // The diff is marked in the header.
+    let mut src = ExtSource::with_header(Cursor::new(diff_buf.clone()));
+    let header = src.read_header().unwrap();
+    assert_eq!(header.msg_id(), 1);
+    assert_eq!(header.msg_ver(), 2);
+    assert_eq!(header.flags(), flags::DIFF);
+
+    let mut src = ExtSource::with_header(Cursor::new(diff_buf));
+    let mut copy = base;
+    read_and_apply_diff(&mut src, &mut copy).unwrap();
+    assert_eq!(copy, target);
+}
+
+#[test]
+fn test_full_then_diff() {
+    // A full message replaces the value, and is upgraded if needed.
+    let mut sink = ExtSink::with_header(Vec::new());
+    sink.write_message(&FooV1 {
+        name: "primary".into(),
+    })
+    .unwrap();
+    let mut upgraded = FooV2::from_version(FooV1 {
+        name: "primary".into(),
+    });
+    let mut next = upgraded.clone();
+    next.limit = 5;
+    write_diff(&mut sink, &upgraded, &next).unwrap();
+
+    let mut src = ExtSource::with_header(Cursor::new(sink.into_inner()));
+    let mut copy = base();
+    read_and_apply_diff(&mut src, &mut copy).unwrap();
+    assert_eq!(copy, upgraded);
+    read_and_apply_diff(&mut src, &mut copy).unwrap();
+    upgraded.limit = 5;
+    assert_eq!(copy, upgraded);
+}
+
+#[test]
+fn test_bad_diff() {
+    let mut copy = base();
+
+    let mut diff = FieldDiff::new();
+    diff.insert("limit", &7u32).unwrap();
+    diff.insert("color", &"red").unwrap();
+    let err = copy.apply_diff(diff).unwrap_err();
+    assert!(matches!(err, CborDataError::UnknownDiffField { field } if field == "color"));
+
+    let mut diff = FieldDiff::new();
+    diff.insert("limit", &7u32).unwrap();
+    diff.insert("name", &12u32).unwrap();
+    assert!(copy.apply_diff(diff).is_err());
+
+    // A failed diff doesn't change anything.
+    assert_eq!(copy, base());
+}
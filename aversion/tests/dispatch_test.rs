@@ -0,0 +1,122 @@
+use aversion::group::{DataSink, DispatchError, Dispatcher, RawMessage, WithUnknown};
+use aversion::util::cbor::CborData;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+use std::sync::mpsc::channel;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BazV1 {
+    baz: bool,
+}
+
+type Baz = BazV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+    Baz: 3,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+    Baz(Baz),
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum SmallGroup {
+    Foo(Foo),
+}
+
+fn bar(s: &str) -> Bar {
+    BarV1 { bar: s.to_owned() }
+}
+
+#[test]
+fn test_dispatch() {
+    let (foo_tx, foo_rx) = channel::<Foo>();
+    let (bar_tx, bar_rx) = channel::<Bar>();
+    let mut dispatcher = Dispatcher::<MyGroup>::new().route(foo_tx).route(bar_tx);
+
+    dispatcher.feed(MyGroup::Foo(FooV1 { foo: 1 })).unwrap();
+    dispatcher.feed(MyGroup::Bar(bar("one"))).unwrap();
+    dispatcher.feed(MyGroup::Foo(FooV1 { foo: 2 })).unwrap();
+
+    let foos: Vec<Foo> = foo_rx.try_iter().collect();
+    assert_eq!(foos, [FooV1 { foo: 1 }, FooV1 { foo: 2 }]);
+    let bars: Vec<Bar> = bar_rx.try_iter().collect();
+    assert_eq!(bars, [bar("one")]);
+
+    // Baz has no route, and there's no fallback.
+    let err = dispatcher.feed(MyGroup::Baz(BazV1 { baz: true }));
+    match err {
+        Err(DispatchError::Unrouted(msg)) => assert_eq!(msg, MyGroup::Baz(BazV1 { baz: true })),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // The receiver is gone.
+    drop(bar_rx);
+    let err = dispatcher.feed(MyGroup::Bar(bar("two"))).unwrap_err();
+    assert!(matches!(err, DispatchError::Disconnected { .. }));
+}
+
+#[test]
+fn test_dispatch_fallback() {
+    let unrouted = Rc::new(RefCell::new(Vec::new()));
+    let seen = unrouted.clone();
+    let (foo_tx, foo_rx) = channel::<Foo>();
+    let mut dispatcher = Dispatcher::<MyGroup>::new()
+        .route(foo_tx)
+        .fallback(move |msg| seen.borrow_mut().push(msg));
+
+    dispatcher.feed(MyGroup::Bar(bar("x"))).unwrap();
+    dispatcher.feed(MyGroup::Foo(FooV1 { foo: 3 })).unwrap();
+    dispatcher.feed(MyGroup::Baz(BazV1 { baz: false })).unwrap();
+
+    assert_eq!(foo_rx.try_recv().unwrap(), FooV1 { foo: 3 });
+    assert_eq!(
+        *unrouted.borrow(),
+        [MyGroup::Bar(bar("x")), MyGroup::Baz(BazV1 { baz: false })]
+    );
+}
+
+#[test]
+fn test_dispatch_decoded() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 7 }).unwrap();
+    sink.write_message(&bar("decoded")).unwrap();
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+
+    // Messages outside the group are routed as `RawMessage`.
+    let (foo_tx, foo_rx) = channel::<Foo>();
+    let raw = Rc::new(RefCell::new(Vec::new()));
+    let seen = raw.clone();
+    let mut dispatcher = Dispatcher::<WithUnknown<SmallGroup>>::new()
+        .route(foo_tx)
+        .route_fn(move |msg: RawMessage| seen.borrow_mut().push(msg.id));
+
+    for _ in 0..2 {
+        let msg = WithUnknown::<SmallGroup>::read_message(&mut src).unwrap();
+        dispatcher.feed(msg).unwrap();
+    }
+    assert_eq!(foo_rx.try_recv().unwrap(), FooV1 { foo: 7 });
+    assert_eq!(*raw.borrow(), [2]);
+}
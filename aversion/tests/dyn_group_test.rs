@@ -0,0 +1,83 @@
+use aversion::group::{DataSink, DynGroup};
+use aversion::util::cbor::CborData;
+use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV2 {
+    foo: u64,
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        Self { foo: v1.foo.into() }
+    }
+}
+
+type Foo = FooV2;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 10,
+    Bar: 20,
+}
+
+#[test]
+fn test_dyn_group() {
+    let mut out_stream = CborData::new(Vec::<u8>::new());
+    out_stream.write_message(&FooV1 { foo: 42 }).unwrap();
+    out_stream
+        .write_message(&BarV1 {
+            bar: "hello".to_owned(),
+        })
+        .unwrap();
+    let buf = out_stream.into_inner();
+
+    let mut group = DynGroup::new();
+    group.register::<Foo>();
+    group.register::<Bar>();
+
+    let mut src = CborData::new(Cursor::new(buf));
+
+    let (msg_id, msg) = group.read(&mut src).unwrap();
+    assert_eq!(msg_id, 10);
+    assert_eq!(*msg.downcast::<Foo>().unwrap(), Foo { foo: 42 });
+
+    let (msg_id, msg) = group.read(&mut src).unwrap();
+    assert_eq!(msg_id, 20);
+    assert_eq!(
+        *msg.downcast::<Bar>().unwrap(),
+        Bar {
+            bar: "hello".to_owned()
+        }
+    );
+}
+
+#[test]
+fn test_dyn_group_unknown() {
+    let mut out_stream = CborData::new(Vec::<u8>::new());
+    out_stream
+        .write_message(&BarV1 {
+            bar: "hello".to_owned(),
+        })
+        .unwrap();
+    let buf = out_stream.into_inner();
+
+    let mut group = DynGroup::new();
+    group.register::<Foo>();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    assert!(group.read(&mut src).is_err());
+}
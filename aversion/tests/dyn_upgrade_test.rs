@@ -0,0 +1,158 @@
+use aversion::group::{DataSink, DynUpgradeError, DynUpgradeRegistry};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::{assign_message_ids, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+// The registry doesn't use these derives; they're only needed to write
+// the test messages.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    count: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV2 {
+    count: u64,
+    name: String,
+}
+
+type Foo = FooV2;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct BarV1 {
+    bar: u32,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+type Source = CborData<Cursor<Vec<u8>>>;
+
+fn upgrade_foo(v1: FooV1) -> FooV2 {
+    FooV2 {
+        count: v1.count.into(),
+        name: "unnamed".into(),
+    }
+}
+
+fn registry() -> DynUpgradeRegistry<Source> {
+    let mut registry = DynUpgradeRegistry::new();
+    registry.register(1, 1, upgrade_foo);
+    registry.register_latest::<FooV2>(1, 2);
+    registry
+}
+
+fn source<T>(msg: &T) -> Source
+where
+    T: Serialize + Versioned,
+    T::Base: aversion::MessageId,
+{
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(msg).unwrap();
+    CborData::new(Cursor::new(sink.into_inner()))
+}
+
+#[test]
+fn test_dyn_upgrade() {
+    let registry = registry();
+    assert!(registry.contains(1));
+    assert!(!registry.contains(2));
+    assert_eq!(registry.latest_version(1), Some(2));
+
+    let mut src = source(&FooV1 { count: 5 });
+    let msg = registry.decode(&mut src).unwrap();
+    let foo = msg.downcast::<FooV2>().unwrap();
+    assert_eq!(
+        *foo,
+        FooV2 {
+            count: 5,
+            name: "unnamed".into()
+        }
+    );
+
+    let latest = FooV2 {
+        count: 6,
+        name: "six".into(),
+    };
+    let mut src = source(&latest);
+    let msg = registry.decode(&mut src).unwrap();
+    assert_eq!(*msg.downcast::<FooV2>().unwrap(), latest);
+}
+
+#[test]
+fn test_dyn_upgrade_unknown() {
+    let registry = registry();
+    let mut src = source(&BarV1 { bar: 1 });
+    let result = registry.decode(&mut src);
+    assert!(matches!(
+        result,
+        Err(DynUpgradeError::Source(
+            CborDataError::UnknownMessage { .. }
+        ))
+    ));
+
+    // A reader that only knows version 1.
+    let mut registry = DynUpgradeRegistry::<Source>::new();
+    registry.register_latest::<FooV1>(1, 1);
+    let mut src = source(&FooV2 {
+        count: 1,
+        name: "one".into(),
+    });
+    let result = registry.decode(&mut src);
+    assert!(matches!(
+        result,
+        Err(DynUpgradeError::UnknownVersion { msg_id: 1, ver: 2 })
+    ));
+}
+
+#[test]
+fn test_dyn_upgrade_broken_chain() {
+    // Version 1 was registered as the latest, so it has no upgrade.
+    let mut registry = DynUpgradeRegistry::<Source>::new();
+    registry.register_latest::<FooV1>(1, 1);
+    registry.register_latest::<FooV2>(1, 2);
+    let mut src = source(&FooV1 { count: 1 });
+    let result = registry.decode(&mut src);
+    assert!(matches!(
+        result,
+        Err(DynUpgradeError::MissingUpgrade { msg_id: 1, ver: 1 })
+    ));
+
+    // The upgrade from version 1 returns the wrong type.
+    registry.register(1, 1, |v1: FooV1| v1);
+    let mut src = source(&FooV1 { count: 1 });
+    let result = registry.decode(&mut src);
+    assert!(matches!(
+        result,
+        Err(DynUpgradeError::TypeMismatch { msg_id: 1, ver: 1 })
+    ));
+}
+
+#[test]
+fn test_dyn_upgrade_skips_body() {
+    // A reader that only knows version 1 skips a version 2 message, and
+    // can read the one after it.
+    let mut registry = DynUpgradeRegistry::<Source>::new();
+    registry.register_latest::<FooV1>(1, 1);
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV2 {
+        count: 1,
+        name: "one".into(),
+    })
+    .unwrap();
+    sink.write_message(&FooV1 { count: 2 }).unwrap();
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+
+    let result = registry.decode(&mut src);
+    assert!(matches!(
+        result,
+        Err(DynUpgradeError::UnknownVersion { msg_id: 1, ver: 2 })
+    ));
+    let msg = registry.decode(&mut src).unwrap();
+    assert_eq!(*msg.downcast::<FooV1>().unwrap(), FooV1 { count: 2 });
+}
@@ -0,0 +1,103 @@
+use aversion::group::{DataSink, DataSourceExt, GroupErrorKind};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Cursor, Read};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+/// A reader that always fails.
+struct BrokenReader;
+
+impl Read for BrokenReader {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+    }
+}
+
+fn foo_bytes() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&Foo { foo: 1234 }).unwrap();
+    sink.into_inner()
+}
+
+/// Decode a message from a `BasicHeader` with these fields, and a Foo body.
+fn read_with_header(msg_id: u16, msg_ver: u16) -> CborDataError {
+    let body = &foo_bytes()[BasicHeader::SIZE..];
+    let mut buf = Vec::new();
+    BasicHeader::new(msg_id, msg_ver, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(body);
+    let mut src = CborData::new(Cursor::new(buf));
+    MyGroup::read_message(&mut src).unwrap_err()
+}
+
+#[test]
+fn test_io_error() {
+    let mut src = CborData::new(BrokenReader);
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert_eq!(err.kind(), GroupErrorKind::Io);
+    assert!(err.kind().is_transient());
+}
+
+#[test]
+fn test_unknown_version() {
+    let err = read_with_header(1, 2);
+    assert!(matches!(err, CborDataError::UnknownVersion { got: 2, .. }));
+    assert_eq!(err.kind(), GroupErrorKind::UnknownVersion);
+    assert!(!err.kind().is_transient());
+}
+
+#[test]
+fn test_unknown_message() {
+    let err = read_with_header(99, 1);
+    assert!(matches!(err, CborDataError::UnknownMessage { msg_id: 99 }));
+    assert_eq!(err.kind(), GroupErrorKind::UnknownMessage);
+}
+
+#[test]
+fn test_codec_error() {
+    // A Foo body, decoded as a Bar.
+    let err = read_with_header(2, 1);
+    assert_eq!(err.kind(), GroupErrorKind::Codec);
+}
+
+#[test]
+fn test_framing_error() {
+    let buf = foo_bytes();
+    let mut src = CborData::new(Cursor::new(&buf[..buf.len() - 1]));
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert_eq!(err.kind(), GroupErrorKind::Framing);
+}
+
+#[test]
+fn test_validation_error() {
+    let mut src = CborData::new(Cursor::new(foo_bytes()));
+    let err = src.expect_message::<Bar>().unwrap_err();
+    assert_eq!(err.kind(), GroupErrorKind::Validation);
+}
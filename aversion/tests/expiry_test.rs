@@ -0,0 +1,121 @@
+use aversion::group::{DataSourceExt, GroupHeader};
+use aversion::test_util::MockClock;
+use aversion::util::cbor::CborData;
+use aversion::util::expiry::{Clock, ExpirySource, SystemClock};
+use aversion::util::ExtendedHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct OfferV1 {
+    price: u32,
+}
+
+type Offer = OfferV1;
+
+assign_message_ids! {
+    Offer: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Market {
+    Offer(Offer),
+}
+
+type ExtSource = CborData<Cursor<Vec<u8>>, ExtendedHeader>;
+
+/// A clock that always returns the same time.
+struct FixedClock(u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Build a stream of `Offer` messages with the given expiry times.
+fn stream(expiries: &[Option<u64>]) -> Cursor<Vec<u8>> {
+    let mut buf = Vec::new();
+    for (price, expiry) in (1..).zip(expiries) {
+        let body = serde_cbor::to_vec(&OfferV1 { price }).unwrap();
+        let mut header = ExtendedHeader::new(1, 1, body.len() as u32);
+        header.expiry = *expiry;
+        buf.extend_from_slice(&header.serialize());
+        buf.extend_from_slice(&body);
+    }
+    Cursor::new(buf)
+}
+
+fn prices<C: Clock>(src: &mut ExpirySource<ExtSource, C>) -> Vec<u32> {
+    Market::iter_filter::<Offer, _>(src)
+        .map(|offer| offer.unwrap().price)
+        .collect()
+}
+
+#[test]
+fn test_expiry_roundtrip() {
+    let header = ExtendedHeader::new(1, 1, 0).with_expiry(0x1234);
+    let buf = header.serialize();
+    assert_eq!(buf.len(), ExtendedHeader::MIN_SIZE + 8);
+    assert_eq!(buf[4], 0x08);
+    let decoded = ExtendedHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(decoded.expiry, Some(0x1234));
+    assert_eq!(decoded.msg_id(), 1);
+}
+
+#[test]
+fn test_expired_and_live() {
+    // The first message expired, the second is live, and the third
+    // never expires.
+    let src = ExtSource::with_header(stream(&[Some(100), Some(300), None]));
+    let mut src = ExpirySource::with_clock(src, FixedClock(200));
+    assert_eq!(prices(&mut src), [2, 3]);
+    assert_eq!(src.dropped(), 1);
+}
+
+#[test]
+fn test_skew_tolerance() {
+    let expiries = [Some(100), Some(150), Some(300)];
+
+    let src = ExtSource::with_header(stream(&expiries));
+    let mut src = ExpirySource::with_clock(src, FixedClock(200)).skew_tolerance(50);
+    assert_eq!(prices(&mut src), [2, 3]);
+    assert_eq!(src.dropped(), 1);
+
+    let src = ExtSource::with_header(stream(&expiries));
+    let mut src = ExpirySource::with_clock(src, FixedClock(200)).skew_tolerance(100);
+    assert_eq!(prices(&mut src), [1, 2, 3]);
+    assert_eq!(src.dropped(), 0);
+}
+
+#[test]
+fn test_expect_message_skips_expired() {
+    let src = ExtSource::with_header(stream(&[Some(1), Some(2), Some(u64::MAX)]));
+    let mut src = ExpirySource::new(src);
+    assert_eq!(src.expect_message::<Offer>().unwrap(), OfferV1 { price: 3 });
+    assert_eq!(src.dropped(), 2);
+}
+
+#[test]
+fn test_mock_clock_ttl() {
+    let clock = MockClock::new(100);
+    let src = ExtSource::with_header(stream(&[Some(150), Some(150), Some(300), Some(300)]));
+    let mut src = ExpirySource::with_clock(src, clock.clone());
+
+    assert_eq!(src.expect_message::<Offer>().unwrap(), OfferV1 { price: 1 });
+    // The second message expires while the first is being handled.
+    clock.advance(100);
+    assert_eq!(src.expect_message::<Offer>().unwrap(), OfferV1 { price: 3 });
+    assert_eq!(src.dropped(), 1);
+
+    clock.set(301);
+    assert!(src.expect_message::<Offer>().is_err());
+    assert_eq!(src.dropped(), 2);
+}
+
+#[test]
+fn test_system_clock() {
+    // Any time after this test was written.
+    assert!(SystemClock.now() > 1_600_000_000);
+}
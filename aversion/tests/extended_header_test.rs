@@ -0,0 +1,103 @@
+use aversion::group::{flags, DataSource, GroupHeader};
+use aversion::util::cbor::CborData;
+use aversion::util::{ExtendedHeader, FramedHeader};
+use std::io::Cursor;
+
+#[test]
+fn test_all_fields_roundtrip() {
+    let header = ExtendedHeader::new(0x1234, 3, 99)
+        .with_timestamp(0x0102_0304_0506_0708)
+        .with_flags(flags::COMPRESSED)
+        .with_seq(42)
+        .with_expiry(0x0a0b)
+        .with_correlation_id(0x0c0d);
+    let buf = header.serialize();
+    assert_eq!(buf.len(), ExtendedHeader::MAX_SIZE);
+    assert_eq!(header.size(), ExtendedHeader::MAX_SIZE);
+    #[rustfmt::skip]
+    assert_eq!(
+        buf,
+        [
+            0x12, 0x34, // id
+            0x00, 0x03, // version
+            0x1f, // all fields present
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // timestamp
+            0x01, // flags
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2a, // seq
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x0b, // expiry
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0d, // correlation id
+            0x00, 0x00, 0x00, 0x63, // length
+        ]
+    );
+
+    let decoded = ExtendedHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(decoded, header);
+    assert_eq!(decoded.msg_id(), 0x1234);
+    assert_eq!(decoded.msg_ver(), 3);
+    assert_eq!(decoded.flags(), flags::COMPRESSED);
+    assert_eq!(decoded.msg_len(), 99);
+    assert_eq!(decoded.timestamp, Some(0x0102_0304_0506_0708));
+    assert_eq!(decoded.seq, Some(42));
+    assert_eq!(decoded.expiry, Some(0x0a0b));
+    assert_eq!(decoded.correlation_id, Some(0x0c0d));
+}
+
+#[test]
+fn test_some_fields_roundtrip() {
+    let header = ExtendedHeader::new(1, 1, 5).with_seq(7);
+    let buf = header.serialize();
+    assert_eq!(buf.len(), ExtendedHeader::MIN_SIZE + 8);
+    let decoded = ExtendedHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(decoded, header);
+    assert_eq!(decoded.timestamp, None);
+    assert_eq!(decoded.flags, None);
+    assert_eq!(decoded.flags(), 0);
+
+    let header = ExtendedHeader::new(1, 1, 5);
+    assert_eq!(header.serialize().len(), ExtendedHeader::MIN_SIZE);
+}
+
+#[test]
+fn test_unknown_fields() {
+    let mut buf = ExtendedHeader::new(1, 1, 5).serialize();
+    buf[4] = 0x80;
+    let err = ExtendedHeader::deserialize_from(&mut &buf[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_cbor_data() {
+    // Headers with optional fields can be read by CborData.
+    let body = serde_cbor::to_vec(&"hello").unwrap();
+    let mut buf = ExtendedHeader::new(1, 1, body.len() as u32)
+        .with_timestamp(1000)
+        .serialize();
+    buf.extend_from_slice(&body);
+
+    let mut src = CborData::<_, ExtendedHeader>::with_header(Cursor::new(buf));
+    let header = src.read_header().unwrap();
+    assert_eq!(header.timestamp, Some(1000));
+    let msg: String = src.read_message(&header).unwrap();
+    assert_eq!(msg, "hello");
+}
+
+#[test]
+fn test_read_header_into() {
+    let mut buf = Vec::new();
+    for seq in 0..3 {
+        let body = serde_cbor::to_vec(&seq).unwrap();
+        let header = ExtendedHeader::new(1, 1, body.len() as u32).with_seq(seq);
+        buf.extend_from_slice(&header.serialize());
+        buf.extend_from_slice(&body);
+    }
+
+    // One header value is reused for every message.
+    let mut src = CborData::<_, ExtendedHeader>::with_header(Cursor::new(buf));
+    let mut header = ExtendedHeader::new(0, 0, 0).with_timestamp(5);
+    for seq in 0..3 {
+        src.read_header_into(&mut header).unwrap();
+        assert_eq!(header, ExtendedHeader::new(1, 1, 1).with_seq(seq));
+        let msg: u64 = src.read_message(&header).unwrap();
+        assert_eq!(msg, seq);
+    }
+}
@@ -0,0 +1,91 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::test_util::FaultSource;
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, ErrorKind};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: String,
+}
+
+type Foo = FooV1;
+
+assign_message_ids! {
+    Foo: 0x0101,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+}
+
+fn stream() -> Cursor<Vec<u8>> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 {
+        foo: "hello world".to_owned(),
+    })
+    .unwrap();
+    sink.write_message(&FooV1 {
+        foo: "goodbye".to_owned(),
+    })
+    .unwrap();
+    Cursor::new(sink.into_inner())
+}
+
+#[test]
+fn test_no_faults() {
+    let mut src = CborData::new(FaultSource::new(stream()));
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg.foo, "hello world");
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg.foo, "goodbye");
+}
+
+#[test]
+fn test_truncated_body() {
+    // Stop partway through the first message body.
+    let reader = FaultSource::new(stream()).truncate_after(BasicHeader::SIZE as u64 + 4);
+    let mut src = CborData::new(reader);
+    let err = src.expect_message::<Foo>().unwrap_err();
+    assert!(matches!(err, CborDataError::Eof), "{:?}", err);
+}
+
+#[test]
+fn test_corrupted_id() {
+    // Flip a bit in the low byte of the first message id.
+    let reader = FaultSource::new(stream()).corrupt_byte(1, 0x02);
+    let mut src = CborData::new(reader);
+    let err = src.expect_message::<Foo>().unwrap_err();
+    match err {
+        CborDataError::UnexpectedMessage { got, .. } => assert_eq!(got, 0x0103),
+        _ => panic!("unexpected error {:?}", err),
+    }
+
+    // The group dispatch sees an unknown message.
+    let reader = FaultSource::new(stream()).corrupt_byte(1, 0x02);
+    let mut src = CborData::new(reader);
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert!(
+        matches!(err, CborDataError::UnknownMessage { .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn test_would_block() {
+    let reader = FaultSource::new(stream()).would_block_on_read(1);
+    let mut src = CborData::new(reader);
+    let err = src.expect_message::<Foo>().unwrap_err();
+    match err {
+        CborDataError::Io(Some(e)) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+        _ => panic!("unexpected error {:?}", err),
+    }
+
+    // No data was consumed, so the next attempt succeeds.
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg.foo, "hello world");
+}
@@ -0,0 +1,89 @@
+use aversion::util::fixed::{FixedSize, FixedSizeCodec, FixedSizeError};
+use aversion::util::Codec;
+use aversion::Versioned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+#[versioned(fixed_size = 16)]
+struct SlotV1 {
+    key: u32,
+    value: i64,
+    flags: [u8; 2],
+    live: bool,
+    kind: u8,
+}
+
+type Slot = SlotV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+#[versioned(fixed_size = 6)]
+struct PairV1(u16, f32);
+
+type Pair = PairV1;
+
+/// A message that can't be encoded with `FixedSizeCodec`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[test]
+fn test_fixed_size_layout() {
+    assert_eq!(Slot::FIXED_SIZE, 16);
+    assert_eq!(Pair::FIXED_SIZE, 6);
+    assert_eq!(<[u16; 3]>::FIXED_SIZE, 6);
+
+    let slot = SlotV1 {
+        key: 0x0102_0304,
+        value: -2,
+        flags: [0xaa, 0xbb],
+        live: true,
+        kind: 7,
+    };
+    let mut buf = Vec::new();
+    FixedSizeCodec.encode(&slot, &mut buf).unwrap();
+    assert_eq!(buf.len(), Slot::FIXED_SIZE);
+    #[rustfmt::skip]
+    assert_eq!(
+        buf,
+        [
+            0x04, 0x03, 0x02, 0x01, // key
+            0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // value
+            0xaa, 0xbb, // flags
+            0x01, // live
+            0x07, // kind
+        ]
+    );
+    assert_eq!(FixedSizeCodec.decode::<Slot>(&buf).unwrap(), slot);
+
+    let pair = PairV1(9, 1.5);
+    let mut buf = Vec::new();
+    FixedSizeCodec.encode(&pair, &mut buf).unwrap();
+    assert_eq!(buf, [0x09, 0x00, 0x00, 0x00, 0xc0, 0x3f]);
+    assert_eq!(FixedSizeCodec.decode::<Pair>(&buf).unwrap(), pair);
+}
+
+#[test]
+fn test_fixed_size_errors() {
+    let mut buf = Vec::new();
+    let err = FixedSizeCodec
+        .encode(
+            &Named {
+                name: "x".to_owned(),
+            },
+            &mut buf,
+        )
+        .unwrap_err();
+    assert!(matches!(err, FixedSizeError::Unsupported("str")));
+
+    let err = FixedSizeCodec.decode::<Pair>(&[0; 5]).unwrap_err();
+    assert!(matches!(err, FixedSizeError::Eof));
+
+    let err = FixedSizeCodec.decode::<Pair>(&[0; 7]).unwrap_err();
+    assert!(matches!(err, FixedSizeError::TrailingBytes(1)));
+
+    let mut buf = [0; 16];
+    buf[14] = 2;
+    let err = FixedSizeCodec.decode::<Slot>(&buf).unwrap_err();
+    assert!(matches!(err, FixedSizeError::InvalidBool(2)));
+}
@@ -0,0 +1,149 @@
+use aversion::group::{flags, DataSink, DataSource, DataSourceExt, GroupHeader};
+use aversion::util::cbor::CborDataError;
+use aversion::util::FlagsHeader;
+use aversion::{assign_message_ids, MessageId, UpgradeLatest, Versioned};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::io::{Cursor, Read, Write};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct TextV1 {
+    text: String,
+}
+
+type Text = TextV1;
+
+assign_message_ids! {
+    Text: 7,
+}
+
+/// A toy run-length encoding, standing in for a real compression format.
+fn rle_compress(buf: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut iter = buf.iter().peekable();
+    while let Some(&byte) = iter.next() {
+        let mut count = 1u8;
+        while count < u8::MAX && iter.peek() == Some(&&byte) {
+            iter.next();
+            count += 1;
+        }
+        out.push(count);
+        out.push(byte);
+    }
+    out
+}
+
+fn rle_decompress(buf: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for pair in buf.chunks(2) {
+        out.resize(out.len() + usize::from(pair[0]), pair[1]);
+    }
+    out
+}
+
+/// A sink that optionally compresses each message body.
+struct CompressingSink {
+    inner: Vec<u8>,
+    compress: bool,
+}
+
+impl DataSink for CompressingSink {
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        let mut body = serde_cbor::to_vec(msg)?;
+        let mut msg_flags = 0;
+        if self.compress {
+            body = rle_compress(&body);
+            msg_flags |= flags::COMPRESSED;
+        }
+        let msg_len: u32 = body.len().try_into().unwrap();
+        let header = FlagsHeader::for_msg(msg, msg_flags, msg_len);
+        header.serialize_into(&mut self.inner)?;
+        self.inner.write_all(&body)?;
+        Ok(())
+    }
+}
+
+/// A source that decompresses message bodies that have the flag set.
+struct DecompressingSource {
+    inner: Cursor<Vec<u8>>,
+}
+
+impl DataSource for DecompressingSource {
+    type Error = CborDataError;
+    type Header = FlagsHeader;
+
+    fn read_header(&mut self) -> Result<FlagsHeader, CborDataError> {
+        Ok(FlagsHeader::deserialize_from(&mut self.inner)?)
+    }
+
+    fn read_message<T>(&mut self, header: &FlagsHeader) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut body = Vec::new();
+        (&mut self.inner)
+            .take(header.msg_len.into())
+            .read_to_end(&mut body)?;
+        if header.flags() & flags::COMPRESSED != 0 {
+            body = rle_decompress(&body);
+        }
+        Ok(serde_cbor::from_slice(&body)?)
+    }
+}
+
+#[test]
+fn test_mixed_compression() {
+    let plain = TextV1 {
+        text: "a".repeat(100),
+    };
+    let compressed = TextV1 {
+        text: "b".repeat(100),
+    };
+
+    let mut sink = CompressingSink {
+        inner: Vec::new(),
+        compress: false,
+    };
+    sink.write_message(&plain).unwrap();
+    sink.compress = true;
+    sink.write_message(&compressed).unwrap();
+
+    let mut src = DecompressingSource {
+        inner: Cursor::new(sink.inner),
+    };
+
+    // Check the headers first.
+    let header1 = src.read_header().unwrap();
+    assert_eq!(header1.flags(), 0);
+    let header_size = FlagsHeader::SIZE as u64;
+    src.inner
+        .set_position(header_size + u64::from(header1.msg_len));
+    let header2 = src.read_header().unwrap();
+    assert_eq!(header2.flags(), flags::COMPRESSED);
+    assert!(header2.msg_len < header1.msg_len);
+
+    src.inner.set_position(0);
+    let msg: Text = src.expect_message().unwrap();
+    assert_eq!(msg, plain);
+    let msg: Text = src.expect_message().unwrap();
+    assert_eq!(msg, compressed);
+}
+
+#[test]
+fn test_flags_header_roundtrip() {
+    let header = FlagsHeader::new(0x1234, 3, flags::COMPRESSED | flags::ENCRYPTED, 99);
+    let buf = header.serialize();
+    assert_eq!(buf, [0x12, 0x34, 0x00, 0x03, 0x03, 0x00, 0x00, 0x00, 0x63]);
+    let header = FlagsHeader::deserialize(&buf);
+    assert_eq!(header.msg_id(), 0x1234);
+    assert_eq!(header.msg_ver(), 3);
+    assert_eq!(header.flags(), flags::COMPRESSED | flags::ENCRYPTED);
+    assert_eq!(header.msg_len, 99);
+}
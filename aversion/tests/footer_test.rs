@@ -0,0 +1,122 @@
+use aversion::group::{DataSink, DataSourceExt, GroupErrorKind};
+use aversion::util::cbor::CborDataError;
+use aversion::util::footer::{FooterData, GroupFooter};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::Cursor;
+
+/// An HMAC-SHA256 footer.
+struct HmacFooter {
+    key: Vec<u8>,
+}
+
+impl HmacFooter {
+    fn new(key: &[u8]) -> Self {
+        HmacFooter { key: key.to_vec() }
+    }
+
+    fn mac(&self, frame: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).unwrap();
+        mac.update(frame);
+        mac
+    }
+}
+
+impl GroupFooter for HmacFooter {
+    fn size(&self) -> usize {
+        32
+    }
+
+    fn compute(&self, frame: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.mac(frame).finalize().into_bytes());
+    }
+
+    fn verify(&self, frame: &[u8], footer: &[u8]) -> bool {
+        self.mac(frame).verify_slice(footer).is_ok()
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+const KEY: &[u8] = b"secret key";
+
+fn encoded() -> Vec<u8> {
+    let mut sink = FooterData::new(Vec::new(), HmacFooter::new(KEY));
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Bar {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_footer_roundtrip() {
+    let buf = encoded();
+    // Foo is a 6-byte body, followed by the 32-byte footer.
+    let header = BasicHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(header.msg_len, 6);
+    assert_eq!(buf.len(), 2 * (BasicHeader::SIZE + 32) + 6 + 11);
+
+    let mut src = FooterData::new(Cursor::new(buf), HmacFooter::new(KEY));
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(msg, MyGroup::Foo(Foo { foo: 1 }));
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(
+        msg,
+        MyGroup::Bar(Bar {
+            bar: "hello".to_owned()
+        })
+    );
+}
+
+#[test]
+fn test_tampered_body() {
+    let mut buf = encoded();
+    // Change the value of `foo` from 1 to 2.
+    let foo_value = BasicHeader::SIZE + 5;
+    assert_eq!(buf[foo_value], 0x01);
+    buf[foo_value] = 0x02;
+
+    let mut src = FooterData::new(Cursor::new(buf), HmacFooter::new(KEY));
+    let err = src.expect_message::<Foo>().unwrap_err();
+    assert!(matches!(err, CborDataError::FooterVerifyFailed));
+    assert_eq!(err.kind(), GroupErrorKind::Validation);
+
+    // The footer was consumed, so the next message can still be read.
+    let msg: Bar = src.expect_message().unwrap();
+    assert_eq!(msg.bar, "hello");
+}
+
+#[test]
+fn test_wrong_key() {
+    let mut src = FooterData::new(Cursor::new(encoded()), HmacFooter::new(b"other key"));
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(err, CborDataError::FooterVerifyFailed));
+}
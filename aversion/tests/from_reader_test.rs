@@ -0,0 +1,66 @@
+use aversion::group::{from_reader, to_writer, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+#[test]
+fn test_stream_of_messages() {
+    let mut buf = Vec::new();
+    to_writer(&mut buf, &MyGroup::Foo(Foo { foo: 1 })).unwrap();
+    to_writer(
+        &mut buf,
+        &MyGroup::Bar(Bar {
+            bar: "hi".to_owned(),
+        }),
+    )
+    .unwrap();
+
+    // Reading through `&mut` leaves the reader at the next message.
+    let mut reader = Cursor::new(buf.clone());
+    let msg: MyGroup = from_reader(&mut reader).unwrap();
+    assert_eq!(msg, MyGroup::Foo(Foo { foo: 1 }));
+    let msg: MyGroup = from_reader(&mut reader).unwrap();
+    assert_eq!(
+        msg,
+        MyGroup::Bar(Bar {
+            bar: "hi".to_owned()
+        })
+    );
+
+    // The format is the same as CborData.
+    let mut src = CborData::new(Cursor::new(buf));
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg, Foo { foo: 1 });
+}
+
+#[test]
+fn test_from_reader_error() {
+    let err = from_reader::<MyGroup, _>(&[][..]).unwrap_err();
+    assert!(matches!(err, CborDataError::Io(Some(_))));
+}
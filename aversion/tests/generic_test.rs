@@ -0,0 +1,128 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use aversion::{
+    assign_message_ids, FromVersion, GroupDeserialize, MessageId, RawVersions, UpgradeLatest,
+    Versioned,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct PingV1 {
+    seq: u32,
+}
+type Ping = PingV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct PongV1 {
+    seq: u32,
+}
+type Pong = PongV1;
+
+assign_message_ids! {
+    Ping: 1,
+    Pong: 2,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct EnvelopeV1<T> {
+    payload: T,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct EnvelopeV2<T> {
+    payload: T,
+    hops: u8,
+}
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, RawVersions, Serialize, Deserialize)]
+struct EnvelopeV3<T> {
+    payload: T,
+    hops: u8,
+    trace: Option<String>,
+}
+
+type Envelope<T> = EnvelopeV3<T>;
+
+impl<T> FromVersion<EnvelopeV1<T>> for EnvelopeV2<T> {
+    fn from_version(v1: EnvelopeV1<T>) -> Self {
+        EnvelopeV2 {
+            payload: v1.payload,
+            hops: 0,
+        }
+    }
+}
+
+impl<T> FromVersion<EnvelopeV2<T>> for EnvelopeV3<T> {
+    fn from_version(v2: EnvelopeV2<T>) -> Self {
+        EnvelopeV3 {
+            payload: v2.payload,
+            hops: v2.hops,
+            trace: None,
+        }
+    }
+}
+
+// Each payload type gets its own envelope id.
+impl<T: MessageId> MessageId for Envelope<T> {
+    const MSG_ID: u16 = 0x100 + T::MSG_ID;
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Wrapped {
+    Ping(Envelope<Ping>),
+    Pong(Envelope<Pong>),
+}
+
+#[test]
+fn test_generic_versioned() {
+    assert_eq!(<EnvelopeV1<Ping> as Versioned>::VER, 1);
+    assert_eq!(<EnvelopeV3<Pong> as Versioned>::VER, 3);
+    assert_eq!(<Envelope<Ping> as MessageId>::MSG_ID, 0x101);
+    assert_eq!(<Envelope<Pong> as MessageId>::MSG_ID, 0x102);
+}
+
+#[test]
+fn test_generic_upgrade() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&EnvelopeV1 {
+        payload: Ping { seq: 1 },
+    })
+    .unwrap();
+    sink.write_message(&EnvelopeV2 {
+        payload: Pong { seq: 2 },
+        hops: 3,
+    })
+    .unwrap();
+    sink.write_message(&EnvelopeV1 {
+        payload: Pong { seq: 4 },
+    })
+    .unwrap();
+
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+
+    let ping: Envelope<Ping> = src.expect_message().unwrap();
+    assert_eq!(
+        ping,
+        EnvelopeV3 {
+            payload: Ping { seq: 1 },
+            hops: 0,
+            trace: None,
+        }
+    );
+
+    let msg = Wrapped::read_message(&mut src).unwrap();
+    assert_eq!(
+        msg,
+        Wrapped::Pong(EnvelopeV3 {
+            payload: Pong { seq: 2 },
+            hops: 3,
+            trace: None,
+        })
+    );
+
+    let (ver, raw) = src.read_raw_version::<Envelope<Pong>>().unwrap();
+    assert_eq!(ver, 1);
+    assert!(matches!(raw, EnvelopeRawVersion::V1(_)));
+    assert_eq!(raw.upgrade().payload, Pong { seq: 4 });
+}
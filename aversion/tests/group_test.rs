@@ -1,6 +1,9 @@
 use aversion::group::{DataSink, DataSourceExt};
 use aversion::util::cbor::CborData;
-use aversion::{assign_message_ids, FromVersion, GroupDeserialize, UpgradeLatest, Versioned};
+use aversion::{
+    assert_wire_golden, assign_message_ids, FromVersion, GroupDeserialize, GroupSerialize,
+    UpgradeLatest, Versioned,
+};
 use serde::{Deserialize, Serialize};
 use std::io::{Cursor, Seek, SeekFrom};
 
@@ -42,7 +45,7 @@ struct BarV1 {
 /// This is the latest version.
 type Bar = BarV1;
 
-#[derive(Debug, PartialEq, GroupDeserialize)]
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
 enum MyGroup1 {
     Foo(Foo),
     Bar(Bar),
@@ -82,3 +85,53 @@ fn test_group() {
         assert_eq!(message, Foo { foo3: 1245 });
     }
 }
+
+#[test]
+fn test_group_serialize() {
+    let messages = vec![
+        MyGroup1::Bar(Bar { bar: 1 }),
+        MyGroup1::Foo(Foo { foo3: 2 }),
+        MyGroup1::Bar(Bar { bar: 3 }),
+    ];
+
+    let mut out_stream = CborData::new(Vec::<u8>::new());
+    for msg in &messages {
+        msg.write_message(&mut out_stream).unwrap();
+    }
+    let cursor = Cursor::new(out_stream.into_inner());
+
+    let mut my_stream = CborData::new(cursor);
+    for msg in &messages {
+        assert_eq!(&MyGroup1::read_message(&mut my_stream).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_wire_golden() {
+    assert_wire_golden!(
+        FooV1 { foo: 1234 },
+        &[
+            0x00, 0x7b, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, // header: id 123, ver 1, len 8
+            0xa1, 0x63, 0x66, 0x6f, 0x6f, 0x19, 0x04, 0xd2, // body: {"foo": 1234}
+        ]
+    );
+    assert_wire_golden!(
+        FooV3 { foo3: 7 },
+        &[
+            0x00, 0x7b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x07, // header: id 123, ver 3, len 7
+            0xa1, 0x64, 0x66, 0x6f, 0x6f, 0x33, 0x07, // body: {"foo3": 7}
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "does not match golden bytes")]
+fn test_wire_golden_mismatch() {
+    assert_wire_golden!(
+        FooV1 { foo: 1235 },
+        &[
+            0x00, 0x7b, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, // header: id 123, ver 1, len 8
+            0xa1, 0x63, 0x66, 0x6f, 0x6f, 0x19, 0x04, 0xd2, // body: {"foo": 1234}
+        ]
+    );
+}
@@ -0,0 +1,126 @@
+use aversion::util::cbor::{CborCodec, CborData};
+use aversion::util::preamble::{self, PreambleError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+/// The current version of the group.
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+#[group(version = 3)]
+enum GroupV3 {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+/// An older reader, from before `Bar` was added.
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[group(version = 2)]
+enum GroupV2 {
+    Foo(Foo),
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Unversioned {
+    Foo(Foo),
+}
+
+fn group_file() -> Vec<u8> {
+    let mut buf = Vec::new();
+    preamble::write_preamble_with_group::<CborCodec, BasicHeader>(&mut buf, GroupV3::GROUP_VERSION)
+        .unwrap();
+    let mut sink = CborData::new(buf);
+    GroupV3::Foo(FooV1 { foo: 1 })
+        .write_message(&mut sink)
+        .unwrap();
+    GroupV3::Bar(BarV1 { bar: "bar".into() })
+        .write_message(&mut sink)
+        .unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_group_version_const() {
+    assert_eq!(GroupV3::GROUP_VERSION, 3);
+    assert_eq!(GroupV2::GROUP_VERSION, 2);
+    assert_eq!(Unversioned::GROUP_VERSION, 0);
+}
+
+#[test]
+fn test_group_version_roundtrip() {
+    let mut file = Cursor::new(group_file());
+    let found = preamble::read_preamble::<CborCodec>(&mut file).unwrap();
+    assert_eq!(found.version, preamble::PREAMBLE_VERSION_5);
+    assert_eq!(found.group_version, 3);
+    assert_eq!(file.position(), found.size() as u64);
+    found.check_group::<GroupV3>().unwrap();
+
+    let mut src = CborData::new(file);
+    assert_eq!(
+        GroupV3::read_message(&mut src).unwrap(),
+        GroupV3::Foo(FooV1 { foo: 1 })
+    );
+    assert_eq!(
+        GroupV3::read_message(&mut src).unwrap(),
+        GroupV3::Bar(BarV1 { bar: "bar".into() })
+    );
+}
+
+#[test]
+fn test_reject_newer_group() {
+    let mut file = Cursor::new(group_file());
+    let found = preamble::read_preamble::<CborCodec>(&mut file).unwrap();
+    let err = found.check_group::<GroupV2>().unwrap_err();
+    assert!(matches!(
+        err,
+        PreambleError::UnsupportedGroupVersion {
+            supported: 2,
+            found: 3
+        }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "Unsupported group version 3: reader supports up to 2"
+    );
+
+    // A reader that isn't versioned can't tell which messages it's missing.
+    assert!(found.check_group::<Unversioned>().is_err());
+}
+
+#[test]
+fn test_older_preamble_has_no_group_version() {
+    let mut buf = Vec::new();
+    preamble::write_preamble::<CborCodec>(&mut buf).unwrap();
+    let found = preamble::read_preamble::<CborCodec>(&mut &buf[..]).unwrap();
+    assert_eq!(found.group_version, 0);
+    found.check_group::<GroupV2>().unwrap();
+    found.check_group::<Unversioned>().unwrap();
+}
+
+#[test]
+fn test_older_group_accepted() {
+    let mut buf = Vec::new();
+    preamble::write_preamble_with_group::<CborCodec, BasicHeader>(&mut buf, GroupV2::GROUP_VERSION)
+        .unwrap();
+    let found = preamble::read_preamble::<CborCodec>(&mut &buf[..]).unwrap();
+    found.check_group::<GroupV3>().unwrap();
+}
@@ -0,0 +1,121 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt};
+use aversion::util::cbor::CborDataError;
+use aversion::util::grpc::{GrpcFramedSink, GrpcFramedSource};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PingV1 {
+    seq: u32,
+}
+
+type Ping = PingV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct NoteV1 {
+    text: String,
+}
+
+type Note = NoteV1;
+
+assign_message_ids! {
+    Ping: 1,
+    Note: 2,
+}
+
+/// Build a gRPC-style frame by hand.
+fn frame<T>(msg: &T) -> Vec<u8>
+where
+    T: Serialize + Versioned,
+    T::Base: aversion::MessageId,
+{
+    let body = serde_cbor::to_vec(msg).unwrap();
+    let mut payload = Vec::new();
+    BasicHeader::for_msg(msg, body.len() as u32)
+        .serialize_into(&mut payload)
+        .unwrap();
+    payload.extend_from_slice(&body);
+
+    let mut frame = vec![0];
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+#[test]
+fn test_grpc_sink_matches_hand_built_frames() {
+    let mut expected = frame(&PingV1 { seq: 7 });
+    expected.extend(frame(&NoteV1 { text: "hi".into() }));
+
+    let mut sink = GrpcFramedSink::new(Vec::new());
+    sink.write_message(&PingV1 { seq: 7 }).unwrap();
+    sink.write_message(&NoteV1 { text: "hi".into() }).unwrap();
+    sink.flush().unwrap();
+    assert_eq!(sink.into_inner(), expected);
+}
+
+#[test]
+fn test_grpc_source_reads_hand_built_frames() {
+    let mut buf = frame(&PingV1 { seq: 7 });
+    buf.extend(frame(&NoteV1 { text: "hi".into() }));
+
+    let mut src = GrpcFramedSource::new(&buf[..]);
+    let ping: Ping = src.expect_message().unwrap();
+    assert_eq!(ping, PingV1 { seq: 7 });
+    let note: Note = src.expect_message().unwrap();
+    assert_eq!(note, NoteV1 { text: "hi".into() });
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_grpc_skip_message() {
+    let mut buf = frame(&NoteV1 {
+        text: "skip".into(),
+    });
+    buf.extend(frame(&PingV1 { seq: 3 }));
+
+    let mut src = GrpcFramedSource::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    src.skip_message(&header).unwrap();
+    let ping: Ping = src.expect_message().unwrap();
+    assert_eq!(ping, PingV1 { seq: 3 });
+}
+
+#[test]
+fn test_grpc_compressed_frame() {
+    let mut buf = frame(&PingV1 { seq: 1 });
+    buf[0] = 1;
+
+    let mut src = GrpcFramedSource::new(&buf[..]);
+    match src.read_header() {
+        Err(CborDataError::Io(Some(e))) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_grpc_length_mismatch() {
+    // Add a trailing byte to the body, and fix up the frame length.
+    let mut buf = frame(&PingV1 { seq: 1 });
+    buf.push(0);
+    let len = (buf.len() - 5) as u32;
+    buf[1..5].copy_from_slice(&len.to_be_bytes());
+
+    let mut src = GrpcFramedSource::new(&buf[..]);
+    match src.read_header() {
+        Err(CborDataError::Io(Some(e))) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_grpc_truncated_frame() {
+    let mut buf = frame(&PingV1 { seq: 1 });
+    buf.pop();
+
+    let mut src = GrpcFramedSource::new(&buf[..]);
+    let result = src.try_read_header();
+    assert!(matches!(result, Err(CborDataError::Eof)));
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "flate2")]
+
+use aversion::group::{flags, DataSink, DataSource, DataSourceExt, GroupHeader};
+use aversion::util::cbor::{CborCodec, CborDataError};
+use aversion::util::gzip::{GzipCodec, GzipSink, GzipSource};
+use aversion::util::transform::BodyTransform;
+use aversion::util::FlagsHeader;
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct TextV1 {
+    text: String,
+}
+
+type Text = TextV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct NumV1 {
+    num: u32,
+}
+
+type Num = NumV1;
+
+assign_message_ids! {
+    Text: 1,
+    Num: 2,
+}
+
+fn long_text() -> TextV1 {
+    TextV1 {
+        text: "abc".repeat(500),
+    }
+}
+
+#[test]
+fn test_gzip_transform_roundtrip() {
+    let codec = GzipCodec::new(CborCodec).level(9);
+    let plain = serde_cbor::to_vec(&long_text()).unwrap();
+    let buf = codec.apply(plain.clone()).unwrap();
+    // gzip magic number
+    assert_eq!(&buf[..2], &[0x1f, 0x8b]);
+    assert!(buf.len() < 100);
+
+    assert_eq!(codec.reverse(buf).unwrap(), plain);
+}
+
+#[test]
+fn test_gzip_roundtrip() {
+    let mut sink = GzipSink::new(Vec::new());
+    sink.write_message(&long_text()).unwrap();
+    sink.write_message(&NumV1 { num: 7 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = GzipSource::new(&buf[..]);
+    let msg: Text = src.expect_message().unwrap();
+    assert_eq!(msg, long_text());
+    let msg: Num = src.expect_message().unwrap();
+    assert_eq!(msg, NumV1 { num: 7 });
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_compressed_flag() {
+    let mut sink = GzipSink::new(Vec::new()).min_size(64);
+    sink.write_message(&NumV1 { num: 7 }).unwrap();
+    sink.write_message(&long_text()).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = GzipSource::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    assert_eq!(header.flags(), 0);
+    let raw = src.read_raw(&header).unwrap();
+    assert_eq!(raw, serde_cbor::to_vec(&NumV1 { num: 7 }).unwrap());
+
+    let header = src.read_header().unwrap();
+    assert_eq!(header.flags(), flags::COMPRESSED);
+    let plain_len = serde_cbor::to_vec(&long_text()).unwrap().len();
+    assert!((header.msg_len as usize) < plain_len);
+    // The raw body is decompressed.
+    let raw = src.read_raw(&header).unwrap();
+    assert_eq!(raw.len(), plain_len);
+}
+
+#[test]
+fn test_length_framing() {
+    let mut sink = GzipSink::new(Vec::new());
+    sink.write_message(&long_text()).unwrap();
+    sink.write_message(&NumV1 { num: 7 }).unwrap();
+    let buf = sink.into_inner();
+
+    // The frames can be walked using only the header's message length.
+    let mut r = &buf[..];
+    let mut lengths = Vec::new();
+    while !r.is_empty() {
+        let header = FlagsHeader::deserialize_from(&mut r).unwrap();
+        assert_eq!(header.flags(), flags::COMPRESSED);
+        let mut body = Vec::new();
+        (&mut r)
+            .take(header.msg_len.into())
+            .read_to_end(&mut body)
+            .unwrap();
+        lengths.push(body.len());
+    }
+    assert_eq!(lengths.len(), 2);
+
+    // Skipping a compressed message leaves the source at the next one.
+    let mut src = GzipSource::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    src.skip_message(&header).unwrap();
+    let msg: Num = src.expect_message().unwrap();
+    assert_eq!(msg, NumV1 { num: 7 });
+}
+
+#[test]
+fn test_truncated_body() {
+    let mut sink = GzipSink::new(Vec::new());
+    sink.write_message(&long_text()).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = GzipSource::new(&buf[..buf.len() - 1]);
+    let err = src.expect_message::<Text>().unwrap_err();
+    assert!(matches!(err, CborDataError::Eof));
+}
+
+#[test]
+fn test_max_decompressed_len() {
+    // About 1 MB of text compresses to a few kilobytes.
+    let big = TextV1 {
+        text: "a".repeat(1 << 20),
+    };
+    let mut sink = GzipSink::new(Vec::new());
+    sink.write_message(&big).unwrap();
+    sink.write_message(&NumV1 { num: 7 }).unwrap();
+    let buf = sink.into_inner();
+    assert!(buf.len() < 10_000);
+
+    let mut src = GzipSource::new(&buf[..]).max_decompressed_len(64 * 1024);
+    let err = src.expect_message::<Text>().unwrap_err();
+    assert!(
+        matches!(err, CborDataError::Io(Some(e)) if e.kind() == std::io::ErrorKind::InvalidData)
+    );
+    // The whole body was consumed, so the next message can be read.
+    let msg: Num = src.expect_message().unwrap();
+    assert_eq!(msg, NumV1 { num: 7 });
+
+    let mut src = GzipSource::new(&buf[..]);
+    let msg: Text = src.expect_message().unwrap();
+    assert_eq!(msg, big);
+
+    // The limit also applies when the codec is used as a transform.
+    let codec = GzipCodec::new(CborCodec).max_decompressed_len(1024);
+    let compressed = codec.apply(serde_cbor::to_vec(&big).unwrap()).unwrap();
+    let err = codec.reverse(compressed).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
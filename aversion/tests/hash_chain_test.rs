@@ -0,0 +1,102 @@
+use aversion::group::{DataSink, DataSourceExt, GroupErrorKind};
+use aversion::util::cbor::CborDataError;
+use aversion::util::hash_chain::{ChainHasher, HashChainSink, HashChainSource, GENESIS_HASH};
+use aversion::util::ChainHeader;
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+struct Sha256Chain;
+
+impl ChainHasher for Sha256Chain {
+    fn chain(&self, prev: &[u8; 32], body: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(prev);
+        hasher.update(body);
+        hasher.finalize().into()
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct EntryV1 {
+    seq: u32,
+    text: String,
+}
+
+type Entry = EntryV1;
+
+assign_message_ids! {
+    Entry: 1,
+}
+
+fn entry(seq: u32) -> Entry {
+    Entry {
+        seq,
+        text: format!("entry {}", seq),
+    }
+}
+
+/// Write three entries, returning the bytes and the offset of each message.
+fn three_entries() -> (Vec<u8>, Vec<usize>) {
+    let mut sink = HashChainSink::new(Vec::new(), Sha256Chain);
+    let mut offsets = Vec::new();
+    for seq in 1..=3 {
+        offsets.push(sink.get_ref().len());
+        sink.write_message(&entry(seq)).unwrap();
+    }
+    (sink.into_inner(), offsets)
+}
+
+#[test]
+fn test_chain_roundtrip() {
+    let (buf, _) = three_entries();
+
+    let mut src = HashChainSource::new(Cursor::new(buf), Sha256Chain);
+    assert_eq!(src.last_hash(), GENESIS_HASH);
+    for seq in 1..=3 {
+        let msg: Entry = src.expect_message().unwrap();
+        assert_eq!(msg, entry(seq));
+    }
+    assert_ne!(src.last_hash(), GENESIS_HASH);
+}
+
+#[test]
+fn test_chain_links() {
+    let (buf, offsets) = three_entries();
+    let first = ChainHeader::deserialize_from(&mut &buf[offsets[0]..]).unwrap();
+    let second = ChainHeader::deserialize_from(&mut &buf[offsets[1]..]).unwrap();
+
+    let body_start = offsets[1] + second.ext.size() + ChainHeader::HASH_SIZE;
+    let body = &buf[body_start..offsets[2]];
+    assert_eq!(Sha256Chain.chain(&first.hash, body), second.hash);
+}
+
+#[test]
+fn test_tampered_message() {
+    let (mut buf, offsets) = three_entries();
+    // Flip a byte in the last character of message 2's text.
+    let last_byte = offsets[2] - 1;
+    assert_eq!(buf[last_byte], b'2');
+    buf[last_byte] ^= 0x01;
+
+    let mut src = HashChainSource::new(Cursor::new(buf), Sha256Chain);
+    let msg: Entry = src.expect_message().unwrap();
+    assert_eq!(msg, entry(1));
+
+    let err = src.expect_message::<Entry>().unwrap_err();
+    assert!(matches!(err, CborDataError::HashChainBroken));
+    assert_eq!(err.kind(), GroupErrorKind::Validation);
+
+    // Once the chain is broken, the rest of the messages are rejected too.
+    let err = src.expect_message::<Entry>().unwrap_err();
+    assert!(matches!(err, CborDataError::HashChainBroken));
+}
+
+#[test]
+fn test_wrong_genesis() {
+    let (buf, _) = three_entries();
+    let mut src = HashChainSource::new(Cursor::new(buf), Sha256Chain).with_genesis([1; 32]);
+    let err = src.expect_message::<Entry>().unwrap_err();
+    assert!(matches!(err, CborDataError::HashChainBroken));
+}
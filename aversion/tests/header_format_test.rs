@@ -0,0 +1,135 @@
+use aversion::group::{DataSink, DataSource, GroupHeader, UpgradeLatest};
+use aversion::util::cbor::{CborCodec, CborData};
+use aversion::util::preamble;
+use aversion::util::{BasicHeader, ExtendedHeader, FramedHeader};
+use aversion::{assign_message_ids, MessageId, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Cursor, Read, Write};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+assign_message_ids! {
+    Foo: 1,
+}
+
+/// A header whose layout has changed: format 1 was a `BasicHeader`, and
+/// format 2 is an `ExtendedHeader`.
+#[derive(Debug)]
+enum EvolvingHeader {
+    V1(BasicHeader),
+    V2(ExtendedHeader),
+}
+
+impl GroupHeader for EvolvingHeader {
+    fn msg_id(&self) -> u16 {
+        match self {
+            EvolvingHeader::V1(h) => h.msg_id,
+            EvolvingHeader::V2(h) => h.msg_id,
+        }
+    }
+
+    fn msg_ver(&self) -> u16 {
+        match self {
+            EvolvingHeader::V1(h) => h.msg_ver,
+            EvolvingHeader::V2(h) => h.msg_ver,
+        }
+    }
+}
+
+impl FramedHeader for EvolvingHeader {
+    const HEADER_FORMAT: u16 = 2;
+
+    fn for_msg<T>(msg: &T, msg_len: u32) -> Self
+    where
+        T: Versioned,
+        T::Base: MessageId,
+    {
+        EvolvingHeader::V2(ExtendedHeader::for_msg(msg, msg_len).with_seq(9))
+    }
+
+    fn msg_len(&self) -> u32 {
+        match self {
+            EvolvingHeader::V1(h) => h.msg_len,
+            EvolvingHeader::V2(h) => h.msg_len,
+        }
+    }
+
+    fn deserialize_from(r: &mut impl Read) -> Result<Self, io::Error> {
+        ExtendedHeader::deserialize_from(r).map(EvolvingHeader::V2)
+    }
+
+    fn deserialize_format(format: u16, r: &mut impl Read) -> Result<Self, io::Error> {
+        match format {
+            1 => BasicHeader::deserialize_from(r).map(EvolvingHeader::V1),
+            2 => Self::deserialize_from(r),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "bad format")),
+        }
+    }
+
+    fn serialize_into(self, w: &mut impl Write) -> Result<(), io::Error> {
+        match self {
+            EvolvingHeader::V1(h) => h.serialize_into(w),
+            EvolvingHeader::V2(h) => h.serialize_into(w),
+        }
+    }
+}
+
+fn write_file<H: FramedHeader>() -> Vec<u8> {
+    let mut buf = Vec::new();
+    preamble::write_preamble_with_header::<CborCodec, H>(&mut buf).unwrap();
+    let mut sink = CborData::<_, H>::with_header(buf);
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Foo { foo: 2 }).unwrap();
+    sink.into_inner()
+}
+
+/// Read the messages, along with the sequence number from each header.
+fn read_file(buf: Vec<u8>) -> Vec<(Foo, Option<u64>)> {
+    let mut file = Cursor::new(buf);
+    let found = preamble::read_preamble::<CborCodec>(&mut file).unwrap();
+    let mut src =
+        CborData::<_, EvolvingHeader>::with_header(file).header_format(found.header_format);
+    let mut msgs = Vec::new();
+    while let Some(header) = src.try_read_header().unwrap() {
+        let seq = match &header {
+            EvolvingHeader::V1(_) => None,
+            EvolvingHeader::V2(h) => h.seq,
+        };
+        msgs.push((Foo::upgrade_latest(&mut src, header).unwrap(), seq));
+    }
+    msgs
+}
+
+#[test]
+fn test_read_old_header_format() {
+    let msgs = read_file(write_file::<BasicHeader>());
+    assert_eq!(msgs, [(Foo { foo: 1 }, None), (Foo { foo: 2 }, None)]);
+}
+
+#[test]
+fn test_read_new_header_format() {
+    let msgs = read_file(write_file::<EvolvingHeader>());
+    assert_eq!(msgs, [(Foo { foo: 1 }, Some(9)), (Foo { foo: 2 }, Some(9))]);
+}
+
+#[test]
+fn test_unknown_header_format() {
+    let mut buf = Vec::new();
+    preamble::write_preamble_with_header::<CborCodec, EvolvingHeader>(&mut buf).unwrap();
+    let mut file = Cursor::new(buf);
+    let found = preamble::read_preamble::<CborCodec>(&mut file).unwrap();
+    assert_eq!(found.version, preamble::PREAMBLE_VERSION_5);
+    assert_eq!(found.header_format, 2);
+    assert_eq!(file.position(), found.size() as u64);
+
+    // A reader that only knows BasicHeader can't read format 2.
+    let mut sink = CborData::<_, EvolvingHeader>::with_header(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    let mut src = CborData::new(Cursor::new(sink.into_inner())).header_format(2);
+    src.read_header().unwrap_err();
+}
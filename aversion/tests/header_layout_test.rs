@@ -0,0 +1,107 @@
+use aversion::util::layout::{FieldEncoding, HeaderField, HeaderLayout};
+use aversion::util::{
+    BasicHeader, ChainHeader, ExtendedHeader, FlagsHeader, SemverHeader, SequencedHeader,
+    TinyHeader, VarintHeader, WideHeader,
+};
+
+fn describe<H: HeaderLayout>() -> Vec<String> {
+    H::layout().iter().map(HeaderField::to_string).collect()
+}
+
+/// The size of a header whose fields all have a fixed offset and width.
+fn fixed_size<H: HeaderLayout>() -> usize {
+    let last = H::layout().last().unwrap();
+    last.offset.unwrap() + last.width.unwrap()
+}
+
+#[test]
+fn test_tiny_header() {
+    assert_eq!(
+        describe::<TinyHeader>(),
+        ["msg_id@0:2 BE", "msg_ver@2:2 BE"]
+    );
+    assert_eq!(
+        TinyHeader::layout()[0],
+        HeaderField {
+            name: "msg_id",
+            offset: Some(0),
+            width: Some(2),
+            encoding: FieldEncoding::BigEndian,
+            present_if: None,
+        }
+    );
+}
+
+#[test]
+fn test_fixed_sizes() {
+    assert_eq!(fixed_size::<TinyHeader>(), TinyHeader::SIZE);
+    assert_eq!(fixed_size::<BasicHeader>(), BasicHeader::SIZE);
+    assert_eq!(fixed_size::<FlagsHeader>(), FlagsHeader::SIZE);
+    assert_eq!(fixed_size::<SemverHeader>(), SemverHeader::SIZE);
+    assert_eq!(fixed_size::<SequencedHeader>(), SequencedHeader::SIZE);
+    assert_eq!(fixed_size::<WideHeader>(), WideHeader::SIZE);
+}
+
+#[test]
+fn test_fixed_offsets_are_contiguous() {
+    fn check<H: HeaderLayout>() {
+        let mut offset = 0;
+        for field in H::layout() {
+            assert_eq!(field.offset, Some(offset), "{}", field);
+            offset += field.width.unwrap();
+        }
+    }
+    check::<TinyHeader>();
+    check::<BasicHeader>();
+    check::<FlagsHeader>();
+    check::<SemverHeader>();
+    check::<SequencedHeader>();
+    check::<WideHeader>();
+}
+
+#[test]
+fn test_extended_header() {
+    let layout = describe::<ExtendedHeader>();
+    assert_eq!(
+        layout,
+        [
+            "msg_id@0:2 BE",
+            "msg_ver@2:2 BE",
+            "present@4:1 bytes",
+            "timestamp@5:8 BE if present & 0x01",
+            "flags@?:1 bytes if present & 0x02",
+            "seq@?:8 BE if present & 0x04",
+            "expiry@?:8 BE if present & 0x08",
+            "correlation_id@?:8 BE if present & 0x10",
+            "msg_len@?:4 BE",
+        ]
+    );
+
+    // The widths add up to the minimum and maximum sizes.
+    let fields = ExtendedHeader::layout();
+    let all: usize = fields.iter().map(|f| f.width.unwrap()).sum();
+    let required: usize = fields
+        .iter()
+        .filter(|f| f.present_if.is_none())
+        .map(|f| f.width.unwrap())
+        .sum();
+    assert_eq!(all, ExtendedHeader::MAX_SIZE);
+    assert_eq!(required, ExtendedHeader::MIN_SIZE);
+
+    let chain = describe::<ChainHeader>();
+    assert_eq!(chain[..layout.len()], layout[..]);
+    assert_eq!(chain.last().unwrap(), "hash@?:32 bytes");
+}
+
+#[test]
+fn test_varint_header() {
+    assert_eq!(
+        describe::<VarintHeader>(),
+        [
+            "marker@0:1 bytes",
+            "msg_id@1:? LEB128",
+            "msg_ver@?:? LEB128",
+            "msg_len@?:? LEB128",
+        ]
+    );
+}
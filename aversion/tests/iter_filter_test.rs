@@ -0,0 +1,121 @@
+use aversion::group::{DataSink, GroupHeader};
+use aversion::util::cbor::{CborData, CborDataError, SliceSource};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, MessageId, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+/// Not part of the group.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BazV1 {
+    baz: u8,
+}
+
+type Baz = BazV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+    Baz: 3,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn foo_bar_foo() -> Vec<u8> {
+    let mut buf = sink_bytes(&Foo { foo: 1 });
+    // A Bar whose body isn't valid CBOR; it must be skipped, not decoded.
+    BasicHeader::new(Bar::MSG_ID, 1, 3)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&[0xff, 0xff, 0xff]);
+    buf.extend(sink_bytes(&Foo { foo: 2 }));
+    buf
+}
+
+fn sink_bytes<T>(msg: &T) -> Vec<u8>
+where
+    T: Serialize + Versioned,
+    T::Base: MessageId,
+{
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(msg).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_iter_filter() {
+    let mut src = CborData::new(Cursor::new(foo_bar_foo()));
+    let foos = MyGroup::iter_filter::<Foo, _>(&mut src)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(foos, vec![Foo { foo: 1 }, Foo { foo: 2 }]);
+}
+
+#[test]
+fn test_iter_filter_slice() {
+    let buf = foo_bar_foo();
+    let mut src = SliceSource::new(&buf);
+    let foos = MyGroup::iter_filter::<Foo, _>(&mut src)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(foos, vec![Foo { foo: 1 }, Foo { foo: 2 }]);
+    assert!(src.remaining().is_empty());
+}
+
+#[test]
+fn test_iter_filter_unknown() {
+    let mut buf = sink_bytes(&Foo { foo: 1 });
+    buf.extend(sink_bytes(&Baz { baz: 9 }));
+    buf.extend(sink_bytes(&Foo { foo: 2 }));
+
+    // By default, the unknown message is an error, and ends the iteration.
+    let mut src = CborData::new(Cursor::new(buf.clone()));
+    let mut iter = MyGroup::iter_filter::<Foo, _>(&mut src);
+    assert_eq!(iter.next().unwrap().unwrap(), Foo { foo: 1 });
+    assert!(matches!(
+        iter.next(),
+        Some(Err(CborDataError::UnknownMessage { msg_id: 3 }))
+    ));
+    assert!(iter.next().is_none());
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let foos = MyGroup::iter_filter::<Foo, _>(&mut src)
+        .skip_unknown(true)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(foos, vec![Foo { foo: 1 }, Foo { foo: 2 }]);
+}
+
+#[test]
+fn test_try_read_header() {
+    use aversion::group::DataSource;
+
+    let buf = sink_bytes(&Foo { foo: 1 });
+    let mut src = CborData::new(Cursor::new(buf.clone()));
+    let header = src.try_read_header().unwrap().unwrap();
+    assert_eq!(header.msg_id(), Foo::MSG_ID);
+    src.skip_message(&header).unwrap();
+    assert!(src.try_read_header().unwrap().is_none());
+
+    // A truncated header is an error, not the end of the data.
+    let mut src = CborData::new(Cursor::new(&buf[..3]));
+    src.try_read_header().unwrap_err();
+}
@@ -0,0 +1,110 @@
+#![cfg(feature = "json")]
+
+use aversion::group::DataSink;
+use aversion::util::json::DebugJsonSink;
+use aversion::{assign_message_ids, GroupSerialize, Versioned};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Versioned, Serialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize)]
+struct FooV2 {
+    foo: u32,
+    name: String,
+}
+
+type Foo = FooV2;
+
+#[derive(Debug, PartialEq, Versioned, Serialize)]
+struct BarV1 {
+    bar: Vec<u8>,
+}
+
+type Bar = BarV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize)]
+struct StatsV1 {
+    zeta: u32,
+    alpha: HashMap<String, Vec<u32>>,
+}
+
+type Stats = StatsV1;
+
+assign_message_ids! {
+    Foo: 112,
+    Bar: 113,
+    Stats: 114,
+}
+
+#[derive(GroupSerialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+    Stats(Stats),
+}
+
+#[test]
+fn test_debug_json() {
+    let mut sink = DebugJsonSink::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 1234 }).unwrap();
+    let output = String::from_utf8(sink.into_inner()).unwrap();
+    assert_eq!(output, "{\"id\":112,\"ver\":1,\"body\":{\"foo\":1234}}\n");
+}
+
+#[test]
+fn test_debug_json_group() {
+    let messages = vec![
+        MyGroup::Foo(Foo {
+            foo: 1,
+            name: "one".to_owned(),
+        }),
+        MyGroup::Bar(Bar { bar: vec![1, 2] }),
+    ];
+    let mut sink = DebugJsonSink::new(Vec::new());
+    for msg in &messages {
+        msg.write_message(&mut sink).unwrap();
+    }
+    let output = String::from_utf8(sink.into_inner()).unwrap();
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            r#"{"id":112,"ver":2,"body":{"foo":1,"name":"one"}}"#,
+            r#"{"id":113,"ver":1,"body":{"bar":[1,2]}}"#,
+        ]
+    );
+}
+
+#[test]
+fn test_canonical_json() {
+    let msg = MyGroup::Foo(Foo {
+        foo: 1,
+        name: "one".to_owned(),
+    });
+    assert_eq!(
+        msg.to_canonical_json().unwrap(),
+        r#"{"body":{"foo":1,"name":"one"},"msg_id":112,"version":2}"#
+    );
+}
+
+#[test]
+fn test_canonical_json_sorted() {
+    // Keys are sorted at every level, regardless of field order or
+    // `HashMap` iteration order.
+    let mut alpha = HashMap::new();
+    for key in &["c", "a", "d", "b"] {
+        alpha.insert(key.to_string(), vec![1, 2]);
+    }
+    let msg = MyGroup::Stats(StatsV1 { zeta: 7, alpha });
+    assert_eq!(
+        msg.to_canonical_json().unwrap(),
+        concat!(
+            r#"{"body":{"alpha":{"a":[1,2],"b":[1,2],"c":[1,2],"d":[1,2]},"zeta":7},"#,
+            r#""msg_id":114,"version":1}"#
+        )
+    );
+}
@@ -0,0 +1,30 @@
+use aversion::{IsLatest, Versioned};
+
+#[derive(Versioned)]
+struct FooV1 {}
+
+#[derive(Versioned)]
+#[versioned(latest)]
+struct FooV2 {}
+
+type Foo = FooV2;
+
+#[derive(Versioned)]
+#[versioned(latest, minor = 1)]
+struct EnvelopeV1<T> {
+    _payload: T,
+}
+
+type Envelope<T> = EnvelopeV1<T>;
+
+fn is_latest<T: IsLatest>() -> u16 {
+    T::VER
+}
+
+#[test]
+fn test_latest_marker() {
+    assert_eq!(is_latest::<Foo>(), 2);
+    assert_eq!(is_latest::<Envelope<u32>>(), 1);
+    assert_eq!(<Envelope<u32> as Versioned>::MINOR_VER, 1);
+    assert_eq!(<FooV1 as Versioned>::VER, 1);
+}
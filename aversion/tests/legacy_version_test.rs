@@ -0,0 +1,116 @@
+use aversion::group::{DataSink, DataSourceExt, UpgradeLatest};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, FromVersion, RawVersions, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+/// The format used before messages had versions.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV0 {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    name: String,
+    count: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, RawVersions, Serialize, Deserialize)]
+#[versioned(legacy)]
+struct FooV2 {
+    name: String,
+    count: u64,
+}
+
+type Foo = FooV2;
+
+impl FromVersion<FooV0> for FooV1 {
+    fn from_version(v0: FooV0) -> Self {
+        FooV1 {
+            name: v0.name,
+            count: 0,
+        }
+    }
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 {
+            name: v1.name,
+            count: v1.count.into(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct BarV1 {
+    bar: u32,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+/// A legacy message: a header with version 0, and the legacy body.
+fn legacy_message(msg_id: u16) -> Vec<u8> {
+    let body = serde_cbor::to_vec(&FooV0 { name: "old".into() }).unwrap();
+    let mut buf = Vec::new();
+    BasicHeader::new(msg_id, 0, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&body);
+    buf
+}
+
+#[test]
+fn test_legacy_upgrade() {
+    assert_eq!(FooV0::VER, 0);
+    assert_eq!(Foo::SUPPORTED_VERSIONS, [0, 1, 2]);
+
+    let buf = legacy_message(1);
+    let foo: Foo = CborData::new(&buf[..]).expect_message().unwrap();
+    assert_eq!(
+        foo,
+        FooV2 {
+            name: "old".into(),
+            count: 0
+        }
+    );
+
+    // The skipping upgrade is generated too.
+    let foo = FooV2::from_version(FooV0 { name: "a".into() });
+    assert_eq!(foo.count, 0);
+}
+
+#[test]
+fn test_legacy_write() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV0 { name: "old".into() }).unwrap();
+    assert_eq!(sink.into_inner(), legacy_message(1));
+}
+
+#[test]
+fn test_legacy_raw_version() {
+    let buf = legacy_message(1);
+    let (ver, raw) = CborData::new(&buf[..]).read_raw_version::<Foo>().unwrap();
+    assert_eq!(ver, 0);
+    assert_eq!(raw.version(), 0);
+    assert!(matches!(raw, FooRawVersion::V0(FooV0 { .. })));
+    assert_eq!(raw.upgrade().name, "old");
+}
+
+#[test]
+fn test_version_zero_without_legacy() {
+    // Without #[versioned(legacy)], version 0 is unknown.
+    assert_eq!(Bar::SUPPORTED_VERSIONS, [1]);
+    let buf = legacy_message(2);
+    let result = CborData::new(&buf[..]).expect_message::<Bar>();
+    assert!(matches!(
+        result,
+        Err(CborDataError::UnknownVersion { got: 0, .. })
+    ));
+}
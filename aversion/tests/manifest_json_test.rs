@@ -0,0 +1,115 @@
+use aversion::group::{
+    DataSink, MessageKey, MessageStats, OwnedEntryStatus, OwnedGroupEntry, Priority, Replayable,
+};
+use aversion::util::cbor::CborData;
+use aversion::{assign_message_ids, FromVersion, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV2 {
+    foo: u64,
+}
+
+type Foo = FooV2;
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 { foo: v1.foo.into() }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: u32,
+}
+
+type Bar = BarV1;
+
+impl Replayable for Bar {}
+
+impl Priority for Bar {
+    const PRIORITY: u8 = 5;
+}
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[reserved(3)]
+#[deprecated_msg(4, "use Bar")]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+#[test]
+fn test_manifest_json_roundtrip() {
+    let json = serde_json::to_string_pretty(MyGroup::messages()).unwrap();
+    let manifest: Vec<OwnedGroupEntry> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(manifest.len(), MyGroup::messages().len());
+    for (owned, entry) in manifest.iter().zip(MyGroup::messages()) {
+        assert_eq!(owned, entry);
+    }
+
+    let bar = manifest.iter().find(|e| e.msg_id == 2).unwrap();
+    assert_eq!(bar.name.as_deref(), Some("Bar"));
+    assert_eq!(bar.status, OwnedEntryStatus::Active);
+    assert!(bar.replayable);
+    assert_eq!(bar.priority, 5);
+
+    let foo = manifest.iter().find(|e| e.msg_id == 1).unwrap();
+    assert_eq!(foo.versions, [1, 2]);
+
+    let deprecated = manifest.iter().find(|e| e.msg_id == 4).unwrap();
+    assert_eq!(
+        deprecated.status,
+        OwnedEntryStatus::Deprecated("use Bar".into())
+    );
+
+    // The owned copy serializes the same way.
+    assert_eq!(serde_json::to_string_pretty(&manifest).unwrap(), json);
+}
+
+#[test]
+fn test_message_key_json() {
+    let mut counts = BTreeMap::new();
+    counts.insert(
+        MessageKey {
+            msg_id: 1,
+            msg_ver: 2,
+        },
+        MessageStats {
+            count: 3,
+            total_bytes: 40,
+        },
+    );
+    let stats: Vec<_> = counts.into_iter().collect();
+    let json = serde_json::to_string(&stats).unwrap();
+    let parsed: Vec<(MessageKey, MessageStats)> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, stats);
+
+    // Also check the stats from a real scan.
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 1 }).unwrap();
+    let buf = sink.into_inner();
+    let stats = MyGroup::scan_stats(&mut CborData::new(&buf[..])).unwrap();
+    let json = serde_json::to_string(&stats.into_iter().collect::<Vec<_>>()).unwrap();
+    let parsed: Vec<(MessageKey, MessageStats)> = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        parsed[0].0,
+        MessageKey {
+            msg_id: 1,
+            msg_ver: 1
+        }
+    );
+    assert_eq!(parsed[0].1.count, 1);
+}
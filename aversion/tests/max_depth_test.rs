@@ -0,0 +1,139 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt, IsTruncated};
+use aversion::util::cbor::{check_depth, CborData, CborDataError};
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value;
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct TreeV1 {
+    root: Value,
+}
+
+type Tree = TreeV1;
+
+assign_message_ids! {
+    Tree: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Trees {
+    Tree(Tree),
+}
+
+/// Build a value made of `depth` nested arrays.
+fn nested(depth: usize) -> Value {
+    (0..depth).fold(Value::Integer(0), |inner, _| Value::Array(vec![inner]))
+}
+
+fn encode(msg: &TreeV1) -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(msg).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_max_depth() {
+    // The struct itself is one level, so this is 11 levels deep.
+    let deep = TreeV1 { root: nested(10) };
+    let buf = encode(&deep);
+    let mut src = CborData::new(Cursor::new(buf.clone())).max_depth(8);
+    let err = Trees::read_message(&mut src).unwrap_err();
+    assert!(matches!(err, CborDataError::NestingTooDeep { max: 8 }));
+
+    // Without a limit, the same message is accepted.
+    let mut src = CborData::new(Cursor::new(buf));
+    let Trees::Tree(msg) = Trees::read_message(&mut src).unwrap();
+    assert_eq!(msg, deep);
+
+    // A shallow message passes.
+    let shallow = TreeV1 { root: nested(3) };
+    let buf = encode(&shallow);
+    let mut src = CborData::new(Cursor::new(buf)).max_depth(8);
+    let Trees::Tree(msg) = Trees::read_message(&mut src).unwrap();
+    assert_eq!(msg, shallow);
+}
+
+#[test]
+fn test_max_depth_exact() {
+    let msg = TreeV1 { root: nested(7) };
+    let buf = encode(&msg);
+    let mut src = CborData::new(Cursor::new(buf.clone())).max_depth(8);
+    let header = src.read_header().unwrap();
+    let decoded: TreeV1 = src.read_message(&header).unwrap();
+    assert_eq!(decoded, msg);
+
+    let mut src = CborData::new(Cursor::new(buf)).max_depth(7);
+    let header = src.read_header().unwrap();
+    let err = src.read_message::<TreeV1>(&header).unwrap_err();
+    assert!(matches!(err, CborDataError::NestingTooDeep { max: 7 }));
+}
+
+#[test]
+fn test_check_depth() {
+    // Scalars and empty containers.
+    check_depth(&[0x01], 0).unwrap();
+    check_depth(&[0x80], 1).unwrap();
+    assert!(matches!(
+        check_depth(&[0x80], 0),
+        Err(CborDataError::NestingTooDeep { max: 0 })
+    ));
+
+    // [[1], {"a": [2]}] is 3 levels deep.
+    let buf = [0x82, 0x81, 0x01, 0xa1, 0x61, b'a', 0x81, 0x02];
+    check_depth(&buf, 3).unwrap();
+    assert!(check_depth(&buf, 2).is_err());
+
+    // Indefinite-length arrays and strings.
+    let buf = [0x9f, 0x9f, 0xff, 0x7f, 0x61, b'a', 0xff, 0xff];
+    check_depth(&buf, 2).unwrap();
+    assert!(check_depth(&buf, 1).is_err());
+
+    // Tags don't add a level.
+    check_depth(&[0xc1, 0x81, 0x01], 1).unwrap();
+
+    // Truncated input.
+    assert!(matches!(
+        check_depth(&[0x82, 0x01], 8),
+        Err(CborDataError::Eof)
+    ));
+    assert!(matches!(
+        check_depth(&[0x62, b'a'], 8),
+        Err(CborDataError::Eof)
+    ));
+}
+
+#[test]
+fn test_max_depth_no_recursion() {
+    // A hostile payload of 100,000 nested arrays is rejected without
+    // overflowing the stack.
+    let buf = vec![0x81; 100_000];
+    assert!(matches!(
+        check_depth(&buf, 64),
+        Err(CborDataError::NestingTooDeep { max: 64 })
+    ));
+    // Even with an unreasonably large limit, it's only truncated.
+    assert!(matches!(
+        check_depth(&buf, usize::MAX),
+        Err(CborDataError::Eof)
+    ));
+}
+
+#[test]
+fn test_oversized_length() {
+    // A header that claims a 4 GiB body, followed by a few bytes. The body
+    // buffer must not be sized from the header before the data arrives.
+    let mut buf = Vec::new();
+    aversion::util::BasicHeader::new(1, 1, u32::MAX)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&[0xa1, 0x64]);
+
+    let mut src = CborData::new(&buf[..]).max_depth(8);
+    let err = src.expect_message::<Tree>().unwrap_err();
+    assert!(err.is_truncated());
+
+    let mut src = CborData::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    assert!(src.read_raw(&header).unwrap_err().is_truncated());
+}
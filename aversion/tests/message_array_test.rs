@@ -0,0 +1,127 @@
+use aversion::group::{DataSink, DataSinkExt, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct PointV1 {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PointV2 {
+    x: i64,
+    y: i64,
+    z: i64,
+}
+
+impl FromVersion<PointV1> for PointV2 {
+    fn from_version(v1: PointV1) -> Self {
+        PointV2 {
+            x: v1.x.into(),
+            y: v1.y.into(),
+            z: 0,
+        }
+    }
+}
+
+type Point = PointV2;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct OtherV1 {
+    other: u8,
+}
+
+type Other = OtherV1;
+
+assign_message_ids! {
+    Point: 1,
+    Other: 2,
+}
+
+fn point(x: i64, y: i64, z: i64) -> Point {
+    Point { x, y, z }
+}
+
+#[test]
+fn test_per_element_headers() {
+    // Each element has its own header, so they can have different versions.
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&PointV1 { x: 1, y: 2 }).unwrap();
+    sink.write_message(&point(3, 4, 5)).unwrap();
+    sink.write_message(&PointV1 { x: 6, y: 7 }).unwrap();
+    sink.write_message(&Other { other: 1 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let points: Vec<Point> = src.expect_message_vec(3).unwrap();
+    assert_eq!(points, vec![point(1, 2, 0), point(3, 4, 5), point(6, 7, 0)]);
+    // The next message is untouched.
+    let other: Other = src.expect_message().unwrap();
+    assert_eq!(other, Other { other: 1 });
+}
+
+#[test]
+fn test_per_element_wrong_message() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&point(1, 2, 3)).unwrap();
+    sink.write_message(&Other { other: 1 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let err = src.expect_message_vec::<Point>(3).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::UnexpectedMessage {
+            expected_id: 1,
+            got: 2,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_shared_header() {
+    let points = vec![point(1, 2, 3), point(4, 5, 6), point(7, 8, 9)];
+
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message_array(&points).unwrap();
+    sink.write_message(&Other { other: 1 }).unwrap();
+    let buf = sink.into_inner();
+
+    // One header for the whole array.
+    let header = BasicHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(header.msg_id, 1);
+    assert_eq!(header.msg_ver, 2);
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let decoded: Vec<Point> = src.expect_message_array().unwrap();
+    assert_eq!(decoded, points);
+    let other: Other = src.expect_message().unwrap();
+    assert_eq!(other, Other { other: 1 });
+}
+
+#[test]
+fn test_shared_header_upgrade() {
+    let old = vec![
+        PointV1 { x: 1, y: 2 },
+        PointV1 { x: 3, y: 4 },
+        PointV1 { x: 5, y: 6 },
+    ];
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message_array(&old).unwrap();
+    sink.write_message_array::<Point>(&[]).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let decoded: Vec<Point> = src.expect_message_array().unwrap();
+    assert_eq!(
+        decoded,
+        vec![point(1, 2, 0), point(3, 4, 0), point(5, 6, 0)]
+    );
+    let decoded: Vec<Point> = src.expect_message_array().unwrap();
+    assert!(decoded.is_empty());
+}
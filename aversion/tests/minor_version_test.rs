@@ -0,0 +1,109 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt, GroupHeader};
+use aversion::util::cbor::CborData;
+use aversion::util::SemverHeader;
+use aversion::{Version, Versioned};
+use std::io::Cursor;
+
+/// The writer knows about a newer minor version, which added a field.
+mod writer {
+    use aversion::{assign_message_ids, Versioned};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+    #[versioned(minor = 5)]
+    pub struct FooV2 {
+        pub foo: u64,
+        pub extra: String,
+    }
+
+    pub type Foo = FooV2;
+
+    assign_message_ids! {
+        Foo: 1,
+    }
+}
+
+/// The reader only knows about minor version 3.
+mod reader {
+    use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+    pub struct FooV1 {
+        pub foo: u32,
+    }
+
+    #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+    #[versioned(minor = 3)]
+    pub struct FooV2 {
+        pub foo: u64,
+    }
+
+    impl FromVersion<FooV1> for FooV2 {
+        fn from_version(v1: FooV1) -> Self {
+            Self { foo: v1.foo.into() }
+        }
+    }
+
+    pub type Foo = FooV2;
+
+    assign_message_ids! {
+        Foo: 1,
+    }
+}
+
+#[test]
+fn test_version_consts() {
+    assert_eq!(reader::FooV1::VERSION, Version { major: 1, minor: 0 });
+    assert_eq!(reader::FooV2::VERSION, Version { major: 2, minor: 3 });
+    assert_eq!(writer::FooV2::VER, 2);
+    assert_eq!(writer::FooV2::MINOR_VER, 5);
+}
+
+#[test]
+fn test_newer_minor_version() {
+    let mut sink = CborData::<_, SemverHeader>::with_header(Vec::new());
+    sink.write_message(&writer::Foo {
+        foo: 1234,
+        extra: "ignored".to_owned(),
+    })
+    .unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::<_, SemverHeader>::with_header(&buf[..]);
+    let header = src.read_header().unwrap();
+    assert_eq!(header.version(), Version { major: 2, minor: 5 });
+    assert_eq!(header.msg_minor_ver(), 5);
+
+    // The message is decoded by the reader's major version 2 type, and
+    // the unknown field is ignored.
+    let mut src = CborData::<_, SemverHeader>::with_header(Cursor::new(buf));
+    let msg: reader::Foo = src.expect_message().unwrap();
+    assert_eq!(msg, reader::Foo { foo: 1234 });
+}
+
+#[test]
+fn test_upgrade_ignores_minor_version() {
+    let mut sink = CborData::<_, SemverHeader>::with_header(Vec::new());
+    sink.write_message(&reader::FooV1 { foo: 7 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::<_, SemverHeader>::with_header(Cursor::new(buf));
+    let msg: reader::Foo = src.expect_message().unwrap();
+    assert_eq!(msg, reader::Foo { foo: 7 });
+}
+
+#[test]
+fn test_semver_header_roundtrip() {
+    let header = SemverHeader::new(0x1234, Version { major: 2, minor: 5 }, 99);
+    let buf = header.serialize();
+    assert_eq!(
+        buf,
+        [0x12, 0x34, 0x00, 0x02, 0x00, 0x05, 0x00, 0x00, 0x00, 0x63]
+    );
+    let header = SemverHeader::deserialize(&buf);
+    assert_eq!(header.msg_id(), 0x1234);
+    assert_eq!(header.msg_ver(), 2);
+    assert_eq!(header.msg_minor_ver(), 5);
+    assert_eq!(header.msg_len, 99);
+}
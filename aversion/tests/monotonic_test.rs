@@ -0,0 +1,132 @@
+use aversion::group::DataSource;
+use aversion::util::cbor::CborData;
+use aversion::util::monotonic::{MonotonicError, MonotonicSource};
+use aversion::util::ExtendedHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct EventV1 {
+    id: u32,
+}
+
+type Event = EventV1;
+
+assign_message_ids! {
+    Event: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Log {
+    Event(Event),
+}
+
+type ExtSource = CborData<Cursor<Vec<u8>>, ExtendedHeader>;
+
+/// Build a stream of `Event` messages with the given timestamps.
+fn stream(timestamps: &[Option<u64>]) -> MonotonicSource<ExtSource> {
+    let mut buf = Vec::new();
+    for (id, timestamp) in (1..).zip(timestamps) {
+        let body = serde_cbor::to_vec(&EventV1 { id }).unwrap();
+        let mut header = ExtendedHeader::new(1, 1, body.len() as u32);
+        header.timestamp = *timestamp;
+        buf.extend_from_slice(&header.serialize());
+        buf.extend_from_slice(&body);
+    }
+    MonotonicSource::new(CborData::with_header(Cursor::new(buf)))
+}
+
+fn read_ids(src: &mut MonotonicSource<ExtSource>) -> Vec<u32> {
+    Log::iter_filter::<Event, _>(src)
+        .map(|event| event.unwrap().id)
+        .collect()
+}
+
+#[test]
+fn test_in_order() {
+    let mut src = stream(&[Some(10), Some(20), Some(20), None, Some(30)]);
+    assert_eq!(read_ids(&mut src), vec![1, 2, 3, 4, 5]);
+    assert_eq!(src.last_timestamp(), Some(30));
+}
+
+#[test]
+fn test_out_of_order() {
+    let mut src = stream(&[Some(10), Some(20), Some(15), Some(30)]);
+    assert_eq!(
+        Log::read_message(&mut src).unwrap(),
+        Log::Event(EventV1 { id: 1 })
+    );
+    assert_eq!(
+        Log::read_message(&mut src).unwrap(),
+        Log::Event(EventV1 { id: 2 })
+    );
+
+    let err = Log::read_message(&mut src).unwrap_err();
+    assert!(matches!(
+        err,
+        MonotonicError::OutOfOrder {
+            previous: 20,
+            found: 15
+        }
+    ));
+    assert_eq!(src.last_timestamp(), Some(20));
+
+    // The rejected body is still unread; skip it and carry on.
+    let inner = src.get_mut();
+    let mut header = ExtendedHeader::new(0, 0, 0);
+    header.msg_len = serde_cbor::to_vec(&EventV1 { id: 3 }).unwrap().len() as u32;
+    inner.skip_message(&header).unwrap();
+    assert_eq!(
+        Log::read_message(&mut src).unwrap(),
+        Log::Event(EventV1 { id: 4 })
+    );
+}
+
+#[test]
+fn test_equal_rejected() {
+    let mut src = stream(&[Some(10), Some(10)]).allow_equal(false);
+    assert_eq!(
+        Log::read_message(&mut src).unwrap(),
+        Log::Event(EventV1 { id: 1 })
+    );
+    let err = Log::read_message(&mut src).unwrap_err();
+    assert!(matches!(
+        err,
+        MonotonicError::OutOfOrder {
+            previous: 10,
+            found: 10
+        }
+    ));
+}
+
+#[test]
+fn test_missing_timestamp() {
+    // A message without a timestamp doesn't reset the last timestamp.
+    let mut src = stream(&[Some(10), None, Some(5)]);
+    assert_eq!(
+        Log::read_message(&mut src).unwrap(),
+        Log::Event(EventV1 { id: 1 })
+    );
+    assert_eq!(
+        Log::read_message(&mut src).unwrap(),
+        Log::Event(EventV1 { id: 2 })
+    );
+    assert!(matches!(
+        Log::read_message(&mut src).unwrap_err(),
+        MonotonicError::OutOfOrder {
+            previous: 10,
+            found: 5
+        }
+    ));
+}
+
+#[test]
+fn test_source_error() {
+    let mut src = stream(&[Some(10)]);
+    Log::read_message(&mut src).unwrap();
+    assert!(matches!(
+        Log::read_message(&mut src).unwrap_err(),
+        MonotonicError::Source(_)
+    ));
+}
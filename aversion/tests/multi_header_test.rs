@@ -0,0 +1,101 @@
+use aversion::group::{DataSink, DataSource};
+use aversion::util::cbor::{CborData, MultiHeaderSource};
+use aversion::util::{BasicHeader, MultiHeader, VarintHeader};
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 0x1234,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn write_basic(buf: Vec<u8>, n: u32) -> Vec<u8> {
+    let mut sink = CborData::<_, BasicHeader>::with_header(buf);
+    sink.write_message(&Foo { foo: n }).unwrap();
+    sink.into_inner()
+}
+
+fn write_varint(buf: Vec<u8>, s: &str) -> Vec<u8> {
+    let mut sink = CborData::<_, VarintHeader>::with_header(buf);
+    sink.write_message(&Bar { bar: s.into() }).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_mixed_headers() {
+    let buf = write_basic(Vec::new(), 1);
+    let buf = write_varint(buf, "two");
+    let buf = write_basic(buf, 3);
+    let buf = write_varint(buf, "four");
+
+    let mut src = MultiHeaderSource::with_header(Cursor::new(buf));
+    let msgs: Vec<MyGroup> = (0..4)
+        .map(|_| MyGroup::read_message(&mut src).unwrap())
+        .collect();
+    assert!(src.try_read_header().unwrap().is_none());
+    assert_eq!(
+        msgs,
+        [
+            MyGroup::Foo(Foo { foo: 1 }),
+            MyGroup::Bar(Bar { bar: "two".into() }),
+            MyGroup::Foo(Foo { foo: 3 }),
+            MyGroup::Bar(Bar { bar: "four".into() }),
+        ]
+    );
+}
+
+#[test]
+fn test_detected_header() {
+    let buf = write_varint(write_basic(Vec::new(), 1), "two");
+    let mut src = MultiHeaderSource::with_header(Cursor::new(buf));
+
+    let header = src.read_header().unwrap();
+    assert!(matches!(header, MultiHeader::Basic(_)));
+    src.skip_message(&header).unwrap();
+
+    let header = src.read_header().unwrap();
+    match header {
+        MultiHeader::Varint(h) => {
+            assert_eq!((h.msg_id, h.msg_ver), (0x1234, 1));
+        }
+        other => panic!("unexpected header {:?}", other),
+    }
+    src.skip_message(&header).unwrap();
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_varint_header_size() {
+    let mut buf = Vec::new();
+    VarintHeader::new(1, 1, 100)
+        .serialize_into(&mut buf)
+        .unwrap();
+    assert_eq!(buf, [VarintHeader::MARKER, 1, 1, 100]);
+
+    let mut buf = Vec::new();
+    let max = VarintHeader::new(u16::MAX, u16::MAX, u32::MAX);
+    max.serialize_into(&mut buf).unwrap();
+    assert_eq!(buf.len(), VarintHeader::MAX_SIZE);
+    assert_eq!(VarintHeader::deserialize_from(&mut &buf[..]).unwrap(), max);
+}
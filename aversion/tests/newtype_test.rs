@@ -0,0 +1,87 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use aversion::util::BasicHeader;
+use aversion::{
+    assign_message_ids, FromVersion, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+#[serde(transparent)]
+struct TimestampV1(u32);
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+#[serde(transparent)]
+struct TimestampV2(u64);
+
+impl FromVersion<TimestampV1> for TimestampV2 {
+    fn from_version(v1: TimestampV1) -> Self {
+        TimestampV2(v1.0.into())
+    }
+}
+
+type Timestamp = TimestampV2;
+
+assign_message_ids! {
+    Timestamp: 1,
+}
+
+// A type alias can't be used to construct a tuple struct, so values are
+// built with the versioned name.
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Timestamp(Timestamp),
+}
+
+#[test]
+fn test_newtype_consts() {
+    assert_eq!(TimestampV1::VER, 1);
+    assert_eq!(TimestampV2::VER, 2);
+    assert_eq!(<Timestamp as aversion::MessageId>::MSG_ID, 1);
+}
+
+#[test]
+fn test_transparent_wire_format() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&TimestampV2(1_600_000_000)).unwrap();
+    let buf = sink.into_inner();
+
+    let header = BasicHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(header.msg_id, 1);
+    assert_eq!(header.msg_ver, 2);
+    // The body is the bare integer, not a one-element array.
+    let body = &buf[BasicHeader::SIZE..];
+    assert_eq!(body, serde_cbor::to_vec(&1_600_000_000u64).unwrap());
+}
+
+#[test]
+fn test_newtype_roundtrip() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&TimestampV1(7)).unwrap();
+    sink.write_message(&TimestampV2(u64::MAX)).unwrap();
+    let buf = sink.into_inner();
+
+    // Old versions are upgraded.
+    let mut src = CborData::new(Cursor::new(&buf));
+    let msg: Timestamp = src.expect_message().unwrap();
+    assert_eq!(msg, TimestampV2(7));
+    let msg: Timestamp = src.expect_message().unwrap();
+    assert_eq!(msg, TimestampV2(u64::MAX));
+
+    // The same messages, through a group.
+    let mut src = CborData::new(Cursor::new(&buf));
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(msg, MyGroup::Timestamp(TimestampV2(7)));
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(msg, MyGroup::Timestamp(TimestampV2(u64::MAX)));
+
+    let mut sink = CborData::new(Vec::new());
+    MyGroup::Timestamp(TimestampV2(7))
+        .write_message(&mut sink)
+        .unwrap();
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+    let msg: Timestamp = src.expect_message().unwrap();
+    assert_eq!(msg, TimestampV2(7));
+}
@@ -0,0 +1,53 @@
+use aversion::util::codec::format_id;
+use aversion::util::fixed::{BigEndian, FixedSizeCodec, LittleEndian, PackedCodec};
+use aversion::util::Codec;
+use aversion::Versioned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+#[versioned(fixed_size = 8)]
+struct WindowV1 {
+    start: u32,
+    len: u32,
+}
+
+type Window = WindowV1;
+
+fn encode<C: Codec>(codec: C, msg: &Window) -> Vec<u8>
+where
+    C::Error: std::fmt::Debug,
+{
+    let mut buf = Vec::new();
+    codec.encode(msg, &mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_packed_roundtrip() {
+    let msg = WindowV1 {
+        start: 0x0102_0304,
+        len: 16,
+    };
+
+    let codec = PackedCodec::<BigEndian>::new();
+    let buf = encode(codec, &msg);
+    assert_eq!(buf, [0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x10]);
+    assert_eq!(codec.decode::<Window>(&buf).unwrap(), msg);
+
+    let codec = PackedCodec::<LittleEndian>::new();
+    let buf = encode(codec, &msg);
+    assert_eq!(buf, [0x04, 0x03, 0x02, 0x01, 0x10, 0x00, 0x00, 0x00]);
+    assert_eq!(codec.decode::<Window>(&buf).unwrap(), msg);
+
+    // Little-endian packing is the FixedSizeCodec format.
+    assert_eq!(encode(FixedSizeCodec, &msg), buf);
+}
+
+#[test]
+fn test_packed_format_ids() {
+    assert_eq!(PackedCodec::<BigEndian>::FORMAT_ID, format_id::PACKED_BE);
+    assert_eq!(
+        PackedCodec::<LittleEndian>::FORMAT_ID,
+        FixedSizeCodec::FORMAT_ID
+    );
+}
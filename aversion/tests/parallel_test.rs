@@ -0,0 +1,80 @@
+#![cfg(feature = "rayon")]
+
+use aversion::group::GroupErrorKind;
+use aversion::util::cbor::CborDataError;
+use aversion::util::parallel::decode_parallel;
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn message(i: u32) -> MyGroup {
+    if i.is_multiple_of(3) {
+        MyGroup::Bar(BarV1 {
+            bar: format!("bar {}", i),
+        })
+    } else {
+        MyGroup::Foo(FooV1 { foo: i })
+    }
+}
+
+#[test]
+fn test_decode_parallel() {
+    let frames: Vec<Vec<u8>> = (0..1000).map(|i| message(i).to_vec().unwrap()).collect();
+    let msgs = decode_parallel::<MyGroup>(frames);
+    assert_eq!(msgs.len(), 1000);
+    for (i, msg) in (0..).zip(msgs) {
+        assert_eq!(msg.unwrap(), message(i));
+    }
+}
+
+#[test]
+fn test_bad_frames() {
+    let mut truncated = message(1).to_vec().unwrap();
+    truncated.pop();
+    let mut extra = message(2).to_vec().unwrap();
+    extra.push(0);
+
+    let frames = vec![
+        message(0).to_vec().unwrap(),
+        truncated,
+        extra,
+        Vec::new(),
+        message(4).to_vec().unwrap(),
+    ];
+    let msgs = decode_parallel::<MyGroup>(frames);
+    assert_eq!(msgs[0].as_ref().unwrap(), &message(0));
+    assert_eq!(
+        msgs[1].as_ref().unwrap_err().kind(),
+        GroupErrorKind::Framing
+    );
+    assert!(matches!(
+        msgs[2],
+        Err(CborDataError::TrailingGarbage { messages: 1 })
+    ));
+    assert!(msgs[3].is_err());
+    assert_eq!(msgs[4].as_ref().unwrap(), &message(4));
+}
@@ -0,0 +1,122 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt, GroupHeader};
+use aversion::util::cbor::{CborData, CborDataError, SliceSource};
+use aversion::util::monotonic::MonotonicSource;
+use aversion::util::trailer::TrailerHeaderSource;
+use aversion::util::{BasicHeader, ExtendedHeader, FramedHeader, MultiHeader, VarintHeader};
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read};
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct FooV1 {
+    x: u32,
+}
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct BarV1 {
+    s: String,
+}
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 0x1234,
+    Bar: 0x0102,
+}
+
+/// A reader that can't seek, and returns one byte at a time.
+struct Trickle<R>(R);
+
+impl<R: Read> Read for Trickle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+fn write_messages<H: FramedHeader>() -> Vec<u8> {
+    let mut sink = CborData::<_, H>::with_header(Vec::new());
+    sink.write_message(&Foo { x: 1 }).unwrap();
+    sink.write_message(&Bar { s: "bar".into() }).unwrap();
+    sink.write_message(&Foo { x: 2 }).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_peek_then_read() {
+    let buf = write_messages::<ExtendedHeader>();
+    let mut src = CborData::<_, ExtendedHeader>::with_header(Trickle(&buf[..]));
+
+    // Peeking twice doesn't consume anything.
+    assert_eq!(src.peek_msg_id().unwrap(), 0x1234);
+    assert_eq!(src.peek_msg_id().unwrap(), 0x1234);
+    let header = src.read_header().unwrap();
+    assert_eq!(header.msg_id(), 0x1234);
+    let foo: Foo = src.read_message(&header).unwrap();
+    assert_eq!(foo, Foo { x: 1 });
+
+    assert_eq!(src.peek_msg_id().unwrap(), 0x0102);
+    let bar: Bar = src.expect_message().unwrap();
+    assert_eq!(bar, Bar { s: "bar".into() });
+
+    // try_read_header also picks up the peeked bytes.
+    assert_eq!(src.peek_msg_id().unwrap(), 0x1234);
+    let header = src.try_read_header().unwrap().unwrap();
+    assert_eq!(header.msg_id(), 0x1234);
+    src.skip_message(&header).unwrap();
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_peek_slice_source() {
+    let buf = write_messages::<BasicHeader>();
+    let mut src = SliceSource::new(&buf);
+
+    for expected in &[0x1234, 0x0102, 0x1234] {
+        let peeked = src.peek_msg_id().unwrap();
+        assert_eq!(peeked, *expected);
+        let header = src.read_header().unwrap();
+        assert_eq!(header.msg_id(), peeked);
+        src.skip_message(&header).unwrap();
+    }
+    assert!(matches!(src.peek_msg_id(), Err(CborDataError::Eof)));
+}
+
+#[test]
+fn test_peek_through_decorator() {
+    let buf = write_messages::<ExtendedHeader>();
+    let src = CborData::<_, ExtendedHeader>::with_header(&buf[..]);
+    let mut src = MonotonicSource::new(src);
+
+    assert_eq!(src.peek_msg_id().unwrap(), 0x1234);
+    let foo: Foo = src.expect_message().unwrap();
+    assert_eq!(foo, Foo { x: 1 });
+    assert_eq!(src.peek_msg_id().unwrap(), 0x0102);
+}
+
+#[test]
+fn test_peek_unsupported() {
+    // The header follows the body, so there's nothing to peek at.
+    let mut src = TrailerHeaderSource::new(&[][..]);
+    match src.peek_msg_id() {
+        Err(CborDataError::Io(Some(e))) => assert_eq!(e.kind(), io::ErrorKind::Unsupported),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_peek_varint() {
+    // Old BasicHeader messages, followed by new VarintHeader messages.
+    let mut buf = write_messages::<BasicHeader>();
+    buf.extend(write_messages::<VarintHeader>());
+    let mut src = CborData::<_, MultiHeader>::with_header(Trickle(&buf[..]));
+
+    for expected in &[0x1234, 0x0102, 0x1234, 0x1234, 0x0102, 0x1234] {
+        let peeked = src.peek_msg_id().unwrap();
+        assert_eq!(peeked, *expected);
+        let header = src.read_header().unwrap();
+        assert_eq!(header.msg_id(), peeked);
+        src.skip_message(&header).unwrap();
+    }
+    assert!(src.try_read_header().unwrap().is_none());
+}
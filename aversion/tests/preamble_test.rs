@@ -0,0 +1,145 @@
+#![cfg(feature = "json")]
+
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CborCodec, CborData};
+use aversion::util::codec::format_id;
+use aversion::util::json::JsonCodec;
+use aversion::util::preamble::{self, Preamble, PreambleError, SchemaManifest};
+use aversion::util::Codec;
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+assign_message_ids! {
+    Foo: 1,
+}
+
+fn cbor_file() -> Vec<u8> {
+    let mut buf = Vec::new();
+    preamble::write_preamble::<CborCodec>(&mut buf).unwrap();
+    let mut sink = CborData::new(buf);
+    sink.write_message(&Foo { foo: 42 }).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_preamble_roundtrip() {
+    let mut file = Cursor::new(cbor_file());
+    let found = preamble::read_preamble::<CborCodec>(&mut file).unwrap();
+    assert_eq!(found.version, preamble::PREAMBLE_VERSION);
+    assert_eq!(found.format_id, format_id::CBOR);
+    assert_eq!(found.flags, 0);
+    assert_eq!(found.header_format, preamble::DEFAULT_HEADER_FORMAT);
+    assert_eq!(found.group_version, 0);
+    assert_eq!(found.size(), preamble::SIZE);
+    assert_eq!(file.position(), preamble::SIZE as u64);
+
+    let mut src = CborData::new(file);
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg, Foo { foo: 42 });
+}
+
+#[test]
+fn test_codec_mismatch() {
+    let mut file = Cursor::new(cbor_file());
+    let err = preamble::read_preamble::<JsonCodec>(&mut file).unwrap_err();
+    match err {
+        PreambleError::CodecMismatch { expected, found } => {
+            assert_eq!(expected, JsonCodec::FORMAT_ID);
+            assert_eq!(found, CborCodec::FORMAT_ID);
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn test_choose_codec() {
+    let mut file = Cursor::new(cbor_file());
+    let found = preamble::read_preamble_any(&mut file).unwrap();
+    assert_eq!(found.format_id, CborCodec::FORMAT_ID);
+}
+
+#[test]
+fn test_missing_preamble() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&Foo { foo: 42 }).unwrap();
+    let mut file = Cursor::new(sink.into_inner());
+    let err = preamble::read_preamble::<CborCodec>(&mut file).unwrap_err();
+    assert!(matches!(err, PreambleError::BadMagic));
+}
+
+#[test]
+fn test_preamble_flags() {
+    let mut buf = Vec::new();
+    let manifest = SchemaManifest::new().with::<FooV1>();
+    Preamble::new::<CborCodec>()
+        .with_group_version(2)
+        .write_with_manifest(&mut buf, &manifest)
+        .unwrap();
+
+    let mut r = &buf[..];
+    let found = preamble::read_preamble::<CborCodec>(&mut r).unwrap();
+    assert_eq!(found.version, preamble::PREAMBLE_VERSION_5);
+    assert_eq!(
+        found.flags,
+        preamble::flags::GROUP_VERSION | preamble::flags::SCHEMA_MANIFEST
+    );
+    assert_eq!(found.header_format, preamble::DEFAULT_HEADER_FORMAT);
+    assert_eq!(found.group_version, 2);
+    assert_eq!(buf.len() - r.len(), found.size());
+    assert!(found.has_schema_manifest());
+    assert_eq!(preamble::read_schema_manifest(&mut r).unwrap(), manifest);
+    assert!(r.is_empty());
+}
+
+#[test]
+fn test_unsupported_flags() {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&preamble::MAGIC);
+    buf.extend_from_slice(&preamble::PREAMBLE_VERSION_5.to_be_bytes());
+    buf.extend_from_slice(&CborCodec::FORMAT_ID.to_be_bytes());
+    buf.extend_from_slice(&0x8001u16.to_be_bytes());
+    buf.extend_from_slice(&[0, 1]);
+    let err = preamble::read_preamble_any(&mut &buf[..]).unwrap_err();
+    assert!(matches!(err, PreambleError::UnsupportedFlags(0x8000)));
+}
+
+#[test]
+fn test_read_legacy_preamble() {
+    // A version 4 preamble records the header format and group version,
+    // with no flags.
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&preamble::MAGIC);
+    buf.extend_from_slice(&preamble::PREAMBLE_VERSION_4.to_be_bytes());
+    buf.extend_from_slice(&CborCodec::FORMAT_ID.to_be_bytes());
+    buf.extend_from_slice(&[0, 2, 0, 3]);
+    let found = preamble::read_preamble::<CborCodec>(&mut &buf[..]).unwrap();
+    assert_eq!(found.header_format, 2);
+    assert_eq!(found.group_version, 3);
+    assert!(!found.has_schema_manifest());
+    assert_eq!(found.size(), preamble::SIZE_V4);
+
+    // A version 3 preamble is always followed by a schema manifest.
+    buf[5] = preamble::PREAMBLE_VERSION_3 as u8;
+    let found = preamble::read_preamble::<CborCodec>(&mut &buf[..]).unwrap();
+    assert_eq!(found.header_format, 2);
+    assert_eq!(found.group_version, 0);
+    assert!(found.has_schema_manifest());
+    assert_eq!(found.size(), preamble::SIZE_V2);
+}
+
+#[test]
+fn test_codec_roundtrip() {
+    let mut buf = Vec::new();
+    JsonCodec.encode(&Foo { foo: 7 }, &mut buf).unwrap();
+    assert_eq!(buf, br#"{"foo":7}"#);
+    let msg: Foo = JsonCodec.decode(&buf).unwrap();
+    assert_eq!(msg, Foo { foo: 7 });
+}
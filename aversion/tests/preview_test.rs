@@ -0,0 +1,80 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt, GroupHeader, UpgradeLatest as _};
+use aversion::util::cbor::{CborData, SliceSource};
+use aversion::{assign_message_ids, MessageId, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct UploadV1 {
+    destination: String,
+    priority: u8,
+    payload: Vec<u8>,
+}
+
+type Upload = UploadV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+assign_message_ids! {
+    Upload: 1,
+    Foo: 2,
+}
+
+/// Just the fields needed for routing.
+#[derive(Debug, PartialEq, Deserialize)]
+struct UploadPreview {
+    destination: String,
+}
+
+fn upload() -> Upload {
+    Upload {
+        destination: "east".to_owned(),
+        priority: 3,
+        payload: vec![0xaa; 4096],
+    }
+}
+
+fn encoded() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&upload()).unwrap();
+    sink.write_message(&Foo { foo: 9 }).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_preview_is_terminal() {
+    let mut src = CborData::new(Cursor::new(encoded()));
+    let header = src.read_header().unwrap();
+    assert_eq!(header.msg_id(), Upload::MSG_ID);
+    let preview: UploadPreview = src.read_message(&header).unwrap();
+    assert_eq!(
+        preview,
+        UploadPreview {
+            destination: "east".to_owned()
+        }
+    );
+
+    // The rest of the body was consumed, so the next message is readable.
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg, Foo { foo: 9 });
+}
+
+#[test]
+fn test_preview_then_full() {
+    let buf = encoded();
+    let mut src = SliceSource::new(&buf);
+    let header = src.read_header().unwrap();
+    let preview: UploadPreview = src.peek_message_ref(&header).unwrap();
+    assert_eq!(preview.destination, "east");
+
+    // Peeking didn't consume the body, so the full message can be read.
+    let msg = Upload::upgrade_latest(&mut src, header).unwrap();
+    assert_eq!(msg, upload());
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg, Foo { foo: 9 });
+}
@@ -0,0 +1,103 @@
+use aversion::group::{DataSink, DataSource, GroupEntry, Priority};
+use aversion::util::cbor::CborData;
+use aversion::util::priority::PriorityQueueSink;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// Bulk data has the default priority.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct DataV1 {
+    seq: u32,
+}
+
+type Data = DataV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct ControlV1 {
+    seq: u32,
+}
+
+type Control = ControlV1;
+
+impl Priority for Control {
+    const PRIORITY: u8 = 10;
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct CancelV1 {
+    seq: u32,
+}
+
+type Cancel = CancelV1;
+
+impl Priority for Cancel {
+    const PRIORITY: u8 = 20;
+}
+
+assign_message_ids! {
+    Data: 1,
+    Control: 2,
+    Cancel: 3,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Data(Data),
+    Control(Control),
+    Cancel(Cancel),
+}
+
+fn read_all(buf: Vec<u8>) -> Vec<MyGroup> {
+    let mut src = CborData::new(Cursor::new(buf));
+    let mut msgs = Vec::new();
+    while src.peek_msg_id().is_ok() {
+        msgs.push(MyGroup::read_message(&mut src).unwrap());
+    }
+    msgs
+}
+
+#[test]
+fn test_priority_registry() {
+    let priorities: Vec<u8> = MyGroup::messages()
+        .iter()
+        .map(|entry: &GroupEntry| entry.priority)
+        .collect();
+    assert_eq!(priorities, [0, 10, 20]);
+    assert_eq!(MyGroup::priority(3), 20);
+    assert_eq!(MyGroup::priority(99), 0);
+}
+
+#[test]
+fn test_flush_order() {
+    let mut sink = PriorityQueueSink::<_, MyGroup>::new(Vec::new());
+    sink.write_message(&Data { seq: 1 }).unwrap();
+    sink.write_message(&Control { seq: 2 }).unwrap();
+    sink.write_message(&Data { seq: 3 }).unwrap();
+    sink.write_message(&Cancel { seq: 4 }).unwrap();
+    sink.write_message(&Control { seq: 5 }).unwrap();
+
+    // Nothing is written until the flush.
+    assert_eq!(sink.pending(), 5);
+    assert!(sink.get_ref().is_empty());
+    sink.flush().unwrap();
+    assert_eq!(sink.pending(), 0);
+
+    // Each flush is a boundary.
+    sink.write_message(&Data { seq: 6 }).unwrap();
+    sink.write_message(&Cancel { seq: 7 }).unwrap();
+    sink.flush().unwrap();
+
+    assert_eq!(
+        read_all(sink.into_inner()),
+        [
+            MyGroup::Cancel(Cancel { seq: 4 }),
+            MyGroup::Control(Control { seq: 2 }),
+            MyGroup::Control(Control { seq: 5 }),
+            MyGroup::Data(Data { seq: 1 }),
+            MyGroup::Data(Data { seq: 3 }),
+            MyGroup::Cancel(Cancel { seq: 7 }),
+            MyGroup::Data(Data { seq: 6 }),
+        ]
+    );
+}
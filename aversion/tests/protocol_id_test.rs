@@ -0,0 +1,182 @@
+use aversion::group::{from_reader_with, to_writer, to_writer_with};
+use aversion::util::cbor::{CborCodec, CborDataError, CborProtocol};
+use aversion::util::codec::format_id;
+use aversion::util::json::JsonCodec;
+use aversion::util::protocol::{
+    check_protocol_id, protocol_id, write_protocol_id, Protocol, ProtocolError, ProtocolId,
+};
+use aversion::util::{header_format, BasicHeader, ExtendedHeader};
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PingV1 {
+    seq: u32,
+}
+
+type Ping = PingV1;
+
+assign_message_ids! {
+    Ping: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Ping(Ping),
+}
+
+/// An error type that uses the default `protocol_mismatch`.
+#[derive(Debug)]
+enum JsonError {
+    Io(io::Error),
+    Other,
+}
+
+impl From<io::Error> for JsonError {
+    fn from(e: io::Error) -> Self {
+        JsonError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(_: serde_json::Error) -> Self {
+        JsonError::Other
+    }
+}
+
+impl ProtocolError for JsonError {
+    fn unknown_message(_msg_id: u16) -> Self {
+        JsonError::Other
+    }
+
+    fn unknown_version(_expected: &'static str, _got: u16, _latest: u16) -> Self {
+        JsonError::Other
+    }
+
+    fn unexpected_message(_expected: &'static str, _expected_id: u16, _got: u16) -> Self {
+        JsonError::Other
+    }
+}
+
+struct JsonProtocol;
+
+/// The same codec as `CborProtocol`, with a different header.
+struct ExtendedProtocol;
+
+impl Protocol for ExtendedProtocol {
+    type Header = ExtendedHeader;
+    type Codec = CborCodec;
+    type Error = CborDataError;
+}
+
+impl Protocol for JsonProtocol {
+    type Header = BasicHeader;
+    type Codec = JsonCodec;
+    type Error = JsonError;
+}
+
+// The protocol id is available at compile time.
+const CBOR_ID: ProtocolId = protocol_id::<CborProtocol>();
+const JSON_ID: ProtocolId = protocol_id::<JsonProtocol>();
+
+#[test]
+fn test_protocol_id() {
+    assert_eq!(
+        CBOR_ID,
+        ProtocolId {
+            format_id: format_id::CBOR,
+            header_format: 1,
+        }
+    );
+    assert_eq!(JSON_ID.format_id, format_id::JSON);
+    assert_eq!(ProtocolId::of::<CborProtocol>(), CBOR_ID);
+    assert_ne!(CBOR_ID, JSON_ID);
+    assert_eq!(CBOR_ID.to_string(), "codec format 1, header format 1");
+}
+
+#[test]
+fn test_round_trip() {
+    let msg = MyGroup::Ping(PingV1 { seq: 3 });
+
+    let mut buf = Vec::new();
+    to_writer_with::<CborProtocol, _, _>(&mut buf, &msg).unwrap();
+    let out: MyGroup = from_reader_with::<CborProtocol, _, _>(&buf[..]).unwrap();
+    assert_eq!(out, msg);
+
+    let mut buf = Vec::new();
+    to_writer_with::<JsonProtocol, _, _>(&mut buf, &msg).unwrap();
+    let out: MyGroup = from_reader_with::<JsonProtocol, _, _>(&buf[..]).unwrap();
+    assert_eq!(out, msg);
+}
+
+#[test]
+fn test_mismatch() {
+    let msg = MyGroup::Ping(PingV1 { seq: 3 });
+    let mut buf = Vec::new();
+    to_writer_with::<JsonProtocol, _, _>(&mut buf, &msg).unwrap();
+
+    let err = from_reader_with::<CborProtocol, MyGroup, _>(&buf[..]).unwrap_err();
+    match &err {
+        CborDataError::ProtocolMismatch { expected, found } => {
+            assert_eq!(*expected, CBOR_ID);
+            assert_eq!(*found, JSON_ID);
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+    assert_eq!(
+        err.to_string(),
+        "Protocol mismatch: expected codec format 1, header format 1, \
+         found codec format 2, header format 1"
+    );
+}
+
+#[test]
+fn test_mismatch_default_error() {
+    let msg = MyGroup::Ping(PingV1 { seq: 3 });
+    let mut buf = Vec::new();
+    to_writer_with::<CborProtocol, _, _>(&mut buf, &msg).unwrap();
+
+    match from_reader_with::<JsonProtocol, MyGroup, _>(&buf[..]).unwrap_err() {
+        JsonError::Io(e) => {
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                e.to_string(),
+                "protocol mismatch: expected codec format 2, header format 1, \
+                 found codec format 1, header format 1"
+            );
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_missing_preamble() {
+    let mut buf = Vec::new();
+    to_writer(&mut buf, &MyGroup::Ping(PingV1 { seq: 3 })).unwrap();
+
+    let err = from_reader_with::<CborProtocol, MyGroup, _>(&buf[..]).unwrap_err();
+    match err {
+        CborDataError::Io(Some(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        e => panic!("unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_header_mismatch() {
+    let extended = protocol_id::<ExtendedProtocol>();
+    assert_eq!(extended.format_id, CBOR_ID.format_id);
+    assert_eq!(extended.header_format, header_format::EXTENDED);
+    assert_ne!(extended, CBOR_ID);
+
+    let mut buf = Vec::new();
+    write_protocol_id::<ExtendedProtocol>(&mut buf).unwrap();
+    check_protocol_id::<ExtendedProtocol>(&mut &buf[..]).unwrap();
+    match check_protocol_id::<CborProtocol>(&mut &buf[..]).unwrap_err() {
+        CborDataError::ProtocolMismatch { expected, found } => {
+            assert_eq!(expected, CBOR_ID);
+            assert_eq!(found, extended);
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+}
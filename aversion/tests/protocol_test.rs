@@ -0,0 +1,130 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt, GroupHeader};
+use aversion::util::cbor::{CborCodec, CborData, CborDataError, CborProtocol};
+use aversion::util::protocol::{Protocol, ReadSource, WriteSink};
+use aversion::util::SemverHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+#[versioned(minor = 2)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+struct SemverProtocol;
+
+impl Protocol for SemverProtocol {
+    type Header = SemverHeader;
+    type Codec = CborCodec;
+    type Error = CborDataError;
+}
+
+#[test]
+fn test_cbor_protocol_matches_cbor_data() {
+    let mut sink = WriteSink::<_, CborProtocol>::new(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Bar {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    let buf = sink.into_inner();
+
+    let mut expected = CborData::new(Vec::new());
+    expected.write_message(&Foo { foo: 1 }).unwrap();
+    expected
+        .write_message(&Bar {
+            bar: "hello".to_owned(),
+        })
+        .unwrap();
+    assert_eq!(buf, expected.into_inner());
+
+    let mut src = ReadSource::<_, CborProtocol>::new(&buf[..]);
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(msg, MyGroup::Foo(Foo { foo: 1 }));
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(
+        msg,
+        MyGroup::Bar(Bar {
+            bar: "hello".to_owned()
+        })
+    );
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_custom_header() {
+    let mut sink = WriteSink::<_, SemverProtocol>::new(Vec::new());
+    sink.write_message(&Foo { foo: 7 }).unwrap();
+    let buf = sink.into_inner();
+    assert_eq!(buf.len(), SemverHeader::SIZE + 6);
+
+    let mut src = ReadSource::<_, SemverProtocol>::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    assert_eq!(header.msg_minor_ver(), 2);
+    let msg: Foo = src.read_message(&header).unwrap();
+    assert_eq!(msg, Foo { foo: 7 });
+}
+
+#[test]
+fn test_errors() {
+    let mut sink = WriteSink::<_, CborProtocol>::new(Vec::new());
+    sink.write_message(&Foo { foo: 7 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = ReadSource::<_, CborProtocol>::new(&buf[..]);
+    let err = src.expect_message::<Bar>().unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::UnexpectedMessage {
+            expected_id: 2,
+            got: 1,
+            ..
+        }
+    ));
+
+    let mut src = ReadSource::<_, CborProtocol>::new(&buf[..buf.len() - 1]);
+    let err = src.expect_message::<Foo>().unwrap_err();
+    assert!(
+        matches!(err, CborDataError::Io(Some(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+    );
+}
+
+#[test]
+fn test_oversized_length() {
+    // A header that claims a 4 GiB body, with only a few bytes after it.
+    let mut buf = Vec::new();
+    aversion::util::BasicHeader::new(1, 1, u32::MAX)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&[0xa1, 0x63]);
+
+    let mut src = ReadSource::<_, CborProtocol>::new(&buf[..]);
+    let err = src.expect_message::<Foo>().unwrap_err();
+    assert!(
+        matches!(err, CborDataError::Io(Some(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+    );
+
+    let mut src = ReadSource::<_, CborProtocol>::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    assert!(src.read_raw(&header).is_err());
+}
@@ -0,0 +1,139 @@
+use aversion::group::{DataSink, DataSource};
+use aversion::util::cbor::CborData;
+use aversion::util::expiry::Clock;
+use aversion::util::rate_limit::{RateLimitError, RateLimitedSource};
+use aversion::util::FramedHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct DataV1 {
+    payload: Vec<u8>,
+}
+
+type Data = DataV1;
+
+assign_message_ids! {
+    Data: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Conn {
+    Data(Data),
+}
+
+/// A clock that only moves when the test advances it.
+#[derive(Clone, Default)]
+struct TestClock(Rc<Cell<u64>>);
+
+impl TestClock {
+    fn advance(&self, ticks: u64) {
+        self.0.set(self.0.get() + ticks);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+fn encoded(sizes: &[usize]) -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    for &size in sizes {
+        sink.write_message(&DataV1 {
+            payload: vec![0; size],
+        })
+        .unwrap();
+    }
+    sink.into_inner()
+}
+
+fn payload_len(msg: Conn) -> usize {
+    match msg {
+        Conn::Data(data) => data.payload.len(),
+    }
+}
+
+#[test]
+fn test_message_limit() {
+    let buf = encoded(&[1; 6]);
+    let clock = TestClock::default();
+    let mut src =
+        RateLimitedSource::with_clock(CborData::new(&buf[..]), 10, clock.clone()).max_messages(2);
+
+    // The bucket starts full, so a burst of 2 is allowed.
+    Conn::read_message(&mut src).unwrap();
+    Conn::read_message(&mut src).unwrap();
+    let err = Conn::read_message(&mut src).unwrap_err();
+    // 2 messages per 10 ticks is one message every 5 ticks.
+    assert!(matches!(
+        err,
+        RateLimitError::RateLimited { retry_after: 5 }
+    ));
+
+    clock.advance(4);
+    let err = Conn::read_message(&mut src).unwrap_err();
+    assert!(matches!(
+        err,
+        RateLimitError::RateLimited { retry_after: 1 }
+    ));
+
+    // Nothing was consumed by the rejected reads.
+    clock.advance(1);
+    Conn::read_message(&mut src).unwrap();
+    assert!(Conn::read_message(&mut src).is_err());
+
+    // The bucket refills up to its capacity, and no further.
+    clock.advance(100);
+    Conn::read_message(&mut src).unwrap();
+    Conn::read_message(&mut src).unwrap();
+    assert!(matches!(
+        Conn::read_message(&mut src),
+        Err(RateLimitError::RateLimited { .. })
+    ));
+    clock.advance(5);
+    Conn::read_message(&mut src).unwrap();
+    clock.advance(5);
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_byte_limit() {
+    let buf = encoded(&[60, 60, 200]);
+    let clock = TestClock::default();
+    let mut src =
+        RateLimitedSource::with_clock(CborData::new(&buf[..]), 1, clock.clone()).max_bytes(100);
+
+    let header = src.read_header().unwrap();
+    let body_len = header.msg_len();
+    src.skip_message(&header).unwrap();
+
+    // The second header is read, but held until there is room for its
+    // body.
+    let err = src.read_header().unwrap_err();
+    assert!(matches!(
+        err,
+        RateLimitError::RateLimited { retry_after: 1 }
+    ));
+    clock.advance(1);
+    let header = src.read_header().unwrap();
+    assert_eq!(header.msg_len(), body_len);
+    src.skip_message(&header).unwrap();
+
+    // A message larger than the limit needs a full bucket.
+    let err = Conn::read_message(&mut src).unwrap_err();
+    assert!(matches!(err, RateLimitError::RateLimited { .. }));
+    clock.advance(1);
+    assert_eq!(payload_len(Conn::read_message(&mut src).unwrap()), 200);
+}
+
+#[test]
+fn test_source_error() {
+    let buf = encoded(&[]);
+    let mut src = RateLimitedSource::new(CborData::new(&buf[..]), 1).max_messages(10);
+    assert!(src.try_read_header().unwrap().is_none());
+    assert!(matches!(src.read_header(), Err(RateLimitError::Source(_))));
+}
@@ -0,0 +1,83 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::{assign_message_ids, FromVersion, RawVersions, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static UPGRADES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+pub struct FooV1 {
+    x: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+pub struct FooV2 {
+    x: u64,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest, RawVersions)]
+pub struct FooV3 {
+    x: u64,
+    y: u64,
+}
+
+type Foo = FooV3;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct BarV1 {
+    s: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        UPGRADES.fetch_add(1, Ordering::SeqCst);
+        FooV2 { x: v1.x.into() }
+    }
+}
+
+impl FromVersion<FooV2> for FooV3 {
+    fn from_version(v2: FooV2) -> Self {
+        FooV3 { x: v2.x, y: 0 }
+    }
+}
+
+#[test]
+fn test_read_raw_version() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { x: 1 }).unwrap();
+    sink.write_message(&FooV3 { x: 3, y: 4 }).unwrap();
+    sink.write_message(&Bar { s: "bar".into() }).unwrap();
+
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+
+    let (ver, raw) = src.read_raw_version::<Foo>().unwrap();
+    assert_eq!(ver, 1);
+    assert_eq!(raw.version(), 1);
+    match &raw {
+        FooRawVersion::V1(v1) => assert_eq!(v1, &FooV1 { x: 1 }),
+        other => panic!("unexpected {:?}", other),
+    }
+    // Decoding the raw version doesn't run the upgrade.
+    assert_eq!(UPGRADES.load(Ordering::SeqCst), 0);
+    assert_eq!(raw.upgrade(), FooV3 { x: 1, y: 0 });
+    assert_eq!(UPGRADES.load(Ordering::SeqCst), 1);
+
+    let (ver, raw) = src.read_raw_version::<Foo>().unwrap();
+    assert_eq!(ver, 3);
+    assert_eq!(raw.upgrade(), FooV3 { x: 3, y: 4 });
+
+    let err = src.read_raw_version::<Foo>().unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::UnexpectedMessage { got: 2, .. }
+    ));
+}
@@ -0,0 +1,103 @@
+use aversion::group::{DataSinkExt, ReadAllError, ReadAllLimits};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum Log {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn log_messages(count: u32) -> Vec<Log> {
+    (0..count)
+        .map(|n| {
+            if n % 2 == 0 {
+                Log::Foo(FooV1 { foo: n })
+            } else {
+                Log::Bar(BarV1 { bar: n.to_string() })
+            }
+        })
+        .collect()
+}
+
+fn log_file(count: u32) -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_all_messages(log_messages(count)).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_read_all() {
+    let buf = log_file(5);
+    let mut src = CborData::new(&buf[..]);
+    let limits = ReadAllLimits::new().reserve(8).max_count(5);
+    let msgs = Log::read_all(&mut src, limits).unwrap();
+    assert_eq!(msgs, log_messages(5));
+    // The reserve hint is capped by the maximum.
+    assert_eq!(msgs.capacity(), 5);
+}
+
+#[test]
+fn test_read_all_empty() {
+    let mut src = CborData::new(&[][..]);
+    let msgs = Log::read_all(&mut src, ReadAllLimits::new()).unwrap();
+    assert!(msgs.is_empty());
+}
+
+#[test]
+fn test_read_all_max_count() {
+    let buf = log_file(10);
+    let mut src = CborData::new(&buf[..]);
+    let err = Log::read_all(&mut src, ReadAllLimits::new().max_count(4)).unwrap_err();
+    assert!(matches!(err, ReadAllError::TooManyMessages { max: 4 }));
+    assert_eq!(err.to_string(), "more than 4 messages");
+
+    // The limit is checked before the extra message is decoded, so its
+    // body is left unread.
+    let buf = log_file(5);
+    let mut r = &buf[..];
+    let err = Log::read_all(
+        &mut CborData::new(&mut r),
+        ReadAllLimits::new().max_count(4),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ReadAllError::TooManyMessages { max: 4 }));
+    assert_eq!(r.len(), buf.len() - log_file(4).len() - BasicHeader::SIZE);
+}
+
+#[test]
+fn test_read_all_error_index() {
+    let mut buf = log_file(3);
+    // Cut the last message short.
+    buf.truncate(buf.len() - 1);
+    let mut src = CborData::new(&buf[..]);
+    let err = Log::read_all(&mut src, ReadAllLimits::new()).unwrap_err();
+    match err {
+        ReadAllError::Source { index, error } => {
+            assert_eq!(index, 2);
+            assert!(matches!(error, CborDataError::Eof));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
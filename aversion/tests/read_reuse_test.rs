@@ -0,0 +1,101 @@
+use aversion::group::{DataSink, DataSource};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::{assign_message_ids, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, PartialEq, Versioned, Serialize, Deserialize)]
+struct SampleV1 {
+    name: String,
+    values: Vec<u32>,
+}
+
+type Sample = SampleV1;
+
+#[derive(Debug, Default, PartialEq, Versioned, Serialize, Deserialize)]
+struct PingV1;
+
+type Ping = PingV1;
+
+assign_message_ids! {
+    Sample: 1,
+    Ping: 2,
+}
+
+fn sample(name: &str, values: &[u32]) -> SampleV1 {
+    SampleV1 {
+        name: name.into(),
+        values: values.to_vec(),
+    }
+}
+
+#[test]
+fn test_reuse_allocations() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&sample("first sample", &[1, 2, 3, 4]))
+        .unwrap();
+    sink.write_message(&sample("second", &[5, 6])).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(&buf[..]);
+    let mut msg = SampleV1::default();
+    let header = src.read_header().unwrap();
+    src.read_message_reuse(&header, &mut msg).unwrap();
+    assert_eq!(msg, sample("first sample", &[1, 2, 3, 4]));
+
+    let name_ptr = msg.name.as_ptr();
+    let values_ptr = msg.values.as_ptr();
+    let header = src.read_header().unwrap();
+    src.read_message_reuse(&header, &mut msg).unwrap();
+    assert_eq!(msg, sample("second", &[5, 6]));
+    // The shorter values fit in the existing allocations.
+    assert_eq!(msg.name.as_ptr(), name_ptr);
+    assert_eq!(msg.values.as_ptr(), values_ptr);
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_reuse_empty_body() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&PingV1).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    let mut msg = PingV1;
+    src.read_message_reuse(&header, &mut msg).unwrap();
+}
+
+#[test]
+fn test_reuse_error() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&PingV1).unwrap();
+    sink.write_message(&sample("a", &[1])).unwrap();
+    sink.write_message(&sample("b", &[2])).unwrap();
+    let buf = sink.into_inner();
+
+    // A body that doesn't decode is still consumed.
+    let mut src = CborData::new(&buf[..]);
+    src.read_header().unwrap();
+    let header = src.read_header().unwrap();
+    let mut wrong = 0u32;
+    let err = src.read_message_reuse(&header, &mut wrong).unwrap_err();
+    assert!(matches!(err, CborDataError::Serializer));
+
+    let header = src.read_header().unwrap();
+    let mut msg = SampleV1::default();
+    src.read_message_reuse(&header, &mut msg).unwrap();
+    assert_eq!(msg, sample("b", &[2]));
+}
+
+#[test]
+fn test_reuse_max_depth() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&sample("a", &[1])).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(&buf[..]).max_depth(1);
+    let header = src.read_header().unwrap();
+    let mut msg = SampleV1::default();
+    let err = src.read_message_reuse(&header, &mut msg).unwrap_err();
+    assert!(matches!(err, CborDataError::NestingTooDeep { max: 1 }));
+}
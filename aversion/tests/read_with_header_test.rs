@@ -0,0 +1,118 @@
+use aversion::group::{DataSink, GroupHeader};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::{BasicHeader, ExtendedHeader};
+use aversion::{
+    assign_message_ids, FromVersion, GroupDeserialize, MessageId, UpgradeLatest, Versioned,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV2 {
+    foo: u64,
+}
+
+type Foo = FooV2;
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 { foo: v1.foo.into() }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+#[test]
+fn test_header_matches_variant() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 1 }).unwrap();
+    sink.write_message(&Bar {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    sink.write_message(&FooV2 { foo: 3 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(&buf[..]);
+    let mut seen = Vec::new();
+    for _ in 0..3 {
+        let (header, msg): (BasicHeader, _) = MyGroup::read_message_with_header(&mut src).unwrap();
+        let expected_id = match &msg {
+            MyGroup::Foo(_) => Foo::MSG_ID,
+            MyGroup::Bar(_) => Bar::MSG_ID,
+        };
+        assert_eq!(header.msg_id(), expected_id);
+        seen.push((header.msg_id(), header.msg_ver(), msg));
+    }
+    assert_eq!(
+        seen,
+        vec![
+            // The header reports the version on the wire, before upgrading.
+            (1, 1, MyGroup::Foo(FooV2 { foo: 1 })),
+            (
+                2,
+                1,
+                MyGroup::Bar(BarV1 {
+                    bar: "hello".to_owned()
+                })
+            ),
+            (1, 2, MyGroup::Foo(FooV2 { foo: 3 })),
+        ]
+    );
+}
+
+#[test]
+fn test_extended_metadata() {
+    let body = serde_cbor::to_vec(&BarV1 {
+        bar: "x".to_owned(),
+    })
+    .unwrap();
+    let mut buf = ExtendedHeader::new(2, 1, body.len() as u32)
+        .with_timestamp(1234)
+        .with_flags(0x04)
+        .serialize();
+    buf.extend_from_slice(&body);
+
+    let mut src = CborData::<_, ExtendedHeader>::with_header(&buf[..]);
+    let (header, msg) = MyGroup::read_message_with_header(&mut src).unwrap();
+    assert_eq!(header.timestamp, Some(1234));
+    assert_eq!(header.flags, Some(0x04));
+    assert_eq!(
+        msg,
+        MyGroup::Bar(BarV1 {
+            bar: "x".to_owned()
+        })
+    );
+}
+
+#[test]
+fn test_error() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 1 }).unwrap();
+    let mut buf = sink.into_inner();
+    buf[1] = 99;
+
+    let mut src = CborData::new(&buf[..]);
+    let err = MyGroup::read_message_with_header(&mut src).unwrap_err();
+    assert!(matches!(err, CborDataError::UnknownMessage { msg_id: 99 }));
+}
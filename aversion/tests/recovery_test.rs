@@ -0,0 +1,117 @@
+use aversion::group::{DataSink, Recovery};
+use aversion::util::cbor::{CborData, CborDataError, SliceSource};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Log {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+/// A log with a corrupt message in the middle.
+fn corrupt_log() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 1 }).unwrap();
+    let mut buf = sink.into_inner();
+
+    // A Foo header, followed by a body that isn't valid CBOR.
+    let body = [0xff; 6];
+    BasicHeader::new(1, 1, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&body);
+
+    let mut sink = CborData::new(buf);
+    sink.write_message(&BarV1 { bar: "two".into() }).unwrap();
+    sink.write_message(&FooV1 { foo: 3 }).unwrap();
+    sink.into_inner()
+}
+
+fn expected() -> Vec<Log> {
+    vec![
+        Log::Foo(FooV1 { foo: 1 }),
+        Log::Bar(BarV1 { bar: "two".into() }),
+        Log::Foo(FooV1 { foo: 3 }),
+    ]
+}
+
+#[test]
+fn test_recovery_skip() {
+    let buf = corrupt_log();
+    let mut src = CborData::new(&buf[..]);
+    let mut bad_headers = Vec::new();
+    let mut iter = Log::iter_messages_with_recovery(&mut src, |err, header: &BasicHeader| {
+        assert!(matches!(err, CborDataError::Serializer));
+        bad_headers.push(header.msg_len);
+        Recovery::Skip
+    });
+    let msgs: Vec<Log> = (&mut iter).collect::<Result<_, _>>().unwrap();
+    assert_eq!(iter.skipped(), 1);
+    assert_eq!(msgs, expected());
+    assert_eq!(bad_headers, [6]);
+}
+
+#[test]
+fn test_recovery_skip_unknown() {
+    // A message id that isn't part of the group is skipped without
+    // reading its body.
+    let mut buf = Vec::new();
+    BasicHeader::new(9, 1, 3).serialize_into(&mut buf).unwrap();
+    buf.extend_from_slice(&[0x83, 0x01, 0x02]);
+    buf.extend(corrupt_log());
+
+    let mut src = CborData::new(&buf[..]);
+    let mut iter = Log::iter_messages_with_recovery(&mut src, |_, _| Recovery::Skip);
+    let msgs: Vec<Log> = (&mut iter).collect::<Result<_, _>>().unwrap();
+    assert_eq!(iter.skipped(), 2);
+    assert_eq!(msgs, expected());
+}
+
+#[test]
+fn test_recovery_slice_source() {
+    let buf = corrupt_log();
+    let mut src = SliceSource::new(&buf);
+    let iter = Log::iter_messages_with_recovery(&mut src, |_, _| Recovery::Skip);
+    let msgs: Vec<Log> = iter.collect::<Result<_, _>>().unwrap();
+    assert_eq!(msgs, expected());
+}
+
+#[test]
+fn test_recovery_stop() {
+    let buf = corrupt_log();
+    let mut src = CborData::new(&buf[..]);
+    let iter = Log::iter_messages_with_recovery(&mut src, |_, _| Recovery::Stop);
+    let msgs: Vec<Log> = iter.collect::<Result<_, _>>().unwrap();
+    assert_eq!(msgs, [Log::Foo(FooV1 { foo: 1 })]);
+}
+
+#[test]
+fn test_recovery_propagate() {
+    let buf = corrupt_log();
+    let mut src = CborData::new(&buf[..]);
+    let mut iter = Log::iter_messages_with_recovery(&mut src, |_, _| Recovery::Propagate);
+    assert_eq!(iter.next().unwrap().unwrap(), Log::Foo(FooV1 { foo: 1 }));
+    assert!(matches!(iter.next(), Some(Err(CborDataError::Serializer))));
+    assert!(iter.next().is_none());
+}
@@ -0,0 +1,131 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use aversion::{
+    assign_message_ids, FromVersion, FromVersionRef, IntoVersion, IntoVersionRef, UpgradeLatest,
+    Versioned,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV2 {
+    name: String,
+    count: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+#[versioned(by_ref)]
+struct FooV3 {
+    name: String,
+    count: u64,
+}
+
+type Foo = FooV3;
+
+impl From<&FooV1> for FooV2 {
+    fn from(v1: &FooV1) -> Self {
+        FooV2 {
+            name: v1.name.clone(),
+            count: 1,
+        }
+    }
+}
+
+impl From<&FooV2> for FooV3 {
+    fn from(v2: &FooV2) -> Self {
+        FooV3 {
+            name: v2.name.clone(),
+            count: v2.count.into(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct EnvelopeV1<T> {
+    inner: T,
+}
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+#[versioned(by_ref)]
+struct EnvelopeV2<T> {
+    inner: T,
+    seq: u32,
+}
+
+type Envelope<T> = EnvelopeV2<T>;
+
+impl<T: Clone> From<&EnvelopeV1<T>> for EnvelopeV2<T> {
+    fn from(v1: &EnvelopeV1<T>) -> Self {
+        EnvelopeV2 {
+            inner: v1.inner.clone(),
+            seq: 0,
+        }
+    }
+}
+
+assign_message_ids! {
+    Foo: 1,
+    Envelope<u32>: 2,
+}
+
+#[test]
+fn test_upgrade_by_ref() {
+    let v1 = FooV1 { name: "old".into() };
+
+    // Upgrading by reference leaves the original in place.
+    let v3 = FooV3::from_version_ref(&v1);
+    assert_eq!(
+        v3,
+        FooV3 {
+            name: "old".into(),
+            count: 1,
+        }
+    );
+    let v2: FooV2 = v1.to_version();
+    assert_eq!(v2.count, 1);
+    assert_eq!(v1.name, "old");
+
+    // The owned upgrade is built from the same steps.
+    let v3_owned: FooV3 = v1.into_version();
+    assert_eq!(v3_owned, v3);
+    assert_eq!(FooV3::from_version(v2), v3);
+}
+
+#[test]
+fn test_upgrade_by_ref_generic() {
+    let v1 = EnvelopeV1 { inner: 7u32 };
+    let v2: EnvelopeV2<u32> = v1.to_version();
+    assert_eq!(v2, EnvelopeV2 { inner: 7, seq: 0 });
+    assert_eq!(v1.inner, 7);
+}
+
+#[test]
+fn test_read_by_ref_upgrade() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { name: "old".into() }).unwrap();
+    sink.write_message(&FooV2 {
+        name: "mid".into(),
+        count: 5,
+    })
+    .unwrap();
+    sink.write_message(&EnvelopeV1 { inner: 3u32 }).unwrap();
+
+    let buf = sink.into_inner();
+    let mut src = CborData::new(&buf[..]);
+    let foo: Foo = src.expect_message().unwrap();
+    assert_eq!(
+        foo,
+        FooV3 {
+            name: "old".into(),
+            count: 1,
+        }
+    );
+    let foo: Foo = src.expect_message().unwrap();
+    assert_eq!(foo.count, 5);
+    let env: Envelope<u32> = src.expect_message().unwrap();
+    assert_eq!(env, EnvelopeV2 { inner: 3, seq: 0 });
+}
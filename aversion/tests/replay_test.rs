@@ -0,0 +1,66 @@
+use aversion::group::{EntryStatus, GroupEntry, Replayable};
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+/// Setting a value is safe to repeat.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct SetValueV1 {
+    value: u64,
+}
+
+type SetValue = SetValueV1;
+
+impl Replayable for SetValue {}
+
+/// Incrementing a value is not.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct IncrementV1 {
+    amount: u64,
+}
+
+type Increment = IncrementV1;
+
+assign_message_ids! {
+    SetValue: 1,
+    Increment: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[reserved(3)]
+enum Journal {
+    SetValue(SetValue),
+    Increment(Increment),
+}
+
+#[test]
+fn test_replayable_registry() {
+    assert_eq!(
+        Journal::messages(),
+        &[
+            GroupEntry {
+                msg_id: 1,
+                name: Some("SetValue"),
+                status: EntryStatus::Active,
+                replayable: true,
+                versions: &[1],
+                priority: 0,
+            },
+            GroupEntry {
+                msg_id: 2,
+                name: Some("Increment"),
+                status: EntryStatus::Active,
+                replayable: false,
+                versions: &[1],
+                priority: 0,
+            },
+            GroupEntry {
+                msg_id: 3,
+                name: None,
+                status: EntryStatus::Reserved,
+                replayable: false,
+                versions: &[],
+                priority: 0,
+            },
+        ]
+    );
+}
@@ -0,0 +1,113 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::reply::{expect_reply_to, write_reply};
+use aversion::util::ExtendedHeader;
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct LookupV1 {
+    key: String,
+}
+
+type Lookup = LookupV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FoundV1 {
+    value: u32,
+}
+
+type Found = FoundV1;
+
+assign_message_ids! {
+    Lookup: 1,
+    Found: 2,
+}
+
+type ExtSink = CborData<Vec<u8>, ExtendedHeader>;
+type ExtSource = CborData<Cursor<Vec<u8>>, ExtendedHeader>;
+
+fn request(correlation_id: u64) -> ExtendedHeader {
+    let lookup = LookupV1 { key: "a".into() };
+    ExtendedHeader::for_msg(&lookup, 0).with_correlation_id(correlation_id)
+}
+
+#[test]
+fn test_reply_roundtrip() {
+    let request = request(42);
+
+    let mut sink = ExtSink::with_header(Vec::new());
+    write_reply(&mut sink, &request, &FoundV1 { value: 5 }).unwrap();
+
+    // The correlation id is in the reply header.
+    let buf = sink.into_inner();
+    let mut src = ExtSource::with_header(Cursor::new(buf.clone()));
+    let header = src.read_header().unwrap();
+    assert_eq!(header.correlation_id, Some(42));
+    assert_eq!(header.msg_id, 2);
+
+    let mut src = ExtSource::with_header(Cursor::new(buf));
+    let found: Found = expect_reply_to(&mut src, &request).unwrap();
+    assert_eq!(found, FoundV1 { value: 5 });
+}
+
+#[test]
+fn test_reply_mismatch() {
+    // A late reply to request 1 arrives before the reply to request 2.
+    let mut sink = ExtSink::with_header(Vec::new());
+    write_reply(&mut sink, &request(1), &FoundV1 { value: 10 }).unwrap();
+    write_reply(&mut sink, &request(2), &FoundV1 { value: 20 }).unwrap();
+
+    let mut src = ExtSource::with_header(Cursor::new(sink.into_inner()));
+    let err = expect_reply_to::<Found, _>(&mut src, &request(2)).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::CorrelationMismatch {
+            expected: Some(2),
+            found: Some(1),
+        }
+    ));
+
+    // The mismatched reply was skipped, so the next one can be read.
+    let found: Found = expect_reply_to(&mut src, &request(2)).unwrap();
+    assert_eq!(found, FoundV1 { value: 20 });
+}
+
+#[test]
+fn test_reply_missing_id() {
+    // A reply written without a correlation id doesn't match.
+    let mut sink = ExtSink::with_header(Vec::new());
+    sink.write_message(&FoundV1 { value: 1 }).unwrap();
+
+    let mut src = ExtSource::with_header(Cursor::new(sink.into_inner()));
+    let err = expect_reply_to::<Found, _>(&mut src, &request(3)).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::CorrelationMismatch {
+            expected: Some(3),
+            found: None,
+        }
+    ));
+}
+
+#[test]
+fn test_reply_wrong_message() {
+    let request = request(4);
+    let mut sink = ExtSink::with_header(Vec::new());
+    write_reply(&mut sink, &request, &LookupV1 { key: "b".into() }).unwrap();
+
+    let mut src = ExtSource::with_header(Cursor::new(sink.into_inner()));
+    let err = expect_reply_to::<Found, _>(&mut src, &request).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::UnexpectedMessage { got: 1, .. }
+    ));
+
+    // Ordinary reads ignore the correlation id.
+    let mut sink = ExtSink::with_header(Vec::new());
+    write_reply(&mut sink, &request, &FoundV1 { value: 9 }).unwrap();
+    let mut src = ExtSource::with_header(Cursor::new(sink.into_inner()));
+    let found: Found = src.expect_message().unwrap();
+    assert_eq!(found.value, 9);
+}
@@ -0,0 +1,149 @@
+use aversion::util::codec::format_id;
+use aversion::util::fixed::{
+    c_layout, check_repr_c, BigEndian, FixedSizeError, LittleEndian, PackedCodec, ReprC,
+};
+use aversion::util::Codec;
+use serde::{Deserialize, Serialize};
+use std::mem::{align_of, offset_of, size_of};
+
+/// The same layout as:
+/// ```c
+/// struct point { int16_t x; int16_t y; };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+struct Point {
+    x: i16,
+    y: i16,
+}
+
+/// The same layout as:
+/// ```c
+/// struct record {
+///     uint8_t kind;        // 1 byte, then 3 bytes of padding
+///     uint32_t id;
+///     uint8_t flags[3];    // 3 bytes, then 5 bytes of padding
+///     double value;
+///     uint8_t tag;         // 1 byte, then 1 byte of padding
+///     struct point origin;
+///     // 2 bytes of trailing padding
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+struct Record {
+    kind: u8,
+    id: u32,
+    flags: [u8; 3],
+    value: f64,
+    tag: u8,
+    origin: Point,
+}
+
+fn record() -> Record {
+    Record {
+        kind: 7,
+        id: 0x0102_0304,
+        flags: [0xa, 0xb, 0xc],
+        value: 1.0,
+        tag: 0xee,
+        origin: Point { x: -2, y: 3 },
+    }
+}
+
+#[test]
+fn test_repr_c_layout() {
+    let layout = check_repr_c::<Record>().unwrap();
+    assert_eq!(layout.size, size_of::<Record>());
+    assert_eq!(layout.size, 32);
+    assert_eq!(layout.align, align_of::<Record>());
+
+    let offsets: Vec<usize> = layout.fields.iter().map(|&(offset, _)| offset).collect();
+    assert_eq!(
+        offsets,
+        [
+            offset_of!(Record, kind),
+            offset_of!(Record, id),
+            offset_of!(Record, flags),
+            offset_of!(Record, flags) + 1,
+            offset_of!(Record, flags) + 2,
+            offset_of!(Record, value),
+            offset_of!(Record, tag),
+            offset_of!(Record, origin) + offset_of!(Point, x),
+            offset_of!(Record, origin) + offset_of!(Point, y),
+        ]
+    );
+}
+
+#[test]
+fn test_repr_c_bytes() {
+    let codec = PackedCodec::<LittleEndian, ReprC>::new();
+    let mut buf = Vec::new();
+    codec.encode(&record(), &mut buf).unwrap();
+
+    #[rustfmt::skip]
+    assert_eq!(
+        buf,
+        [
+            7, 0, 0, 0, // kind, padding
+            0x04, 0x03, 0x02, 0x01, // id
+            0xa, 0xb, 0xc, 0, 0, 0, 0, 0, // flags, padding
+            0, 0, 0, 0, 0, 0, 0xf0, 0x3f, // value
+            0xee, 0, // tag, padding
+            0xfe, 0xff, 0x03, 0x00, // origin
+            0, 0, // trailing padding
+        ]
+    );
+    assert_eq!(codec.decode::<Record>(&buf).unwrap(), record());
+
+    // Padding is ignored when reading.
+    buf[1] = 0x55;
+    buf[31] = 0x55;
+    assert_eq!(codec.decode::<Record>(&buf).unwrap(), record());
+
+    let err = codec.decode::<Record>(&buf[..31]).unwrap_err();
+    assert!(matches!(err, FixedSizeError::Eof));
+}
+
+#[test]
+fn test_repr_c_big_endian() {
+    let codec = PackedCodec::<BigEndian, ReprC>::new();
+    let mut buf = Vec::new();
+    codec.encode(&Point { x: 1, y: -1 }, &mut buf).unwrap();
+    assert_eq!(buf, [0, 1, 0xff, 0xff]);
+    assert_eq!(codec.decode::<Point>(&buf).unwrap(), Point { x: 1, y: -1 });
+
+    assert_eq!(
+        <PackedCodec<BigEndian, ReprC> as Codec>::FORMAT_ID,
+        format_id::REPR_C_BE
+    );
+    assert_eq!(
+        <PackedCodec<LittleEndian, ReprC> as Codec>::FORMAT_ID,
+        format_id::REPR_C_LE
+    );
+    assert_eq!(
+        <PackedCodec<LittleEndian> as Codec>::FORMAT_ID,
+        format_id::FIXED_SIZE
+    );
+}
+
+#[test]
+fn test_repr_c_mismatch() {
+    #[derive(Serialize, Deserialize)]
+    #[repr(C, align(16))]
+    struct Aligned {
+        byte: u8,
+    }
+
+    assert_eq!(c_layout::<Aligned>().unwrap().size, 1);
+    let err = check_repr_c::<Aligned>().unwrap_err();
+    assert!(matches!(
+        err,
+        FixedSizeError::LayoutMismatch {
+            size: 1,
+            align: 1,
+            expected_size: 16,
+            expected_align: 16,
+        }
+    ));
+}
@@ -0,0 +1,108 @@
+use aversion::group::{DataSink, EntryStatus, GroupEntry};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct Foo2V1 {
+    foo: u32,
+}
+
+type Foo2 = Foo2V1;
+
+assign_message_ids! {
+    Foo2: 0x71,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[reserved(0x72)]
+#[deprecated_msg(0x70, "use Foo2")]
+enum MyGroup {
+    Foo2(Foo2),
+}
+
+/// Build a stream containing a single message with an arbitrary id.
+fn raw_message(msg_id: u16) -> CborData<Cursor<Vec<u8>>> {
+    let body = serde_cbor::to_vec(&Foo2V1 { foo: 1 }).unwrap();
+    let mut buf = Vec::new();
+    BasicHeader::new(msg_id, 1, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&body);
+    CborData::new(Cursor::new(buf))
+}
+
+#[test]
+fn test_reserved_id() {
+    let err = MyGroup::read_message(&mut raw_message(0x72)).unwrap_err();
+    match err {
+        CborDataError::DeprecatedMessage { msg_id, note } => {
+            assert_eq!(msg_id, 0x72);
+            assert_eq!(note, "reserved");
+        }
+        _ => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn test_deprecated_id() {
+    let err = MyGroup::read_message(&mut raw_message(0x70)).unwrap_err();
+    assert_eq!(err.to_string(), "Deprecated message id 112 (use Foo2)");
+    match err {
+        CborDataError::DeprecatedMessage { msg_id, note } => {
+            assert_eq!(msg_id, 0x70);
+            assert_eq!(note, "use Foo2");
+        }
+        _ => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn test_active_and_unknown_ids() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&Foo2V1 { foo: 5 }).unwrap();
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(msg, MyGroup::Foo2(Foo2 { foo: 5 }));
+
+    let err = MyGroup::read_message(&mut raw_message(0x99)).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::UnknownMessage { msg_id: 0x99 }
+    ));
+}
+
+#[test]
+fn test_registry() {
+    assert_eq!(
+        MyGroup::messages(),
+        &[
+            GroupEntry {
+                msg_id: 0x71,
+                name: Some("Foo2"),
+                status: EntryStatus::Active,
+                replayable: false,
+                versions: &[1],
+                priority: 0,
+            },
+            GroupEntry {
+                msg_id: 0x72,
+                name: None,
+                status: EntryStatus::Reserved,
+                replayable: false,
+                versions: &[],
+                priority: 0,
+            },
+            GroupEntry {
+                msg_id: 0x70,
+                name: None,
+                status: EntryStatus::Deprecated("use Foo2"),
+                replayable: false,
+                versions: &[],
+                priority: 0,
+            },
+        ]
+    );
+}
@@ -0,0 +1,88 @@
+use aversion::group::{DataSourceExt, GroupErrorKind};
+use aversion::test_util::{wire_bytes, RingSource};
+use aversion::util::cbor::CborDataError;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PingV1 {
+    seq: u32,
+}
+
+type Ping = PingV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct NoteV1 {
+    text: String,
+}
+
+type Note = NoteV1;
+
+assign_message_ids! {
+    Ping: 1,
+    Note: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Ping(Ping),
+    Note(Note),
+}
+
+fn assert_would_block(err: CborDataError) {
+    assert_eq!(err.kind(), GroupErrorKind::Io);
+    match err {
+        CborDataError::Io(Some(e)) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+        other => panic!("expected WouldBlock, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_read_drain_refill() {
+    let mut src = RingSource::new(4);
+    assert!(src.is_empty());
+    assert_would_block(MyGroup::read_message(&mut src).unwrap_err());
+
+    src.push_frame(&wire_bytes(&PingV1 { seq: 1 }));
+    src.push_frame(&wire_bytes(&NoteV1 {
+        text: "hello".to_owned(),
+    }));
+    assert_eq!(src.len(), 2);
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Ping(PingV1 { seq: 1 })
+    );
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Note(NoteV1 {
+            text: "hello".to_owned()
+        })
+    );
+
+    // Drained: the source blocks instead of ending.
+    assert!(src.is_empty());
+    assert_would_block(MyGroup::read_message(&mut src).unwrap_err());
+    assert_would_block(src.expect_message::<Ping>().unwrap_err());
+
+    // A frame may also hold more than one message.
+    let mut frame = wire_bytes(&PingV1 { seq: 2 });
+    frame.extend(wire_bytes(&PingV1 { seq: 3 }));
+    src.push_frame(&frame);
+    assert_eq!(src.expect_message::<Ping>().unwrap(), PingV1 { seq: 2 });
+    assert_eq!(src.expect_message::<Ping>().unwrap(), PingV1 { seq: 3 });
+    assert_would_block(src.expect_message::<Ping>().unwrap_err());
+}
+
+#[test]
+fn test_ring_overflow() {
+    let mut src = RingSource::new(2);
+    for seq in 1..=5 {
+        src.push_frame(&wire_bytes(&PingV1 { seq }));
+    }
+    assert_eq!(src.len(), 2);
+    assert_eq!(src.dropped(), 3);
+    assert_eq!(src.expect_message::<Ping>().unwrap(), PingV1 { seq: 4 });
+    assert_eq!(src.expect_message::<Ping>().unwrap(), PingV1 { seq: 5 });
+    assert_would_block(src.expect_message::<Ping>().unwrap_err());
+}
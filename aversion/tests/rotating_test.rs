@@ -0,0 +1,218 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::rotating::{RotatingSink, RotationPolicy};
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct EntryV1 {
+    seq: u32,
+    text: String,
+}
+
+type Entry = EntryV1;
+
+assign_message_ids! {
+    Entry: 1,
+}
+
+const PREAMBLE: &[u8] = b"LOG1";
+
+fn entry(seq: u32) -> Entry {
+    Entry {
+        seq,
+        text: "x".repeat(40),
+    }
+}
+
+/// Read all of the messages from a file, after checking the preamble.
+fn read_file(path: &PathBuf) -> Vec<Entry> {
+    let mut buf = Cursor::new(fs::read(path).unwrap());
+    let mut preamble = [0u8; 4];
+    buf.read_exact(&mut preamble).unwrap();
+    assert_eq!(preamble, PREAMBLE);
+
+    let mut src = CborData::new(buf);
+    let mut entries = Vec::new();
+    loop {
+        match src.expect_message::<Entry>() {
+            Ok(entry) => entries.push(entry),
+            Err(CborDataError::Io(Some(e))) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+    entries
+}
+
+#[test]
+fn test_rotation() {
+    let dir = std::env::temp_dir().join(format!("aversion-rotating-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let template = dir.join("log-{}.cbor");
+
+    // Each message is about 60 bytes, so this rotates after 3 messages.
+    let policy = RotationPolicy::new().max_bytes(150);
+    let mut sink = RotatingSink::new(template.to_str().unwrap(), policy).with_preamble(PREAMBLE);
+    assert_eq!(sink.current_path(), None);
+
+    for seq in 0..5 {
+        sink.write_message(&entry(seq)).unwrap();
+    }
+    assert_eq!(sink.current_path(), Some(dir.join("log-1.cbor").as_path()));
+    sink.flush().unwrap();
+    drop(sink);
+
+    let file0 = read_file(&dir.join("log-0.cbor"));
+    let file1 = read_file(&dir.join("log-1.cbor"));
+    assert_eq!(file0, vec![entry(0), entry(1), entry(2)]);
+    assert_eq!(file1, vec![entry(3), entry(4)]);
+    assert!(!dir.join("log-2.cbor").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_rotation_message_count() {
+    let dir = std::env::temp_dir().join(format!("aversion-rotating-count-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let template = dir.join("log-{}.cbor");
+
+    let policy = RotationPolicy::new().max_messages(2);
+    let mut sink = RotatingSink::new(template.to_str().unwrap(), policy).with_preamble(PREAMBLE);
+    for seq in 0..4 {
+        sink.write_message(&entry(seq)).unwrap();
+    }
+    drop(sink);
+
+    assert_eq!(read_file(&dir.join("log-0.cbor")), vec![entry(0), entry(1)]);
+    assert_eq!(read_file(&dir.join("log-1.cbor")), vec![entry(2), entry(3)]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "must contain {}")]
+fn test_bad_template() {
+    RotatingSink::new("log.cbor", RotationPolicy::new());
+}
+
+/// Create an empty directory for one test.
+fn test_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("aversion-rotating-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write `count` entries to file 0.
+fn write_log(template: &str, count: u32) {
+    let mut sink = RotatingSink::new(template, RotationPolicy::new()).with_preamble(PREAMBLE);
+    for seq in 0..count {
+        sink.write_message(&entry(seq)).unwrap();
+    }
+    sink.flush().unwrap();
+}
+
+#[test]
+fn test_append_clean_tail() {
+    let dir = test_dir("append");
+    let template = dir.join("log-{}.cbor");
+    let template = template.to_str().unwrap();
+    write_log(template, 2);
+
+    let policy = RotationPolicy::new().max_messages(3);
+    let mut sink = RotatingSink::new(template, policy)
+        .with_preamble(PREAMBLE)
+        .append(0)
+        .unwrap();
+    let path0 = dir.join("log-0.cbor");
+    assert_eq!(sink.current_path(), Some(path0.as_path()));
+    // The existing messages count towards the limit, so the second
+    // message goes to the next file.
+    sink.write_message(&entry(2)).unwrap();
+    sink.write_message(&entry(3)).unwrap();
+    sink.flush().unwrap();
+    drop(sink);
+
+    assert_eq!(read_file(&path0), (0..3).map(entry).collect::<Vec<_>>());
+    assert_eq!(read_file(&dir.join("log-1.cbor")), vec![entry(3)]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_append_new_file() {
+    let dir = test_dir("append-new");
+    let template = dir.join("log-{}.cbor");
+    let mut sink = RotatingSink::new(template.to_str().unwrap(), RotationPolicy::new())
+        .with_preamble(PREAMBLE)
+        .append(5)
+        .unwrap();
+    sink.write_message(&entry(0)).unwrap();
+    sink.flush().unwrap();
+    drop(sink);
+
+    assert_eq!(read_file(&dir.join("log-5.cbor")), vec![entry(0)]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_append_truncated_tail() {
+    let dir = test_dir("append-truncated");
+    let template = dir.join("log-{}.cbor");
+    let template = template.to_str().unwrap();
+    write_log(template, 2);
+
+    // Cut the last message short.
+    let path = dir.join("log-0.cbor");
+    let full_len = fs::metadata(&path).unwrap().len();
+    let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(full_len - 10).unwrap();
+    drop(file);
+
+    let err = RotatingSink::new(template, RotationPolicy::new())
+        .with_preamble(PREAMBLE)
+        .append(0)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, CborDataError::Io(Some(ref e)) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+    );
+    // The file wasn't changed.
+    assert_eq!(fs::metadata(&path).unwrap().len(), full_len - 10);
+
+    let mut sink = RotatingSink::new(template, RotationPolicy::new())
+        .with_preamble(PREAMBLE)
+        .truncate_partial(true)
+        .append(0)
+        .unwrap();
+    sink.write_message(&entry(9)).unwrap();
+    sink.flush().unwrap();
+    drop(sink);
+
+    assert_eq!(read_file(&path), vec![entry(0), entry(9)]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_append_preamble_mismatch() {
+    let dir = test_dir("append-mismatch");
+    let template = dir.join("log-{}.cbor");
+    let template = template.to_str().unwrap();
+    write_log(template, 1);
+
+    let err = RotatingSink::new(template, RotationPolicy::new())
+        .with_preamble(*b"LOG2")
+        .append(0)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, CborDataError::Io(Some(ref e)) if e.kind() == std::io::ErrorKind::InvalidData)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
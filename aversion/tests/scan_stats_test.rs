@@ -0,0 +1,141 @@
+use aversion::group::{DataSink, MessageKey, MessageStats};
+use aversion::util::cbor::CborData;
+use aversion::util::WideHeader;
+use aversion::{assign_message_ids, FromVersion, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV2 {
+    foo: u64,
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        Self { foo: v1.foo.into() }
+    }
+}
+
+type Foo = FooV2;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct WideFooV1 {
+    foo: u32,
+}
+
+type WideFoo = WideFooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct WideBarV1 {
+    bar: u32,
+}
+
+type WideBar = WideBarV1;
+
+// Both ids share the same low 16 bits.
+assign_message_ids! {
+    WideFoo: 0x1_0001u32,
+    WideBar: 0x2_0001u32,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum WideGroup {
+    WideFoo(WideFoo),
+    WideBar(WideBar),
+}
+
+#[test]
+fn test_scan_stats() {
+    let mut sink = CborData::new(Vec::new());
+    // Each FooV1 body is 6 bytes: a1 63 "foo" 01
+    sink.write_message(&FooV1 { foo: 1 }).unwrap();
+    sink.write_message(&FooV1 { foo: 2 }).unwrap();
+    // A FooV2 body is 10 bytes: a1 63 "foo" 1a 00 01 00 00
+    sink.write_message(&FooV2 { foo: 0x10000 }).unwrap();
+    // Each BarV1 body is 6 bytes + the string length: a1 63 "bar" 6x ...
+    sink.write_message(&BarV1 {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    sink.write_message(&BarV1 {
+        bar: "x".to_owned(),
+    })
+    .unwrap();
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+
+    let stats = MyGroup::scan_stats(&mut src).unwrap();
+    let key = |msg_id, msg_ver| MessageKey { msg_id, msg_ver };
+    assert_eq!(stats.len(), 3);
+    assert_eq!(
+        stats[&key(1, 1)],
+        MessageStats {
+            count: 2,
+            total_bytes: 12
+        }
+    );
+    assert_eq!(
+        stats[&key(1, 2)],
+        MessageStats {
+            count: 1,
+            total_bytes: 10
+        }
+    );
+    assert_eq!(
+        stats[&key(2, 1)],
+        MessageStats {
+            count: 2,
+            total_bytes: 18
+        }
+    );
+}
+
+#[test]
+fn test_scan_stats_wide_ids() {
+    let mut sink = CborData::<_, WideHeader>::with_header(Vec::new());
+    // Each body is 6 bytes: a1 63 "foo" 0x / a1 63 "bar" 0x
+    sink.write_message(&WideFooV1 { foo: 1 }).unwrap();
+    sink.write_message(&WideBarV1 { bar: 2 }).unwrap();
+    sink.write_message(&WideBarV1 { bar: 3 }).unwrap();
+    let mut src = CborData::<_, WideHeader>::with_header(Cursor::new(sink.into_inner()));
+
+    let stats = WideGroup::scan_stats(&mut src).unwrap();
+    let key = |msg_id, msg_ver| MessageKey { msg_id, msg_ver };
+    assert_eq!(stats.len(), 2);
+    assert_eq!(
+        stats[&key(0x1_0001, 1)],
+        MessageStats {
+            count: 1,
+            total_bytes: 6
+        }
+    );
+    assert_eq!(
+        stats[&key(0x2_0001, 1)],
+        MessageStats {
+            count: 2,
+            total_bytes: 12
+        }
+    );
+}
@@ -0,0 +1,148 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CborCodec, CborData};
+use aversion::util::preamble::{self, PreambleError, SchemaEntry, SchemaManifest};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct AccountV1 {
+    id: u64,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct AccountV2 {
+    id: u64,
+    owner: Option<String>,
+}
+
+type Account = AccountV2;
+
+impl FromVersion<AccountV1> for AccountV2 {
+    fn from_version(v1: AccountV1) -> Self {
+        AccountV2 {
+            id: v1.id,
+            owner: None,
+        }
+    }
+}
+
+assign_message_ids! {
+    Account: 3,
+}
+
+fn archive() -> Vec<u8> {
+    let manifest = SchemaManifest::new()
+        .with::<AccountV1>()
+        .with::<AccountV2>();
+    let mut buf = Vec::new();
+    preamble::write_preamble_with_manifest::<CborCodec, BasicHeader>(&mut buf, &manifest).unwrap();
+    let mut sink = CborData::new(buf);
+    sink.write_message(&AccountV2 {
+        id: 9,
+        owner: Some("ann".to_owned()),
+    })
+    .unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_schema_strings() {
+    assert_eq!(AccountV1::SCHEMA, "id:u64 ;");
+    assert_eq!(AccountV2::SCHEMA, "id:u64 ;owner:Option < String > ;");
+}
+
+#[test]
+fn test_read_manifest() {
+    let buf = archive();
+
+    // Decode the manifest with no knowledge of the message types.
+    let mut r = &buf[..];
+    let preamble = preamble::read_preamble_any(&mut r).unwrap();
+    assert_eq!(preamble.version, preamble::PREAMBLE_VERSION_5);
+    assert!(preamble.has_schema_manifest());
+    let manifest = preamble::read_schema_manifest(&mut r).unwrap();
+    assert_eq!(
+        manifest.entries,
+        [
+            SchemaEntry {
+                msg_id: 3,
+                msg_ver: 1,
+                name: "AccountV1".to_owned(),
+                schema: "id:u64 ;".to_owned(),
+            },
+            SchemaEntry {
+                msg_id: 3,
+                msg_ver: 2,
+                name: "AccountV2".to_owned(),
+                schema: "id:u64 ;owner:Option < String > ;".to_owned(),
+            },
+        ]
+    );
+    assert_eq!(manifest.find(3, 2).unwrap().name, "AccountV2");
+    assert!(manifest.find(3, 3).is_none());
+
+    // The messages follow the manifest.
+    let mut src = CborData::new(r);
+    let account: Account = src.expect_message().unwrap();
+    assert_eq!(account.owner.as_deref(), Some("ann"));
+}
+
+#[test]
+fn test_skip_manifest() {
+    let mut r = Cursor::new(archive());
+    let preamble = preamble::read_preamble::<CborCodec>(&mut r).unwrap();
+    assert!(preamble.has_schema_manifest());
+    preamble::skip_schema_manifest(&mut r).unwrap();
+    let mut src = CborData::new(r);
+    assert_eq!(src.expect_message::<Account>().unwrap().id, 9);
+}
+
+#[test]
+fn test_unsupported_manifest_version() {
+    let mut buf = archive();
+    // The manifest version follows the preamble and the length.
+    let size = preamble::read_preamble_any(&mut &buf[..]).unwrap().size();
+    buf[size + 5] = 9;
+    let mut r = &buf[..];
+    preamble::read_preamble_any(&mut r).unwrap();
+    let err = preamble::read_schema_manifest(&mut r).unwrap_err();
+    assert!(matches!(err, PreambleError::UnsupportedManifestVersion(9)));
+}
+
+#[test]
+fn test_oversized_schema_length() {
+    // A manifest with one entry, whose schema claims to be 4 GiB long.
+    let mut body = vec![0, 1, 0, 1];
+    body.extend_from_slice(&[0, 1, 0, 1, 0, 1, b'x']);
+    body.extend_from_slice(&u32::MAX.to_be_bytes());
+    body.extend_from_slice(b"id:u64 ;");
+    let mut buf = (body.len() as u32).to_be_bytes().to_vec();
+    buf.extend_from_slice(&body);
+
+    let err = preamble::read_schema_manifest(&mut &buf[..]).unwrap_err();
+    assert!(matches!(err, PreambleError::Io(e) if e.kind() == std::io::ErrorKind::InvalidData));
+}
+
+#[test]
+fn test_read_manifest_v1() {
+    // A version 1 manifest stores the message id as a u16.
+    let mut body = vec![0, 1, 0, 1];
+    body.extend_from_slice(&[0, 3, 0, 1, 0, 1, b'x']);
+    body.extend_from_slice(&8u32.to_be_bytes());
+    body.extend_from_slice(b"id:u64 ;");
+    let mut buf = (body.len() as u32).to_be_bytes().to_vec();
+    buf.extend_from_slice(&body);
+
+    let manifest = preamble::read_schema_manifest(&mut &buf[..]).unwrap();
+    assert_eq!(
+        manifest.entries,
+        [SchemaEntry {
+            msg_id: 3,
+            msg_ver: 1,
+            name: "x".to_owned(),
+            schema: "id:u64 ;".to_owned(),
+        }]
+    );
+}
@@ -0,0 +1,150 @@
+use aversion::group::{DataSource, GroupHeader};
+use aversion::util::cbor::CborDataError;
+use aversion::util::FlagsHeader;
+use aversion::{assign_message_ids, GroupDeserialize, MessageId, UpgradeLatest, Versioned};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::io::{Cursor, Read};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PingV1 {
+    seq: u32,
+}
+
+type Ping = PingV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PongV1 {
+    reply: String,
+}
+
+type Pong = PongV1;
+
+assign_message_ids! {
+    Ping: 5,
+    Pong: 6,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum PingPong {
+    Ping(Ping),
+    Pong(Pong),
+}
+
+/// An application-specific flag, marking a `Pong` sent with `Ping`'s id.
+const IS_PONG: u8 = 0x10;
+
+/// A source that reads `FlagsHeader` + CBOR messages.
+struct FlagsSource {
+    inner: Cursor<Vec<u8>>,
+}
+
+impl DataSource for FlagsSource {
+    type Error = CborDataError;
+    type Header = FlagsHeader;
+
+    fn read_header(&mut self) -> Result<FlagsHeader, CborDataError> {
+        Ok(FlagsHeader::deserialize_from(&mut self.inner)?)
+    }
+
+    fn read_message<T>(&mut self, header: &FlagsHeader) -> Result<T, CborDataError>
+    where
+        T: DeserializeOwned,
+    {
+        let subreader = (&mut self.inner).take(header.msg_len.into());
+        Ok(serde_cbor::from_reader(subreader)?)
+    }
+
+    fn unsupported_operation(&self, operation: &'static str) -> CborDataError {
+        std::io::Error::new(std::io::ErrorKind::Unsupported, operation).into()
+    }
+}
+
+/// A hand-written group, which only implements `read_message`.
+#[derive(Debug, PartialEq)]
+struct OnlyPing(Ping);
+
+impl GroupDeserialize for OnlyPing {
+    fn read_message<Src>(src: &mut Src) -> Result<Self, Src::Error>
+    where
+        Src: DataSource,
+    {
+        let header = src.read_header()?;
+        if header.msg_id() != Ping::MSG_ID {
+            return Err(src.unknown_message(header.msg_id()));
+        }
+        Ok(OnlyPing(src.read_message(&header)?))
+    }
+}
+
+/// Write a message with a specific id and flags.
+fn write_frame<T: Serialize>(buf: &mut Vec<u8>, msg_id: u16, flags: u8, msg: &T) {
+    let body = serde_cbor::to_vec(msg).unwrap();
+    let header = FlagsHeader::new(msg_id, 1, flags, body.len().try_into().unwrap());
+    header.serialize_into(buf).unwrap();
+    buf.extend_from_slice(&body);
+}
+
+#[test]
+fn test_selector() {
+    // Both messages are sent with Ping's message id.
+    let mut buf = Vec::new();
+    write_frame(&mut buf, Ping::MSG_ID, 0, &PingV1 { seq: 1 });
+    write_frame(
+        &mut buf,
+        Ping::MSG_ID,
+        IS_PONG,
+        &PongV1 {
+            reply: "pong".to_owned(),
+        },
+    );
+
+    let selector = |header: &FlagsHeader| {
+        if header.flags() & IS_PONG != 0 {
+            Some(Pong::WIDE_MSG_ID)
+        } else {
+            None
+        }
+    };
+
+    let mut src = FlagsSource {
+        inner: Cursor::new(buf),
+    };
+    let msg = PingPong::read_message_with(&mut src, selector).unwrap();
+    assert_eq!(msg, PingPong::Ping(Ping { seq: 1 }));
+    let msg = PingPong::read_message_with(&mut src, selector).unwrap();
+    assert_eq!(
+        msg,
+        PingPong::Pong(Pong {
+            reply: "pong".to_owned()
+        })
+    );
+
+    // Without the selector, the second message is misrouted, and fails to
+    // deserialize as a `Ping`.
+    src.inner.set_position(0);
+    let msg = PingPong::read_message(&mut src).unwrap();
+    assert_eq!(msg, PingPong::Ping(Ping { seq: 1 }));
+    PingPong::read_message(&mut src).unwrap_err();
+}
+
+#[test]
+fn test_selector_default() {
+    let mut buf = Vec::new();
+    write_frame(&mut buf, Ping::MSG_ID, 0, &PingV1 { seq: 1 });
+    write_frame(&mut buf, Ping::MSG_ID, IS_PONG, &PingV1 { seq: 2 });
+    let mut src = FlagsSource {
+        inner: Cursor::new(buf),
+    };
+
+    // The default implementation can use the header's own id...
+    let msg = OnlyPing::read_message_with(&mut src, |_| Some(Ping::WIDE_MSG_ID)).unwrap();
+    assert_eq!(msg, OnlyPing(Ping { seq: 1 }));
+
+    // ...but can't dispatch on a different one.
+    let err = OnlyPing::read_message_with(&mut src, |_| Some(Pong::WIDE_MSG_ID)).unwrap_err();
+    assert!(
+        matches!(err, CborDataError::Io(Some(e)) if e.kind() == std::io::ErrorKind::Unsupported)
+    );
+}
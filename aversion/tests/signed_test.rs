@@ -0,0 +1,104 @@
+#![cfg(feature = "signing")]
+
+use aversion::group::{DataSink, DataSourceExt, GroupErrorKind};
+use aversion::util::cbor::CborDataError;
+use aversion::util::signed::{SignedSink, SignedSource, SigningKey};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn key() -> SigningKey {
+    SigningKey::from_bytes(&[0x42; 32])
+}
+
+fn encoded() -> Vec<u8> {
+    let mut sink = SignedSink::new(Vec::new(), key());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Bar {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    sink.flush().unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_signed_roundtrip() {
+    let buf = encoded();
+    // Foo is a 6-byte body, and Bar an 11-byte body, each followed by a
+    // 64-byte signature.
+    assert_eq!(buf.len(), 2 * (BasicHeader::SIZE + 64) + 6 + 11);
+
+    let mut src = SignedSource::new(Cursor::new(buf), key().verifying_key());
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(msg, MyGroup::Foo(Foo { foo: 1 }));
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(
+        msg,
+        MyGroup::Bar(Bar {
+            bar: "hello".to_owned()
+        })
+    );
+}
+
+#[test]
+fn test_tampered_body() {
+    let mut buf = encoded();
+    // Change the value of `foo` from 1 to 2.
+    let foo_value = BasicHeader::SIZE + 5;
+    assert_eq!(buf[foo_value], 0x01);
+    buf[foo_value] = 0x02;
+
+    let mut src = SignedSource::new(Cursor::new(buf), key().verifying_key());
+    let err = src.expect_message::<Foo>().unwrap_err();
+    assert!(matches!(err, CborDataError::SignatureInvalid));
+    assert_eq!(err.kind(), GroupErrorKind::Validation);
+
+    // The signature was consumed, so the next message can still be read.
+    let msg: Bar = src.expect_message().unwrap();
+    assert_eq!(msg.bar, "hello");
+}
+
+#[test]
+fn test_tampered_signature() {
+    let mut buf = encoded();
+    buf[BasicHeader::SIZE + 6] ^= 0x80;
+
+    let mut src = SignedSource::new(Cursor::new(buf), key().verifying_key());
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(err, CborDataError::SignatureInvalid));
+}
+
+#[test]
+fn test_wrong_key() {
+    let other = SigningKey::from_bytes(&[0x43; 32]);
+    let mut src = SignedSource::new(Cursor::new(encoded()), other.verifying_key());
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(err, CborDataError::SignatureInvalid));
+}
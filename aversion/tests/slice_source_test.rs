@@ -0,0 +1,102 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt};
+use aversion::util::cbor::{CborData, SliceSource};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct BlobV1<'a> {
+    id: u32,
+    #[serde(borrow)]
+    data: Cow<'a, [u8]>,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PlainV1 {
+    id: u32,
+}
+
+type Blob = BlobV1<'static>;
+type Plain = PlainV1;
+
+assign_message_ids! {
+    Blob: 1,
+    Plain: 2,
+}
+
+#[test]
+fn test_borrowed_cow() {
+    // serde serializes Cow<[u8]> as a sequence unless told otherwise, so
+    // write the bytes as a CBOR byte string.
+    let blob = serde_cbor::Value::Map(
+        vec![
+            (
+                serde_cbor::Value::Text("id".to_owned()),
+                serde_cbor::Value::Integer(7),
+            ),
+            (
+                serde_cbor::Value::Text("data".to_owned()),
+                serde_cbor::Value::Bytes(vec![1, 2, 3, 4]),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let body = serde_cbor::to_vec(&blob).unwrap();
+    let mut buf = Vec::new();
+    BasicHeader::new(1, 1, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&body);
+
+    // Follow it with another message.
+    let mut sink = CborData::new(buf);
+    sink.write_message(&PlainV1 { id: 8 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = SliceSource::new(&buf);
+    let header = src.read_header().unwrap();
+    let msg: BlobV1 = src.read_message_ref(&header).unwrap();
+    assert_eq!(msg.id, 7);
+    assert_eq!(&*msg.data, &[1, 2, 3, 4]);
+    match msg.data {
+        Cow::Borrowed(data) => {
+            // The borrowed data must point into the source buffer.
+            let buf_range = buf.as_ptr_range();
+            assert!(buf_range.contains(&data.as_ptr()));
+        }
+        Cow::Owned(_) => panic!("expected borrowed data"),
+    }
+
+    // The source stays in sync for the next message.
+    let msg: Plain = src.expect_message().unwrap();
+    assert_eq!(msg, PlainV1 { id: 8 });
+    assert!(src.remaining().is_empty());
+}
+
+#[test]
+fn test_chunked_cow() {
+    // {"id": 7, "data": (_ h'0102', h'03')}
+    let body = [
+        0xa2, // map(2)
+        0x62, b'i', b'd', 0x07, // "id": 7
+        0x64, b'd', b'a', b't', b'a', // "data":
+        0x5f, // indefinite-length bytes
+        0x42, 0x01, 0x02, // h'0102'
+        0x41, 0x03, // h'03'
+        0xff, // break
+    ];
+    let mut buf = Vec::new();
+    BasicHeader::new(1, 1, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&body);
+
+    let mut src = SliceSource::new(&buf);
+    let header = src.read_header().unwrap();
+    let msg: BlobV1 = src.read_message_ref(&header).unwrap();
+    assert_eq!(msg.id, 7);
+    assert_eq!(msg.data, Cow::Owned::<[u8]>(vec![1, 2, 3]));
+    assert!(matches!(msg.data, Cow::Owned(_)));
+}
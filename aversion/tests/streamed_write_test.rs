@@ -0,0 +1,83 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::{BasicHeader, VarintHeader};
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Cursor};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BlobV1 {
+    name: String,
+    data: Vec<u32>,
+}
+
+type Blob = BlobV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct PingV1;
+
+type Ping = PingV1;
+
+assign_message_ids! {
+    Blob: 1,
+    Ping: 2,
+}
+
+fn big_blob() -> Blob {
+    Blob {
+        name: "big".to_owned(),
+        data: (0..1_000_000).collect(),
+    }
+}
+
+#[test]
+fn test_streamed_large_body() {
+    let blob = big_blob();
+    let mut sink = CborData::new(Cursor::new(Vec::new()));
+    sink.write_message_streamed(&blob).unwrap();
+    sink.write_message_streamed(&PingV1).unwrap();
+    let buf = sink.into_inner().into_inner();
+    assert!(buf.len() > 4_000_000);
+
+    // The first header was backpatched with the body length.
+    let header = BasicHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(header.msg_id, 1);
+    let ping_start = BasicHeader::SIZE + header.msg_len as usize;
+    assert_eq!(buf.len(), ping_start + BasicHeader::SIZE);
+
+    // The output matches the buffered write path.
+    let mut buffered = CborData::new(Vec::new());
+    buffered.write_message(&blob).unwrap();
+    buffered.write_message(&PingV1).unwrap();
+    assert_eq!(buf, buffered.into_inner());
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let decoded: Blob = src.expect_message().unwrap();
+    assert_eq!(decoded, blob);
+    let _: Ping = src.expect_message().unwrap();
+}
+
+#[test]
+fn test_streamed_canonical() {
+    let blob = Blob {
+        name: "small".to_owned(),
+        data: vec![1, 2, 3],
+    };
+    let mut streamed = CborData::new(Cursor::new(Vec::new())).canonical();
+    streamed.write_message_streamed(&blob).unwrap();
+    let mut buffered = CborData::new(Vec::new()).canonical();
+    buffered.write_message(&blob).unwrap();
+    assert_eq!(streamed.into_inner().into_inner(), buffered.into_inner());
+}
+
+#[test]
+fn test_streamed_variable_header() {
+    // The length of a VarintHeader changes the size of the header.
+    let mut sink = CborData::<_, VarintHeader>::with_header(Cursor::new(Vec::new()));
+    sink.write_message_streamed(&PingV1).unwrap();
+    let err = sink.write_message_streamed(&big_blob()).unwrap_err();
+    match err {
+        CborDataError::Io(Some(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+        other => panic!("unexpected error {:?}", other),
+    }
+}
@@ -0,0 +1,107 @@
+use aversion::group::DataSink;
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn file() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 1 }).unwrap();
+    sink.write_message(&BarV1 { bar: "two".into() }).unwrap();
+    sink.write_message(&FooV1 { foo: 3 }).unwrap();
+    sink.into_inner()
+}
+
+fn read_strict(buf: &[u8]) -> Result<Vec<MyGroup>, CborDataError> {
+    CborData::new(buf).read_until_eof_strict()
+}
+
+#[test]
+fn test_strict_clean() {
+    let msgs = read_strict(&file()).unwrap();
+    assert_eq!(
+        msgs,
+        [
+            MyGroup::Foo(FooV1 { foo: 1 }),
+            MyGroup::Bar(BarV1 { bar: "two".into() }),
+            MyGroup::Foo(FooV1 { foo: 3 }),
+        ]
+    );
+
+    assert!(read_strict(&[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_strict_trailing_junk() {
+    let mut buf = file();
+    buf.extend_from_slice(b"junk");
+    let result = read_strict(&buf);
+    assert!(matches!(
+        result,
+        Err(CborDataError::TrailingGarbage { messages: 3 })
+    ));
+}
+
+#[test]
+fn test_strict_mid_header() {
+    let mut buf = file();
+    let mut header = Vec::new();
+    BasicHeader::new(1, 1, 2)
+        .serialize_into(&mut header)
+        .unwrap();
+    buf.extend_from_slice(&header[..BasicHeader::SIZE - 3]);
+    let result = read_strict(&buf);
+    assert!(matches!(
+        result,
+        Err(CborDataError::TrailingGarbage { messages: 3 })
+    ));
+}
+
+#[test]
+fn test_strict_mid_body() {
+    let mut buf = file();
+    buf.pop();
+    let result = read_strict(&buf);
+    assert!(matches!(
+        result,
+        Err(CborDataError::TrailingGarbage { messages: 2 })
+    ));
+}
+
+#[test]
+fn test_strict_unknown_message() {
+    // A complete header with an unknown id isn't garbage; it's reported
+    // as usual.
+    let mut buf = file();
+    BasicHeader::new(9, 1, 0).serialize_into(&mut buf).unwrap();
+    let result = read_strict(&buf);
+    assert!(matches!(
+        result,
+        Err(CborDataError::UnknownMessage { msg_id: 9 })
+    ));
+}
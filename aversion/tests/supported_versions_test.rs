@@ -0,0 +1,70 @@
+use aversion::group::UpgradeLatest;
+use aversion::{assign_message_ids, FromVersion, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV2 {
+    foo: u64,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV3 {
+    foo: u64,
+    bar: u64,
+}
+
+type Foo = FooV3;
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 { foo: v1.foo.into() }
+    }
+}
+
+impl FromVersion<FooV2> for FooV3 {
+    fn from_version(v2: FooV2) -> Self {
+        FooV3 {
+            foo: v2.foo,
+            bar: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: u32,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[reserved(3)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+#[test]
+fn test_supported_versions() {
+    assert_eq!(Foo::SUPPORTED_VERSIONS, [1, 2, 3]);
+    assert_eq!(Bar::SUPPORTED_VERSIONS, [1]);
+}
+
+#[test]
+fn test_group_supported_versions() {
+    assert_eq!(MyGroup::supported_versions(1), [1, 2, 3]);
+    assert_eq!(MyGroup::supported_versions(2), [1]);
+    // Reserved and unknown ids have no versions.
+    assert!(MyGroup::supported_versions(3).is_empty());
+    assert!(MyGroup::supported_versions(4).is_empty());
+}
@@ -0,0 +1,127 @@
+use aversion::group::DataSource;
+use aversion::util::cbor::CborData;
+use aversion::util::sync::SyncSink;
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Cursor, Write};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    thread: u32,
+    seq: u32,
+    // Large enough that a torn write would be noticed.
+    data: String,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    thread: u32,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+const THREADS: u32 = 8;
+const MESSAGES: u32 = 200;
+
+#[test]
+fn test_many_writers() {
+    let sink = Arc::new(SyncSink::new(CborData::new(Vec::new())));
+
+    let handles = (0..THREADS)
+        .map(|thread| {
+            let sink = Arc::clone(&sink);
+            thread::spawn(move || {
+                for seq in 0..MESSAGES {
+                    let data = thread.to_string().repeat(seq as usize % 64 + 1);
+                    sink.write_message(&Foo { thread, seq, data }).unwrap();
+                }
+                // Group messages can be written through `&SyncSink`.
+                MyGroup::Bar(Bar { thread })
+                    .write_message(&mut &*sink)
+                    .unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let buf = Arc::try_unwrap(sink)
+        .ok()
+        .unwrap()
+        .into_inner()
+        .into_inner();
+    let mut src = CborData::new(Cursor::new(buf));
+
+    // Each thread's messages must arrive intact and in order, with the
+    // `Bar` last.
+    let mut next_seq = vec![0; THREADS as usize];
+    let mut done = vec![false; THREADS as usize];
+    for _ in 0..THREADS * (MESSAGES + 1) {
+        match MyGroup::read_message(&mut src).unwrap() {
+            MyGroup::Foo(foo) => {
+                let thread = foo.thread as usize;
+                assert!(!done[thread]);
+                assert_eq!(foo.seq, next_seq[thread]);
+                let data = foo.thread.to_string().repeat(foo.seq as usize % 64 + 1);
+                assert_eq!(foo.data, data);
+                next_seq[thread] += 1;
+            }
+            MyGroup::Bar(bar) => {
+                let thread = bar.thread as usize;
+                assert_eq!(next_seq[thread], MESSAGES);
+                done[thread] = true;
+            }
+        }
+    }
+    assert!(done.iter().all(|done| *done));
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+/// A writer that records each call to `write`.
+#[derive(Default)]
+struct WriteLog {
+    writes: Vec<Vec<u8>>,
+}
+
+impl Write for WriteLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_single_write_per_message() {
+    let sink = SyncSink::new(CborData::new(WriteLog::default()));
+    sink.write_message(&Bar { thread: 1 }).unwrap();
+    sink.write_message(&Bar { thread: 2 }).unwrap();
+
+    // Each frame, header and body, is handed to the writer at once.
+    let writes = sink.into_inner().into_inner().writes;
+    assert_eq!(writes.len(), 2);
+    let mut src = CborData::new(&writes[1][..]);
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Bar(Bar { thread: 2 })
+    );
+}
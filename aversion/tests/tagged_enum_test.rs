@@ -0,0 +1,209 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use aversion::util::BasicHeader;
+use aversion::{
+    assign_message_ids, FromVersion, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned,
+};
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value;
+use std::io::Cursor;
+
+// An internally tagged enum as a message body.
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum ShapeV1 {
+    Circle { radius: u32 },
+    Square { side: u32 },
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+#[serde(tag = "type")]
+enum ShapeV2 {
+    Circle { radius: u32 },
+    Rect { width: u32, height: u32 },
+    Point,
+}
+
+impl FromVersion<ShapeV1> for ShapeV2 {
+    fn from_version(v1: ShapeV1) -> Self {
+        match v1 {
+            ShapeV1::Circle { radius } => ShapeV2::Circle { radius },
+            ShapeV1::Square { side } => ShapeV2::Rect {
+                width: side,
+                height: side,
+            },
+        }
+    }
+}
+
+type Shape = ShapeV2;
+
+// An adjacently tagged enum inside a message body.
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", content = "args")]
+enum Command {
+    Move(i32, i32),
+    Say(String),
+    Stop,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct ScriptV1 {
+    commands: Vec<Command>,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct ScriptV2 {
+    name: String,
+    commands: Vec<Command>,
+}
+
+impl FromVersion<ScriptV1> for ScriptV2 {
+    fn from_version(v1: ScriptV1) -> Self {
+        ScriptV2 {
+            name: String::new(),
+            commands: v1.commands,
+        }
+    }
+}
+
+type Script = ScriptV2;
+
+assign_message_ids! {
+    Shape: 1,
+    Script: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum Drawing {
+    Shape(Shape),
+    Script(Script),
+}
+
+/// Decode the body of the only message in `buf`.
+fn body(buf: &[u8]) -> Value {
+    serde_cbor::from_slice(&buf[BasicHeader::SIZE..]).unwrap()
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(s.into())
+}
+
+#[test]
+fn test_internal_tag_wire_format() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&ShapeV2::Circle { radius: 3 }).unwrap();
+    let buf = sink.into_inner();
+
+    let header = BasicHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!((header.msg_id, header.msg_ver), (1, 2));
+    // The tag is a field of the body, not a wrapper around it.
+    let expected = [
+        (text("type"), text("Circle")),
+        (text("radius"), Value::Integer(3)),
+    ];
+    assert_eq!(body(&buf), Value::Map(expected.iter().cloned().collect()));
+}
+
+#[test]
+fn test_internal_tag_roundtrip() {
+    let shapes = vec![
+        ShapeV2::Circle { radius: 1 },
+        ShapeV2::Rect {
+            width: 2,
+            height: 3,
+        },
+        ShapeV2::Point,
+    ];
+    let mut sink = CborData::new(Vec::new());
+    for shape in &shapes {
+        sink.write_message(shape).unwrap();
+    }
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    for shape in shapes {
+        let msg: Shape = src.expect_message().unwrap();
+        assert_eq!(msg, shape);
+    }
+}
+
+#[test]
+fn test_internal_tag_upgrade() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&ShapeV1::Circle { radius: 4 }).unwrap();
+    sink.write_message(&ShapeV1::Square { side: 5 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let msg = Drawing::read_message(&mut src).unwrap();
+    assert_eq!(msg, Drawing::Shape(ShapeV2::Circle { radius: 4 }));
+    let msg = Drawing::read_message(&mut src).unwrap();
+    assert_eq!(
+        msg,
+        Drawing::Shape(ShapeV2::Rect {
+            width: 5,
+            height: 5
+        })
+    );
+}
+
+#[test]
+fn test_adjacent_tag_roundtrip() {
+    let commands = vec![
+        Command::Move(1, -1),
+        Command::Say("hi".into()),
+        Command::Stop,
+    ];
+    let script = ScriptV2 {
+        name: "demo".into(),
+        commands: commands.clone(),
+    };
+    let mut sink = CborData::new(Vec::new());
+    Drawing::Script(script).write_message(&mut sink).unwrap();
+    let buf = sink.into_inner();
+
+    // Each command is a map with the tag and content side by side.
+    let value = body(&buf);
+    let commands_value = match &value {
+        Value::Map(map) => &map[&text("commands")],
+        _ => panic!("expected a map"),
+    };
+    let say = [(text("op"), text("Say")), (text("args"), text("hi"))];
+    let stop = [(text("op"), text("Stop"))];
+    match commands_value {
+        Value::Array(values) => {
+            assert_eq!(values[1], Value::Map(say.iter().cloned().collect()));
+            assert_eq!(values[2], Value::Map(stop.iter().cloned().collect()));
+        }
+        _ => panic!("expected an array"),
+    }
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let msg: Script = src.expect_message().unwrap();
+    assert_eq!(msg.name, "demo");
+    assert_eq!(msg.commands, commands);
+}
+
+#[test]
+fn test_adjacent_tag_upgrade() {
+    let commands = vec![Command::Say("old".into()), Command::Move(0, 2)];
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&ScriptV1 {
+        commands: commands.clone(),
+    })
+    .unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let msg: Script = src.expect_message().unwrap();
+    assert_eq!(
+        msg,
+        ScriptV2 {
+            name: String::new(),
+            commands,
+        }
+    );
+}
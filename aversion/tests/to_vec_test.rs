@@ -0,0 +1,81 @@
+use aversion::group::{from_reader, DataSink};
+use aversion::util::cbor::{CborCodec, CborDataError, CborProtocol};
+use aversion::util::protocol::{Protocol, WriteSink};
+use aversion::util::SemverHeader;
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+#[versioned(minor = 2)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+struct SemverProtocol;
+
+impl Protocol for SemverProtocol {
+    type Header = SemverHeader;
+    type Codec = CborCodec;
+    type Error = CborDataError;
+}
+
+fn messages() -> Vec<MyGroup> {
+    vec![
+        MyGroup::Foo(Foo { foo: 7 }),
+        MyGroup::Bar(Bar {
+            bar: "hi".to_owned(),
+        }),
+    ]
+}
+
+#[test]
+fn test_to_vec_matches_write_sink() {
+    for msg in messages() {
+        let mut sink = WriteSink::<_, CborProtocol>::new(Vec::new());
+        msg.write_message(&mut sink).unwrap();
+        let expected = sink.into_inner();
+
+        let buf = msg.to_vec().unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!(buf, msg.to_vec_with::<CborProtocol>().unwrap());
+
+        let decoded: MyGroup = from_reader(&buf[..]).unwrap();
+        assert_eq!(decoded, msg);
+    }
+}
+
+#[test]
+fn test_to_vec_with_protocol() {
+    let msg = MyGroup::Bar(Bar {
+        bar: "hi".to_owned(),
+    });
+    let mut sink = WriteSink::<_, SemverProtocol>::new(Vec::new());
+    msg.write_message(&mut sink).unwrap();
+    sink.flush().unwrap();
+    let expected = sink.into_inner();
+
+    let buf = msg.to_vec_with::<SemverProtocol>().unwrap();
+    assert_eq!(buf, expected);
+    // SemverHeader is 2 bytes longer than the default header.
+    assert_eq!(buf.len(), msg.to_vec().unwrap().len() + 2);
+}
@@ -0,0 +1,143 @@
+#![cfg(feature = "tokio-codec")]
+
+use aversion::util::cbor::CborDataError;
+use aversion::util::tokio_codec::GroupCodec;
+use aversion::{
+    assign_message_ids, FromVersion, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned,
+};
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV2 {
+    foo: u64,
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        Self { foo: v1.foo.into() }
+    }
+}
+
+type Foo = FooV2;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn messages() -> Vec<MyGroup> {
+    vec![
+        MyGroup::Foo(Foo { foo: 1 }),
+        MyGroup::Bar(Bar {
+            bar: "hello".to_owned(),
+        }),
+        MyGroup::Foo(Foo { foo: u64::MAX }),
+        MyGroup::Bar(Bar {
+            bar: "x".repeat(10_000),
+        }),
+    ]
+}
+
+#[tokio::test]
+async fn test_tcp_roundtrip() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = TcpStream::connect(addr);
+    let server = listener.accept();
+    let (client, server) = tokio::join!(client, server);
+    let client = client.unwrap();
+    let (server, _) = server.unwrap();
+
+    let mut client = Framed::new(client, GroupCodec::<MyGroup>::new());
+    let mut server = Framed::new(server, GroupCodec::<MyGroup>::new());
+
+    let writer = async {
+        for msg in messages() {
+            client.send(msg).await.unwrap();
+        }
+    };
+    let reader = async {
+        let mut received = Vec::new();
+        for _ in 0..messages().len() {
+            let msg = server.next().await.unwrap().unwrap();
+            received.push(msg);
+        }
+        received
+    };
+    let ((), received) = tokio::join!(writer, reader);
+    assert_eq!(received, messages());
+}
+
+#[test]
+fn test_split_frames() {
+    let mut encoded = BytesMut::new();
+    let mut codec = GroupCodec::<MyGroup>::new();
+    for msg in messages() {
+        codec.encode(msg, &mut encoded).unwrap();
+    }
+
+    // Feed the bytes one at a time.
+    let mut buf = BytesMut::new();
+    let mut received = Vec::new();
+    for byte in encoded.iter() {
+        buf.extend_from_slice(&[*byte]);
+        if let Some(msg) = codec.decode(&mut buf).unwrap() {
+            received.push(msg);
+        }
+    }
+    assert!(buf.is_empty());
+    assert_eq!(received, messages());
+}
+
+#[test]
+fn test_upgrade() {
+    // Encode an old version, using the same framing as `CborData`.
+    let mut sink = aversion::util::cbor::CborData::new(Vec::new());
+    aversion::group::DataSink::write_message(&mut sink, &FooV1 { foo: 7 }).unwrap();
+    let mut buf = BytesMut::from(&sink.into_inner()[..]);
+
+    let mut codec = GroupCodec::<MyGroup>::new();
+    let msg = codec.decode(&mut buf).unwrap();
+    assert_eq!(msg, Some(MyGroup::Foo(Foo { foo: 7 })));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+}
+
+#[test]
+fn test_max_frame_len() {
+    // A header for message 1, version 1, that claims a 4 GiB body.
+    let mut buf = BytesMut::from(&[0, 1, 0, 1, 0xff, 0xff, 0xff, 0xff][..]);
+    let mut codec = GroupCodec::<MyGroup>::new().max_frame_len(1024);
+    let err = codec.decode(&mut buf).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::FrameTooLong {
+            len: 0x1_0000_0007,
+            max: 1024
+        }
+    ));
+    // Nothing was reserved for the body.
+    assert!(buf.capacity() < 1024);
+}
@@ -0,0 +1,165 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, FromVersion, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct FooV2 {
+    foo: u64,
+}
+
+type Foo = FooV2;
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 { foo: v1.foo.into() }
+    }
+}
+
+assign_message_ids! {
+    Foo: 7,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+}
+
+/// A span, and the events recorded inside it.
+#[derive(Debug, Default)]
+struct CapturedSpan {
+    name: &'static str,
+    fields: BTreeMap<&'static str, String>,
+    events: Vec<String>,
+}
+
+/// A subscriber that records every span and event.
+#[derive(Default)]
+struct Capture {
+    next_id: AtomicU64,
+    spans: Mutex<Vec<CapturedSpan>>,
+    current: Mutex<Vec<usize>>,
+}
+
+struct FieldVisitor<'a>(&'a mut BTreeMap<&'static str, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_owned());
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut span = CapturedSpan {
+            name: attrs.metadata().name(),
+            ..CapturedSpan::default()
+        };
+        attrs.record(&mut FieldVisitor(&mut span.fields));
+        self.spans.lock().unwrap().push(span);
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = BTreeMap::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let message = fields.remove("message").unwrap_or_default();
+        if let Some(&index) = self.current.lock().unwrap().last() {
+            self.spans.lock().unwrap()[index].events.push(message);
+        }
+    }
+
+    fn enter(&self, id: &Id) {
+        let index = usize::try_from(id.into_u64() - 1).unwrap();
+        self.current.lock().unwrap().push(index);
+    }
+
+    fn exit(&self, _: &Id) {
+        self.current.lock().unwrap().pop();
+    }
+}
+
+fn capture(f: impl FnOnce()) -> Vec<CapturedSpan> {
+    let capture = Arc::new(Capture::default());
+    tracing::subscriber::with_default(capture.clone(), f);
+    let spans = std::mem::take(&mut *capture.spans.lock().unwrap());
+    spans
+}
+
+fn encoded() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&FooV1 { foo: 5 }).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_trace_read() {
+    let buf = encoded();
+    let spans = capture(|| {
+        let msg = MyGroup::read_message(&mut CborData::new(&buf[..])).unwrap();
+        assert_eq!(msg, MyGroup::Foo(FooV2 { foo: 5 }));
+    });
+
+    assert_eq!(spans.len(), 1);
+    let span = &spans[0];
+    assert_eq!(span.name, "read_message");
+    assert_eq!(span.fields["msg_id"], "7");
+    assert_eq!(span.fields["ver"], "1");
+    assert!(span.fields["msg_type"].ends_with("FooV2"));
+    assert!(span.events.is_empty());
+}
+
+#[test]
+fn test_trace_write() {
+    let spans = capture(|| {
+        encoded();
+    });
+
+    assert_eq!(spans.len(), 1);
+    let span = &spans[0];
+    assert_eq!(span.name, "write_message");
+    assert_eq!(span.fields["msg_id"], "7");
+    assert_eq!(span.fields["ver"], "1");
+    assert!(span.fields["msg_type"].ends_with("FooV1"));
+}
+
+#[test]
+fn test_trace_read_error() {
+    let mut buf = Vec::new();
+    BasicHeader::new(7, 9, 0).serialize_into(&mut buf).unwrap();
+    let spans = capture(|| {
+        let result = CborData::new(&buf[..]).expect_message::<Foo>();
+        assert!(result.is_err());
+    });
+
+    assert_eq!(spans.len(), 1);
+    let span = &spans[0];
+    assert_eq!(span.fields["ver"], "9");
+    assert_eq!(span.events, ["failed to read message"]);
+}
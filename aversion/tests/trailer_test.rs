@@ -0,0 +1,134 @@
+use aversion::group::{DataSink, DataSource, GroupErrorKind};
+use aversion::util::cbor::CborDataError;
+use aversion::util::trailer::{TrailerHeaderSink, TrailerHeaderSource};
+use aversion::util::{BasicHeader, SemverHeader};
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+/// Build a frame by hand: length, body, then header.
+fn frame(header: BasicHeader, body: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    buf.extend_from_slice(body);
+    header.serialize_into(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_round_trip() {
+    let mut sink = TrailerHeaderSink::new(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Bar {
+        bar: "hello".to_owned(),
+    })
+    .unwrap();
+    sink.write_message(&Foo { foo: 3 }).unwrap();
+    let buf = sink.into_inner();
+
+    // The first frame matches the documented layout.
+    let body = serde_cbor::to_vec(&FooV1 { foo: 1 }).unwrap();
+    let expected = frame(BasicHeader::new(1, 1, body.len() as u32), &body);
+    assert_eq!(buf[..expected.len()], expected[..]);
+
+    let mut src = TrailerHeaderSource::new(&buf[..]);
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(FooV1 { foo: 1 })
+    );
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Bar(BarV1 {
+            bar: "hello".to_owned()
+        })
+    );
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(FooV1 { foo: 3 })
+    );
+    assert!(src.try_read_header().unwrap().is_none());
+}
+
+#[test]
+fn test_other_header() {
+    let mut sink = TrailerHeaderSink::<_, SemverHeader>::with_header(Vec::new());
+    sink.write_message(&Foo { foo: 5 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = TrailerHeaderSource::<_, SemverHeader>::with_header(&buf[..]);
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(FooV1 { foo: 5 })
+    );
+}
+
+#[test]
+fn test_unknown_message_skipped() {
+    let body = serde_cbor::to_vec(&FooV1 { foo: 9 }).unwrap();
+    let mut buf = frame(BasicHeader::new(99, 1, body.len() as u32), &body);
+    buf.extend(frame(BasicHeader::new(1, 1, body.len() as u32), &body));
+
+    let mut src = TrailerHeaderSource::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    src.skip_message(&header).unwrap();
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(FooV1 { foo: 9 })
+    );
+}
+
+#[test]
+fn test_length_mismatch() {
+    let body = serde_cbor::to_vec(&FooV1 { foo: 9 }).unwrap();
+    let buf = frame(BasicHeader::new(1, 1, 1), &body);
+    let err = MyGroup::read_message(&mut TrailerHeaderSource::new(&buf[..])).unwrap_err();
+    assert_eq!(err.kind(), GroupErrorKind::Framing);
+}
+
+#[test]
+fn test_max_body_len() {
+    let mut sink = TrailerHeaderSink::new(Vec::new());
+    sink.write_message(&Bar {
+        bar: "a long string".to_owned(),
+    })
+    .unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = TrailerHeaderSource::new(&buf[..]).max_body_len(8);
+    let err = MyGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(err, CborDataError::Io(Some(_))));
+}
+
+#[test]
+fn test_truncated() {
+    let mut sink = TrailerHeaderSink::new(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = TrailerHeaderSource::new(&buf[..buf.len() - 1]);
+    assert!(src.try_read_header().is_err());
+}
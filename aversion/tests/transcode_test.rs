@@ -0,0 +1,144 @@
+use aversion::group::{DataSink, MessageKey};
+use aversion::util::cbor::CborData;
+use aversion::util::transcode::{transcode_dry_run, TranscodeFailure};
+use aversion::{
+    assign_message_ids, FromVersion, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct TempV1 {
+    celsius: i32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct TempV2 {
+    millicelsius: i64,
+}
+
+type Temp = TempV2;
+
+impl FromVersion<TempV1> for TempV2 {
+    fn from_version(v1: TempV1) -> Self {
+        TempV2 {
+            millicelsius: i64::from(v1.celsius) * 1000,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct NoteV1 {
+    text: String,
+}
+
+type Note = NoteV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct ReadingV1 {
+    kelvin: u64,
+}
+
+type Reading = ReadingV1;
+
+assign_message_ids! {
+    Temp: 1,
+    Note: 2,
+    Reading: 10,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum OldLog {
+    Temp(Temp),
+    Note(Note),
+}
+
+#[derive(Debug, PartialEq, GroupSerialize)]
+enum NewLog {
+    Reading(Reading),
+    Note(Note),
+}
+
+/// Convert temperatures to Kelvin. Temperatures below absolute zero fail.
+fn mapper(msg: OldLog) -> Result<NewLog, String> {
+    match msg {
+        OldLog::Temp(temp) => {
+            let kelvin = temp.millicelsius + 273_150;
+            if kelvin < 0 {
+                return Err(format!("{} is below absolute zero", temp.millicelsius));
+            }
+            Ok(NewLog::Reading(ReadingV1 {
+                kelvin: kelvin as u64,
+            }))
+        }
+        OldLog::Note(note) => Ok(NewLog::Note(note)),
+    }
+}
+
+fn mixed_log() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&TempV1 { celsius: 20 }).unwrap();
+    sink.write_message(&NoteV1 {
+        text: "calibrated".to_owned(),
+    })
+    .unwrap();
+    sink.write_message(&TempV2 {
+        millicelsius: -300_000,
+    })
+    .unwrap();
+    sink.write_message(&TempV2 { millicelsius: 500 }).unwrap();
+    sink.write_message(&NoteV1 {
+        text: "done".to_owned(),
+    })
+    .unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_dry_run_report() {
+    let mut src = CborData::new(Cursor::new(mixed_log()));
+    let report = transcode_dry_run(&mut src, mapper);
+
+    assert_eq!(report.read, 5);
+    assert_eq!(report.written(), 4);
+    let counts: Vec<(MessageKey, usize)> = report.counts.into_iter().collect();
+    assert_eq!(
+        counts,
+        [
+            (
+                MessageKey {
+                    msg_id: 2,
+                    msg_ver: 1
+                },
+                2
+            ),
+            (
+                MessageKey {
+                    msg_id: 10,
+                    msg_ver: 1
+                },
+                2
+            ),
+        ]
+    );
+    assert_eq!(
+        report.failures,
+        [TranscodeFailure {
+            index: 2,
+            error: "-300000 is below absolute zero".to_owned(),
+        }]
+    );
+}
+
+#[test]
+fn test_dry_run_decode_failure() {
+    let mut buf = mixed_log();
+    buf.truncate(buf.len() - 2);
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let report = transcode_dry_run(&mut src, mapper);
+    assert_eq!(report.read, 5);
+    assert_eq!(report.written(), 3);
+    assert_eq!(report.failures.len(), 2);
+    assert_eq!(report.failures[1].index, 4);
+}
@@ -0,0 +1,152 @@
+#![cfg(feature = "flate2")]
+
+use aversion::group::{flags, DataSink, DataSource, DataSourceExt, GroupHeader};
+use aversion::util::cbor::{CborCodec, CborDataError};
+use aversion::util::gzip::GzipCodec;
+use aversion::util::transform::{
+    BodyTransform, Checksum, TransformPipeline, TransformSink, TransformSource,
+};
+use aversion::util::FlagsHeader;
+use aversion::{assign_message_ids, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+#[derive(Debug, PartialEq, Versioned, UpgradeLatest, Serialize, Deserialize)]
+struct TextV1 {
+    text: String,
+}
+
+type Text = TextV1;
+
+assign_message_ids! {
+    Text: 1,
+}
+
+/// A toy cipher, standing in for real encryption.
+struct Xor(u8);
+
+impl BodyTransform for Xor {
+    fn flag(&self) -> u8 {
+        flags::ENCRYPTED
+    }
+
+    fn apply(&self, body: Vec<u8>) -> io::Result<Vec<u8>> {
+        Ok(body.into_iter().map(|b| b ^ self.0).collect())
+    }
+
+    fn reverse(&self, body: Vec<u8>) -> io::Result<Vec<u8>> {
+        self.apply(body)
+    }
+}
+
+fn pipeline() -> TransformPipeline {
+    TransformPipeline::new()
+        .then(GzipCodec::new(CborCodec))
+        .then(Xor(0x5a))
+        .then(Checksum)
+}
+
+fn long_text(c: &str) -> TextV1 {
+    TextV1 {
+        text: c.repeat(500),
+    }
+}
+
+#[test]
+fn test_compress_encrypt_roundtrip() {
+    let mut sink = TransformSink::new(Vec::new(), pipeline());
+    sink.write_message(&long_text("a")).unwrap();
+    let buf = sink.into_inner();
+    // The body was compressed.
+    assert!(buf.len() < 100);
+    // The gzip magic number was encrypted.
+    let body = &buf[FlagsHeader::SIZE..];
+    assert_eq!(&body[..2], &[0x1f ^ 0x5a, 0x8b ^ 0x5a]);
+
+    let mut src = TransformSource::new(&buf[..], pipeline());
+    let header = src.read_header().unwrap();
+    assert_eq!(
+        header.flags(),
+        flags::COMPRESSED | flags::ENCRYPTED | flags::CHECKSUM
+    );
+    let mut src = TransformSource::new(&buf[..], pipeline());
+    let msg: Text = src.expect_message().unwrap();
+    assert_eq!(msg, long_text("a"));
+}
+
+#[test]
+fn test_mixed_messages() {
+    let mut sink = TransformSink::new(Vec::new(), pipeline());
+    sink.write_message(&long_text("a")).unwrap();
+    sink.set_enabled(0);
+    sink.write_message(&long_text("b")).unwrap();
+    sink.set_enabled(flags::CHECKSUM);
+    sink.write_message(&long_text("c")).unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = TransformSource::new(&buf[..], pipeline());
+    let mut found = Vec::new();
+    while let Some(header) = src.try_read_header().unwrap() {
+        found.push(header.flags());
+        src.skip_message(&header).unwrap();
+    }
+    assert_eq!(
+        found,
+        [
+            flags::COMPRESSED | flags::ENCRYPTED | flags::CHECKSUM,
+            0,
+            flags::CHECKSUM
+        ]
+    );
+
+    let mut src = TransformSource::new(&buf[..], pipeline());
+    for c in &["a", "b", "c"] {
+        let msg: Text = src.expect_message().unwrap();
+        assert_eq!(msg, long_text(c));
+    }
+}
+
+#[test]
+fn test_missing_transform() {
+    let mut sink = TransformSink::new(Vec::new(), pipeline());
+    sink.write_message(&long_text("a")).unwrap();
+    let buf = sink.into_inner();
+
+    // A reader without the cipher can't decode the message.
+    let reader = TransformPipeline::new()
+        .then(GzipCodec::new(CborCodec))
+        .then(Checksum);
+    let mut src = TransformSource::new(&buf[..], reader);
+    let err = src.expect_message::<Text>().unwrap_err();
+    assert!(
+        matches!(err, CborDataError::Io(Some(ref e)) if e.kind() == io::ErrorKind::InvalidData)
+    );
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let mut sink = TransformSink::new(Vec::new(), TransformPipeline::new().then(Checksum));
+    sink.write_message(&long_text("a")).unwrap();
+    let mut buf = sink.into_inner();
+    buf[FlagsHeader::SIZE + 5] ^= 1;
+
+    let mut src = TransformSource::new(&buf[..], TransformPipeline::new().then(Checksum));
+    let err = src.expect_message::<Text>().unwrap_err();
+    assert!(
+        matches!(err, CborDataError::Io(Some(ref e)) if e.kind() == io::ErrorKind::InvalidData)
+    );
+}
+
+#[test]
+#[should_panic(expected = "compression must come before encryption")]
+fn test_encrypt_before_compress() {
+    let _ = TransformPipeline::new()
+        .then(Xor(1))
+        .then(GzipCodec::new(CborCodec));
+}
+
+#[test]
+#[should_panic(expected = "two transforms use flag")]
+fn test_duplicate_flag() {
+    let _ = TransformPipeline::new().then(Xor(1)).then(Xor(2));
+}
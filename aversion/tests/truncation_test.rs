@@ -0,0 +1,111 @@
+use aversion::group::{DataSink, IsTruncated, TruncationPolicy};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct EntryV1 {
+    text: String,
+}
+
+type Entry = EntryV1;
+
+assign_message_ids! {
+    Entry: 1,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum Log {
+    Entry(Entry),
+}
+
+fn entries(texts: &[&str]) -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    for text in texts {
+        sink.write_message(&EntryV1 {
+            text: text.to_string(),
+        })
+        .unwrap();
+    }
+    sink.into_inner()
+}
+
+/// Read entries until the iterator ends, returning the texts, whether the
+/// iterator was incomplete, and the position after the last entry.
+fn read_all(src: &mut CborData<Cursor<Vec<u8>>>) -> (Vec<String>, bool, u64) {
+    let mut iter = Log::iter_filter::<Entry, _>(src).on_truncation(TruncationPolicy::Retry);
+    let mut texts = Vec::new();
+    let mut pos = iter.get_mut().get_ref().position();
+    while let Some(entry) = iter.next() {
+        texts.push(entry.unwrap().text);
+        pos = iter.get_mut().get_ref().position();
+    }
+    (texts, iter.is_incomplete(), pos)
+}
+
+#[test]
+fn test_truncated_fatal() {
+    let full = entries(&["one", "two", "three"]);
+    let truncated = full[..full.len() - 2].to_vec();
+
+    let mut src = CborData::new(Cursor::new(truncated));
+    let mut iter = Log::iter_filter::<Entry, _>(&mut src);
+    assert_eq!(iter.next().unwrap().unwrap().text, "one");
+    assert_eq!(iter.next().unwrap().unwrap().text, "two");
+    let err = iter.next().unwrap().unwrap_err();
+    assert!(err.is_truncated());
+    assert!(iter.next().is_none());
+    assert!(!iter.is_incomplete());
+}
+
+#[test]
+fn test_truncated_retry() {
+    let full = entries(&["one", "two", "three"]);
+    let split = full.len() - 2;
+
+    let mut src = CborData::new(Cursor::new(full[..split].to_vec()));
+    let (texts, incomplete, pos) = read_all(&mut src);
+    assert_eq!(texts, ["one", "two"]);
+    assert!(incomplete);
+
+    // The writer finishes the message; resume after the last complete one.
+    let cursor = src.get_mut();
+    cursor.get_mut().extend_from_slice(&full[split..]);
+    cursor.set_position(pos);
+    let (texts, incomplete, _) = read_all(&mut src);
+    assert_eq!(texts, ["three"]);
+    assert!(!incomplete);
+}
+
+#[test]
+fn test_truncated_header() {
+    // The data ends partway through a header.
+    let mut buf = entries(&["one"]);
+    let mut header = Vec::new();
+    BasicHeader::new(1, 1, 10)
+        .serialize_into(&mut header)
+        .unwrap();
+    buf.extend_from_slice(&header[..3]);
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let (texts, incomplete, pos) = read_all(&mut src);
+    assert_eq!(texts, ["one"]);
+    assert!(incomplete);
+    assert!(pos < src.get_ref().get_ref().len() as u64);
+}
+
+#[test]
+fn test_retry_other_errors() {
+    // Errors other than truncation are still returned.
+    let mut buf = entries(&["one"]);
+    BasicHeader::new(9, 1, 0).serialize_into(&mut buf).unwrap();
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let mut iter = Log::iter_filter::<Entry, _>(&mut src).on_truncation(TruncationPolicy::Retry);
+    assert!(iter.next().unwrap().is_ok());
+    let err = iter.next().unwrap().unwrap_err();
+    assert!(matches!(err, CborDataError::UnknownMessage { msg_id: 9 }));
+    assert!(!iter.is_incomplete());
+}
@@ -0,0 +1,126 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CborData, SliceSource};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, GroupDeserialize, GroupSerialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// A control message with no payload.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct UnitMessageV1;
+
+type UnitMessage = UnitMessageV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+/// A message that encodes as CBOR `null`, but isn't a unit struct.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct MaybeV1(Option<u32>);
+
+type Maybe = MaybeV1;
+
+assign_message_ids! {
+    UnitMessage: 1,
+    Foo: 2,
+    Maybe: 3,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    UnitMessage(UnitMessage),
+    Foo(Foo),
+}
+
+fn unit_then_foo() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&UnitMessage {}).unwrap();
+    sink.write_message(&Foo { foo: 7 }).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_unit_wire_format() {
+    let buf = unit_then_foo();
+    // The unit message is just a header, with a length of 0.
+    let header = BasicHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(header.msg_id, 1);
+    assert_eq!(header.msg_len, 0);
+    // The next header follows immediately.
+    let header = BasicHeader::deserialize_from(&mut &buf[BasicHeader::SIZE..]).unwrap();
+    assert_eq!(header.msg_id, 2);
+}
+
+#[test]
+fn test_unit_roundtrip() {
+    let mut src = CborData::new(Cursor::new(unit_then_foo()));
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::UnitMessage(UnitMessage {})
+    );
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(Foo { foo: 7 })
+    );
+
+    let mut src = CborData::new(Cursor::new(unit_then_foo()));
+    let msg: UnitMessage = src.expect_message().unwrap();
+    assert_eq!(msg, UnitMessage {});
+    let msg: Foo = src.expect_message().unwrap();
+    assert_eq!(msg, Foo { foo: 7 });
+}
+
+#[test]
+fn test_unit_slice_source() {
+    let buf = unit_then_foo();
+    let mut src = SliceSource::new(&buf);
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::UnitMessage(UnitMessage {})
+    );
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(Foo { foo: 7 })
+    );
+    assert!(src.remaining().is_empty());
+}
+
+#[test]
+fn test_unit_group_serialize() {
+    let mut sink = CborData::new(Vec::new());
+    MyGroup::UnitMessage(UnitMessage {})
+        .write_message(&mut sink)
+        .unwrap();
+    MyGroup::Foo(Foo { foo: 7 })
+        .write_message(&mut sink)
+        .unwrap();
+    assert_eq!(sink.into_inner(), unit_then_foo());
+}
+
+#[test]
+fn test_unit_legacy_null_body() {
+    // Older versions wrote unit messages as a CBOR `null` body.
+    let mut buf = Vec::new();
+    BasicHeader::new(1, 1, 1).serialize_into(&mut buf).unwrap();
+    buf.push(0xf6);
+    let mut src = CborData::new(Cursor::new(buf));
+    let msg: UnitMessage = src.expect_message().unwrap();
+    assert_eq!(msg, UnitMessage {});
+}
+
+#[test]
+fn test_null_is_not_unit() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&MaybeV1(None)).unwrap();
+    let buf = sink.into_inner();
+
+    // Only unit structs get an empty body.
+    let header = BasicHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(header.msg_len, 1);
+    let mut src = CborData::new(Cursor::new(buf));
+    assert_eq!(src.expect_message::<Maybe>().unwrap(), MaybeV1(None));
+}
@@ -0,0 +1,96 @@
+use aversion::group::{DataSink, DataSource};
+use aversion::util::cbor::{CborCodec, CborData};
+use aversion::util::json::JsonCodec;
+use aversion::util::unknown_fields::UnknownFields;
+use aversion::util::Codec;
+use aversion::{assign_message_ids, Versioned};
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct ConfigV1 {
+    name: String,
+    size: u32,
+}
+
+type Config = ConfigV1;
+
+assign_message_ids! {
+    Config: 1,
+}
+
+/// The same message, as a newer peer would send it.
+mod newer {
+    use aversion::Versioned;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Versioned, Serialize, Deserialize)]
+    pub struct ConfigV1 {
+        pub name: String,
+        pub size: u32,
+        pub color: String,
+        pub weight: u64,
+    }
+
+    pub type Config = ConfigV1;
+
+    aversion::assign_message_ids! {
+        Config: 1,
+    }
+}
+
+#[test]
+fn test_capture_unknown_fields() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&newer::ConfigV1 {
+        name: "widget".to_owned(),
+        size: 3,
+        color: "blue".to_owned(),
+        weight: 70,
+    })
+    .unwrap();
+    let buf = sink.into_inner();
+
+    let mut src = CborData::new(&buf[..]);
+    let header = src.read_header().unwrap();
+    let msg: UnknownFields<Config, Value> = src.read_message(&header).unwrap();
+    assert!(msg.has_unknown_fields());
+    assert_eq!(
+        msg.unknown_fields.into_iter().collect::<Vec<_>>(),
+        [
+            ("color".to_owned(), Value::Text("blue".to_owned())),
+            ("weight".to_owned(), Value::Integer(70)),
+        ]
+    );
+    assert_eq!(
+        msg.msg,
+        ConfigV1 {
+            name: "widget".to_owned(),
+            size: 3
+        }
+    );
+}
+
+#[test]
+fn test_no_unknown_fields() {
+    let config = ConfigV1 {
+        name: "widget".to_owned(),
+        size: 3,
+    };
+    let mut buf = Vec::new();
+    CborCodec.encode(&config, &mut buf).unwrap();
+    let msg: UnknownFields<Config, Value> = CborCodec.decode(&buf).unwrap();
+    assert!(!msg.has_unknown_fields());
+    assert_eq!(msg.into_inner(), config);
+}
+
+#[test]
+fn test_json_unknown_fields() {
+    let buf = br#"{"name":"widget","size":3,"extra":[1,2]}"#;
+    let msg: UnknownFields<Config, serde_json::Value> = JsonCodec.decode(buf).unwrap();
+    assert_eq!(
+        msg.unknown_fields["extra"],
+        serde_json::Value::from(vec![1, 2])
+    );
+    assert_eq!(msg.msg.size, 3);
+}
@@ -0,0 +1,86 @@
+use aversion::test_util::{assert_upgrades_to, wire_bytes};
+use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV2 {
+    foo: u64,
+    doubled: u64,
+}
+
+type Foo = FooV2;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: u32,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 {
+            foo: v1.foo.into(),
+            doubled: u64::from(v1.foo) * 2,
+        }
+    }
+}
+
+#[test]
+fn test_upgrades_to() {
+    // Golden bytes of a FooV1 { foo: 21 }.
+    let v1_bytes = [
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, // header
+        0xa1, 0x63, 0x66, 0x6f, 0x6f, 0x15, // body
+    ];
+    assert_eq!(v1_bytes[..], wire_bytes(&FooV1 { foo: 21 })[..]);
+
+    let ver = assert_upgrades_to(
+        &v1_bytes,
+        Foo {
+            foo: 21,
+            doubled: 42,
+        },
+    );
+    assert_eq!(ver, 1);
+
+    let v2_bytes = wire_bytes(&FooV2 { foo: 1, doubled: 5 });
+    let ver = assert_upgrades_to(&v2_bytes, Foo { foo: 1, doubled: 5 });
+    assert_eq!(ver, 2);
+}
+
+#[test]
+#[should_panic(expected = "version 1 did not upgrade to the expected value")]
+fn test_upgrades_to_mismatch() {
+    let v1_bytes = wire_bytes(&FooV1 { foo: 21 });
+    assert_upgrades_to(
+        &v1_bytes,
+        Foo {
+            foo: 21,
+            doubled: 21,
+        },
+    );
+}
+
+#[test]
+#[should_panic(expected = "got message id 2")]
+fn test_upgrades_to_wrong_message() {
+    let bar_bytes = wire_bytes(&Bar { bar: 21 });
+    assert_upgrades_to(
+        &bar_bytes,
+        Foo {
+            foo: 21,
+            doubled: 42,
+        },
+    );
+}
@@ -0,0 +1,92 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::{assign_message_ids, FromVersion, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+macro_rules! counter_versions {
+    ($($name:ident),*) => {
+        $(
+            #[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+            struct $name {
+                n: u32,
+            }
+        )*
+    };
+}
+
+counter_versions!(CounterV1, CounterV2, CounterV3, CounterV4);
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct CounterV5 {
+    n: u32,
+}
+
+type Counter = CounterV5;
+
+assign_message_ids! {
+    Counter: 1,
+}
+
+macro_rules! counter_upgrade {
+    ($($from:ident => $to:ident),*) => {
+        $(
+            impl FromVersion<$from> for $to {
+                fn from_version(old: $from) -> Self {
+                    $to { n: old.n + 1 }
+                }
+            }
+        )*
+    };
+}
+
+counter_upgrade!(
+    CounterV1 => CounterV2,
+    CounterV2 => CounterV3,
+    CounterV3 => CounterV4,
+    CounterV4 => CounterV5
+);
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum MyGroup {
+    Counter(Counter),
+}
+
+fn stream() -> Cursor<Vec<u8>> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&CounterV1 { n: 0 }).unwrap();
+    sink.write_message(&CounterV3 { n: 0 }).unwrap();
+    Cursor::new(sink.into_inner())
+}
+
+#[test]
+fn test_default_limit() {
+    let mut src = CborData::new(stream());
+    let msg: Counter = src.expect_message().unwrap();
+    assert_eq!(msg, Counter { n: 4 });
+    let msg: Counter = src.expect_message().unwrap();
+    assert_eq!(msg, Counter { n: 2 });
+}
+
+#[test]
+fn test_upgrade_too_deep() {
+    let mut src = CborData::new(stream()).upgrade_limit(2);
+
+    // Version 1 needs 4 upgrade steps.
+    let err = src.expect_message::<Counter>().unwrap_err();
+    match err {
+        CborDataError::UpgradeTooDeep {
+            got, latest, max, ..
+        } => assert_eq!((got, latest, max), (1, 5, 2)),
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    // The message body is left unread, so skip it.
+    let mut src = CborData::new(stream()).upgrade_limit(2);
+    let header = src.read_header().unwrap();
+    src.skip_message(&header).unwrap();
+
+    // Version 3 needs only 2 steps.
+    let msg = MyGroup::read_message(&mut src).unwrap();
+    assert_eq!(msg, MyGroup::Counter(Counter { n: 2 }));
+}
@@ -0,0 +1,77 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::CborData;
+use aversion::{assign_message_ids, FromVersion, IntoVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct ThingV1 {
+    x: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct ThingV2 {
+    x: u64,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct ThingV3 {
+    x: u64,
+    y: u64,
+}
+
+type Thing = ThingV3;
+
+assign_message_ids! {
+    Thing: 1,
+}
+
+impl FromVersion<ThingV1> for ThingV2 {
+    fn from_version(v1: ThingV1) -> Self {
+        ThingV2 { x: v1.x.into() }
+    }
+}
+
+impl FromVersion<ThingV2> for ThingV3 {
+    fn from_version(v2: ThingV2) -> Self {
+        ThingV3 { x: v2.x, y: 0 }
+    }
+}
+
+// Downgrades, in the opposite direction. Together with the upgrades these
+// form cycles, but the derived upgrade path only moves to higher versions,
+// so it never uses them.
+impl FromVersion<ThingV2> for ThingV1 {
+    fn from_version(_v2: ThingV2) -> Self {
+        ThingV1 { x: u32::MAX }
+    }
+}
+
+impl FromVersion<ThingV3> for ThingV2 {
+    fn from_version(v3: ThingV3) -> Self {
+        ThingV2 { x: v3.x + v3.y }
+    }
+}
+
+#[test]
+fn test_upgrade_ignores_downgrades() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&ThingV1 { x: 1 }).unwrap();
+    sink.write_message(&ThingV2 { x: 2 }).unwrap();
+    sink.write_message(&ThingV3 { x: 3, y: 4 }).unwrap();
+
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+    let things: Vec<Thing> = src.expect_message_vec(3).unwrap();
+    assert_eq!(
+        things,
+        vec![
+            ThingV3 { x: 1, y: 0 },
+            ThingV3 { x: 2, y: 0 },
+            ThingV3 { x: 3, y: 4 },
+        ]
+    );
+
+    // The downgrades can still be called directly.
+    let v2: ThingV2 = ThingV3 { x: 3, y: 4 }.into_version();
+    assert_eq!(v2, ThingV2 { x: 7 });
+}
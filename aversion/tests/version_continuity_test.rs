@@ -0,0 +1,139 @@
+use aversion::group::{DataSource, GroupHeader, UpgradeLatest};
+use aversion::{assert_version_continuity, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV2 {
+    foo: u64,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV3 {
+    foo: u64,
+    bar: bool,
+}
+
+type Foo = FooV3;
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        FooV2 { foo: v1.foo.into() }
+    }
+}
+
+impl FromVersion<FooV2> for FooV3 {
+    fn from_version(v2: FooV2) -> Self {
+        FooV3 {
+            foo: v2.foo,
+            bar: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct LegacyV0 {
+    x: u8,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+#[versioned(legacy)]
+struct LegacyV1 {
+    x: u16,
+}
+
+type Legacy = LegacyV1;
+
+impl FromVersion<LegacyV0> for LegacyV1 {
+    fn from_version(v0: LegacyV0) -> Self {
+        LegacyV1 { x: v0.x.into() }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct BarV1 {
+    bar: u32,
+}
+
+// There was never a `BarV2`.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct BarV3 {
+    bar: u64,
+}
+
+type Bar = BarV3;
+
+/// A hand-written impl for a family that skips version 2.
+impl UpgradeLatest for BarV3 {
+    const SUPPORTED_VERSIONS: &'static [u16] = &[1, 3];
+
+    fn upgrade_latest<Src>(src: &mut Src, header: Src::Header) -> Result<Self, Src::Error>
+    where
+        Src: DataSource,
+    {
+        match header.msg_ver() {
+            1 => {
+                let v1: BarV1 = src.read_message(&header)?;
+                Ok(BarV3 { bar: v1.bar.into() })
+            }
+            3 => src.read_message(&header),
+            ver => Err(src.unknown_version::<Self>(ver)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct BrokenV2 {
+    x: u32,
+}
+
+type Broken = BrokenV2;
+
+/// Claims to support version 1, but someone forgot to add the upgrade.
+impl UpgradeLatest for BrokenV2 {
+    const SUPPORTED_VERSIONS: &'static [u16] = &[1, 2];
+
+    fn upgrade_latest<Src>(src: &mut Src, header: Src::Header) -> Result<Self, Src::Error>
+    where
+        Src: DataSource,
+    {
+        match header.msg_ver() {
+            2 => src.read_message(&header),
+            ver => Err(src.unknown_version::<Self>(ver)),
+        }
+    }
+}
+
+#[test]
+fn test_derived() {
+    assert_version_continuity!(Foo);
+    assert_version_continuity!(Foo, [1, 2, 3]);
+    assert_version_continuity!(Legacy);
+}
+
+#[test]
+fn test_sparse() {
+    assert_version_continuity!(Bar, [1, 3]);
+}
+
+#[test]
+#[should_panic(expected = "is missing version 2")]
+fn test_sparse_gap() {
+    assert_version_continuity!(Bar);
+}
+
+#[test]
+#[should_panic(expected = "supports versions [1, 3], expected [1, 2, 3]")]
+fn test_wrong_set() {
+    assert_version_continuity!(Bar, [1, 2, 3]);
+}
+
+#[test]
+#[should_panic(expected = "lists version 1 as supported, but has no upgrade path from it")]
+fn test_missing_upgrade() {
+    assert_version_continuity!(Broken);
+}
@@ -0,0 +1,103 @@
+use aversion::group::{DataSink, DataSourceExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::BasicHeader;
+use aversion::{assign_message_ids, FromVersion, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV1 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize)]
+struct FooV2 {
+    foo: u32,
+}
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV3 {
+    foo: u32,
+}
+
+impl FromVersion<FooV1> for FooV2 {
+    fn from_version(v1: FooV1) -> Self {
+        Self { foo: v1.foo }
+    }
+}
+
+impl FromVersion<FooV2> for FooV3 {
+    fn from_version(v2: FooV2) -> Self {
+        Self { foo: v2.foo }
+    }
+}
+
+type Foo = FooV3;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: u32,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[test]
+fn test_version_skew() {
+    // A message from the future: version 5 of `Foo`.
+    let body = serde_cbor::to_vec(&FooV3 { foo: 1 }).unwrap();
+    let mut buf = Vec::new();
+    BasicHeader::new(1, 5, body.len() as u32)
+        .serialize_into(&mut buf)
+        .unwrap();
+    buf.extend_from_slice(&body);
+
+    let mut src = CborData::new(Cursor::new(buf));
+    let err = src.expect_message::<Foo>().unwrap_err();
+    // The type name's module path isn't guaranteed, so only look for the
+    // type itself.
+    let message = err.to_string();
+    assert!(message.contains("FooV3"), "{}", message);
+    assert!(
+        message.ends_with("got version 5, highest supported is 3"),
+        "{}",
+        message
+    );
+    match err {
+        CborDataError::UnknownVersion {
+            expected,
+            got,
+            latest,
+        } => {
+            assert!(expected.ends_with("FooV3"), "{}", expected);
+            assert_eq!(got, 5);
+            assert_eq!(latest, 3);
+        }
+        _ => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn test_unexpected_message() {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&BarV1 { bar: 1 }).unwrap();
+
+    let mut src = CborData::new(Cursor::new(sink.into_inner()));
+    let err = src.expect_message::<Foo>().unwrap_err();
+    match err {
+        CborDataError::UnexpectedMessage {
+            expected,
+            expected_id,
+            got,
+        } => {
+            assert!(expected.ends_with("FooV3"), "{}", expected);
+            assert_eq!(expected_id, 1);
+            assert_eq!(got, 2);
+        }
+        _ => panic!("unexpected error {:?}", err),
+    }
+}
@@ -0,0 +1,38 @@
+use aversion::{assign_message_ids, Versioned, VersionedDyn};
+
+#[derive(Versioned)]
+struct FooV1 {
+    _foo: u32,
+}
+
+#[derive(Versioned)]
+struct FooV2 {
+    _foo: u64,
+}
+
+type Foo = FooV2;
+
+#[derive(Versioned)]
+struct BarV1 {
+    _bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[test]
+fn test_versioned_dyn() {
+    let msgs: Vec<Box<dyn VersionedDyn>> = vec![
+        Box::new(FooV1 { _foo: 1 }),
+        Box::new(FooV2 { _foo: 2 }),
+        Box::new(BarV1 {
+            _bar: "three".into(),
+        }),
+    ];
+    let info: Vec<(u16, u16)> = msgs.iter().map(|m| (m.msg_id(), m.version())).collect();
+    assert_eq!(info, [(1, 1), (1, 2), (2, 1)]);
+}
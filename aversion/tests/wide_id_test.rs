@@ -0,0 +1,145 @@
+use aversion::group::{DataSink, DataSource, DataSourceExt, GroupEntry, GroupHeader};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::util::WideHeader;
+use aversion::{assign_message_ids, GroupDeserialize, MessageId, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct AlphaV1 {
+    x: u32,
+}
+
+type Alpha = AlphaV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BetaV1 {
+    name: String,
+}
+
+type Beta = BetaV1;
+
+// Both ids share the same low 16 bits.
+assign_message_ids! {
+    Alpha: 0x1_0001u32,
+    Beta: 0x2_0001u32,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+enum WideGroup {
+    Alpha(Alpha),
+    Beta(Beta),
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[reserved(0x2_0001)]
+#[deprecated_msg(0x3_0001, "use Alpha")]
+enum RetiredWideGroup {
+    Alpha(Alpha),
+}
+
+type WideData<RW> = CborData<RW, WideHeader>;
+
+#[test]
+fn test_wide_ids() {
+    assert_eq!(Alpha::WIDE_MSG_ID, 0x1_0001);
+    assert_eq!(Beta::WIDE_MSG_ID, 0x2_0001);
+    assert_eq!(Alpha::MSG_ID, 1);
+    assert_eq!(Beta::MSG_ID, 1);
+
+    let ids: Vec<u32> = WideGroup::messages()
+        .iter()
+        .map(|entry: &GroupEntry| entry.msg_id)
+        .collect();
+    assert_eq!(ids, [0x1_0001, 0x2_0001]);
+}
+
+#[test]
+fn test_wide_roundtrip() {
+    let mut sink = WideData::with_header(Vec::new());
+    sink.write_message(&AlphaV1 { x: 7 }).unwrap();
+    sink.write_message(&BetaV1 {
+        name: "wide".to_owned(),
+    })
+    .unwrap();
+    let buf = sink.into_inner();
+
+    let header = WideHeader::deserialize_from(&mut &buf[..]).unwrap();
+    assert_eq!(header.wide_msg_id(), 0x1_0001);
+    assert_eq!(GroupHeader::msg_id(&header), 1);
+
+    let mut src = WideData::with_header(Cursor::new(buf.clone()));
+    assert_eq!(
+        WideGroup::read_message(&mut src).unwrap(),
+        WideGroup::Alpha(AlphaV1 { x: 7 })
+    );
+    assert_eq!(
+        WideGroup::read_message(&mut src).unwrap(),
+        WideGroup::Beta(BetaV1 {
+            name: "wide".to_owned()
+        })
+    );
+
+    // The low 16 bits match, but the wide id doesn't.
+    let mut src = WideData::with_header(Cursor::new(buf));
+    let err = src.expect_message::<Beta>().unwrap_err();
+    assert!(matches!(err, CborDataError::UnexpectedMessage { .. }));
+}
+
+#[test]
+fn test_unknown_wide_id() {
+    let mut buf = Vec::new();
+    WideHeader::new(0x3_0001, 1, 0)
+        .serialize_into(&mut buf)
+        .unwrap();
+
+    let mut src = WideData::with_header(Cursor::new(buf));
+    let err = WideGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::UnknownMessage { msg_id: 0x3_0001 }
+    ));
+}
+
+#[test]
+fn test_retired_wide_ids() {
+    let mut buf = Vec::new();
+    WideHeader::new(0x2_0001, 1, 0)
+        .serialize_into(&mut buf)
+        .unwrap();
+    WideHeader::new(0x3_0001, 1, 0)
+        .serialize_into(&mut buf)
+        .unwrap();
+
+    let mut src = WideData::with_header(Cursor::new(buf));
+    let err = RetiredWideGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::DeprecatedMessage {
+            msg_id: 0x2_0001,
+            note: "reserved",
+        }
+    ));
+    let err = RetiredWideGroup::read_message(&mut src).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::DeprecatedMessage {
+            msg_id: 0x3_0001,
+            note: "use Alpha",
+        }
+    ));
+}
+
+#[test]
+fn test_peek_wide_id() {
+    let mut sink = WideData::with_header(Vec::new());
+    sink.write_message(&AlphaV1 { x: 7 }).unwrap();
+    let buf = sink.into_inner();
+
+    // The peeked id matches the header's msg_id: the low 16 bits.
+    let mut src = WideData::with_header(&buf[..]);
+    assert_eq!(src.peek_msg_id().unwrap(), 1);
+    let header = src.read_header().unwrap();
+    assert_eq!(header.msg_id(), 1);
+    assert_eq!(header.wide_msg_id(), 0x1_0001);
+}
@@ -0,0 +1,93 @@
+use aversion::group::{DataSink, RawMessage, WithUnknown};
+use aversion::util::cbor::{CborData, CborDataError, SliceSource};
+use aversion::{assign_message_ids, GroupDeserialize, UpgradeLatest, Versioned};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+/// Not part of the group.
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: u32,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize)]
+#[deprecated_msg(3, "removed")]
+enum MyGroup {
+    Foo(Foo),
+}
+
+fn encoded() -> Vec<u8> {
+    let mut sink = CborData::new(Vec::new());
+    sink.write_message(&Foo { foo: 1 }).unwrap();
+    sink.write_message(&Bar { bar: 2 }).unwrap();
+    sink.write_message(&Foo { foo: 3 }).unwrap();
+    sink.into_inner()
+}
+
+#[test]
+fn test_capture_unknown() {
+    let mut src = CborData::new(Cursor::new(encoded()));
+    let msg = WithUnknown::<MyGroup>::read_message(&mut src).unwrap();
+    assert_eq!(msg, WithUnknown::Known(MyGroup::Foo(Foo { foo: 1 })));
+
+    let msg = WithUnknown::<MyGroup>::read_message(&mut src).unwrap();
+    let expected_body = serde_cbor::to_vec(&Bar { bar: 2 }).unwrap();
+    assert_eq!(
+        msg,
+        WithUnknown::Unknown(RawMessage {
+            id: 2,
+            ver: 1,
+            bytes: expected_body.clone(),
+        })
+    );
+
+    // The stream is still aligned after capturing the raw message.
+    let msg = WithUnknown::<MyGroup>::read_message(&mut src).unwrap();
+    assert_eq!(msg, WithUnknown::Known(MyGroup::Foo(Foo { foo: 3 })));
+
+    // The raw bytes can be decoded later, by something that knows the type.
+    let bar: Bar = serde_cbor::from_slice(&expected_body).unwrap();
+    assert_eq!(bar, Bar { bar: 2 });
+}
+
+#[test]
+fn test_capture_unknown_slice() {
+    let buf = encoded();
+    let mut src = SliceSource::new(&buf);
+    let msgs = (0..3)
+        .map(|_| WithUnknown::<MyGroup>::read_message(&mut src).unwrap())
+        .collect::<Vec<_>>();
+    assert!(matches!(
+        msgs[1],
+        WithUnknown::Unknown(RawMessage { id: 2, .. })
+    ));
+    assert!(src.remaining().is_empty());
+}
+
+#[test]
+fn test_deprecated_still_errors() {
+    let mut buf = Vec::new();
+    aversion::util::BasicHeader::new(3, 1, 0)
+        .serialize_into(&mut buf)
+        .unwrap();
+    let mut src = CborData::new(Cursor::new(buf));
+    let err = WithUnknown::<MyGroup>::read_message(&mut src).unwrap_err();
+    assert!(matches!(
+        err,
+        CborDataError::DeprecatedMessage { msg_id: 3, .. }
+    ));
+}
@@ -0,0 +1,116 @@
+use aversion::group::{DataSink, DataSinkExt};
+use aversion::util::cbor::{CborData, CborDataError};
+use aversion::{
+    assign_message_ids, GroupDeserialize, GroupSerialize, MessageId, UpgradeLatest, Versioned,
+};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Cursor, Write};
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct FooV1 {
+    foo: u32,
+}
+
+type Foo = FooV1;
+
+#[derive(Debug, PartialEq, Versioned, Serialize, Deserialize, UpgradeLatest)]
+struct BarV1 {
+    bar: String,
+}
+
+type Bar = BarV1;
+
+assign_message_ids! {
+    Foo: 1,
+    Bar: 2,
+}
+
+#[derive(Debug, PartialEq, GroupDeserialize, GroupSerialize)]
+enum MyGroup {
+    Foo(Foo),
+    Bar(Bar),
+}
+
+fn messages() -> Vec<MyGroup> {
+    vec![
+        MyGroup::Foo(Foo { foo: 1 }),
+        MyGroup::Bar(Bar {
+            bar: "two".to_owned(),
+        }),
+        MyGroup::Foo(Foo { foo: 3 }),
+    ]
+}
+
+/// A writer that counts flushes.
+#[derive(Default)]
+struct FlushCounter {
+    buf: Vec<u8>,
+    flushes: usize,
+}
+
+impl Write for FlushCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_write_all() {
+    // Write the messages by reference.
+    let msgs = messages();
+    let mut sink = CborData::new(FlushCounter::default());
+    let count = sink.write_all_messages(&msgs).unwrap();
+    assert_eq!(count, 3);
+
+    let writer = sink.into_inner();
+    assert_eq!(writer.flushes, 1);
+
+    let mut src = CborData::new(Cursor::new(writer.buf));
+    for msg in msgs {
+        assert_eq!(MyGroup::read_message(&mut src).unwrap(), msg);
+    }
+}
+
+/// A sink that refuses to write `Bar` messages.
+struct NoBarSink {
+    inner: CborData<Vec<u8>>,
+}
+
+impl DataSink for NoBarSink {
+    type Error = CborDataError;
+
+    fn write_message<T>(&mut self, msg: &T) -> Result<(), CborDataError>
+    where
+        T: Serialize + Versioned,
+        T::Base: MessageId,
+    {
+        if T::Base::MSG_ID == Bar::MSG_ID {
+            return Err(CborDataError::Serializer);
+        }
+        self.inner.write_message(msg)
+    }
+}
+
+#[test]
+fn test_write_all_error() {
+    let mut sink = NoBarSink {
+        inner: CborData::new(Vec::new()),
+    };
+    let err = sink.write_all_messages(messages()).unwrap_err();
+    assert_eq!(err.index, 1);
+    assert!(matches!(err.error, CborDataError::Serializer));
+    assert_eq!(err.to_string(), "failed to write message 1");
+
+    // Only the first message was written.
+    let mut src = CborData::new(Cursor::new(sink.inner.into_inner()));
+    assert_eq!(
+        MyGroup::read_message(&mut src).unwrap(),
+        MyGroup::Foo(Foo { foo: 1 })
+    );
+    MyGroup::read_message(&mut src).unwrap_err();
+}